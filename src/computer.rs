@@ -1,26 +1,257 @@
-mod cpu;
-mod memory;
+pub mod assembler;
+mod cp0;
+mod cp1;
+pub mod cpu;
+pub mod decoder;
+pub mod disassembler;
+pub mod exception;
+pub mod linux;
+pub mod memory;
+pub mod micromips;
+pub mod mips16;
+mod dsp;
+mod msa;
+pub mod program;
+pub mod spim;
+pub mod syscall;
+mod tlb;
+
+use decoder::IsaRevision;
+use exception::Exception;
+use syscall::{SyscallHandler, SyscallResult};
+
+// Where the stack starts, relative to the top of the memory a program can see.
+const STACK_TOP_ALIGN: u64 = 16;
+const SP: usize = 29;
+// How much of physical memory kseg0 can see.
+const KSEG0_SIZE: u64 = 512 * 1024 * 1024;
 
 pub struct Computer {
     cpus: Vec<cpu::Cpu>,
     memory: memory::Memory,
+    syscall_handler: Option<Box<dyn SyscallHandler>>,
+    exit_status: Option<i32>,
+    mmu_kind: cpu::MmuKind,
 }
 
-pub fn new(cpus: u64, memory: u64) -> Computer {
+// Every CPU implements the same `revision` of the architecture.
+pub fn new(cpus: u64, memory: u64, revision: IsaRevision) -> Computer {
     let mut com = Computer {
         cpus: Vec::new(),
         memory: memory::new(memory, cpus),
+        syscall_handler: None,
+        exit_status: None,
+        mmu_kind: cpu::MmuKind::BaseLimit,
     };
     for i in 0..cpus {
-        com.cpus.push(cpu::new(i));
+        com.cpus.push(cpu::new(i, revision));
     }
     com
 }
 
 impl Computer {
-    pub fn step(&mut self) {
+    // Runs one instruction on every CPU. Syscalls go to the syscall handler
+    // first, and any other exception goes to the program's own handler if it
+    // has one. The exceptions that couldn't be handled are returned along
+    // with the ID of the CPU that raised each one, and leave that CPU halted.
+    //
+    // The CPUs take turns in ID order, and each load and store goes straight
+    // to the shared memory. So every CPU sees every other CPU's stores as
+    // soon as they're made and in the order they were made: memory is
+    // sequentially consistent. That's at least as strong as any SYNC type
+    // asks for, so SYNC is a no-op. LL and SC work because a store to a
+    // linked doubleword, from any CPU, breaks the link.
+    pub fn step(&mut self) -> Vec<(u64, Exception)> {
+        let mut exceptions = Vec::new();
+        if self.exit_status.is_some() {
+            return exceptions;
+        }
+
+        for cpu in self.cpus.iter_mut() {
+            let exception = match cpu.step(&mut self.memory) {
+                None => continue,
+                Some(exception) => exception,
+            };
+
+            if let (Exception::Syscall { .. }, Some(handler)) =
+                    (exception, self.syscall_handler.as_mut()) {
+                match handler.syscall(cpu, &mut self.memory) {
+                    SyscallResult::Resume => {
+                        cpu.skip_exception();
+                        continue;
+                    },
+                    SyscallResult::Exit(status) => {
+                        self.exit_status = Some(status);
+                        break;
+                    },
+                    SyscallResult::Unhandled => {},
+                }
+            }
+
+            if !cpu.deliver_exception(&mut self.memory) {
+                exceptions.push((cpu.id(), exception));
+            }
+        }
+        exceptions
+    }
+
+    pub fn memory(&mut self) -> &mut memory::Memory {
+        &mut self.memory
+    }
+
+    pub fn exception(&self, cpu: u64) -> Option<Exception> {
+        self.cpus[cpu as usize].exception()
+    }
+
+    // Clears a CPU's pending exception and lets it carry on from `pc`.
+    pub fn resume(&mut self, cpu: u64, pc: u64) {
+        let cpu = &mut self.cpus[cpu as usize];
+        cpu.clear_exception();
+        cpu.set_pc(pc);
+    }
+
+    pub fn running(&self) -> bool {
+        self.exit_status.is_none() && self.cpus.iter().any(|cpu| !cpu.halted())
+    }
+
+    // The status the program gave when it exited, if it has.
+    pub fn exit_status(&self) -> Option<i32> {
+        self.exit_status
+    }
+
+    pub fn set_syscall_handler(&mut self, handler: Box<dyn SyscallHandler>) {
+        self.syscall_handler = Some(handler);
+    }
+
+    // How every CPU deals with loads and stores that aren't naturally
+    // aligned. They're allowed unless this says otherwise.
+    pub fn set_unaligned_policy(&mut self, policy: cpu::UnalignedPolicy) {
+        for cpu in self.cpus.iter_mut() {
+            cpu.set_unaligned_policy(policy);
+        }
+    }
+
+    // Whether every CPU translates with base and limit or with a TLB. It's
+    // base and limit unless this says otherwise, and it decides how
+    // programs are loaded.
+    pub fn set_mmu_kind(&mut self, kind: cpu::MmuKind) {
+        self.mmu_kind = kind;
+        for cpu in self.cpus.iter_mut() {
+            cpu.set_mmu_kind(kind);
+        }
+    }
+
+    // How many unaligned loads and stores a CPU has made so far.
+    pub fn unaligned_accesses(&self, cpu: u64) -> u64 {
+        self.cpus[cpu as usize].unaligned_accesses()
+    }
+
+    // Copies the program's segments into memory and points every CPU at
+    // its entry point. The image is placed at the bottom of physical memory
+    // and each MMU is set up so the program's virtual addresses land on it,
+    // with whatever memory is left over mapped directly above the image.
+    // The stack starts at the top of that memory, and memory takes on the
+    // program's byte order. An n64 program gets 64-bit addressing turned
+    // on in Status, the way a 64-bit kernel would set it up; the others are
+    // left in 32-bit compatibility mode. MSA and the DSP ASE are enabled up
+    // front, where a kernel would do it on the program's first MSA or DSP
+    // instruction. An entry point with bit 0 set starts the CPUs in
+    // microMIPS or MIPS16e code.
+    //
+    // With a TLB, the program is taken to be a kernel instead; see
+    // `load_kernel`.
+    pub fn load(&mut self,
+                program: &program::Program) -> Result<(), program::ProgramError> {
+        let (low, high) = program.bounds();
+        let available = self.memory.size();
+        // Not even an empty program fits in no memory at all.
+        if available == 0 {
+            return Err(program::ProgramError::TooLarge {
+                needed: (high - low).max(1),
+                available,
+            });
+        }
+        if self.mmu_kind == cpu::MmuKind::Tlb {
+            return self.load_kernel(program);
+        }
+        if high - low > available {
+            return Err(program::ProgramError::TooLarge {
+                needed: high - low,
+                available,
+            });
+        }
+
+        self.memory.set_endianness(program.endianness);
+        for segment in program.segments.iter() {
+            let address = segment.address - low;
+            let data_size = segment.data.len() as u64;
+            self.memory.write_bytes(address, &segment.data);
+            self.memory.fill(address + data_size, 0, segment.size - data_size);
+        }
+
+        let (mode, status) = match program.class {
+            program::Class::Elf32 => (memory::AddressMode::Bits32, 0),
+            program::Class::Elf64 => (memory::AddressMode::Bits64,
+                                      cp0::STATUS_KX | cp0::STATUS_SX |
+                                      cp0::STATUS_UX),
+        };
+        let limit = low.saturating_add(available - 1);
+        for cpu in self.cpus.iter_mut() {
+            self.memory.set_mmu(cpu.id(), low.wrapping_neg(), limit, mode);
+            cpu.set_status(cpu.status() | status);
+            cpu.set_msa_enabled(true);
+            cpu.set_dsp_enabled(true);
+            cpu.set_pc(program.entry);
+            cpu.set_register(SP, (limit + 1) & !(STACK_TOP_ALIGN - 1));
+        }
+
+        if let Some(handler) = self.syscall_handler.as_mut() {
+            handler.load(program, &mut self.cpus, &mut self.memory)?;
+        }
+
+        Ok(())
+    }
+    // Copies a kernel's segments to the physical addresses behind their
+    // kseg0, kseg1 or xkphys addresses, and points every CPU at its entry
+    // point. The kernel sets up its own stack, TLB and coprocessors. The
+    // host sees memory through kseg0, so a syscall handler can follow the
+    // kernel's pointers.
+    fn load_kernel(&mut self, program: &program::Program)
+        -> Result<(), program::ProgramError> {
+        let available = self.memory.size();
+        let mut placed = Vec::new();
+        for segment in program.segments.iter() {
+            let address = cp0::unmapped(segment.address)
+                .ok_or(program::ProgramError::Mapped(segment.address))?;
+            let needed = address.saturating_add(segment.size);
+            if needed > available {
+                return Err(program::ProgramError::TooLarge {
+                    needed,
+                    available,
+                });
+            }
+            placed.push((address, segment));
+        }
+
+        self.memory.set_endianness(program.endianness);
+        for (address, segment) in placed {
+            let data_size = segment.data.len() as u64;
+            self.memory.write_bytes(address, &segment.data);
+            self.memory.fill(address + data_size, 0, segment.size - data_size);
+        }
+
+        let status = match program.class {
+            program::Class::Elf32 => 0,
+            program::Class::Elf64 =>
+                cp0::STATUS_KX | cp0::STATUS_SX | cp0::STATUS_UX,
+        };
+        let limit = cp0::KSEG0 + available.min(KSEG0_SIZE) - 1;
         for cpu in self.cpus.iter_mut() {
-            cpu.step(&mut self.memory)
+            self.memory.set_mmu(cpu.id(), cp0::KSEG0.wrapping_neg(), limit,
+                                memory::AddressMode::Bits64);
+            cpu.set_status(cpu.status() | status);
+            cpu.set_pc(program.entry);
         }
+        Ok(())
     }
 }
@@ -0,0 +1,1797 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::computer::cp0;
+use crate::computer::decoder;
+use crate::computer::memory::Endianness;
+use crate::computer::program::{Class, Program, Segment};
+
+// Where each section goes. Text starts where GNU ld puts n64 programs, and
+// data starts on the next 64 KiB boundary after the end of the text.
+const TEXT_BASE: u64 = 0x120000000;
+const DATA_ALIGN: u64 = 0x10000;
+
+// The program starts at the first of these labels it defines, or at the
+// start of the text if it defines none of them.
+const ENTRY_LABELS: [&str; 3] = ["__start", "_start", "main"];
+
+// The SPECIAL opcode, which picks the instruction by its function field.
+const SPECIAL: i32 = 0x00;
+
+// Register names for the n64 ABI.
+pub(crate) const REGISTER_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3",
+    "a4", "a5", "a6", "a7", "t0", "t1", "t2", "t3",
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7",
+    "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+// The CMP.condn.fmt conditions, by their encoding.
+const FLOAT_CONDITIONS: [(&str, u32); 22] = [
+    ("af", 0x00), ("un", 0x01), ("eq", 0x02), ("ueq", 0x03),
+    ("lt", 0x04), ("ult", 0x05), ("le", 0x06), ("ule", 0x07),
+    ("saf", 0x08), ("sun", 0x09), ("seq", 0x0a), ("sueq", 0x0b),
+    ("slt", 0x0c), ("sult", 0x0d), ("sle", 0x0e), ("sule", 0x0f),
+    ("or", 0x11), ("une", 0x12), ("ne", 0x13),
+    ("sor", 0x19), ("sune", 0x1a), ("sne", 0x1b),
+];
+
+// MSA operations on three vectors, by minor opcode, with each operation's
+// number its position. Empty names are gaps.
+const VECTOR_OPS: [(i32, [&str; 8]); 9] = [
+    (decoder::MSA_3R_SHIFT,
+     ["sll", "sra", "srl", "bclr", "bset", "bneg", "binsl", "binsr"]),
+    (decoder::MSA_3R_ADD,
+     ["addv", "subv", "max_s", "max_u", "min_s", "min_u", "max_a", "min_a"]),
+    (decoder::MSA_3R_COMPARE,
+     ["ceq", "", "clt_s", "clt_u", "cle_s", "cle_u", "", ""]),
+    (decoder::MSA_3R_ADD_SATURATE,
+     ["add_a", "adds_a", "adds_s", "adds_u", "ave_s", "ave_u", "aver_s",
+      "aver_u"]),
+    (decoder::MSA_3R_SUBTRACT,
+     ["subs_s", "subs_u", "subsus_u", "subsuu_s", "asub_s", "asub_u", "", ""]),
+    (decoder::MSA_3R_MULTIPLY,
+     ["mulv", "maddv", "msubv", "", "div_s", "div_u", "mod_s", "mod_u"]),
+    (decoder::MSA_3R_DOT_PRODUCT,
+     ["dotp_s", "dotp_u", "dpadd_s", "dpadd_u", "dpsub_s", "dpsub_u", "", ""]),
+    (decoder::MSA_3R_SHUFFLE,
+     ["", "", "pckev", "pckod", "ilvl", "ilvr", "ilvev", "ilvod"]),
+    (decoder::MSA_3R_HORIZONTAL,
+     ["vshf", "srar", "srlr", "", "hadd_s", "hadd_u", "hsub_s", "hsub_u"]),
+];
+
+// The same for the ones with a five-bit immediate, which is signed for the
+// signed operations and CEQI.
+const VECTOR_IMMEDIATE_OPS: [(i32, [&str; 8]); 2] = [
+    (decoder::MSA_I5_ARITHMETIC,
+     ["addvi", "subvi", "maxi_s", "maxi_u", "mini_s", "mini_u", "", ""]),
+    (decoder::MSA_I5_COMPARE,
+     ["ceqi", "", "clti_s", "clti_u", "clei_s", "clei_u", "", ""]),
+];
+
+// And for the ones that take a bit number.
+const VECTOR_BIT_OPS: [(i32, [&str; 8]); 2] = [
+    (decoder::MSA_BIT_SHIFT,
+     ["slli", "srai", "srli", "bclri", "bseti", "bnegi", "binsli", "binsri"]),
+    (decoder::MSA_BIT_SATURATE,
+     ["sat_s", "sat_u", "srari", "srlri", "", "", "", ""]),
+];
+
+// The 3RF format: floating-point operations on W and D, and ones on H and W
+// for the ones that narrow or work on fixed point.
+const VECTOR_FLOAT_OPS: [(&str, i32, u32, bool); 19] = [
+    ("fadd", decoder::MSA_3RF_ARITHMETIC, 0, false),
+    ("fsub", decoder::MSA_3RF_ARITHMETIC, 1, false),
+    ("fmul", decoder::MSA_3RF_ARITHMETIC, 2, false),
+    ("fdiv", decoder::MSA_3RF_ARITHMETIC, 3, false),
+    ("fmadd", decoder::MSA_3RF_ARITHMETIC, 4, false),
+    ("fmsub", decoder::MSA_3RF_ARITHMETIC, 5, false),
+    ("fexp2", decoder::MSA_3RF_ARITHMETIC, 7, false),
+    ("fexdo", decoder::MSA_3RF_ARITHMETIC, 8, true),
+    ("ftq", decoder::MSA_3RF_ARITHMETIC, 10, true),
+    ("fmin", decoder::MSA_3RF_ARITHMETIC, 12, false),
+    ("fmin_a", decoder::MSA_3RF_ARITHMETIC, 13, false),
+    ("fmax", decoder::MSA_3RF_ARITHMETIC, 14, false),
+    ("fmax_a", decoder::MSA_3RF_ARITHMETIC, 15, false),
+    ("mul_q", decoder::MSA_3RF_FIXED, 4, true),
+    ("madd_q", decoder::MSA_3RF_FIXED, 5, true),
+    ("msub_q", decoder::MSA_3RF_FIXED, 6, true),
+    ("mulr_q", decoder::MSA_3RF_FIXED, 12, true),
+    ("maddr_q", decoder::MSA_3RF_FIXED, 13, true),
+    ("msubr_q", decoder::MSA_3RF_FIXED, 14, true),
+];
+
+// The 2RF format, in the order of their operation numbers.
+const VECTOR_FLOAT_UNARY_OPS: [&str; 16] = [
+    "fclass", "ftrunc_s", "ftrunc_u", "fsqrt", "frsqrt", "frcp", "frint",
+    "flog2", "fexupl", "fexupr", "ffql", "ffqr", "ftint_s", "ftint_u",
+    "ffint_s", "ffint_u",
+];
+
+// The 2R format, and the VEC and I8 bitwise operations, the same way.
+const VECTOR_COUNT_OPS: [&str; 4] = ["fill", "pcnt", "nloc", "nlzc"];
+const VECTOR_LOGIC_OPS: [&str; 7] = [
+    "and", "or", "nor", "xor", "bmnz", "bmz", "bsel",
+];
+
+// The ELM format's operations, by their operation numbers.
+const ELEMENT_OPS: [&str; 6] = [
+    "sldi", "splati", "copy_s", "copy_u", "insert", "insve",
+];
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    UnknownInstruction(String),
+    UnknownDirective(String),
+    UnknownRegister(String),
+    BadOperand(String),
+    WrongOperandCount { expected: usize, found: usize },
+    OutOfRange(i64),
+    Misaligned(i64),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    NeedsConstant(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownInstruction(name) =>
+                write!(f, "unknown instruction {}", name),
+            ErrorKind::UnknownDirective(name) =>
+                write!(f, "unknown directive {}", name),
+            ErrorKind::UnknownRegister(name) =>
+                write!(f, "unknown register {}", name),
+            ErrorKind::BadOperand(operand) =>
+                write!(f, "bad operand {}", operand),
+            ErrorKind::WrongOperandCount { expected, found } =>
+                write!(f, "expected {} operands but found {}", expected,
+                       found),
+            ErrorKind::OutOfRange(value) =>
+                write!(f, "value {} is out of range", value),
+            ErrorKind::Misaligned(offset) =>
+                write!(f, "offset {} is misaligned", offset),
+            ErrorKind::UndefinedLabel(label) =>
+                write!(f, "undefined label {}", label),
+            ErrorKind::DuplicateLabel(label) =>
+                write!(f, "label {} is already defined", label),
+            ErrorKind::NeedsConstant(operand) =>
+                write!(f, "{} must be a constant", operand),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Text,
+    Data,
+}
+
+struct Assembler {
+    // The first pass only works out where every label is; nothing it emits
+    // is kept. The second pass emits the real thing.
+    final_pass: bool,
+    labels: HashMap<String, (Section, u64)>,
+    section: Section,
+    text: Vec<u8>,
+    data: Vec<u8>,
+    data_base: u64,
+}
+
+// Assembles GNU as style source into a program that can be loaded like any
+// ELF file.
+pub fn assemble(source: &str) -> Result<Program, AssemblyError> {
+    let mut assembler = Assembler {
+        final_pass: false,
+        labels: HashMap::new(),
+        section: Section::Text,
+        text: Vec::new(),
+        data: Vec::new(),
+        data_base: 0,
+    };
+    assembler.pass(source)?;
+
+    // Everything is the same size the second time around, so the data can
+    // go right after the text.
+    assembler.data_base = (TEXT_BASE + assembler.text.len() as u64)
+        .div_ceil(DATA_ALIGN) * DATA_ALIGN;
+    assembler.final_pass = true;
+    assembler.section = Section::Text;
+    assembler.text.clear();
+    assembler.data.clear();
+    assembler.pass(source)?;
+
+    let entry = ENTRY_LABELS.iter()
+        .find_map(|label| assembler.labels.get(*label))
+        .map(|(section, offset)| assembler.address(*section, *offset))
+        .unwrap_or(TEXT_BASE);
+
+    let mut segments = vec![Segment {
+        address: TEXT_BASE,
+        size: assembler.text.len() as u64,
+        data: assembler.text,
+        executable: true,
+    }];
+    if !assembler.data.is_empty() {
+        segments.push(Segment {
+            address: assembler.data_base,
+            size: assembler.data.len() as u64,
+            data: assembler.data,
+            executable: false,
+        });
+    }
+
+    Ok(Program {
+        class: Class::Elf64,
+        endianness: Endianness::Big,
+        entry,
+        segments,
+        headers: None,
+        header_size: 0,
+        header_count: 0,
+    })
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {},
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' ||
+              c == '$')
+}
+
+// Drops everything from a '#' onwards, unless it's inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && in_string {
+            escaped = true;
+        } else if c == '"' {
+            in_string = !in_string;
+        } else if c == '#' && !in_string {
+            return &line[..i];
+        }
+    }
+    line
+}
+
+// Splits operands on the commas that aren't inside a string.
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && in_string {
+            escaped = true;
+        } else if c == '"' {
+            in_string = !in_string;
+        } else if c == ',' && !in_string {
+            operands.push(current.trim().to_string());
+            current.clear();
+            continue;
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current.trim().to_string());
+    }
+    operands
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let value = if let Some(hex) = text.strip_prefix("0x")
+            .or_else(|| text.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = text.strip_prefix("0b") {
+        u64::from_str_radix(binary, 2).ok()?
+    } else {
+        text.parse::<u64>().ok()?
+    };
+    Some(value as i64)
+}
+
+fn parse_string(operand: &str) -> Result<Vec<u8>, ErrorKind> {
+    let bad = || ErrorKind::BadOperand(operand.to_string());
+    let inner = operand.strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(bad)?;
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next().ok_or_else(bad)? {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            '0' => 0,
+            '\\' => b'\\',
+            '"' => b'"',
+            '\'' => b'\'',
+            _ => return Err(bad()),
+        });
+    }
+    Ok(bytes)
+}
+
+fn register(operand: &str) -> Result<u32, ErrorKind> {
+    let unknown = || ErrorKind::UnknownRegister(operand.to_string());
+    let name = operand.strip_prefix('$').ok_or_else(unknown)?;
+    if let Ok(number) = name.parse::<u32>() {
+        return if number < 32 { Ok(number) } else { Err(unknown()) };
+    }
+    if name == "s8" {
+        return Ok(30);
+    }
+    REGISTER_NAMES.iter()
+        .position(|register| *register == name)
+        .map(|number| number as u32)
+        .ok_or_else(unknown)
+}
+
+fn float_register(operand: &str) -> Result<u32, ErrorKind> {
+    operand.strip_prefix("$f")
+        .and_then(|number| number.parse::<u32>().ok())
+        .filter(|number| *number < 32)
+        .ok_or_else(|| ErrorKind::UnknownRegister(operand.to_string()))
+}
+
+fn vector_register(operand: &str) -> Result<u32, ErrorKind> {
+    operand.strip_prefix("$w")
+        .and_then(|number| number.parse::<u32>().ok())
+        .filter(|number| *number < 32)
+        .ok_or_else(|| ErrorKind::UnknownRegister(operand.to_string()))
+}
+
+// A vector register and what's in the brackets after it, like $w2[1].
+fn element_operand(operand: &str) -> Result<(u32, &str), ErrorKind> {
+    let (register, index) = operand.strip_suffix(']')
+        .and_then(|rest| rest.split_once('['))
+        .ok_or_else(|| ErrorKind::BadOperand(operand.to_string()))?;
+    Ok((vector_register(register.trim())?, index.trim()))
+}
+
+// Coprocessor and hardware registers only go by number.
+fn numbered_register(operand: &str) -> Result<u32, ErrorKind> {
+    operand.strip_prefix('$')
+        .and_then(|number| number.parse::<u32>().ok())
+        .filter(|number| *number < 32)
+        .ok_or_else(|| ErrorKind::UnknownRegister(operand.to_string()))
+}
+
+fn signed(value: i64, bits: u32) -> Result<u32, ErrorKind> {
+    let limit = 1i64 << (bits - 1);
+    if value < -limit || value >= limit {
+        Err(ErrorKind::OutOfRange(value))
+    } else {
+        Ok(value as u32 & ((1u64 << bits) - 1) as u32)
+    }
+}
+
+fn unsigned(value: i64, bits: u32) -> Result<u32, ErrorKind> {
+    if value < 0 || value >= 1i64 << bits {
+        Err(ErrorKind::OutOfRange(value))
+    } else {
+        Ok(value as u32)
+    }
+}
+
+fn expect(operands: &[String], count: usize) -> Result<(), ErrorKind> {
+    if operands.len() != count {
+        Err(ErrorKind::WrongOperandCount {
+            expected: count,
+            found: operands.len(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn encode(opcode: i32, rs: u32, rt: u32, rd: u32, sa: u32, function: i32)
+    -> u32 {
+    ((opcode as u32) << decoder::OPCODE) | (rs << decoder::RS) |
+        (rt << decoder::RT) | (rd << decoder::RD) | (sa << 6) | function as u32
+}
+
+fn encode_immediate(opcode: i32, rs: u32, rt: u32, immediate: u32) -> u32 {
+    ((opcode as u32) << decoder::OPCODE) | (rs << decoder::RS) |
+        (rt << decoder::RT) | (immediate & 0xffff)
+}
+
+impl Assembler {
+    fn pass(&mut self, source: &str) -> Result<(), AssemblyError> {
+        for (i, line) in source.lines().enumerate() {
+            self.statement(line)
+                .map_err(|kind| AssemblyError { line: i + 1, kind })?;
+        }
+        Ok(())
+    }
+
+    fn address(&self, section: Section, offset: u64) -> u64 {
+        match section {
+            Section::Text => TEXT_BASE + offset,
+            Section::Data => self.data_base + offset,
+        }
+    }
+
+    fn bytes(&mut self) -> &mut Vec<u8> {
+        match self.section {
+            Section::Text => &mut self.text,
+            Section::Data => &mut self.data,
+        }
+    }
+
+    // The address the next thing emitted will end up at.
+    fn pc(&mut self) -> u64 {
+        let offset = self.bytes().len() as u64;
+        self.address(self.section, offset)
+    }
+
+    // Programs are assembled big-endian, instructions and data alike.
+    fn emit_instruction(&mut self, instruction: u32) {
+        self.bytes().extend_from_slice(&instruction.to_be_bytes());
+    }
+
+    fn emit_value(&mut self, value: u64, size: usize) {
+        let bytes = value.to_be_bytes();
+        self.bytes().extend_from_slice(&bytes[bytes.len() - size..]);
+    }
+
+    fn statement(&mut self, line: &str) -> Result<(), ErrorKind> {
+        let mut rest = strip_comment(line).trim();
+
+        while let Some((label, after)) = rest.split_once(':') {
+            let label = label.trim();
+            if !is_identifier(label) {
+                break;
+            }
+            if !self.final_pass {
+                if self.labels.contains_key(label) {
+                    return Err(ErrorKind::DuplicateLabel(label.to_string()));
+                }
+                let offset = self.bytes().len() as u64;
+                self.labels.insert(label.to_string(), (self.section, offset));
+            }
+            rest = after.trim();
+        }
+
+        if rest.is_empty() {
+            return Ok(());
+        }
+        let (mnemonic, operands) = match rest.split_once(char::is_whitespace) {
+            None => (rest, ""),
+            Some((mnemonic, operands)) => (mnemonic, operands),
+        };
+        let mnemonic = mnemonic.to_lowercase();
+        let operands = split_operands(operands);
+        if mnemonic.starts_with('.') {
+            self.directive(&mnemonic, &operands)
+        } else {
+            self.instruction(&mnemonic, &operands)
+        }
+    }
+
+    // Evaluates a sum of numbers and labels. Also says whether a label was
+    // involved, since those are addresses rather than plain numbers. Labels
+    // are all zero in the first pass.
+    fn value(&self, operand: &str) -> Result<(i64, bool), ErrorKind> {
+        let bad = || ErrorKind::BadOperand(operand.to_string());
+        let mut total: i64 = 0;
+        let mut relocatable = false;
+        let mut negative = false;
+        let mut rest = operand.trim();
+        if rest.is_empty() {
+            return Err(bad());
+        }
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('-') {
+                negative = !negative;
+                rest = after.trim_start();
+                continue;
+            }
+            if let Some(after) = rest.strip_prefix('+') {
+                rest = after.trim_start();
+                continue;
+            }
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let term = rest[..end].trim();
+            rest = &rest[end..];
+
+            let value = if term.starts_with(|c: char| c.is_ascii_digit()) {
+                parse_number(term).ok_or_else(bad)?
+            } else if is_identifier(term) {
+                relocatable = true;
+                match self.labels.get(term) {
+                    Some((section, offset)) if self.final_pass =>
+                        self.address(*section, *offset) as i64,
+                    None if self.final_pass => return Err(
+                        ErrorKind::UndefinedLabel(term.to_string())),
+                    _ => 0,
+                }
+            } else {
+                return Err(bad());
+            };
+            total = if negative {
+                total.wrapping_sub(value)
+            } else {
+                total.wrapping_add(value)
+            };
+            negative = false;
+            if !rest.is_empty() && !rest.starts_with(['+', '-']) {
+                return Err(bad());
+            }
+        }
+        if negative {
+            return Err(bad());
+        }
+        Ok((total, relocatable))
+    }
+
+    fn constant(&self, operand: &str) -> Result<i64, ErrorKind> {
+        match self.value(operand)? {
+            (value, false) => Ok(value),
+            (_, true) => Err(ErrorKind::NeedsConstant(operand.to_string())),
+        }
+    }
+
+    // An offset from `base` to the operand. A label is turned into the
+    // offset to it; a plain number already is one. The result is in units
+    // of 1 << `shift` bytes and has to fit in `bits` bits.
+    fn offset(&self, operand: &str, base: u64, shift: u32, bits: u32)
+        -> Result<u32, ErrorKind> {
+        let (value, relocatable) = self.value(operand)?;
+        if !self.final_pass {
+            return Ok(0);
+        }
+        let offset = if relocatable {
+            value.wrapping_sub(base as i64)
+        } else {
+            value
+        };
+        if offset & ((1 << shift) - 1) != 0 {
+            return Err(ErrorKind::Misaligned(offset));
+        }
+        signed(offset >> shift, bits)
+    }
+
+    // A base register with an optional offset, like 8($sp). The offset has
+    // to fit in `bits` bits.
+    fn memory_operand(&self, operand: &str, bits: u32)
+        -> Result<(u32, u32), ErrorKind> {
+        self.scaled_memory_operand(operand, bits, 0)
+    }
+
+    // The same with an offset in units of 1 << `shift` bytes, the way
+    // vector loads and stores count elements.
+    fn scaled_memory_operand(&self, operand: &str, bits: u32, shift: u32)
+        -> Result<(u32, u32), ErrorKind> {
+        let bad = || ErrorKind::BadOperand(operand.to_string());
+        let (offset, base) = operand.strip_suffix(')')
+            .and_then(|rest| rest.split_once('('))
+            .ok_or_else(bad)?;
+        let offset = if offset.trim().is_empty() {
+            0
+        } else {
+            self.value(offset)?.0
+        };
+        if offset & ((1 << shift) - 1) != 0 {
+            return Err(ErrorKind::Misaligned(offset));
+        }
+        Ok((signed(offset >> shift, bits)?, register(base.trim())?))
+    }
+
+    fn directive(&mut self, name: &str, operands: &[String])
+        -> Result<(), ErrorKind> {
+        match name {
+            ".text" => self.section = Section::Text,
+            ".data" => self.section = Section::Data,
+            ".byte" | ".half" | ".word" | ".dword" => {
+                let size = match name {
+                    ".byte" => 1,
+                    ".half" => 2,
+                    ".word" => 4,
+                    _ => 8,
+                };
+                for operand in operands {
+                    let (value, _) = self.value(operand)?;
+                    self.emit_value(value as u64, size);
+                }
+            },
+            ".float" | ".double" => {
+                for operand in operands {
+                    let value = operand.parse::<f64>()
+                        .map_err(|_| ErrorKind::BadOperand(operand.clone()))?;
+                    if name == ".float" {
+                        self.emit_value((value as f32).to_bits() as u64, 4);
+                    } else {
+                        self.emit_value(value.to_bits(), 8);
+                    }
+                }
+            },
+            ".ascii" | ".asciiz" => {
+                for operand in operands {
+                    let string = parse_string(operand)?;
+                    self.bytes().extend_from_slice(&string);
+                    if name == ".asciiz" {
+                        self.bytes().push(0);
+                    }
+                }
+            },
+            ".space" => {
+                expect(operands, 1)?;
+                let size = unsigned(self.constant(&operands[0])?, 32)?;
+                let length = self.bytes().len() + size as usize;
+                self.bytes().resize(length, 0);
+            },
+            ".align" => {
+                expect(operands, 1)?;
+                let alignment = 1 << unsigned(self.constant(&operands[0])?, 4)?;
+                let length = self.bytes().len().div_ceil(alignment) * alignment;
+                self.bytes().resize(length, 0);
+            },
+            // Only matter to a linker.
+            ".globl" | ".global" | ".ent" | ".end" | ".set" => {},
+            _ => return Err(ErrorKind::UnknownDirective(name.to_string())),
+        }
+        Ok(())
+    }
+
+    fn instruction(&mut self, mnemonic: &str, operands: &[String])
+        -> Result<(), ErrorKind> {
+        let pc = self.pc();
+        let instruction = match mnemonic {
+            // Pseudo-instructions
+            "nop" => {
+                expect(operands, 0)?;
+                0
+            },
+            "move" => {
+                expect(operands, 2)?;
+                encode(SPECIAL, register(&operands[1])?, 0,
+                       register(&operands[0])?, 0, decoder::OR)
+            },
+            "b" => {
+                expect(operands, 1)?;
+                encode_immediate(decoder::BEQ, 0, 0,
+                                 self.offset(&operands[0], pc + 4, 2, 16)?)
+            },
+            "li" => {
+                expect(operands, 2)?;
+                let rt = register(&operands[0])?;
+                let value = self.constant(&operands[1])?;
+                return self.load_immediate(rt, value);
+            },
+            "la" => {
+                expect(operands, 2)?;
+                let rt = register(&operands[0])?;
+                let (address, _) = self.value(&operands[1])?;
+                return self.load_address(rt, address as u64);
+            },
+
+            // Naturally aligned loads and stores
+            "lb" => self.load_store(decoder::LB, operands)?,
+            "lbu" => self.load_store(decoder::LBU, operands)?,
+            "ld" => self.load_store(decoder::LD, operands)?,
+            "lh" => self.load_store(decoder::LH, operands)?,
+            "lhu" => self.load_store(decoder::LHU, operands)?,
+            "lw" => self.load_store(decoder::LW, operands)?,
+            "lwu" => self.load_store(decoder::LWU, operands)?,
+            "sb" => self.load_store(decoder::SB, operands)?,
+            "sd" => self.load_store(decoder::SD, operands)?,
+            "sh" => self.load_store(decoder::SH, operands)?,
+            "sw" => self.load_store(decoder::SW, operands)?,
+
+            // Load-linked and store-conditional
+            "ll" | "lld" | "sc" | "scd" => {
+                expect(operands, 2)?;
+                let function = match mnemonic {
+                    "ll" => decoder::LL,
+                    "lld" => decoder::LLD,
+                    "sc" => decoder::SC,
+                    _ => decoder::SCD,
+                };
+                let (offset, base) = self.memory_operand(&operands[1], 9)?;
+                encode(decoder::SPECIAL3, base, register(&operands[0])?, 0, 0,
+                       function) | (offset << 7)
+            },
+            "llwp" | "scwp" => {
+                expect(operands, 3)?;
+                let function = if mnemonic == "llwp" {
+                    decoder::LL
+                } else {
+                    decoder::SC
+                };
+                // There's no room for an offset.
+                let (offset, base) = self.memory_operand(&operands[2], 9)?;
+                if offset != 0 {
+                    return Err(ErrorKind::BadOperand(operands[2].clone()));
+                }
+                encode(decoder::SPECIAL3, base, register(&operands[0])?,
+                       register(&operands[1])?, decoder::PAIRED, function)
+            },
+            "sync" => {
+                let stype = match operands.len() {
+                    0 => 0,
+                    _ => {
+                        expect(operands, 1)?;
+                        unsigned(self.constant(&operands[0])?, 5)?
+                    },
+                };
+                encode(SPECIAL, 0, 0, 0, stype, decoder::SYNC)
+            },
+
+            // Cache hints
+            "pref" => {
+                expect(operands, 2)?;
+                let hint = unsigned(self.constant(&operands[0])?, 5)?;
+                let (offset, base) = self.memory_operand(&operands[1], 9)?;
+                encode(decoder::SPECIAL3, base, hint, 0, 0, decoder::PREF) |
+                    (offset << 7)
+            },
+            "synci" => {
+                expect(operands, 1)?;
+                let (offset, base) = self.memory_operand(&operands[0], 16)?;
+                encode_immediate(decoder::REGIMM, base, decoder::SYNCI as u32,
+                                 offset)
+            },
+
+            // PC-relative loads and addresses
+            "addiupc" | "lwpc" | "lwupc" => {
+                expect(operands, 2)?;
+                let function = match mnemonic {
+                    "addiupc" => decoder::ADDIUPC,
+                    "lwpc" => decoder::LWPC,
+                    _ => decoder::LWUPC,
+                };
+                encode(decoder::PCREL, register(&operands[0])?, 0, 0, 0, 0) |
+                    ((function as u32) << 19) |
+                    self.offset(&operands[1], pc, 2, 19)?
+            },
+            "ldpc" => {
+                expect(operands, 2)?;
+                encode(decoder::PCREL, register(&operands[0])?, 0, 0, 0, 0) |
+                    ((decoder::LDPC as u32) << 18) |
+                    self.offset(&operands[1], pc & !7, 3, 18)?
+            },
+            "auipc" | "aluipc" => {
+                expect(operands, 2)?;
+                let value = self.constant(&operands[1])?;
+                let immediate = unsigned(value, 16).or(signed(value, 16))?;
+                let function = if mnemonic == "auipc" {
+                    decoder::AUIPC
+                } else {
+                    decoder::ALUIPC
+                };
+                encode_immediate(decoder::PCREL, register(&operands[0])?,
+                                 function as u32, immediate)
+            },
+
+            // ALU instructions with a 16-bit immediate
+            "addiu" => self.alu_immediate(decoder::ADDIU, operands, true)?,
+            "andi" => self.alu_immediate(decoder::ANDI, operands, false)?,
+            "daddiu" => self.alu_immediate(decoder::DADDIU, operands, true)?,
+            "ori" => self.alu_immediate(decoder::ORI, operands, false)?,
+            "slti" => self.alu_immediate(decoder::SLTI, operands, true)?,
+            "sltiu" => self.alu_immediate(decoder::SLTIU, operands, true)?,
+            "xori" => self.alu_immediate(decoder::XORI, operands, false)?,
+            "lui" => {
+                expect(operands, 2)?;
+                let value = self.constant(&operands[1])?;
+                let immediate = unsigned(value, 16).or(signed(value, 16))?;
+                encode_immediate(decoder::LUI, 0, register(&operands[0])?,
+                                 immediate)
+            },
+            // AUI with rs $zero would be LUI.
+            "aui" | "daui" => {
+                expect(operands, 3)?;
+                let rs = nonzero(register(&operands[1])?, &operands[1])?;
+                let value = self.constant(&operands[2])?;
+                let immediate = unsigned(value, 16).or(signed(value, 16))?;
+                let opcode = if mnemonic == "aui" {
+                    decoder::AUI
+                } else {
+                    decoder::DAUI
+                };
+                encode_immediate(opcode, rs, register(&operands[0])?, immediate)
+            },
+            "dahi" | "dati" => {
+                expect(operands, 2)?;
+                let value = self.constant(&operands[1])?;
+                let immediate = unsigned(value, 16).or(signed(value, 16))?;
+                let rt = if mnemonic == "dahi" {
+                    decoder::DAHI
+                } else {
+                    decoder::DATI
+                };
+                encode_immediate(decoder::REGIMM, register(&operands[0])?,
+                                 rt as u32, immediate)
+            },
+
+            // Three-operand ALU instructions
+            "add" => three_operand(operands, 0, decoder::ADD)?,
+            "addu" => three_operand(operands, 0, decoder::ADDU)?,
+            "and" => three_operand(operands, 0, decoder::AND)?,
+            "dadd" => three_operand(operands, 0, decoder::DADD)?,
+            "daddu" => three_operand(operands, 0, decoder::DADDU)?,
+            "dsub" => three_operand(operands, 0, decoder::DSUB)?,
+            "dsubu" => three_operand(operands, 0, decoder::DSUBU)?,
+            "nor" => three_operand(operands, 0, decoder::NOR)?,
+            "or" => three_operand(operands, 0, decoder::OR)?,
+            "slt" => three_operand(operands, 0, decoder::SLT)?,
+            "sltu" => three_operand(operands, 0, decoder::SLTU)?,
+            "sub" => three_operand(operands, 0, decoder::SUB)?,
+            "subu" => three_operand(operands, 0, decoder::SUBU)?,
+            "xor" => three_operand(operands, 0, decoder::XOR)?,
+            "seleqz" => three_operand(operands, 0, decoder::SELEQZ)?,
+            "selnez" => three_operand(operands, 0, decoder::SELNEZ)?,
+            // The shift is 1 to 4, and encoded as one less.
+            "lsa" | "dlsa" => {
+                expect(operands, 4)?;
+                let sa = self.constant(&operands[3])?;
+                if !(1..=4).contains(&sa) {
+                    return Err(ErrorKind::OutOfRange(sa));
+                }
+                let function = if mnemonic == "lsa" {
+                    decoder::LSA
+                } else {
+                    decoder::DLSA
+                };
+                encode(SPECIAL, register(&operands[1])?,
+                       register(&operands[2])?, register(&operands[0])?,
+                       sa as u32 - 1, function)
+            },
+
+            // Two-operand ALU instructions. The function constants include
+            // the sa field.
+            "clo" => two_operand(operands, decoder::CLO)?,
+            "clz" => two_operand(operands, decoder::CLZ)?,
+            "dclo" => two_operand(operands, decoder::DCLO)?,
+            "dclz" => two_operand(operands, decoder::DCLZ)?,
+
+            // Shifts by a constant. The doubleword ones also take amounts
+            // of 32 and up, which turn into the *32 forms.
+            "sll" => self.shift(operands, 0, decoder::SLL, None)?,
+            "srl" => self.shift(operands, 0, decoder::SRL, None)?,
+            "sra" => self.shift(operands, 0, decoder::SRA, None)?,
+            "rotr" => self.shift(operands, 1, decoder::SRL, None)?,
+            "dsll" => self.shift(operands, 0, decoder::DSLL, Some(decoder::DSLL32))?,
+            "dsrl" => self.shift(operands, 0, decoder::DSRL, Some(decoder::DSRL32))?,
+            "dsra" => self.shift(operands, 0, decoder::DSRA, Some(decoder::DSRA32))?,
+            "drotr" => self.shift(operands, 1, decoder::DSRL, Some(decoder::DSRL32))?,
+            "dsll32" => self.shift(operands, 0, decoder::DSLL32, None)?,
+            "dsrl32" => self.shift(operands, 0, decoder::DSRL32, None)?,
+            "dsra32" => self.shift(operands, 0, decoder::DSRA32, None)?,
+            "drotr32" => self.shift(operands, 1, decoder::DSRL32, None)?,
+
+            // Shifts by a register
+            "sllv" => shift_variable(operands, 0, decoder::SLLV)?,
+            "srlv" => shift_variable(operands, 0, decoder::SRLV)?,
+            "srav" => shift_variable(operands, 0, decoder::SRAV)?,
+            "rotrv" => shift_variable(operands, 1, decoder::SRLV)?,
+            "dsllv" => shift_variable(operands, 0, decoder::DSLLV)?,
+            "dsrlv" => shift_variable(operands, 0, decoder::DSRLV)?,
+            "dsrav" => shift_variable(operands, 0, decoder::DSRAV)?,
+            "drotrv" => shift_variable(operands, 1, decoder::DSRLV)?,
+
+            // Byte shuffles
+            "align" | "dalign" => {
+                expect(operands, 4)?;
+                let bp = self.constant(&operands[3])?;
+                let (function, shuffle) = if mnemonic == "align" {
+                    (decoder::BSHFL, ((decoder::ALIGN as u32) << 2) | unsigned(bp, 2)?)
+                } else {
+                    (decoder::DBSHFL,
+                     ((decoder::DALIGN as u32) << 3) | unsigned(bp, 3)?)
+                };
+                encode(decoder::SPECIAL3, register(&operands[1])?,
+                       register(&operands[2])?, register(&operands[0])?,
+                       shuffle, function)
+            },
+            "bitswap" | "dbitswap" => {
+                expect(operands, 2)?;
+                let (function, shuffle) = if mnemonic == "bitswap" {
+                    (decoder::BSHFL, decoder::BITSWAP)
+                } else {
+                    (decoder::DBSHFL, decoder::DBITSWAP)
+                };
+                encode(decoder::SPECIAL3, 0, register(&operands[1])?,
+                       register(&operands[0])?, shuffle as u32, function)
+            },
+            "wsbh" | "seb" | "seh" | "dsbh" | "dshd" => {
+                expect(operands, 2)?;
+                let (function, shuffle) = match mnemonic {
+                    "wsbh" => (decoder::BSHFL, decoder::WSBH),
+                    "seb" => (decoder::BSHFL, decoder::SEB),
+                    "seh" => (decoder::BSHFL, decoder::SEH),
+                    "dsbh" => (decoder::DBSHFL, decoder::DSBH),
+                    _ => (decoder::DBSHFL, decoder::DSHD),
+                };
+                encode(decoder::SPECIAL3, 0, register(&operands[1])?,
+                       register(&operands[0])?, shuffle as u32, function)
+            },
+
+            // Bit fields
+            "ext" => self.bit_field(operands, false, false)?,
+            "ins" => self.bit_field(operands, true, false)?,
+            "dext" | "dextm" | "dextu" =>
+                self.bit_field(operands, false, true)?,
+            "dins" | "dinsm" | "dinsu" =>
+                self.bit_field(operands, true, true)?,
+
+            "rdhwr" => {
+                expect(operands, 2)?;
+                encode(decoder::SPECIAL3, 0, register(&operands[0])?,
+                       numbered_register(&operands[1])?, 0, decoder::RDHWR)
+            },
+
+            // Same-width multiplies and divides
+            "mul" => three_operand(operands, decoder::MUL, decoder::SOP30)?,
+            "muh" => three_operand(operands, decoder::MUH, decoder::SOP30)?,
+            "mulu" => three_operand(operands, decoder::MULU, decoder::SOP31)?,
+            "muhu" => three_operand(operands, decoder::MUHU, decoder::SOP31)?,
+            "dmul" => three_operand(operands, decoder::DMUL, decoder::SOP34)?,
+            "dmuh" => three_operand(operands, decoder::DMUH, decoder::SOP34)?,
+            "dmulu" => three_operand(operands, decoder::DMULU, decoder::SOP35)?,
+            "dmuhu" => three_operand(operands, decoder::DMUHU, decoder::SOP35)?,
+            "div" => three_operand(operands, decoder::DIV, decoder::SOP32)?,
+            "mod" => three_operand(operands, decoder::MOD, decoder::SOP32)?,
+            "divu" => three_operand(operands, decoder::DIVU, decoder::SOP33)?,
+            "modu" => three_operand(operands, decoder::MODU, decoder::SOP33)?,
+            "ddiv" => three_operand(operands, decoder::DDIV, decoder::SOP36)?,
+            "dmod" => three_operand(operands, decoder::DMOD, decoder::SOP36)?,
+            "ddivu" => three_operand(operands, decoder::DDIVU, decoder::SOP37)?,
+            "dmodu" => three_operand(operands, decoder::DMODU, decoder::SOP37)?,
+
+            // Compact branches and jumps
+            "bc" | "balc" => {
+                expect(operands, 1)?;
+                let opcode = if mnemonic == "bc" { decoder::BC } else { decoder::BALC };
+                encode(opcode, 0, 0, 0, 0, 0) |
+                    self.offset(&operands[0], pc + 4, 2, 26)?
+            },
+            "jic" | "jialc" => {
+                expect(operands, 2)?;
+                let opcode = if mnemonic == "jic" {
+                    decoder::POP66
+                } else {
+                    decoder::POP76
+                };
+                let offset = signed(self.constant(&operands[1])?, 16)?;
+                encode_immediate(opcode, 0, register(&operands[0])?, offset)
+            },
+            "beqzc" | "bnezc" => {
+                expect(operands, 2)?;
+                let opcode = if mnemonic == "beqzc" {
+                    decoder::POP66
+                } else {
+                    decoder::POP76
+                };
+                let rs = nonzero(register(&operands[0])?, &operands[0])?;
+                encode(opcode, rs, 0, 0, 0, 0) |
+                    self.offset(&operands[1], pc + 4, 2, 21)?
+            },
+            "bgeuc" => self.compare_branch(operands, decoder::POP06, pc)?,
+            "bltuc" => self.compare_branch(operands, decoder::POP07, pc)?,
+            "bgec" => self.compare_branch(operands, decoder::POP26, pc)?,
+            "bltc" => self.compare_branch(operands, decoder::POP27, pc)?,
+            "blezalc" => self.zero_branch(operands, decoder::POP06, false, pc)?,
+            "bgezalc" => self.zero_branch(operands, decoder::POP06, true, pc)?,
+            "bgtzalc" => self.zero_branch(operands, decoder::POP07, false, pc)?,
+            "bltzalc" => self.zero_branch(operands, decoder::POP07, true, pc)?,
+            "blezc" => self.zero_branch(operands, decoder::POP26, false, pc)?,
+            "bgezc" => self.zero_branch(operands, decoder::POP26, true, pc)?,
+            "bgtzc" => self.zero_branch(operands, decoder::POP27, false, pc)?,
+            "bltzc" => self.zero_branch(operands, decoder::POP27, true, pc)?,
+            "beqzalc" => self.zero_branch(operands, decoder::POP10, false, pc)?,
+            "bnezalc" => self.zero_branch(operands, decoder::POP30, false, pc)?,
+            "beqc" | "bnec" | "bovc" | "bnvc" => {
+                expect(operands, 3)?;
+                let opcode = match mnemonic {
+                    "beqc" | "bovc" => decoder::POP10,
+                    _ => decoder::POP30,
+                };
+                let a = register(&operands[0])?;
+                let b = register(&operands[1])?;
+                // The register order tells these apart: BEQC and BNEC need
+                // rs < rt and neither can be $zero, while BOVC and BNVC get
+                // everything else.
+                let (rs, rt) = match mnemonic {
+                    "beqc" | "bnec" => {
+                        nonzero(a, &operands[0])?;
+                        nonzero(b, &operands[1])?;
+                        if a == b {
+                            return Err(ErrorKind::BadOperand(
+                                operands[1].clone()));
+                        }
+                        (a.min(b), a.max(b))
+                    },
+                    _ => (a.max(b), a.min(b)),
+                };
+                encode(opcode, rs, rt, 0, 0, 0) |
+                    self.offset(&operands[2], pc + 4, 2, 16)?
+            },
+
+            // Delayed branches and jumps
+            "beq" | "bne" => {
+                expect(operands, 3)?;
+                let opcode = if mnemonic == "beq" {
+                    decoder::BEQ
+                } else {
+                    decoder::BNE
+                };
+                encode_immediate(opcode, register(&operands[0])?,
+                                 register(&operands[1])?,
+                                 self.offset(&operands[2], pc + 4, 2, 16)?)
+            },
+            "blez" | "bgtz" => {
+                expect(operands, 2)?;
+                let opcode = if mnemonic == "blez" {
+                    decoder::BLEZ
+                } else {
+                    decoder::BGTZ
+                };
+                encode_immediate(opcode, register(&operands[0])?, 0,
+                                 self.offset(&operands[1], pc + 4, 2, 16)?)
+            },
+            "bltz" | "bgez" => {
+                expect(operands, 2)?;
+                let rt = if mnemonic == "bltz" {
+                    decoder::BLTZ
+                } else {
+                    decoder::BGEZ
+                };
+                encode_immediate(decoder::REGIMM, register(&operands[0])?,
+                                 rt as u32,
+                                 self.offset(&operands[1], pc + 4, 2, 16)?)
+            },
+            "bal" => {
+                expect(operands, 1)?;
+                encode_immediate(decoder::REGIMM, 0, decoder::BGEZAL as u32,
+                                 self.offset(&operands[0], pc + 4, 2, 16)?)
+            },
+            // Links without branching, since $zero is never less than zero.
+            "nal" => {
+                expect(operands, 0)?;
+                encode_immediate(decoder::REGIMM, 0, decoder::BLTZAL as u32, 0)
+            },
+            "j" | "jal" => {
+                expect(operands, 1)?;
+                let opcode = if mnemonic == "j" { decoder::J } else { decoder::JAL };
+                let (target, _) = self.value(&operands[0])?;
+                let target = target as u64;
+                if self.final_pass {
+                    if target & 3 != 0 {
+                        return Err(ErrorKind::Misaligned(target as i64));
+                    }
+                    // Jumps stay inside the 256 MiB region they start in.
+                    if target >> 28 != (pc + 4) >> 28 {
+                        return Err(ErrorKind::OutOfRange(target as i64));
+                    }
+                }
+                encode(opcode, 0, 0, 0, 0, 0) | ((target >> 2) as u32 & 0x3ffffff)
+            },
+            "jalr" => {
+                let (rd, rs) = match operands.len() {
+                    1 => (31, register(&operands[0])?),
+                    _ => {
+                        expect(operands, 2)?;
+                        (register(&operands[0])?, register(&operands[1])?)
+                    },
+                };
+                encode(SPECIAL, rs, 0, rd, 0, decoder::JALR)
+            },
+            "jr" => {
+                expect(operands, 1)?;
+                encode(SPECIAL, register(&operands[0])?, 0, 0, 0, decoder::JALR)
+            },
+
+            // Coprocessor 0
+            "mfc0" | "dmfc0" | "mtc0" | "dmtc0" => {
+                if operands.len() != 3 {
+                    expect(operands, 2)?;
+                }
+                let function = match mnemonic {
+                    "mfc0" => decoder::MFC0,
+                    "dmfc0" => decoder::DMFC0,
+                    "mtc0" => decoder::MTC0,
+                    _ => decoder::DMTC0,
+                };
+                let sel = match operands.get(2) {
+                    None => 0,
+                    Some(sel) => unsigned(self.constant(sel)?, 3)?,
+                };
+                encode(decoder::COP0, function as u32, register(&operands[0])?,
+                       numbered_register(&operands[1])?, 0, 0) | sel
+            },
+            "di" | "ei" => {
+                let rt = match operands.len() {
+                    0 => 0,
+                    _ => {
+                        expect(operands, 1)?;
+                        register(&operands[0])?
+                    },
+                };
+                let enable = if mnemonic == "ei" { 1 << 5 } else { 0 };
+                encode(decoder::COP0, decoder::MFMC0 as u32, rt, cp0::STATUS as u32,
+                       0, 0) | enable
+            },
+            "eret" | "deret" | "wait" | "tlbp" | "tlbr" | "tlbwi" | "tlbwr" |
+            "tlbinv" | "tlbinvf" => {
+                expect(operands, 0)?;
+                let function = match mnemonic {
+                    "eret" => decoder::ERET,
+                    "deret" => decoder::DERET,
+                    "tlbp" => decoder::TLBP,
+                    "tlbr" => decoder::TLBR,
+                    "tlbwi" => decoder::TLBWI,
+                    "tlbwr" => decoder::TLBWR,
+                    "tlbinv" => decoder::TLBINV,
+                    "tlbinvf" => decoder::TLBINVF,
+                    _ => decoder::WAIT,
+                };
+                encode(decoder::COP0, decoder::C0 as u32, 0, 0, 0, function)
+            },
+
+            // Exceptions, with an optional code for the handler
+            "teq" | "tne" | "tge" | "tgeu" | "tlt" | "tltu" => {
+                let code = match operands.len() {
+                    2 => 0,
+                    _ => {
+                        expect(operands, 3)?;
+                        unsigned(self.constant(&operands[2])?, 10)?
+                    },
+                };
+                let function = match mnemonic {
+                    "teq" => decoder::TEQ,
+                    "tne" => decoder::TNE,
+                    "tge" => decoder::TGE,
+                    "tgeu" => decoder::TGEU,
+                    "tlt" => decoder::TLT,
+                    _ => decoder::TLTU,
+                };
+                encode(SPECIAL, register(&operands[0])?,
+                       register(&operands[1])?, 0, 0, function) | (code << 6)
+            },
+            "sigrie" => {
+                expect(operands, 1)?;
+                encode_immediate(decoder::REGIMM, 0, decoder::SIGRIE as u32,
+                                 unsigned(self.constant(&operands[0])?, 16)?)
+            },
+            "sdbbp" => {
+                let code = match operands.len() {
+                    0 => 0,
+                    _ => {
+                        expect(operands, 1)?;
+                        unsigned(self.constant(&operands[0])?, 20)?
+                    },
+                };
+                encode(SPECIAL, 0, 0, 0, 0, decoder::SDBBP) | (code << 6)
+            },
+            "syscall" => {
+                let code = match operands.len() {
+                    0 => 0,
+                    _ => {
+                        expect(operands, 1)?;
+                        unsigned(self.constant(&operands[0])?, 20)?
+                    },
+                };
+                encode(SPECIAL, 0, 0, 0, 0, decoder::SYSCALL) | (code << 6)
+            },
+            // BREAK's code is split in two 10-bit halves, and a single code
+            // goes in the upper one.
+            "break" => {
+                if operands.len() > 2 {
+                    expect(operands, 2)?;
+                }
+                let mut code = 0;
+                for (i, operand) in operands.iter().enumerate() {
+                    code |= unsigned(self.constant(operand)?, 10)? <<
+                        (16 - 10 * i);
+                }
+                encode(SPECIAL, 0, 0, 0, 0, decoder::BREAK) | code
+            },
+
+            // Floating point
+            "lwc1" => self.float_load_store(decoder::LWC1, operands)?,
+            "ldc1" => self.float_load_store(decoder::LDC1, operands)?,
+            "swc1" => self.float_load_store(decoder::SWC1, operands)?,
+            "sdc1" => self.float_load_store(decoder::SDC1, operands)?,
+            "mfc1" | "dmfc1" | "mfhc1" | "mtc1" | "dmtc1" | "mthc1" => {
+                expect(operands, 2)?;
+                let function = match mnemonic {
+                    "mfc1" => decoder::MFC1,
+                    "dmfc1" => decoder::DMFC1,
+                    "mfhc1" => decoder::MFHC1,
+                    "mtc1" => decoder::MTC1,
+                    "dmtc1" => decoder::DMTC1,
+                    _ => decoder::MTHC1,
+                };
+                encode(decoder::COP1, function as u32, register(&operands[0])?,
+                       float_register(&operands[1])?, 0, 0)
+            },
+            "cfc1" | "ctc1" => {
+                expect(operands, 2)?;
+                let function = if mnemonic == "cfc1" {
+                    decoder::CFC1
+                } else {
+                    decoder::CTC1
+                };
+                encode(decoder::COP1, function as u32, register(&operands[0])?,
+                       numbered_register(&operands[1])?, 0, 0)
+            },
+            "bc1eqz" | "bc1nez" => {
+                expect(operands, 2)?;
+                let function = if mnemonic == "bc1eqz" {
+                    decoder::BC1EQZ
+                } else {
+                    decoder::BC1NEZ
+                };
+                encode_immediate(decoder::COP1, function as u32,
+                                 float_register(&operands[0])?,
+                                 self.offset(&operands[1], pc + 4, 2, 16)?)
+            },
+            "ctcmsa" => {
+                expect(operands, 2)?;
+                encode_msa(0, decoder::MSA_ELM_CONTROL << 16,
+                           register(&operands[1])?,
+                           numbered_register(&operands[0])?, decoder::MSA_ELM)
+            },
+            "cfcmsa" => {
+                expect(operands, 2)?;
+                encode_msa(1 << 22, decoder::MSA_ELM_CONTROL << 16,
+                           numbered_register(&operands[1])?,
+                           register(&operands[0])?, decoder::MSA_ELM)
+            },
+            _ if mnemonic.contains('.') => {
+                match self.vector(mnemonic, operands, pc)? {
+                    Some(instruction) => instruction,
+                    None => float(mnemonic, operands)?,
+                }
+            },
+
+            _ => return Err(ErrorKind::UnknownInstruction(
+                mnemonic.to_string())),
+        };
+        self.emit_instruction(instruction);
+        Ok(())
+    }
+
+    // MSA instructions, named for the operation and then the element format,
+    // or v for the ones on whole vectors. None if the name isn't one.
+    fn vector(&self, mnemonic: &str, operands: &[String], pc: u64)
+        -> Result<Option<u32>, ErrorKind> {
+        let unknown = || ErrorKind::UnknownInstruction(mnemonic.to_string());
+        let (name, format) = mnemonic.rsplit_once('.').ok_or_else(unknown)?;
+        let df = match format {
+            "b" => 0,
+            "h" => 1,
+            "w" => 2,
+            "d" => 3,
+            "v" => {
+                let instruction = if name == "move" {
+                    expect(operands, 2)?;
+                    encode_msa(2 << 22, decoder::MSA_ELM_CONTROL << 16,
+                               vector_register(&operands[1])?,
+                               vector_register(&operands[0])?, decoder::MSA_ELM)
+                } else if name == "bz" || name == "bnz" {
+                    let function = if name == "bz" {
+                        decoder::BZ_V
+                    } else {
+                        decoder::BNZ_V
+                    };
+                    self.vector_branch(function, operands, pc)?
+                } else if let Some(op) = VECTOR_LOGIC_OPS.iter()
+                        .position(|op| *op == name) {
+                    expect(operands, 3)?;
+                    encode_msa((op as u32) << 21,
+                               vector_register(&operands[2])? << 16,
+                               vector_register(&operands[1])?,
+                               vector_register(&operands[0])?, decoder::MSA_VEC)
+                } else {
+                    return Ok(None);
+                };
+                return Ok(Some(instruction));
+            },
+            _ => return Ok(None),
+        };
+        let three = |operands: &[String]|
+            -> Result<(u32, u32, u32), ErrorKind> {
+            expect(operands, 3)?;
+            Ok((vector_register(&operands[2])?, vector_register(&operands[1])?,
+                vector_register(&operands[0])?))
+        };
+        let two = |operands: &[String]| -> Result<(u32, u32), ErrorKind> {
+            expect(operands, 2)?;
+            Ok((vector_register(&operands[1])?, vector_register(&operands[0])?))
+        };
+        // The number of bits it takes to index the elements.
+        let index_bits = 4 - df;
+
+        let instruction = if let Some((minor, op)) =
+                vector_operation(&VECTOR_OPS, name) {
+            let (wt, ws, wd) = three(operands)?;
+            encode_msa((op << 23) | (df << 21), wt << 16, ws, wd, minor)
+        } else if let Some((minor, op)) =
+                vector_operation(&VECTOR_IMMEDIATE_OPS, name) {
+            expect(operands, 3)?;
+            let value = self.constant(&operands[2])?;
+            let immediate = if name.ends_with("_s") || name == "ceqi" {
+                signed(value, 5)?
+            } else {
+                unsigned(value, 5)?
+            };
+            encode_msa((op << 23) | (df << 21), immediate << 16,
+                       vector_register(&operands[1])?,
+                       vector_register(&operands[0])?, minor)
+        } else if let Some((minor, op)) =
+                vector_operation(&VECTOR_BIT_OPS, name) {
+            expect(operands, 3)?;
+            // The format is marked by the number of ones above the bit
+            // number, three for B down to none for D.
+            let m = unsigned(self.constant(&operands[2])?, 3 + df)?;
+            let prefix = (0x7f << (4 + df)) & 0x7f;
+            encode_msa(op << 23, (prefix | m) << 16,
+                       vector_register(&operands[1])?,
+                       vector_register(&operands[0])?, minor)
+        } else if let Some((_, minor, op, fixed)) = VECTOR_FLOAT_OPS.iter()
+                .find(|(op, _, _, _)| *op == name) {
+            let (wt, ws, wd) = three(operands)?;
+            let first = if *fixed { 1 } else { 2 };
+            if df != first && df != first + 1 {
+                return Err(unknown());
+            }
+            encode_msa((op << 22) | ((df - first) << 21), wt << 16, ws, wd,
+                       *minor)
+        } else if let Some(code) = vector_condition(name) {
+            let (wt, ws, wd) = three(operands)?;
+            let minor = if code & 0x10 == 0 {
+                decoder::MSA_3RF_COMPARE
+            } else {
+                decoder::MSA_3RF_FIXED
+            };
+            if df < 2 {
+                return Err(unknown());
+            }
+            encode_msa(((code & 0xf) << 22) | ((df - 2) << 21), wt << 16, ws,
+                       wd, minor)
+        } else if let Some(op) = VECTOR_FLOAT_UNARY_OPS.iter()
+                .position(|op| *op == name) {
+            let (ws, wd) = two(operands)?;
+            if df < 2 {
+                return Err(unknown());
+            }
+            encode_msa((decoder::MSA_2RF << 21) | ((op as u32) << 17),
+                       (df - 2) << 16, ws, wd, decoder::MSA_VEC)
+        } else if let Some(op) = VECTOR_COUNT_OPS.iter()
+                .position(|op| *op == name) {
+            expect(operands, 2)?;
+            let ws = if op == 0 {
+                register(&operands[1])?
+            } else {
+                vector_register(&operands[1])?
+            };
+            encode_msa((decoder::MSA_2R << 21) | ((op as u32) << 18), df << 16,
+                       ws, vector_register(&operands[0])?, decoder::MSA_VEC)
+        } else if let Some(op) = ELEMENT_OPS.iter().position(|op| *op == name) {
+            expect(operands, 2)?;
+            // INSERT and INSVE put the element on the destination.
+            let (wd, ws, n) = match op {
+                4 => {
+                    let (wd, n) = element_operand(&operands[0])?;
+                    (wd, register(&operands[1])?, n)
+                },
+                5 => {
+                    let (wd, n) = element_operand(&operands[0])?;
+                    let (ws, zero) = element_operand(&operands[1])?;
+                    if self.constant(zero)? != 0 {
+                        return Err(ErrorKind::BadOperand(operands[1].clone()));
+                    }
+                    (wd, ws, n)
+                },
+                _ => {
+                    let (ws, n) = element_operand(&operands[1])?;
+                    let wd = if op == 2 || op == 3 {
+                        register(&operands[0])?
+                    } else {
+                        vector_register(&operands[0])?
+                    };
+                    (wd, ws, n)
+                },
+            };
+            let n = unsigned(self.constant(n)?, index_bits)?;
+            // The same with the index, from none for B up to three for D.
+            let prefix = (0x3f << (6 - df)) & 0x3f;
+            encode_msa((op as u32) << 22, (prefix | n) << 16, ws, wd,
+                       decoder::MSA_ELM)
+        } else if name == "sld" || name == "splat" {
+            expect(operands, 2)?;
+            let (ws, rt) = element_operand(&operands[1])?;
+            let op = if name == "sld" { 0 } else { 1 };
+            encode_msa((op << 23) | (df << 21), register(rt)? << 16, ws,
+                       vector_register(&operands[0])?, decoder::MSA_3R_SHUFFLE)
+        } else if name == "ldi" {
+            expect(operands, 2)?;
+            let immediate = signed(self.constant(&operands[1])?, 10)?;
+            encode_msa((decoder::LDI << 23) | (df << 21), immediate << 16 >> 5,
+                       0, vector_register(&operands[0])?,
+                       decoder::MSA_I5_COMPARE)
+        } else if name == "shf" {
+            expect(operands, 3)?;
+            let immediate = unsigned(self.constant(&operands[2])?, 8)?;
+            encode_msa(df << 24, immediate << 16,
+                       vector_register(&operands[1])?,
+                       vector_register(&operands[0])?, decoder::MSA_I8_SHF)
+        } else if let Some(op) = name.strip_suffix('i')
+                .and_then(|op| VECTOR_LOGIC_OPS.iter().position(|o| *o == op)) {
+            expect(operands, 3)?;
+            if df != 0 {
+                return Err(unknown());
+            }
+            let immediate = unsigned(self.constant(&operands[2])?, 8)?;
+            let (minor, op) = if op < 4 {
+                (decoder::MSA_I8_LOGIC, op)
+            } else {
+                (decoder::MSA_I8_SELECT, op - 4)
+            };
+            encode_msa((op as u32) << 24, immediate << 16,
+                       vector_register(&operands[1])?,
+                       vector_register(&operands[0])?, minor)
+        } else if name == "ld" || name == "st" {
+            expect(operands, 2)?;
+            let (offset, base) =
+                self.scaled_memory_operand(&operands[1], 10, df)?;
+            let minor = if name == "ld" {
+                decoder::MSA_LD
+            } else {
+                decoder::MSA_ST
+            };
+            encode_msa(0, offset << 16, base, vector_register(&operands[0])?,
+                       minor | df as i32)
+        } else if name == "bz" || name == "bnz" {
+            let function = if name == "bz" {
+                decoder::BZ_DF
+            } else {
+                decoder::BNZ_DF
+            };
+            self.vector_branch(function + df as i32, operands, pc)?
+        } else {
+            return Ok(None);
+        };
+        // The decoder knows which formats each operation takes.
+        if decoder::decode(instruction, decoder::IsaRevision::Release6) ==
+                decoder::Instruction::Reserved {
+            return Err(unknown());
+        }
+        Ok(Some(instruction))
+    }
+
+    fn vector_branch(&self, function: i32, operands: &[String], pc: u64)
+        -> Result<u32, ErrorKind> {
+        expect(operands, 2)?;
+        Ok(encode_immediate(decoder::COP1, function as u32,
+                            vector_register(&operands[0])?,
+                            self.offset(&operands[1], pc + 4, 2, 16)?))
+    }
+
+    fn load_store(&self, opcode: i32, operands: &[String])
+        -> Result<u32, ErrorKind> {
+        expect(operands, 2)?;
+        let (offset, base) = self.memory_operand(&operands[1], 16)?;
+        Ok(encode_immediate(opcode, base, register(&operands[0])?, offset))
+    }
+
+    fn float_load_store(&self, opcode: i32, operands: &[String])
+        -> Result<u32, ErrorKind> {
+        expect(operands, 2)?;
+        let (offset, base) = self.memory_operand(&operands[1], 16)?;
+        Ok(encode_immediate(opcode, base, float_register(&operands[0])?,
+                            offset))
+    }
+
+    fn alu_immediate(&self, opcode: i32, operands: &[String], is_signed: bool)
+        -> Result<u32, ErrorKind> {
+        expect(operands, 3)?;
+        let value = self.constant(&operands[2])?;
+        let immediate = if is_signed {
+            signed(value, 16)?
+        } else {
+            unsigned(value, 16)?
+        };
+        Ok(encode_immediate(opcode, register(&operands[1])?,
+                            register(&operands[0])?, immediate))
+    }
+
+    fn shift(&self,
+             operands: &[String],
+             rs: u32,
+             function: i32,
+             function32: Option<i32>) -> Result<u32, ErrorKind> {
+        expect(operands, 3)?;
+        let amount = self.constant(&operands[2])?;
+        let (sa, function) = match function32 {
+            Some(function32) if (32..64).contains(&amount) =>
+                (amount as u32 - 32, function32),
+            _ => (unsigned(amount, 5)?, function),
+        };
+        Ok(encode(SPECIAL, rs, register(&operands[1])?,
+                  register(&operands[0])?, sa, function))
+    }
+
+    // EXT, INS and their doubleword forms: rt, rs, pos, size. The doubleword
+    // ones get whichever of their three encodings fits the field, whatever
+    // name they're written with.
+    fn bit_field(&self, operands: &[String], insert: bool, doubleword: bool)
+        -> Result<u32, ErrorKind> {
+        expect(operands, 4)?;
+        let width = if doubleword { 64 } else { 32 };
+        let pos = self.constant(&operands[2])?;
+        let size = self.constant(&operands[3])?;
+        if !(0..width).contains(&pos) {
+            return Err(ErrorKind::OutOfRange(pos));
+        }
+        if size < 1 || pos + size > width {
+            return Err(ErrorKind::OutOfRange(size));
+        }
+        // The rd field holds the size less one for an extract, and the last
+        // bit for an insert.
+        let (pos, size) = (pos as u32, size as u32);
+        let last = pos + size - 1;
+        let (function, msb, lsb) = match (insert, doubleword) {
+            (false, false) => (decoder::EXT, size - 1, pos),
+            (true, false) => (decoder::INS, last, pos),
+            (false, true) if pos >= 32 => (decoder::DEXTU, size - 1, pos - 32),
+            (false, true) if size > 32 => (decoder::DEXTM, size - 33, pos),
+            (false, true) => (decoder::DEXT, size - 1, pos),
+            (true, true) if pos >= 32 => (decoder::DINSU, last - 32, pos - 32),
+            (true, true) if last >= 32 => (decoder::DINSM, last - 32, pos),
+            (true, true) => (decoder::DINS, last, pos),
+        };
+        Ok(encode(decoder::SPECIAL3, register(&operands[1])?,
+                  register(&operands[0])?, msb, lsb, function))
+    }
+
+    // BGEUC, BLTUC, BGEC and BLTC, which need two different registers,
+    // neither of them $zero.
+    fn compare_branch(&self, operands: &[String], opcode: i32, pc: u64)
+        -> Result<u32, ErrorKind> {
+        expect(operands, 3)?;
+        let rs = nonzero(register(&operands[0])?, &operands[0])?;
+        let rt = nonzero(register(&operands[1])?, &operands[1])?;
+        if rs == rt {
+            return Err(ErrorKind::BadOperand(operands[1].clone()));
+        }
+        Ok(encode(opcode, rs, rt, 0, 0, 0) |
+           self.offset(&operands[2], pc + 4, 2, 16)?)
+    }
+
+    // Branches that compare one register against zero. Which comparison it
+    // is depends on whether rs is $zero or the same register as rt.
+    fn zero_branch(&self, operands: &[String], opcode: i32, same: bool, pc: u64)
+        -> Result<u32, ErrorKind> {
+        expect(operands, 2)?;
+        let rt = nonzero(register(&operands[0])?, &operands[0])?;
+        let rs = if same { rt } else { 0 };
+        Ok(encode(opcode, rs, rt, 0, 0, 0) |
+           self.offset(&operands[1], pc + 4, 2, 16)?)
+    }
+
+    // LI: as few instructions as the value needs. The sequence only depends
+    // on the value, so it's the same length in both passes.
+    fn load_immediate(&mut self, rt: u32, value: i64) -> Result<(), ErrorKind> {
+        if let Ok(immediate) = signed(value, 16) {
+            self.emit_instruction(encode_immediate(decoder::ADDIU, 0, rt,
+                                                   immediate));
+        } else if let Ok(immediate) = unsigned(value, 16) {
+            self.emit_instruction(encode_immediate(decoder::ORI, 0, rt,
+                                                   immediate));
+        } else if value as i32 as i64 == value {
+            self.emit_instruction(encode_immediate(decoder::LUI, 0, rt,
+                                                   (value >> 16) as u32));
+            if value & 0xffff != 0 {
+                self.emit_instruction(encode_immediate(decoder::ORI, rt, rt,
+                                                       value as u32));
+            }
+        } else {
+            // The upper half as a 32-bit value, then the lower half shifted
+            // in 16 bits at a time.
+            self.load_immediate(rt, value >> 32)?;
+            for shift in [16, 0] {
+                self.emit_instruction(encode(SPECIAL, 0, rt, rt, 16,
+                                             decoder::DSLL));
+                let part = (value >> shift) as u32 & 0xffff;
+                if part != 0 {
+                    self.emit_instruction(encode_immediate(decoder::ORI, rt, rt,
+                                                           part));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // LA: labels aren't known in the first pass, so this is always the full
+    // 64-bit sequence.
+    fn load_address(&mut self, rt: u32, address: u64) -> Result<(), ErrorKind> {
+        self.emit_instruction(encode_immediate(decoder::LUI, 0, rt,
+                                               (address >> 48) as u32));
+        self.emit_instruction(encode_immediate(decoder::ORI, rt, rt,
+                                               (address >> 32) as u32));
+        for shift in [16, 0] {
+            self.emit_instruction(encode(SPECIAL, 0, rt, rt, 16, decoder::DSLL));
+            self.emit_instruction(encode_immediate(decoder::ORI, rt, rt,
+                                                   (address >> shift) as u32));
+        }
+        Ok(())
+    }
+}
+
+// The MSA opcode with the fields below the top: `top` is what goes above
+// the wt field, then wt already shifted, ws, wd and the minor opcode.
+fn encode_msa(top: u32, wt: u32, ws: u32, wd: u32, minor: i32) -> u32 {
+    ((decoder::MSA as u32) << decoder::OPCODE) | top | wt | (ws << 11) |
+        (wd << 6) | minor as u32
+}
+
+// Looks a name up in one of the tables of operations by minor opcode.
+fn vector_operation(table: &[(i32, [&str; 8])], name: &str)
+    -> Option<(i32, u32)> {
+    table.iter().find_map(|(minor, ops)| {
+        ops.iter()
+            .position(|op| !op.is_empty() && *op == name)
+            .map(|op| (*minor, op as u32))
+    })
+}
+
+// The MSA compares, like fceq and fsune: fc and then a condition that
+// doesn't signal, or f and one that does.
+fn vector_condition(name: &str) -> Option<u32> {
+    let condition = if let Some(condition) = name.strip_prefix("fc") {
+        condition.to_string()
+    } else {
+        name.strip_prefix("fs").map(|condition| format!("s{}", condition))?
+    };
+    FLOAT_CONDITIONS.iter()
+        .find(|(name, _)| *name == condition)
+        .map(|(_, code)| *code)
+        .filter(|code| (code & 0x08 != 0) == name.starts_with("fs"))
+}
+
+fn nonzero(register: u32, operand: &str) -> Result<u32, ErrorKind> {
+    if register == 0 {
+        Err(ErrorKind::BadOperand(operand.to_string()))
+    } else {
+        Ok(register)
+    }
+}
+
+// rd, rs, rt
+fn three_operand(operands: &[String], sa: i32, function: i32)
+    -> Result<u32, ErrorKind> {
+    expect(operands, 3)?;
+    Ok(encode(SPECIAL, register(&operands[1])?, register(&operands[2])?,
+              register(&operands[0])?, sa as u32, function))
+}
+
+// rd, rs
+fn two_operand(operands: &[String], function: i32) -> Result<u32, ErrorKind> {
+    expect(operands, 2)?;
+    Ok(encode(SPECIAL, register(&operands[1])?, 0, register(&operands[0])?,
+              0, function))
+}
+
+// rd, rt, rs
+fn shift_variable(operands: &[String], sa: u32, function: i32)
+    -> Result<u32, ErrorKind> {
+    expect(operands, 3)?;
+    Ok(encode(SPECIAL, register(&operands[2])?, register(&operands[1])?,
+              register(&operands[0])?, sa, function))
+}
+
+// Floating-point arithmetic, named for the operation and then the format of
+// the operands, like add.d, cmp.lt.s or cvt.s.w.
+fn float(mnemonic: &str, operands: &[String]) -> Result<u32, ErrorKind> {
+    let unknown = || ErrorKind::UnknownInstruction(mnemonic.to_string());
+    let (name, format) = mnemonic.rsplit_once('.').ok_or_else(unknown)?;
+    let fmt = match format {
+        "s" => decoder::FMT_S,
+        "d" => decoder::FMT_D,
+        "w" => decoder::FMT_W,
+        "l" => decoder::FMT_L,
+        _ => return Err(unknown()),
+    };
+    let float_format = fmt == decoder::FMT_S || fmt == decoder::FMT_D;
+
+    // The compares are encoded with the W and L formats.
+    if let Some(condition) = name.strip_prefix("cmp.") {
+        let (_, function) = FLOAT_CONDITIONS.iter()
+            .find(|(name, _)| *name == condition)
+            .ok_or_else(unknown)?;
+        if !float_format {
+            return Err(unknown());
+        }
+        expect(operands, 3)?;
+        let fmt = if fmt == decoder::FMT_S {
+            decoder::FMT_W
+        } else {
+            decoder::FMT_L
+        };
+        return Ok(encode(decoder::COP1, fmt as u32,
+                         float_register(&operands[2])?,
+                         float_register(&operands[1])?,
+                         float_register(&operands[0])?, *function as i32));
+    }
+
+    let (function, count) = match name {
+        "add" => (decoder::ADD_FMT, 3),
+        "sub" => (decoder::SUB_FMT, 3),
+        "mul" => (decoder::MUL_FMT, 3),
+        "div" => (decoder::DIV_FMT, 3),
+        "maddf" => (decoder::MADDF_FMT, 3),
+        "msubf" => (decoder::MSUBF_FMT, 3),
+        "min" => (decoder::MIN_FMT, 3),
+        "mina" => (decoder::MINA_FMT, 3),
+        "max" => (decoder::MAX_FMT, 3),
+        "maxa" => (decoder::MAXA_FMT, 3),
+        "sel" => (decoder::SEL_FMT, 3),
+        "seleqz" => (decoder::SELEQZ_FMT, 3),
+        "selnez" => (decoder::SELNEZ_FMT, 3),
+        "sqrt" => (decoder::SQRT_FMT, 2),
+        "abs" => (decoder::ABS_FMT, 2),
+        "mov" => (decoder::MOV_FMT, 2),
+        "neg" => (decoder::NEG_FMT, 2),
+        "recip" => (decoder::RECIP_FMT, 2),
+        "rsqrt" => (decoder::RSQRT_FMT, 2),
+        "rint" => (decoder::RINT_FMT, 2),
+        "class" => (decoder::CLASS_FMT, 2),
+        "round.l" => (decoder::ROUND_L_FMT, 2),
+        "trunc.l" => (decoder::TRUNC_L_FMT, 2),
+        "ceil.l" => (decoder::CEIL_L_FMT, 2),
+        "floor.l" => (decoder::FLOOR_L_FMT, 2),
+        "round.w" => (decoder::ROUND_W_FMT, 2),
+        "trunc.w" => (decoder::TRUNC_W_FMT, 2),
+        "ceil.w" => (decoder::CEIL_W_FMT, 2),
+        "floor.w" => (decoder::FLOOR_W_FMT, 2),
+        "cvt.s" => (decoder::CVT_S_FMT, 2),
+        "cvt.d" => (decoder::CVT_D_FMT, 2),
+        "cvt.w" => (decoder::CVT_W_FMT, 2),
+        "cvt.l" => (decoder::CVT_L_FMT, 2),
+        _ => return Err(unknown()),
+    };
+    // Integers can only be converted to S or D, and nothing converts to its
+    // own format.
+    let converts_to_float = function == decoder::CVT_S_FMT ||
+        function == decoder::CVT_D_FMT;
+    if (!float_format && !converts_to_float) ||
+            name == format!("cvt.{}", format) {
+        return Err(unknown());
+    }
+    expect(operands, count)?;
+    let ft = match operands.get(2) {
+        None => 0,
+        Some(operand) => float_register(operand)?,
+    };
+    Ok(encode(decoder::COP1, fmt as u32, ft, float_register(&operands[1])?,
+              float_register(&operands[0])?, function))
+}
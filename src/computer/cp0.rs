@@ -0,0 +1,551 @@
+use crate::computer::exception::Exception;
+use crate::computer::tlb::{self, Fault, Tlb};
+
+// Register numbers
+const INDEX: usize = 0;
+const RANDOM: usize = 1;
+const ENTRYLO0: usize = 2;
+const ENTRYLO1: usize = 3;
+const CONTEXT: usize = 4;
+pub const USERLOCAL: usize = 4;
+const PAGEMASK: usize = 5;
+const WIRED: usize = 6;
+pub const COUNT: usize = 9;
+pub const BADVADDR: usize = 8;
+const ENTRYHI: usize = 10;
+pub const STATUS: usize = 12;
+pub const CAUSE: usize = 13;
+pub const EPC: usize = 14;
+pub const PRID: usize = 15;
+pub const CONFIG: usize = 16;
+const XCONTEXT: usize = 20;
+pub const DEBUG: usize = 23;
+pub const DEPC: usize = 24;
+pub const ERROREPC: usize = 30;
+
+// Register selects
+pub const USERLOCAL_SEL: usize = 2;
+const EBASE_SEL: usize = 1;
+const CONFIG1_SEL: usize = 1;
+const CONFIG2_SEL: usize = 2;
+const CONFIG3_SEL: usize = 3;
+const CONFIG4_SEL: usize = 4;
+const CONFIG5_SEL: usize = 5;
+
+// Status fields
+pub const STATUS_IE: u64 = 0x1;
+pub const STATUS_EXL: u64 = 0x2;
+pub const STATUS_ERL: u64 = 0x4;
+const STATUS_KSU: u64 = 0x3 << 3;
+const STATUS_KSU_SUPERVISOR: u64 = 0x1 << 3;
+const STATUS_KSU_USER: u64 = 0x2 << 3;
+pub const STATUS_UX: u64 = 0x1 << 5;
+pub const STATUS_SX: u64 = 0x1 << 6;
+pub const STATUS_KX: u64 = 0x1 << 7;
+const STATUS_PX: u64 = 0x1 << 23;
+const STATUS_MX: u64 = 0x1 << 24;
+const STATUS_RE: u64 = 0x1 << 25;
+pub const STATUS_BEV: u64 = 0x1 << 22;
+pub const STATUS_CU0: u64 = 0x1 << 28;
+
+// Cause fields
+const CAUSE_EXCCODE_SHIFT: u64 = 2;
+const CAUSE_EXCCODE: u64 = 0x1f << CAUSE_EXCCODE_SHIFT;
+const CAUSE_IP_SOFTWARE: u64 = 0x3 << 8;
+const CAUSE_IV: u64 = 0x1 << 23;
+const CAUSE_DC: u64 = 0x1 << 27;
+const CAUSE_CE_SHIFT: u64 = 28;
+const CAUSE_CE: u64 = 0x3 << CAUSE_CE_SHIFT;
+const CAUSE_BD: u64 = 0x1 << 31;
+
+// Debug fields
+const DEBUG_DBP: u64 = 0x1 << 1;
+const DEBUG_DM: u64 = 0x1 << 30;
+const DEBUG_DBD: u64 = 0x1 << 31;
+
+// Index: P says TLBP found no match.
+const INDEX_INDEX: u64 = 0x3f;
+const INDEX_P: u64 = 0x1 << 31;
+
+// Context and XContext: the page table base software sets, and the VPN2
+// of the last TLB exception's address, with R as well in XContext.
+const CONTEXT_PTEBASE: u64 = !0x7fffff;
+const XCONTEXT_PTEBASE: u64 = !0x1ffffffff;
+
+// EBase fields
+const EBASE_CPUNUM: u64 = 0x3ff;
+const EBASE_BASE: u64 = !0xfff;
+
+// Exception vectors
+const BOOT_VECTOR_BASE: u64 = 0xffffffffbfc00200;
+const RESET_EBASE: u64 = 0xffffffff80000000;
+const GENERAL_VECTOR_OFFSET: u64 = 0x180;
+// A TLB miss outside a handler has a vector of its own, with another for
+// 64-bit addresses.
+const TLB_REFILL_VECTOR_OFFSET: u64 = 0x000;
+const XTLB_REFILL_VECTOR_OFFSET: u64 = 0x080;
+
+// The 32-bit compatibility segments at the top of the address space.
+// kseg0 and kseg1 are unmapped windows on the bottom 512 MiB of physical
+// memory, and sseg and kseg3 are mapped.
+pub const KSEG0: u64 = 0xffffffff80000000;
+const SSEG: u64 = 0xffffffffc0000000;
+const KSEG3: u64 = 0xffffffffe0000000;
+const KSEG_PHYSICAL: u64 = 0x1fffffff;
+// xkphys is an unmapped window on all of physical memory, with a cache
+// attribute in bits 61:59.
+const XKPHYS: u64 = 0x2;
+const XKPHYS_RESERVED: u64 = ((1 << 59) - 1) & !((1 << tlb::PABITS) - 1);
+const DEBUG_VECTOR: u64 = 0xffffffffbfc00480;
+
+// Processor identification. Company ID 1 is MIPS Technologies.
+const PRID_VALUE: u64 = 0x0001a800;
+
+// Config0: M set, AT = MIPS64 with access to all segments, AR = Release 6,
+// MT = fixed mapping unless there's a TLB.
+const CONFIG0_VALUE: u64 = (0x1 << 31) | (0x2 << 13) | (0x2 << 10) | (0x3 << 7);
+const CONFIG0_MT: u64 = 0x7 << 7;
+const CONFIG0_MT_FIXED: u64 = 0x3 << 7;
+const CONFIG0_MT_TLB: u64 = 0x1 << 7;
+
+// The M bit in each of Config0 to Config4 says the next one exists.
+const CONFIG_M: u64 = 0x1 << 31;
+// Config1: an FPU, and how many TLB entries there are, less one.
+const CONFIG1_FP: u64 = 0x1;
+const CONFIG1_MMU_SIZE_SHIFT: u64 = 25;
+// Config3: MSA and revision 2 of the DSP ASE are implemented.
+const CONFIG3_DSPP: u64 = 0x1 << 10;
+const CONFIG3_DSP2P: u64 = 0x1 << 11;
+const CONFIG3_MSAP: u64 = 0x1 << 28;
+// Config4: TLBINV and TLBINVF work on the whole TLB.
+const CONFIG4_IE: u64 = 0x3 << 29;
+// Config5: MSA is enabled.
+const CONFIG5_MSAEN: u64 = 0x1 << 27;
+
+// The privilege level the CPU is running at.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Kernel,
+    Supervisor,
+    User,
+}
+
+pub struct Cp0 {
+    registers: [[u64; 8]; 32],
+    // Only there when addresses are mapped through a TLB.
+    tlb: Option<Tlb>,
+}
+
+pub fn new(id: u64) -> Cp0 {
+    let mut cp0 = Cp0 {
+        registers: [[0; 8]; 32],
+        tlb: None,
+    };
+    cp0.registers[PRID][0] = PRID_VALUE;
+    cp0.registers[PRID][EBASE_SEL] = RESET_EBASE | (id & EBASE_CPUNUM);
+    cp0.registers[CONFIG][0] = CONFIG0_VALUE;
+    cp0.registers[CONFIG][CONFIG1_SEL] = CONFIG_M | CONFIG1_FP;
+    cp0.registers[CONFIG][CONFIG2_SEL] = CONFIG_M;
+    cp0.registers[CONFIG][CONFIG3_SEL] =
+        CONFIG_M | CONFIG3_DSPP | CONFIG3_DSP2P | CONFIG3_MSAP;
+    cp0.registers[CONFIG][CONFIG4_SEL] = CONFIG_M;
+    cp0
+}
+
+impl Cp0 {
+    // Which bits of a register software is allowed to change. Registers that
+    // aren't listed here are read-only.
+    fn writable(reg: usize, sel: usize) -> u64 {
+        match (reg, sel) {
+            (STATUS, 0) | (COUNT, 0) => 0xffffffff,
+            (CAUSE, 0) => CAUSE_IP_SOFTWARE | CAUSE_IV | CAUSE_DC,
+            (EPC, 0) | (DEPC, 0) | (ERROREPC, 0) => !0,
+            (PRID, EBASE_SEL) => EBASE_BASE,
+            (DEBUG, 0) => !DEBUG_DM,
+            (INDEX, 0) | (WIRED, 0) => INDEX_INDEX,
+            (ENTRYLO0, 0) | (ENTRYLO1, 0) => tlb::ENTRYLO_MASK,
+            (CONTEXT, 0) => CONTEXT_PTEBASE,
+            (PAGEMASK, 0) => tlb::PAGEMASK_MASK,
+            (ENTRYHI, 0) => tlb::ENTRYHI_R | tlb::ENTRYHI_VPN2 |
+                tlb::ENTRYHI_EHINV | tlb::ENTRYHI_ASID,
+            (XCONTEXT, 0) => XCONTEXT_PTEBASE,
+            (RANDOM, 0) => 0,
+            (CONFIG, CONFIG5_SEL) => CONFIG5_MSAEN,
+            (BADVADDR, _) | (PRID, _) | (CONFIG, _) => 0,
+            _ => !0,
+        }
+    }
+
+    pub fn read(&self, reg: usize, sel: usize) -> u64 {
+        self.registers[reg][sel]
+    }
+
+    // Writing Wired starts Random again from the top.
+    pub fn write(&mut self, reg: usize, sel: usize, value: u64) {
+        let mask = Cp0::writable(reg, sel);
+        self.registers[reg][sel] =
+            (self.registers[reg][sel] & !mask) | (value & mask);
+        if (reg, sel) == (WIRED, 0) {
+            self.registers[RANDOM][0] = tlb::ENTRIES as u64 - 1;
+        }
+    }
+
+    // Count goes up by one for every instruction, which is the cycle
+    // counter's resolution as far as RDHWR is concerned.
+    pub fn tick(&mut self) {
+        let count = &mut self.registers[COUNT][0];
+        *count = count.wrapping_add(1) & 0xffffffff;
+    }
+
+    pub fn cpu_number(&self) -> u64 {
+        self.registers[PRID][EBASE_SEL] & EBASE_CPUNUM
+    }
+
+    pub fn status(&self) -> u64 {
+        self.registers[STATUS][0]
+    }
+
+    pub fn set_status(&mut self, value: u64) {
+        self.registers[STATUS][0] = value;
+    }
+
+    // Without Config5.MSAEn, MSA instructions raise MSA Disabled exceptions
+    // so the kernel knows to start saving the vector registers.
+    pub fn msa_enabled(&self) -> bool {
+        self.registers[CONFIG][CONFIG5_SEL] & CONFIG5_MSAEN != 0
+    }
+
+    pub fn set_msa_enabled(&mut self, enabled: bool) {
+        let config5 = &mut self.registers[CONFIG][CONFIG5_SEL];
+        if enabled {
+            *config5 |= CONFIG5_MSAEN;
+        } else {
+            *config5 &= !CONFIG5_MSAEN;
+        }
+    }
+
+    // Status.MX gates the DSP ASE, so a kernel can leave the accumulators
+    // and DSPControl out of a context switch until a program uses them.
+    pub fn dsp_enabled(&self) -> bool {
+        self.status() & STATUS_MX != 0
+    }
+
+    pub fn set_dsp_enabled(&mut self, enabled: bool) {
+        if enabled {
+            self.registers[STATUS][0] |= STATUS_MX;
+        } else {
+            self.registers[STATUS][0] &= !STATUS_MX;
+        }
+    }
+
+    pub fn tlb_enabled(&self) -> bool {
+        self.tlb.is_some()
+    }
+
+    // Puts in a TLB, or takes it out, and has Config say which.
+    pub fn set_tlb_enabled(&mut self, enabled: bool) {
+        let config0 = self.registers[CONFIG][0] & !CONFIG0_MT;
+        let config1 = self.registers[CONFIG][CONFIG1_SEL] &
+            !(0x3f << CONFIG1_MMU_SIZE_SHIFT);
+        let config4 = self.registers[CONFIG][CONFIG4_SEL] & !CONFIG4_IE;
+        if enabled {
+            self.tlb = Some(tlb::new());
+            self.registers[CONFIG][0] = config0 | CONFIG0_MT_TLB;
+            self.registers[CONFIG][CONFIG1_SEL] = config1 |
+                ((tlb::ENTRIES as u64 - 1) << CONFIG1_MMU_SIZE_SHIFT);
+            self.registers[CONFIG][CONFIG4_SEL] = config4 | CONFIG4_IE;
+            self.registers[RANDOM][0] = tlb::ENTRIES as u64 - 1;
+        } else {
+            self.tlb = None;
+            self.registers[CONFIG][0] = config0 | CONFIG0_MT_FIXED;
+            self.registers[CONFIG][CONFIG1_SEL] = config1;
+            self.registers[CONFIG][CONFIG4_SEL] = config4;
+        }
+    }
+
+    // Where a virtual address lands in physical memory when there's a TLB.
+    // Each segment can only be reached from its own mode or a more
+    // privileged one, and kuseg is left unmapped at the error level so boot
+    // code can run before the TLB is set up.
+    pub fn map(&self, address: u64, store: bool) -> Result<u64, Fault> {
+        let erl = self.status() & STATUS_ERL != 0;
+        let in_segment = (address << 2) >> (tlb::SEGBITS + 2) == 0;
+        let (needed, mapped) = match address {
+            0..=0x7fffffff if erl => return Ok(address),
+            KSEG0..SSEG => (Mode::Kernel, false),
+            SSEG..KSEG3 => (Mode::Supervisor, true),
+            KSEG3..=u64::MAX => (Mode::Kernel, true),
+            _ => match address >> 62 {
+                0 if in_segment => (Mode::User, true),
+                1 if in_segment => (Mode::Supervisor, true),
+                XKPHYS => (Mode::Kernel, false),
+                3 if in_segment => (Mode::Kernel, true),
+                _ => return Err(Fault::AddressError),
+            },
+        };
+        let allowed = match self.mode() {
+            Mode::Kernel => true,
+            Mode::Supervisor => needed != Mode::Kernel,
+            Mode::User => needed == Mode::User,
+        };
+        match &self.tlb {
+            _ if !allowed => Err(Fault::AddressError),
+            _ if !mapped => unmapped(address).ok_or(Fault::AddressError),
+            None => Err(Fault::AddressError),
+            Some(tlb) => {
+                let asid = self.registers[ENTRYHI][0] & tlb::ENTRYHI_ASID;
+                tlb.lookup(address, asid, store)
+            },
+        }
+    }
+
+    // TLBP: sets Index to the entry matching EntryHi, or sets its P bit if
+    // there isn't one.
+    pub fn tlb_probe(&mut self) {
+        let tlb = self.tlb.as_ref().expect("no TLB to probe");
+        self.registers[INDEX][0] = match tlb.probe(self.registers[ENTRYHI][0]) {
+            None => INDEX_P,
+            Some(index) => index as u64,
+        };
+    }
+
+    // TLBR: loads EntryHi, PageMask and the EntryLos from the entry Index
+    // picks.
+    pub fn tlb_read(&mut self) {
+        let tlb = self.tlb.as_ref().expect("no TLB to read");
+        let index = (self.registers[INDEX][0] & INDEX_INDEX) as usize;
+        if index >= tlb::ENTRIES {
+            return;
+        }
+        let entry = tlb.entry(index);
+        self.registers[ENTRYHI][0] = entry.entry_hi;
+        self.registers[PAGEMASK][0] = entry.page_mask;
+        self.registers[ENTRYLO0][0] = entry.entry_lo[0];
+        self.registers[ENTRYLO1][0] = entry.entry_lo[1];
+    }
+
+    // TLBWI writes the entry Index picks, and TLBWR the one Random does.
+    // Random counts down each time, wrapping round to the top when it gets
+    // to the entries Wired keeps for itself.
+    pub fn tlb_write(&mut self, random: bool) {
+        let last = tlb::ENTRIES as u64 - 1;
+        let index = if random {
+            let index = self.registers[RANDOM][0];
+            let wired = self.registers[WIRED][0];
+            self.registers[RANDOM][0] =
+                if index <= wired { last } else { index - 1 };
+            index
+        } else {
+            self.registers[INDEX][0] & INDEX_INDEX
+        };
+        if index > last {
+            return;
+        }
+        let entry = tlb::Entry {
+            entry_hi: self.registers[ENTRYHI][0],
+            page_mask: self.registers[PAGEMASK][0],
+            entry_lo: [self.registers[ENTRYLO0][0],
+                       self.registers[ENTRYLO1][0]],
+        };
+        let tlb = self.tlb.as_mut().expect("no TLB to write");
+        tlb.set_entry(index as usize, entry);
+    }
+
+    // TLBINV takes out the entries for EntryHi's ASID, and TLBINVF every
+    // entry.
+    pub fn tlb_invalidate(&mut self, all: bool) {
+        let asid = self.registers[ENTRYHI][0] & tlb::ENTRYHI_ASID;
+        let tlb = self.tlb.as_mut().expect("no TLB to invalidate");
+        tlb.invalidate(if all { None } else { Some(asid) });
+    }
+
+    // A TLB exception leaves the page that missed in EntryHi, Context and
+    // XContext, ready for the handler to look up and write.
+    fn record_tlb_fault(&mut self, address: u64) {
+        let vpn2 = address & tlb::ENTRYHI_VPN2;
+        let entry_hi = &mut self.registers[ENTRYHI][0];
+        *entry_hi = (*entry_hi & tlb::ENTRYHI_ASID) |
+            (address & tlb::ENTRYHI_R) | vpn2;
+        let context = &mut self.registers[CONTEXT][0];
+        *context = (*context & CONTEXT_PTEBASE) | ((vpn2 >> 9) & 0x7ffff0);
+        let xcontext = &mut self.registers[XCONTEXT][0];
+        *xcontext = (*xcontext & XCONTEXT_PTEBASE) |
+            ((address >> 62) << 31) | (vpn2 >> 9);
+    }
+
+    // Status.KSU picks the mode, except that the CPU is always in kernel
+    // mode at the exception, error or debug level.
+    fn mode(&self) -> Mode {
+        let status = self.status();
+        if status & (STATUS_EXL | STATUS_ERL) != 0 ||
+                self.registers[DEBUG][0] & DEBUG_DM != 0 {
+            return Mode::Kernel;
+        }
+        match status & STATUS_KSU {
+            STATUS_KSU_SUPERVISOR => Mode::Supervisor,
+            STATUS_KSU_USER => Mode::User,
+            _ => Mode::Kernel,
+        }
+    }
+
+    pub fn user_mode(&self) -> bool {
+        self.mode() == Mode::User
+    }
+
+    // CP0 instructions are always allowed in kernel mode; anywhere else
+    // Status.CU0 has to let them through.
+    pub fn usable(&self) -> bool {
+        self.mode() == Mode::Kernel || self.status() & STATUS_CU0 != 0
+    }
+
+    // Status.RE has user mode run in the opposite byte order to the rest of
+    // the system, so a kernel of one endianness can run programs built for
+    // the other.
+    pub fn reverse_endian(&self) -> bool {
+        self.status() & STATUS_RE != 0 && self.user_mode()
+    }
+
+    // Whether instructions that work on doublewords are allowed. Kernel mode
+    // can always use them. Status.PX lets user mode use them while it's
+    // still limited to 32-bit addresses.
+    pub fn doubleword_operations(&self) -> bool {
+        let status = self.status();
+        match self.mode() {
+            Mode::Kernel => true,
+            Mode::Supervisor => status & STATUS_SX != 0,
+            Mode::User => status & (STATUS_UX | STATUS_PX) != 0,
+        }
+    }
+
+    // Whether the whole 64-bit address space can be reached. Without it
+    // only sign-extended 32-bit addresses are valid, the way they are on a
+    // MIPS32 CPU.
+    pub fn doubleword_addressing(&self) -> bool {
+        let bit = match self.mode() {
+            Mode::Kernel => STATUS_KX,
+            Mode::Supervisor => STATUS_SX,
+            Mode::User => STATUS_UX,
+        };
+        self.status() & bit != 0
+    }
+
+    // Where the CPU goes to handle an exception. A TLB miss has its own
+    // vector unless it happens inside a handler. The 64-bit one is for
+    // misses on segments whose 64-bit addressing is enabled.
+    pub fn exception_vector(&self, exception: &Exception) -> u64 {
+        if let Exception::DebugBreakpoint { .. } = exception {
+            return DEBUG_VECTOR;
+        }
+        let status = self.status();
+        let offset = match *exception {
+            Exception::TlbLoad { address, .. } |
+            Exception::TlbStore { address, .. } if status & STATUS_EXL == 0 => {
+                let extended = match address >> 62 {
+                    0 => status & STATUS_UX != 0,
+                    3 => status & STATUS_KX != 0,
+                    _ => true,
+                };
+                if extended {
+                    XTLB_REFILL_VECTOR_OFFSET
+                } else {
+                    TLB_REFILL_VECTOR_OFFSET
+                }
+            },
+            _ => GENERAL_VECTOR_OFFSET,
+        };
+        if status & STATUS_BEV != 0 {
+            BOOT_VECTOR_BASE + offset
+        } else {
+            (self.registers[PRID][EBASE_SEL] & EBASE_BASE) + offset
+        }
+    }
+
+    // Records an exception the way the hardware does on the way into a
+    // handler. EPC is only written if we weren't already handling an
+    // exception, so a fault inside a handler still returns to the original
+    // faulting instruction. `restart` is where to go back to, with the ISA
+    // mode in bit 0. For an instruction in a delay slot it's the branch, so
+    // that returning runs the branch again, and Cause.BD says so.
+    pub fn enter_exception(&mut self,
+                           exception: &Exception,
+                           restart: u64,
+                           delay_slot: bool) {
+        if let Exception::DebugBreakpoint { .. } = exception {
+            self.enter_debug_mode(restart, delay_slot);
+            return;
+        }
+        if self.status() & STATUS_EXL == 0 {
+            self.registers[EPC][0] = restart;
+            if delay_slot {
+                self.registers[CAUSE][0] |= CAUSE_BD;
+            } else {
+                self.registers[CAUSE][0] &= !CAUSE_BD;
+            }
+        }
+        if let Some(address) = exception.bad_address() {
+            self.registers[BADVADDR][0] = address;
+        }
+        match *exception {
+            Exception::TlbLoad { address, .. } |
+            Exception::TlbStore { address, .. } |
+            Exception::TlbInvalidLoad { address, .. } |
+            Exception::TlbInvalidStore { address, .. } |
+            Exception::TlbModified { address, .. } =>
+                self.record_tlb_fault(address),
+            _ => {},
+        }
+        // CE says which coprocessor was unusable, and is left alone
+        // otherwise.
+        if let Exception::CoprocessorUnusable { unit, .. } = *exception {
+            self.registers[CAUSE][0] = (self.registers[CAUSE][0] & !CAUSE_CE) |
+                ((unit as u64) << CAUSE_CE_SHIFT & CAUSE_CE);
+        }
+        self.registers[CAUSE][0] = (self.registers[CAUSE][0] & !CAUSE_EXCCODE) |
+            (exception.code() << CAUSE_EXCCODE_SHIFT);
+        self.registers[STATUS][0] |= STATUS_EXL;
+    }
+
+    // Debug exceptions use DEPC and Debug in place of EPC and Cause, and set
+    // Debug.DM rather than Status.EXL. One raised in debug mode leaves DEPC
+    // alone, the way EPC is left alone at exception level.
+    fn enter_debug_mode(&mut self, restart: u64, delay_slot: bool) {
+        let debug = self.registers[DEBUG][0];
+        if debug & DEBUG_DM != 0 {
+            return;
+        }
+        let dbd = if delay_slot { DEBUG_DBD } else { 0 };
+        self.registers[DEPC][0] = restart;
+        self.registers[DEBUG][0] =
+            (debug & !DEBUG_DBD) | dbd | DEBUG_DM | DEBUG_DBP;
+    }
+
+    // ERET: leaves the error level if we're at it, otherwise the exception
+    // level, and returns where to resume.
+    pub fn exception_return(&mut self) -> u64 {
+        if self.status() & STATUS_ERL != 0 {
+            self.registers[STATUS][0] &= !STATUS_ERL;
+            self.registers[ERROREPC][0]
+        } else {
+            self.registers[STATUS][0] &= !STATUS_EXL;
+            self.registers[EPC][0]
+        }
+    }
+
+    // DERET: leaves debug mode and returns where to resume.
+    pub fn debug_return(&mut self) -> u64 {
+        self.registers[DEBUG][0] &= !DEBUG_DM;
+        self.registers[DEPC][0]
+    }
+}
+
+// The physical address behind an address in one of the unmapped kernel
+// segments.
+pub fn unmapped(address: u64) -> Option<u64> {
+    if (KSEG0..SSEG).contains(&address) {
+        Some(address & KSEG_PHYSICAL)
+    } else if address >> 62 == XKPHYS && address & XKPHYS_RESERVED == 0 {
+        Some(address & ((1 << tlb::PABITS) - 1))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,1080 @@
+use std::cmp::Ordering;
+use std::num::FpCategory;
+
+use crate::computer::decoder::{FixedFormat, FloatCondition, FloatFormat,
+                               FloatOp, FloatUnaryOp, VectorFloatOp,
+                               VectorFloatUnaryOp, VectorFormat};
+use crate::computer::msa;
+
+// Control register numbers
+pub const FIR: usize = 0;
+pub const FEXR: usize = 26;
+pub const FENR: usize = 28;
+pub const FCSR: usize = 31;
+
+// FIR: 2008 NaNs, 64-bit registers, and the L, W, D and S formats.
+const FIR_VALUE: u32 = (0x1 << 23) | (0x1 << 22) | (0x1 << 21) | (0x1 << 20) |
+    (0x1 << 17) | (0x1 << 16);
+
+// MSA control register numbers
+pub const MSAIR: usize = 0;
+pub const MSACSR: usize = 1;
+
+// MSAIR: revision 1 of MSA, with a processor ID of 0.
+const MSAIR_VALUE: u32 = 0x1;
+
+// FCSR fields. MSACSR has the same layout for the ones they share.
+const FCSR_RM: u32 = 0x3;
+const FCSR_FLAGS_SHIFT: u32 = 2;
+const FCSR_FLAGS: u32 = 0x1f << FCSR_FLAGS_SHIFT;
+const FCSR_ENABLES_SHIFT: u32 = 7;
+const FCSR_ENABLES: u32 = 0x1f << FCSR_ENABLES_SHIFT;
+const FCSR_CAUSE_SHIFT: u32 = 12;
+const FCSR_CAUSE: u32 = 0x3f << FCSR_CAUSE_SHIFT;
+const FCSR_FS: u32 = 0x1 << 24;
+const MSACSR_WRITABLE: u32 = FCSR_CAUSE | FCSR_FLAGS | FCSR_ENABLES |
+    FCSR_FS | FCSR_RM;
+// Release 6 only has the IEEE 754-2008 behaviour, so these always read as
+// set.
+const FCSR_NAN2008: u32 = 0x1 << 18;
+const FCSR_ABS2008: u32 = 0x1 << 19;
+
+// FENR has FS down next to the rounding mode.
+const FENR_FS: u32 = 0x1 << 2;
+
+// Exceptions, in the order the Flags, Enables and Cause fields list them.
+const INEXACT: u32 = 0x01;
+const UNDERFLOW: u32 = 0x02;
+const OVERFLOW: u32 = 0x04;
+const DIVIDE_BY_ZERO: u32 = 0x08;
+const INVALID: u32 = 0x10;
+// Only Cause has this one, and it can't be disabled.
+const UNIMPLEMENTED: u32 = 0x20;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Rounding {
+    Nearest,
+    Zero,
+    Up,
+    Down,
+}
+
+// What the arithmetic needs to know about each format. Everything is worked
+// out in f64 and then rounded to the format.
+trait Float: Copy {
+    const MIN_POSITIVE: f64;
+    const DEFAULT_NAN: u64;
+    const QUIET: u64;
+    const SIGN: u64;
+
+    fn unpack(bits: u64) -> Self;
+    fn pack(self) -> u64;
+    fn widen(self) -> f64;
+    // Rounds to nearest.
+    fn narrow(value: f64) -> Self;
+    fn next_up(self) -> Self;
+    fn next_down(self) -> Self;
+    fn classify(self) -> FpCategory;
+}
+
+impl Float for f32 {
+    const MIN_POSITIVE: f64 = f32::MIN_POSITIVE as f64;
+    const DEFAULT_NAN: u64 = 0x7fc00000;
+    const QUIET: u64 = 0x1 << 22;
+    const SIGN: u64 = 0x1 << 31;
+
+    fn unpack(bits: u64) -> f32 {
+        f32::from_bits(bits as u32)
+    }
+
+    fn pack(self) -> u64 {
+        self.to_bits() as u64
+    }
+
+    fn widen(self) -> f64 {
+        self as f64
+    }
+
+    fn narrow(value: f64) -> f32 {
+        value as f32
+    }
+
+    fn next_up(self) -> f32 {
+        f32::next_up(self)
+    }
+
+    fn next_down(self) -> f32 {
+        f32::next_down(self)
+    }
+
+    fn classify(self) -> FpCategory {
+        f32::classify(self)
+    }
+}
+
+impl Float for f64 {
+    const MIN_POSITIVE: f64 = f64::MIN_POSITIVE;
+    const DEFAULT_NAN: u64 = 0x7ff8000000000000;
+    const QUIET: u64 = 0x1 << 51;
+    const SIGN: u64 = 0x1 << 63;
+
+    fn unpack(bits: u64) -> f64 {
+        f64::from_bits(bits)
+    }
+
+    fn pack(self) -> u64 {
+        self.to_bits()
+    }
+
+    fn widen(self) -> f64 {
+        self
+    }
+
+    fn narrow(value: f64) -> f64 {
+        value
+    }
+
+    fn next_up(self) -> f64 {
+        f64::next_up(self)
+    }
+
+    fn next_down(self) -> f64 {
+        f64::next_down(self)
+    }
+
+    fn classify(self) -> FpCategory {
+        f64::classify(self)
+    }
+}
+
+// IEEE half precision, which only MSA's conversions use.
+#[derive(Clone, Copy)]
+struct Half(u16);
+
+impl Half {
+    const EXPONENT: u16 = 0x7c00;
+    const FRACTION: u16 = 0x03ff;
+}
+
+impl Float for Half {
+    const MIN_POSITIVE: f64 = 1.0 / 16384.0;
+    const DEFAULT_NAN: u64 = 0x7e00;
+    const QUIET: u64 = 0x1 << 9;
+    const SIGN: u64 = 0x1 << 15;
+
+    fn unpack(bits: u64) -> Half {
+        Half(bits as u16)
+    }
+
+    fn pack(self) -> u64 {
+        self.0 as u64
+    }
+
+    // NaNs keep their payload at the top of the f64 one.
+    fn widen(self) -> f64 {
+        let negative = self.0 & Half::SIGN as u16 != 0;
+        let exponent = ((self.0 & Half::EXPONENT) >> 10) as i32;
+        let fraction = (self.0 & Half::FRACTION) as u64;
+        let magnitude = match exponent {
+            0 => fraction as f64 * 2f64.powi(-24),
+            0x1f if fraction == 0 => f64::INFINITY,
+            0x1f => f64::from_bits(0x7ff0000000000000 | (fraction << 42)),
+            _ => (0x400 | fraction) as f64 * 2f64.powi(exponent - 25),
+        };
+        if negative { -magnitude } else { magnitude }
+    }
+
+    fn narrow(value: f64) -> Half {
+        let sign = if value.is_sign_negative() { Half::SIGN as u16 } else { 0 };
+        let magnitude = value.abs();
+        if magnitude.is_nan() {
+            let fraction = (magnitude.to_bits() >> 42) as u16 & Half::FRACTION;
+            return Half(sign | Half::EXPONENT | fraction);
+        }
+        // Halfway between the largest half and the next power of two rounds
+        // up to infinity.
+        if magnitude >= 65520.0 {
+            return Half(sign | Half::EXPONENT);
+        }
+        // Round to a multiple of the spacing of halves around the value,
+        // which is exact in f64, and then pack that.
+        let exponent = (((magnitude.to_bits() >> 52) as i32) - 1023).max(-14);
+        let rounded = (magnitude * 2f64.powi(10 - exponent)).round_ties_even() *
+            2f64.powi(exponent - 10);
+        if rounded < Half::MIN_POSITIVE {
+            return Half(sign | (rounded * 2f64.powi(24)) as u16);
+        }
+        let exponent = ((rounded.to_bits() >> 52) as i32) - 1023;
+        let fraction = (rounded * 2f64.powi(10 - exponent)) as u16 & 0x3ff;
+        Half(sign | (((exponent + 15) as u16) << 10) | fraction)
+    }
+
+    fn next_up(self) -> Half {
+        let bits = self.0;
+        if self.widen().is_nan() || bits == Half::EXPONENT {
+            self
+        } else if bits & !(Half::SIGN as u16) == 0 {
+            Half(1)
+        } else if bits & Half::SIGN as u16 == 0 {
+            Half(bits + 1)
+        } else {
+            Half(bits - 1)
+        }
+    }
+
+    fn next_down(self) -> Half {
+        let negated = Half(self.0 ^ Half::SIGN as u16).next_up();
+        Half(negated.0 ^ Half::SIGN as u16)
+    }
+
+    fn classify(self) -> FpCategory {
+        match (self.0 & Half::EXPONENT, self.0 & Half::FRACTION) {
+            (Half::EXPONENT, 0) => FpCategory::Infinite,
+            (Half::EXPONENT, _) => FpCategory::Nan,
+            (0, 0) => FpCategory::Zero,
+            (0, _) => FpCategory::Subnormal,
+            _ => FpCategory::Normal,
+        }
+    }
+}
+
+fn is_signaling<F: Float>(bits: u64) -> bool {
+    let value = F::unpack(bits);
+    value.widen().is_nan() && value.pack() & F::QUIET == 0
+}
+
+// The result of an operation with a NaN operand is the first NaN, made
+// quiet. Signaling NaNs are invalid.
+fn nan_result<F: Float>(operands: &[u64]) -> Option<(u64, u32)> {
+    let nan = operands.iter().find(|bits| F::unpack(**bits).widen().is_nan())?;
+    let causes = if operands.iter().any(|bits| is_signaling::<F>(*bits)) {
+        INVALID
+    } else {
+        0
+    };
+    Some((F::unpack(*nan).pack() | F::QUIET, causes))
+}
+
+fn sign(value: f64) -> Ordering {
+    value.partial_cmp(&0.0).unwrap_or(Ordering::Equal)
+}
+
+// a + b, and the rounding error of the sum, which is exact.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let b_part = s - a;
+    (s, (a - (s - b_part)) + (b - b_part))
+}
+
+// Each of these gives the f64 result along with which side of it the exact
+// result is on, worked out from the rounding error.
+fn sum(a: f64, b: f64) -> (f64, Ordering) {
+    let (s, error) = two_sum(a, b);
+    if !s.is_finite() {
+        return (s, Ordering::Equal);
+    }
+    (s, sign(error))
+}
+
+fn product(a: f64, b: f64) -> (f64, Ordering) {
+    let p = a * b;
+    if !p.is_finite() {
+        return (p, Ordering::Equal);
+    }
+    (p, sign(a.mul_add(b, -p)))
+}
+
+// x * y + z with a single rounding. A second fused step splits the product
+// exactly into p + e, and the four terms of p + e + z - s are then summed
+// exactly to find the sign of the rounding error.
+fn fused(x: f64, y: f64, z: f64) -> (f64, Ordering) {
+    let s = x.mul_add(y, z);
+    let p = x * y;
+    if !s.is_finite() || !p.is_finite() {
+        return (s, Ordering::Equal);
+    }
+    let e = x.mul_add(y, -p);
+    // Shewchuk's expansion sum: the components stay in increasing order
+    // without overlapping, so the largest nonzero one has the sign of the
+    // whole.
+    let mut expansion = [0.0; 4];
+    for (length, term) in [p, e, z, -s].into_iter().enumerate() {
+        let mut q = term;
+        for component in &mut expansion[..length] {
+            (q, *component) = two_sum(q, *component);
+        }
+        expansion[length] = q;
+    }
+    let error = expansion.iter().rev().find(|component| **component != 0.0);
+    (s, error.map_or(Ordering::Equal, |error| sign(*error)))
+}
+
+fn quotient(a: f64, b: f64) -> (f64, Ordering) {
+    let q = a / b;
+    if !q.is_finite() || b == 0.0 {
+        return (q, Ordering::Equal);
+    }
+    let remainder = sign(-q.mul_add(b, -a));
+    (q, if b < 0.0 { remainder.reverse() } else { remainder })
+}
+
+fn root(a: f64) -> (f64, Ordering) {
+    let s = a.sqrt();
+    if !s.is_finite() {
+        return (s, Ordering::Equal);
+    }
+    (s, sign(-s.mul_add(s, -a)))
+}
+
+fn round_integral(value: f64, rounding: Rounding) -> f64 {
+    match rounding {
+        Rounding::Nearest => value.round_ties_even(),
+        Rounding::Zero => value.trunc(),
+        Rounding::Up => value.ceil(),
+        Rounding::Down => value.floor(),
+    }
+}
+
+// Rounds to F. `exact` is the f64 result and `error` says which side of it
+// the real result is on. If the operands were all finite, an infinite result
+// means the operation overflowed.
+fn round<F: Float>(exact: f64, error: Ordering, finite: bool,
+                   rounding: Rounding) -> (u64, u32) {
+    let mut error = error;
+    if exact.is_infinite() {
+        if !finite {
+            return (F::narrow(exact).pack(), 0);
+        }
+        error = if exact > 0.0 { Ordering::Less } else { Ordering::Greater };
+    }
+
+    let mut value = F::narrow(exact);
+    if value.widen() != exact {
+        // When `exact` is a tie in F but was itself rounded, the real
+        // result isn't a tie, and goes the way of the error.
+        let direction = exact.partial_cmp(&value.widen());
+        let other = match direction {
+            Some(Ordering::Greater) => value.next_up(),
+            _ => value.next_down(),
+        };
+        if direction == Some(error) && other.widen().is_finite() &&
+                (value.widen() + other.widen()) / 2.0 == exact {
+            value = other;
+        }
+        error = exact.partial_cmp(&value.widen()).unwrap_or(error);
+    }
+    let mut overflow = value.widen().is_infinite();
+    let negative = exact.is_sign_negative();
+    match (rounding, error) {
+        (Rounding::Up, Ordering::Greater) => value = value.next_up(),
+        (Rounding::Down, Ordering::Less) => value = value.next_down(),
+        (Rounding::Zero, Ordering::Greater) if negative =>
+            value = value.next_up(),
+        (Rounding::Zero, Ordering::Less) if !negative =>
+            value = value.next_down(),
+        _ => {},
+    }
+    overflow |= value.widen().is_infinite();
+
+    let mut causes = 0;
+    if error != Ordering::Equal {
+        causes |= INEXACT;
+        if value.widen().abs() < F::MIN_POSITIVE {
+            causes |= UNDERFLOW;
+        }
+    }
+    if overflow {
+        causes |= OVERFLOW | INEXACT;
+    }
+    (value.pack(), causes)
+}
+
+// Finishes off an arithmetic operation: NaN operands, invalid operations and
+// division by zero, then rounding.
+fn result<F: Float>(operands: &[u64],
+                    (exact, error): (f64, Ordering),
+                    divide: bool,
+                    rounding: Rounding) -> (u64, u32) {
+    if let Some(result) = nan_result::<F>(operands) {
+        return result;
+    }
+    if exact.is_nan() {
+        return (F::DEFAULT_NAN, INVALID);
+    }
+    let finite = operands.iter().all(|bits| F::unpack(*bits).widen().is_finite());
+    if divide && finite && exact.is_infinite() {
+        return (F::narrow(exact).pack(), DIVIDE_BY_ZERO);
+    }
+    round::<F>(exact, error, finite, rounding)
+}
+
+fn arithmetic<F: Float>(op: FloatOp, fd: u64, fs: u64, ft: u64,
+                        rounding: Rounding) -> (u64, u32) {
+    let x = F::unpack(fs).widen();
+    let y = F::unpack(ft).widen();
+    let z = F::unpack(fd).widen();
+    match op {
+        FloatOp::Add | FloatOp::Sub => {
+            let y = if op == FloatOp::Sub { -y } else { y };
+            let (mut exact, error) = sum(x, y);
+            // An exact zero is -0 when rounding down, unless it's the sum
+            // of two +0s.
+            if exact == 0.0 && rounding == Rounding::Down &&
+                    (x != 0.0 || x.is_sign_negative() || y.is_sign_negative()) {
+                exact = -0.0;
+            }
+            result::<F>(&[fs, ft], (exact, error), false, rounding)
+        },
+        FloatOp::Mul => result::<F>(&[fs, ft], product(x, y), false, rounding),
+        FloatOp::Div =>
+            result::<F>(&[fs, ft], quotient(x, y), y == 0.0, rounding),
+        FloatOp::Maddf =>
+            result::<F>(&[fd, fs, ft], fused(x, y, z), false, rounding),
+        FloatOp::Msubf =>
+            result::<F>(&[fd, fs, ft], fused(-x, y, z), false, rounding),
+        FloatOp::Min | FloatOp::Mina | FloatOp::Max | FloatOp::Maxa => {
+            if (x.is_nan() && y.is_nan()) || is_signaling::<F>(fs) ||
+                    is_signaling::<F>(ft) {
+                return nan_result::<F>(&[fs, ft])
+                    .unwrap_or((F::DEFAULT_NAN, INVALID));
+            }
+            // A quiet NaN loses to a number.
+            if x.is_nan() {
+                return (F::unpack(ft).pack(), 0);
+            }
+            if y.is_nan() {
+                return (F::unpack(fs).pack(), 0);
+            }
+            let maximum = op == FloatOp::Max || op == FloatOp::Maxa;
+            let (a, b) = if op == FloatOp::Mina || op == FloatOp::Maxa {
+                (x.abs(), y.abs())
+            } else {
+                (x, y)
+            };
+            // Ties go by sign, so the minimum of -0 and +0 is -0.
+            let first = match a.partial_cmp(&b) {
+                Some(Ordering::Less) => !maximum,
+                Some(Ordering::Greater) => maximum,
+                _ => x.is_sign_negative() != maximum,
+            };
+            (F::unpack(if first { fs } else { ft }).pack(), 0)
+        },
+        // Not arithmetic; they're picked off before they get here.
+        FloatOp::Sel | FloatOp::Seleqz | FloatOp::Selnez => (fd, 0),
+    }
+}
+
+// Rounds to an integer, W or L. Anything that doesn't fit, NaNs included, is
+// invalid, and the result then saturates with NaNs going to zero.
+fn to_integer(value: f64, rounding: Rounding, long: bool) -> (u64, u32) {
+    let rounded = round_integral(value, rounding);
+    let limit = if long { 2f64.powi(63) } else { 2f64.powi(31) };
+    let causes = if value.is_nan() || rounded < -limit || rounded >= limit {
+        INVALID
+    } else if rounded != value {
+        INEXACT
+    } else {
+        0
+    };
+    if long {
+        (rounded as i64 as u64, causes)
+    } else {
+        (rounded as i32 as u32 as u64, causes)
+    }
+}
+
+// The same for MSA's unsigned conversions.
+fn to_unsigned(value: f64, rounding: Rounding, long: bool) -> (u64, u32) {
+    let rounded = round_integral(value, rounding);
+    let limit = if long { 2f64.powi(64) } else { 2f64.powi(32) };
+    let causes = if value.is_nan() || rounded < 0.0 || rounded >= limit {
+        INVALID
+    } else if rounded != value {
+        INEXACT
+    } else {
+        0
+    };
+    if long {
+        (rounded as u64, causes)
+    } else {
+        (rounded as u32 as u64, causes)
+    }
+}
+
+fn from_integer<F: Float>(integer: i128, rounding: Rounding) -> (u64, u32) {
+    let exact = integer as f64;
+    let error = integer.cmp(&(exact as i128));
+    round::<F>(exact, error, true, rounding)
+}
+
+// MSA's fixed-point formats are fractions `width` bits wide, sign
+// included, so Q15 is a halfword. Converting to them saturates.
+fn to_fixed<F: Float>(bits: u64, width: u32, rounding: Rounding)
+    -> (u64, u32) {
+    let value = F::unpack(bits).widen();
+    if value.is_nan() {
+        return (0, INVALID);
+    }
+    let limit = 2f64.powi(width as i32 - 1);
+    let scaled = value * limit;
+    let rounded = round_integral(scaled, rounding);
+    let (fixed, causes) = if rounded >= limit {
+        (limit - 1.0, OVERFLOW | INEXACT)
+    } else if rounded < -limit {
+        (-limit, OVERFLOW | INEXACT)
+    } else if rounded != scaled {
+        (rounded, INEXACT)
+    } else {
+        (rounded, 0)
+    };
+    (fixed as i64 as u64, causes)
+}
+
+fn from_fixed<F: Float>(bits: u64, width: u32, rounding: Rounding)
+    -> (u64, u32) {
+    let fraction = ((bits << (64 - width)) as i64 >> (64 - width)) as f64;
+    round::<F>(fraction / 2f64.powi(width as i32 - 1), Ordering::Equal, true,
+               rounding)
+}
+
+// Multiplies by 2 to the power n in steps that can't overflow f64 on the
+// way. Past the clamp the result over- or underflows whatever it is.
+fn scale(value: f64, n: i64) -> f64 {
+    let mut n = n.clamp(-2200, 2200) as i32;
+    let mut value = value;
+    while n != 0 {
+        let step = n.clamp(-1000, 1000);
+        value *= 2f64.powi(step);
+        n -= step;
+    }
+    value
+}
+
+// FEXP2. Only a result that's subnormal in f64 can have lost bits, and
+// scaling it back shows which way.
+fn exp2<F: Float>(bits: u64, n: i64, rounding: Rounding) -> (u64, u32) {
+    let x = F::unpack(bits).widen();
+    let value = scale(x, n);
+    let error = if value.is_finite() {
+        x.partial_cmp(&scale(value, -n)).unwrap_or(Ordering::Equal)
+    } else {
+        Ordering::Equal
+    };
+    result::<F>(&[bits], (value, error), false, rounding)
+}
+
+// FLOG2: the integer part of the base 2 logarithm, which is the exponent.
+fn log2<F: Float>(bits: u64) -> (u64, u32) {
+    if let Some(result) = nan_result::<F>(&[bits]) {
+        return result;
+    }
+    let x = F::unpack(bits).widen();
+    if x == 0.0 {
+        return (F::narrow(f64::NEG_INFINITY).pack(), DIVIDE_BY_ZERO);
+    }
+    if x < 0.0 {
+        return (F::DEFAULT_NAN, INVALID);
+    }
+    if x.is_infinite() {
+        return (bits, 0);
+    }
+    // f64 subnormals have to be made normal to have an exponent.
+    let (x, adjust) = if x < f64::MIN_POSITIVE {
+        (x * 2f64.powi(64), -64)
+    } else {
+        (x, 0)
+    };
+    let exponent = ((x.to_bits() >> 52) as i32) - 1023 + adjust;
+    (F::narrow(exponent as f64).pack(), 0)
+}
+
+// CVT.S.D and CVT.D.S. NaNs keep as much of their payload as fits.
+fn convert<F: Float, G: Float>(bits: u64, rounding: Rounding) -> (u64, u32) {
+    let value = F::unpack(bits).widen();
+    if value.is_nan() {
+        let causes = if is_signaling::<F>(bits) { INVALID } else { 0 };
+        return (G::narrow(value).pack() | G::QUIET, causes);
+    }
+    round::<G>(value, Ordering::Equal, value.is_finite(), rounding)
+}
+
+fn unary<F: Float>(op: FloatUnaryOp, bits: u64,
+                   rounding: Rounding) -> (u64, u32) {
+    let x = F::unpack(bits).widen();
+    match op {
+        FloatUnaryOp::Sqrt => result::<F>(&[bits], root(x), false, rounding),
+        FloatUnaryOp::Recip =>
+            result::<F>(&[bits], quotient(1.0, x), x == 0.0, rounding),
+        FloatUnaryOp::Rsqrt => {
+            let (s, _) = root(x);
+            result::<F>(&[bits], quotient(1.0, s), x == 0.0, rounding)
+        },
+        FloatUnaryOp::Rint => {
+            if let Some(result) = nan_result::<F>(&[bits]) {
+                return result;
+            }
+            let rounded = round_integral(x, rounding);
+            let causes = if rounded != x { INEXACT } else { 0 };
+            (F::narrow(rounded).pack(), causes)
+        },
+        FloatUnaryOp::RoundL => to_integer(x, Rounding::Nearest, true),
+        FloatUnaryOp::TruncL => to_integer(x, Rounding::Zero, true),
+        FloatUnaryOp::CeilL => to_integer(x, Rounding::Up, true),
+        FloatUnaryOp::FloorL => to_integer(x, Rounding::Down, true),
+        FloatUnaryOp::RoundW => to_integer(x, Rounding::Nearest, false),
+        FloatUnaryOp::TruncW => to_integer(x, Rounding::Zero, false),
+        FloatUnaryOp::CeilW => to_integer(x, Rounding::Up, false),
+        FloatUnaryOp::FloorW => to_integer(x, Rounding::Down, false),
+        FloatUnaryOp::CvtW => to_integer(x, rounding, false),
+        FloatUnaryOp::CvtL => to_integer(x, rounding, true),
+        FloatUnaryOp::CvtS => convert::<F, f32>(bits, rounding),
+        FloatUnaryOp::CvtD => convert::<F, f64>(bits, rounding),
+        // With ABS2008 set these only touch the sign bit.
+        FloatUnaryOp::Abs => (F::unpack(bits).pack() & !F::SIGN, 0),
+        FloatUnaryOp::Neg => (F::unpack(bits).pack() ^ F::SIGN, 0),
+        FloatUnaryOp::Mov => (bits, 0),
+        FloatUnaryOp::Class => {
+            let value = F::unpack(bits);
+            let class = if value.widen().is_nan() {
+                if is_signaling::<F>(bits) { 0x001 } else { 0x002 }
+            } else {
+                // Negative classes are the positive ones shifted down by 4.
+                let positive = match value.classify() {
+                    FpCategory::Infinite => 0x040,
+                    FpCategory::Normal => 0x080,
+                    FpCategory::Subnormal => 0x100,
+                    _ => 0x200,
+                };
+                if value.widen().is_sign_negative() {
+                    positive >> 4
+                } else {
+                    positive
+                }
+            };
+            (class, 0)
+        },
+    }
+}
+
+// The rounding mode in an FCSR or MSACSR.
+fn rounding(csr: u32) -> Rounding {
+    match csr & FCSR_RM {
+        0 => Rounding::Nearest,
+        1 => Rounding::Zero,
+        2 => Rounding::Up,
+        _ => Rounding::Down,
+    }
+}
+
+// Whether an FCSR or MSACSR's Cause has an exception that traps.
+fn trapping(csr: u32) -> bool {
+    let cause = (csr & FCSR_CAUSE) >> FCSR_CAUSE_SHIFT;
+    let enables = (csr & FCSR_ENABLES) >> FCSR_ENABLES_SHIFT;
+    cause & (enables | UNIMPLEMENTED) != 0
+}
+
+// Whether a CMP.condn.fmt condition holds, and the exceptions comparing
+// raises.
+fn holds<F: Float>(condition: FloatCondition, a: u64, b: u64) -> (bool, u32) {
+    let (x, y) = (F::unpack(a).widen(), F::unpack(b).widen());
+    let signaling = is_signaling::<F>(a) || is_signaling::<F>(b);
+    let code = condition as u32;
+    let unordered = x.is_nan() || y.is_nan();
+    let causes = if signaling || (unordered && code & 0x08 != 0) {
+        INVALID
+    } else {
+        0
+    };
+    let holds = (unordered && code & 0x01 != 0) ||
+        (x == y && code & 0x02 != 0) ||
+        (x < y && code & 0x04 != 0);
+    (holds != (code & 0x10 != 0), causes)
+}
+
+// Works out a vector an element at a time, collecting every element's
+// exceptions.
+fn lanes(df: VectorFormat,
+         mut lane: impl FnMut(usize) -> (u64, u32)) -> (u128, u32) {
+    let mut causes = 0;
+    let value = msa::from_elements(df, |i| {
+        let (value, raised) = lane(i);
+        causes |= raised;
+        value
+    });
+    (value, causes)
+}
+
+fn vector_arithmetic<F: Float>(op: VectorFloatOp,
+                               df: VectorFormat,
+                               (d, s, t): (u128, u128, u128),
+                               rounding: Rounding) -> (u128, u32) {
+    let op = match op {
+        VectorFloatOp::Fadd => FloatOp::Add,
+        VectorFloatOp::Fsub => FloatOp::Sub,
+        VectorFloatOp::Fmul => FloatOp::Mul,
+        VectorFloatOp::Fdiv => FloatOp::Div,
+        VectorFloatOp::Fmadd => FloatOp::Maddf,
+        VectorFloatOp::Fmsub => FloatOp::Msubf,
+        VectorFloatOp::Fmin => FloatOp::Min,
+        VectorFloatOp::FminA => FloatOp::Mina,
+        VectorFloatOp::Fmax => FloatOp::Max,
+        VectorFloatOp::FmaxA => FloatOp::Maxa,
+        _ => return lanes(df, |i| {
+            exp2::<F>(msa::element(s, df, i), msa::signed_element(t, df, i),
+                      rounding)
+        }),
+    };
+    lanes(df, |i| {
+        arithmetic::<F>(op, msa::element(d, df, i), msa::element(s, df, i),
+                        msa::element(t, df, i), rounding)
+    })
+}
+
+// FEXDO and FTQ, from F to G or to fixed point. ws goes in the left half of
+// wd and wt in the right.
+fn vector_narrow<F: Float, G: Float>(op: VectorFloatOp,
+                                     df: VectorFormat,
+                                     s: u128,
+                                     t: u128,
+                                     rounding: Rounding) -> (u128, u32) {
+    let wide = if df == VectorFormat::H {
+        VectorFormat::W
+    } else {
+        VectorFormat::D
+    };
+    let half = df.elements() / 2;
+    lanes(df, |i| {
+        let bits = if i < half {
+            msa::element(t, wide, i)
+        } else {
+            msa::element(s, wide, i - half)
+        };
+        if op == VectorFloatOp::Fexdo {
+            convert::<F, G>(bits, rounding)
+        } else {
+            to_fixed::<F>(bits, df.bits(), rounding)
+        }
+    })
+}
+
+// FEXUPL, FEXUPR, FFQL and FFQR, from F or fixed point to G.
+fn vector_widen<F: Float, G: Float>(op: VectorFloatUnaryOp,
+                                    df: VectorFormat,
+                                    s: u128,
+                                    rounding: Rounding) -> (u128, u32) {
+    let narrow = if df == VectorFormat::W {
+        VectorFormat::H
+    } else {
+        VectorFormat::W
+    };
+    let left = matches!(op, VectorFloatUnaryOp::Fexupl |
+                            VectorFloatUnaryOp::Ffql);
+    let start = if left { df.elements() } else { 0 };
+    lanes(df, |i| {
+        let bits = msa::element(s, narrow, start + i);
+        match op {
+            VectorFloatUnaryOp::Fexupl | VectorFloatUnaryOp::Fexupr =>
+                convert::<F, G>(bits, rounding),
+            _ => from_fixed::<G>(bits, narrow.bits(), rounding),
+        }
+    })
+}
+
+fn vector_unary<F: Float>(op: VectorFloatUnaryOp,
+                          df: VectorFormat,
+                          s: u128,
+                          rounding: Rounding) -> (u128, u32) {
+    let long = df == VectorFormat::D;
+    lanes(df, |i| {
+        let bits = msa::element(s, df, i);
+        let x = F::unpack(bits).widen();
+        match op {
+            VectorFloatUnaryOp::Fclass =>
+                unary::<F>(FloatUnaryOp::Class, bits, rounding),
+            VectorFloatUnaryOp::Fsqrt =>
+                unary::<F>(FloatUnaryOp::Sqrt, bits, rounding),
+            VectorFloatUnaryOp::Frsqrt =>
+                unary::<F>(FloatUnaryOp::Rsqrt, bits, rounding),
+            VectorFloatUnaryOp::Frcp =>
+                unary::<F>(FloatUnaryOp::Recip, bits, rounding),
+            VectorFloatUnaryOp::Frint =>
+                unary::<F>(FloatUnaryOp::Rint, bits, rounding),
+            VectorFloatUnaryOp::Flog2 => log2::<F>(bits),
+            VectorFloatUnaryOp::FtruncS => to_integer(x, Rounding::Zero, long),
+            VectorFloatUnaryOp::FtruncU =>
+                to_unsigned(x, Rounding::Zero, long),
+            VectorFloatUnaryOp::FtintS => to_integer(x, rounding, long),
+            VectorFloatUnaryOp::FtintU => to_unsigned(x, rounding, long),
+            VectorFloatUnaryOp::FfintS => from_integer::<F>(
+                msa::signed_element(s, df, i) as i128, rounding),
+            VectorFloatUnaryOp::FfintU =>
+                from_integer::<F>(bits as i128, rounding),
+            _ => unreachable!("{:?} changes the format", op),
+        }
+    })
+}
+
+// The MSA vector registers extend the floating-point ones to 128 bits, and
+// `upper` holds the extra halves.
+pub struct Cp1 {
+    registers: [u64; 32],
+    upper: [u64; 32],
+    fcsr: u32,
+    msacsr: u32,
+}
+
+pub fn new() -> Cp1 {
+    Cp1 {
+        registers: [0; 32],
+        upper: [0; 32],
+        fcsr: FCSR_NAN2008 | FCSR_ABS2008,
+        msacsr: 0,
+    }
+}
+
+impl Cp1 {
+    pub fn read(&self, index: usize) -> u64 {
+        self.registers[index]
+    }
+
+    pub fn write(&mut self, index: usize, value: u64) {
+        self.registers[index] = value;
+    }
+
+    pub fn read_vector(&self, index: usize) -> u128 {
+        ((self.upper[index] as u128) << 64) | self.registers[index] as u128
+    }
+
+    pub fn write_vector(&mut self, index: usize, value: u128) {
+        self.registers[index] = value as u64;
+        self.upper[index] = (value >> 64) as u64;
+    }
+
+    // None if there's no such control register.
+    pub fn read_control(&self, reg: usize) -> Option<u32> {
+        match reg {
+            FIR => Some(FIR_VALUE),
+            FEXR => Some(self.fcsr & (FCSR_CAUSE | FCSR_FLAGS)),
+            FENR => Some((self.fcsr & (FCSR_ENABLES | FCSR_RM)) |
+                         if self.fcsr & FCSR_FS != 0 { FENR_FS } else { 0 }),
+            FCSR => Some(self.fcsr),
+            _ => None,
+        }
+    }
+
+    // FEXR and FENR write their parts of the FCSR, and FIR can't be
+    // written. Returns false if the new Cause has an enabled exception in
+    // it, which traps.
+    pub fn write_control(&mut self, reg: usize, value: u32) -> bool {
+        let (mask, value) = match reg {
+            FEXR => (FCSR_CAUSE | FCSR_FLAGS, value),
+            FENR => (FCSR_ENABLES | FCSR_FS | FCSR_RM,
+                     (value & (FCSR_ENABLES | FCSR_RM)) |
+                     if value & FENR_FS != 0 { FCSR_FS } else { 0 }),
+            FCSR => (FCSR_CAUSE | FCSR_FLAGS | FCSR_ENABLES | FCSR_FS | FCSR_RM,
+                     value),
+            _ => (0, 0),
+        };
+        self.fcsr = (self.fcsr & !mask) | (value & mask);
+        !trapping(self.fcsr)
+    }
+
+    // Records the exceptions an instruction raised in Cause. If any of them
+    // is enabled the instruction traps: fd is left alone and this returns
+    // false. Otherwise the result is written and the exceptions are added
+    // to the Flags.
+    fn finish(&mut self, fd: usize, value: u64, causes: u32) -> bool {
+        self.fcsr = (self.fcsr & !FCSR_CAUSE) | (causes << FCSR_CAUSE_SHIFT);
+        if trapping(self.fcsr) {
+            return false;
+        }
+        self.fcsr |= causes << FCSR_FLAGS_SHIFT;
+        self.registers[fd] = value;
+        true
+    }
+
+    // Each of these returns false if the instruction trapped.
+    pub fn operate(&mut self,
+                   op: FloatOp,
+                   fmt: FloatFormat,
+                   fd: usize,
+                   fs: usize,
+                   ft: usize) -> bool {
+        let (a, b, c) = (self.registers[fs], self.registers[ft],
+                         self.registers[fd]);
+        // The selects just move bits around and don't touch the FCSR.
+        let selected = match op {
+            FloatOp::Sel => Some(if c & 1 != 0 { b } else { a }),
+            FloatOp::Seleqz => Some(if b & 1 == 0 { a } else { 0 }),
+            FloatOp::Selnez => Some(if b & 1 != 0 { a } else { 0 }),
+            _ => None,
+        };
+        if let Some(value) = selected {
+            self.registers[fd] = value;
+            return true;
+        }
+
+        let rounding = rounding(self.fcsr);
+        let (value, causes) = match fmt {
+            FloatFormat::S => arithmetic::<f32>(op, c, a, b, rounding),
+            FloatFormat::D => arithmetic::<f64>(op, c, a, b, rounding),
+        };
+        self.finish(fd, value, causes)
+    }
+
+    pub fn operate_unary(&mut self,
+                         op: FloatUnaryOp,
+                         fmt: FloatFormat,
+                         fd: usize,
+                         fs: usize) -> bool {
+        let rounding = rounding(self.fcsr);
+        let (value, causes) = match fmt {
+            FloatFormat::S =>
+                unary::<f32>(op, self.registers[fs], rounding),
+            FloatFormat::D =>
+                unary::<f64>(op, self.registers[fs], rounding),
+        };
+        match op {
+            FloatUnaryOp::Mov | FloatUnaryOp::Abs | FloatUnaryOp::Neg |
+            FloatUnaryOp::Class => {
+                self.registers[fd] = value;
+                true
+            },
+            _ => self.finish(fd, value, causes),
+        }
+    }
+
+    pub fn convert_fixed(&mut self,
+                         to: FloatFormat,
+                         from: FixedFormat,
+                         fd: usize,
+                         fs: usize) -> bool {
+        let integer = match from {
+            FixedFormat::W => self.registers[fs] as i32 as i64,
+            FixedFormat::L => self.registers[fs] as i64,
+        };
+        let rounding = rounding(self.fcsr);
+        let (value, causes) = match to {
+            FloatFormat::S => from_integer::<f32>(integer as i128, rounding),
+            FloatFormat::D => from_integer::<f64>(integer as i128, rounding),
+        };
+        self.finish(fd, value, causes)
+    }
+
+    // Sets fd to all ones if the condition holds and all zeros if not.
+    pub fn compare(&mut self,
+                   condition: FloatCondition,
+                   fmt: FloatFormat,
+                   fd: usize,
+                   fs: usize,
+                   ft: usize) -> bool {
+        let (a, b) = (self.registers[fs], self.registers[ft]);
+        let (holds, causes) = match fmt {
+            FloatFormat::S => holds::<f32>(condition, a, b),
+            FloatFormat::D => holds::<f64>(condition, a, b),
+        };
+        self.finish(fd, if holds { !0 } else { 0 }, causes)
+    }
+
+    // None if there's no such MSA control register.
+    pub fn read_msa_control(&self, reg: usize) -> Option<u32> {
+        match reg {
+            MSAIR => Some(MSAIR_VALUE),
+            MSACSR => Some(self.msacsr),
+            _ => None,
+        }
+    }
+
+    // MSAIR can't be written. Returns false if the new MSACSR has an
+    // enabled exception in its Cause.
+    pub fn write_msa_control(&mut self, reg: usize, value: u32) -> bool {
+        if reg == MSACSR {
+            self.msacsr = value & MSACSR_WRITABLE;
+        }
+        !trapping(self.msacsr)
+    }
+
+    // Like finish, but with MSACSR. The exceptions from every element go
+    // into Cause together, and any enabled one traps the whole
+    // instruction.
+    fn finish_vector(&mut self, wd: usize, value: u128, causes: u32) -> bool {
+        self.msacsr = (self.msacsr & !FCSR_CAUSE) |
+            (causes << FCSR_CAUSE_SHIFT);
+        if trapping(self.msacsr) {
+            return false;
+        }
+        self.msacsr |= causes << FCSR_FLAGS_SHIFT;
+        self.write_vector(wd, value);
+        true
+    }
+
+    // The MSA floating-point instructions. Like the scalar ones, each
+    // returns false if it trapped.
+    pub fn operate_vector(&mut self,
+                          op: VectorFloatOp,
+                          df: VectorFormat,
+                          wd: usize,
+                          ws: usize,
+                          wt: usize) -> bool {
+        let vectors = (self.read_vector(wd), self.read_vector(ws),
+                       self.read_vector(wt));
+        let (_, s, t) = vectors;
+        let rounding = rounding(self.msacsr);
+        let (value, causes) = match (op, df) {
+            (VectorFloatOp::Fexdo | VectorFloatOp::Ftq, VectorFormat::H) =>
+                vector_narrow::<f32, Half>(op, df, s, t, rounding),
+            (VectorFloatOp::Fexdo | VectorFloatOp::Ftq, _) =>
+                vector_narrow::<f64, f32>(op, df, s, t, rounding),
+            (_, VectorFormat::W) =>
+                vector_arithmetic::<f32>(op, df, vectors, rounding),
+            _ => vector_arithmetic::<f64>(op, df, vectors, rounding),
+        };
+        self.finish_vector(wd, value, causes)
+    }
+
+    pub fn operate_vector_unary(&mut self,
+                                op: VectorFloatUnaryOp,
+                                df: VectorFormat,
+                                wd: usize,
+                                ws: usize) -> bool {
+        let s = self.read_vector(ws);
+        let rounding = rounding(self.msacsr);
+        let widens = matches!(op, VectorFloatUnaryOp::Fexupl |
+                                  VectorFloatUnaryOp::Fexupr |
+                                  VectorFloatUnaryOp::Ffql |
+                                  VectorFloatUnaryOp::Ffqr);
+        let (value, causes) = match (widens, df) {
+            (true, VectorFormat::W) =>
+                vector_widen::<Half, f32>(op, df, s, rounding),
+            (true, _) => vector_widen::<f32, f64>(op, df, s, rounding),
+            (false, VectorFormat::W) =>
+                vector_unary::<f32>(op, df, s, rounding),
+            (false, _) => vector_unary::<f64>(op, df, s, rounding),
+        };
+        // Like CLASS.fmt, FCLASS leaves MSACSR alone.
+        if op == VectorFloatUnaryOp::Fclass {
+            self.write_vector(wd, value);
+            return true;
+        }
+        self.finish_vector(wd, value, causes)
+    }
+
+    pub fn compare_vector(&mut self,
+                          condition: FloatCondition,
+                          df: VectorFormat,
+                          wd: usize,
+                          ws: usize,
+                          wt: usize) -> bool {
+        let (s, t) = (self.read_vector(ws), self.read_vector(wt));
+        let (value, causes) = lanes(df, |i| {
+            let (a, b) = (msa::element(s, df, i), msa::element(t, df, i));
+            let (holds, causes) = if df == VectorFormat::W {
+                holds::<f32>(condition, a, b)
+            } else {
+                holds::<f64>(condition, a, b)
+            };
+            (if holds { !0 } else { 0 }, causes)
+        });
+        self.finish_vector(wd, value, causes)
+    }
+}
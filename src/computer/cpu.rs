@@ -1,1125 +1,1783 @@
-const LOW19: i32 = 0x7ffff;
-const LOW18: i32 = 0x7ffff;
-const LOW11: i32 = 0x7ff;
-const LOW6: i32 = 0x3f;
-const LOW5: i32 = 0x1f;
-
-// Naturally Aligned CPU Load/Store Instructions
-const LB: i32 = 0x20;
-const LBU: i32 = 0x24;
-const LD: i32 = 0x37;
-const LH: i32 = 0x21;
-const LHU: i32 = 0x25;
-const LW: i32 = 0x23;
-const LWU: i32 = 0x27;
-const SB: i32 = 0x28;
-const SD: i32 = 0x3f;
-const SH: i32 = 0x29;
-const SW: i32 = 0x2b;
-
-// PC-relative Loads
-const PCREL: i32 = 0x3b;
-const LWPC: i32 = 0x1;
-const LWUPC: i32 = 0x2;
-const LDPC: i32 = 0x6;
-
-// ALU Instructions with 16-bit Immediate Operand
-const ADDIU: i32 = 0x09;
-const ANDI: i32 = 0x0c;
-const DADDIU: i32 = 0x19;
-const LUI: i32 = 0x0f;
-const ORI: i32 = 0x0d;
-const SLTI: i32 = 0x0a;
-const SLTIU: i32 = 0x0b;
-const XORI: i32 = 0x0e;
-
-// Three-Operand ALU Instructions
-const ADD: i32 = 0x20;
-const ADDU: i32 = 0x21;
-const AND: i32 = 0x24;
-const DADD: i32 = 0x2c;
-const DADDU: i32 = 0x2d;
-const DSUB: i32 = 0x2e;
-const DSUBU: i32 = 0x2f;
-const NOR: i32 = 0x27;
-const OR: i32 = 0x25;
-const SLT: i32 = 0x2a;
-const SLTU: i32 = 0x2b;
-const SUB: i32 = 0x22;
-const SUBU: i32 = 0x23;
-const XOR: i32 = 0x26;
-
-// Two-Operand ALU Instructions
-const CLO: i32 = 0x51;
-const CLZ: i32 = 0x50;
-const DCLO: i32 = 0x53;
-const DCLZ: i32 = 0x52;
-
-// Shift Instructions
-const BSHFL: i32 = 0x20;
-const ALIGN: i32 = 0x2;
-const DBSHFL: i32 = 0x24;
-const DALIGN: i32 = 0x1;
-const BITSWAP: i32 = 0x00;
-const DBITSWAP: i32 = 0x00;
-const DSRL: i32 = 0x3a;
-const DSRL32: i32 = 0x3e;
-const DSRLV: i32 = 0x16;
-const DSLL: i32 = 0x38;
-const DSLL32: i32 = 0x3c;
-const DSLLV: i32 = 0x14;
-const DSRA: i32 = 0x3b;
-const DSRA32: i32 = 0x3f;
-const DSRAV: i32 = 0x17;
-const SRL: i32 = 0x02;
-const SRLV: i32 = 0x06;
-const SLL: i32 = 0x00;
-const SLLV: i32 = 0x04;
-const SRA: i32 = 0x03;
-const SRAV: i32 = 0x07;
-
-// Same-Width Multiply and Divide Instructions
-const MUL: i32 = 0x02;
-const SOP30: i32 = 0x18;
-const MUH: i32 = 0x03;
-const MULU: i32 = 0x02;
-const MUHU: i32 = 0x03;
-const SOP31: i32 = 0x19;
-const DMUL: i32 = 0x02;
-const DMUH: i32 = 0x03;
-const SOP34: i32 = 0x1c;
-const DMULU: i32 = 0x02;
-const DMUHU: i32 = 0x03;
-const SOP35: i32 = 0x1d;
-const DIV: i32 = 0x02;
-const MOD: i32 = 0x03;
-const SOP32: i32 = 0x1a;
-const DIVU: i32 = 0x02;
-const MODU: i32 = 0x03;
-const SOP33: i32 = 0x1b;
-const DDIV: i32 = 0x02;
-const DMOD: i32 = 0x03;
-const SOP36: i32 = 0x1e;
-const DDIVU: i32 = 0x02;
-const DMODU: i32 = 0x03;
-const SOP37: i32 = 0x1f;
-
-// Release 6 Compact Branch and Jump Instructions
-const BC: i32 = 0x32;
-const BALC: i32 = 0x3a;
-const POP66: i32 = 0x36;
-const JIC: i32 = 0;
-const POP76: i32 = 0x3e;
-const JIALC: i32 = 0;
-const POP26: i32 = 0x16;
-const POP27: i32 = 0x17;
-const POP06: i32 = 0x06;
-const POP07: i32 = 0x07;
-const POP10: i32 = 0x08;
-const POP30: i32 = 0x18;
-
-// Delayed Branch Instructions
-const J: i32 = 0x02;
-const JAL: i32 = 0x03;
-const JALR: i32 = 0x09;
-const BEQ: i32 = 0x04;
-
-// Special Constants
-const SPECIAL3: i32 = 0x1f;
-const BREAK: i32 = 0x0d;
-const OPCODE: i32 = 26;
-const RS: i32 = 21;
-const RT: i32 = 16;
-const RD: i32 = 11;
+use crate::computer::cp0;
+use crate::computer::cp1;
+use crate::computer::decoder::{self, BitFieldOp, Condition, Cop0Op, Cop1Op,
+                               CountOp, FloatMemoryOp, HiLoOp, ImmediateOp,
+                               Instruction, IsaMode, IsaRevision, LinkedOp,
+                               MemoryOp, PcRelativeOp, RegisterOp, ShiftOp,
+                               ShiftVariableOp, ShuffleOp, TlbOp,
+                               VectorFormat,
+                               HWR_CC, HWR_CCRES, HWR_CPUNUM, HWR_SYNCI_STEP,
+                               HWR_ULR};
+use crate::computer::exception::Exception;
+use crate::computer::memory::{Endianness, Memory};
+use crate::computer::tlb::Fault;
+use crate::computer::{dsp, micromips, mips16, msa};
+
+// What happens to a load or store whose address isn't a multiple of its
+// size. Release 6 leaves it to the implementation: some cores trap with an
+// address error, some have the kernel's handler emulate the access a byte
+// at a time, and some do it in hardware. LL, SC and their relatives trap
+// whatever the policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnalignedPolicy {
+    Trap,
+    // Each byte is a separate access, so one that runs off the end of
+    // memory faults on its first missing byte, and a store that faults has
+    // already written the bytes before it.
+    Emulate,
+    // The access goes ahead as one, like an aligned one.
+    Allow,
+}
+
+// How a CPU turns virtual addresses into physical ones. The base and limit
+// pair memory keeps for each CPU suits a program that runs on its own. The
+// TLB is what a kernel expects to manage, with kseg0 and kseg1 unmapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MmuKind {
+    BaseLimit,
+    Tlb,
+}
 
 struct Registers {
     registers: [u64; 32],
     pc: u64,
+    // Only instructions from before Release 6 use these. HI and LO are
+    // accumulator 0 of the DSP ASE's four.
+    hi: [u64; 4],
+    lo: [u64; 4],
+    dsp_control: u32,
 }
 
 pub struct Cpu {
     rf: Registers,
+    cp0: cp0::Cp0,
+    cp1: cp1::Cp1,
     id: u64,
-    syscall: bool,
-    exception: bool,
+    revision: IsaRevision,
+    exception: Option<Exception>,
     next_branching: bool,
     branching: bool,
     branch_target: u64,
+    // Where the jump whose delay slot we're in is.
+    branch_pc: u64,
+    // Like the branching flags, but for the forbidden slot after a compact
+    // branch that wasn't taken, or the delay slot of a branch that wasn't.
+    next_forbidden: bool,
+    forbidden: bool,
+    // Which encoding the PC is in, and how many bytes the instruction being
+    // run takes up.
+    isa_mode: IsaMode,
+    size: u64,
+    unaligned_policy: UnalignedPolicy,
+    // Every unaligned load and store, including the ones that trapped.
+    unaligned_accesses: u64,
 }
 
-pub fn new(id: u64) -> Cpu {
+pub fn new(id: u64, revision: IsaRevision) -> Cpu {
     Cpu {
         rf: Registers {
             registers: [0; 32],
             pc: 0,
+            hi: [0; 4],
+            lo: [0; 4],
+            dsp_control: 0,
         },
+        cp0: cp0::new(id),
+        cp1: cp1::new(),
         id,
-        syscall: false,
-        exception: false,
+        revision,
+        exception: None,
         next_branching: false,
         branching: false,
         branch_target: 0,
+        branch_pc: 0,
+        next_forbidden: false,
+        forbidden: false,
+        isa_mode: IsaMode::Standard,
+        size: 4,
+        unaligned_policy: UnalignedPolicy::Allow,
+        unaligned_accesses: 0,
     }
 }
 
 impl Cpu {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
 
-    // This function contains panics!
-    // They should never be hit, but I don't know the MIPS ISA well enough
-    // to say that definitively.
-    // Reserved instructions will probably panic.
-    pub fn step(&mut self, memory: &mut crate::computer::memory::Memory) {
-        // If there's a current exception or syscall that hasn't
-        // been handled, we just need to stop then and there.
-        if self.exception || self.syscall {
-            return;
+    pub fn revision(&self) -> IsaRevision {
+        self.revision
+    }
+
+    pub fn set_unaligned_policy(&mut self, policy: UnalignedPolicy) {
+        self.unaligned_policy = policy;
+    }
+
+    pub fn unaligned_accesses(&self) -> u64 {
+        self.unaligned_accesses
+    }
+
+    // Switching to a TLB starts it out empty.
+    pub fn set_mmu_kind(&mut self, kind: MmuKind) {
+        self.cp0.set_tlb_enabled(kind == MmuKind::Tlb);
+    }
+
+    pub fn mmu_kind(&self) -> MmuKind {
+        if self.cp0.tlb_enabled() {
+            MmuKind::Tlb
+        } else {
+            MmuKind::BaseLimit
         }
+    }
 
-        // Get the real address from the memory's translation unit.
-        let pc_address = memory.translate_address(self.id, self.rf.pc);
-        if pc_address == None {
-            self.exception = true;
-            return;
+    pub fn pc(&self) -> u64 {
+        self.rf.pc
+    }
+
+    pub fn isa_mode(&self) -> IsaMode {
+        self.isa_mode
+    }
+
+    // Moving the PC from outside abandons any branch in progress. Bit 0
+    // picks the ISA mode the way it does for a register jump.
+    pub fn set_pc(&mut self, pc: u64) {
+        self.rf.pc = self.switch_isa_mode(pc);
+        self.branching = false;
+        self.forbidden = false;
+    }
+
+    pub fn register(&self, index: usize) -> u64 {
+        self.rf.registers[index]
+    }
+
+    pub fn float_register(&self, index: usize) -> u64 {
+        self.cp1.read(index)
+    }
+
+    pub fn set_float_register(&mut self, index: usize, value: u64) {
+        self.cp1.write(index, value);
+    }
+
+    // The 128-bit MSA registers. Each one's low 64 bits are the
+    // floating-point register of the same number.
+    pub fn vector_register(&self, index: usize) -> u128 {
+        self.cp1.read_vector(index)
+    }
+
+    pub fn set_vector_register(&mut self, index: usize, value: u128) {
+        self.cp1.write_vector(index, value);
+    }
+
+    // The CP0 Status register, which says what mode the CPU runs in.
+    pub fn status(&self) -> u64 {
+        self.cp0.status()
+    }
+
+    pub fn set_status(&mut self, value: u64) {
+        self.cp0.set_status(value);
+    }
+
+    // Config5.MSAEn, which MSA instructions need.
+    pub fn set_msa_enabled(&mut self, enabled: bool) {
+        self.cp0.set_msa_enabled(enabled);
+    }
+
+    // Status.MX, which DSP ASE instructions need.
+    pub fn set_dsp_enabled(&mut self, enabled: bool) {
+        self.cp0.set_dsp_enabled(enabled);
+    }
+
+    // A DSP accumulator as one 64-bit value, HI on top.
+    pub fn accumulator(&self, ac: usize) -> u64 {
+        (self.rf.hi[ac] << 32) | (self.rf.lo[ac] & 0xffffffff)
+    }
+
+    // Each half goes in as a sign-extended word.
+    pub fn set_accumulator(&mut self, ac: usize, value: u64) {
+        self.rf.hi[ac] = (value >> 32) as i32 as i64 as u64;
+        self.rf.lo[ac] = value as i32 as i64 as u64;
+    }
+
+    pub fn dsp_control(&self) -> u32 {
+        self.rf.dsp_control
+    }
+
+    pub fn set_dsp_control(&mut self, value: u32) {
+        self.rf.dsp_control = value;
+    }
+
+    // The thread pointer RDHWR $29 reads.
+    pub fn set_user_local(&mut self, value: u64) {
+        self.cp0.write(cp0::USERLOCAL, cp0::USERLOCAL_SEL, value);
+    }
+
+    // Writes to $zero are dropped.
+    pub fn set_register(&mut self, index: usize, value: u64) {
+        if index != 0 {
+            self.rf.registers[index] = value;
         }
+    }
 
-        // Get the actual instruction from memory.
-        let instruction = memory.read_instruction(pc_address.unwrap());
-        if instruction == None {
-            self.exception = true;
-            return;
+    // A CPU stops running as soon as it hits an exception that nothing has
+    // handled yet.
+    pub fn halted(&self) -> bool {
+        self.exception.is_some()
+    }
+
+    pub fn exception(&self) -> Option<Exception> {
+        self.exception
+    }
+
+    // Forgets the pending exception so the CPU can run again. The PC is left
+    // pointing at the instruction that raised it, so unless the caller moves
+    // the PC on, that instruction is retried.
+    pub fn clear_exception(&mut self) {
+        self.exception = None;
+    }
+
+    // Forgets the pending exception and carries on as though the instruction
+    // that raised it had finished, which for an instruction in a delay slot
+    // means going on to the branch target.
+    pub fn skip_exception(&mut self) {
+        self.exception = None;
+        self.advance();
+    }
+
+    // Hands the pending exception to the program's own handler by vectoring
+    // through CP0. If there's nothing at the exception vector to run, the
+    // exception is left pending and the CPU stays halted. Returns whether
+    // the exception was handed off.
+    pub fn deliver_exception(&mut self, memory: &mut Memory) -> bool {
+        let exception = match self.exception {
+            None => return false,
+            Some(exception) => exception,
+        };
+
+        // The vectors are in kseg0 or kseg1, so with a TLB they're never
+        // mapped.
+        let vector = self.cp0.exception_vector(&exception);
+        let physical = if self.cp0.tlb_enabled() {
+            cp0::unmapped(vector)
+        } else {
+            memory.translate_address(self.id, vector)
+        };
+        let handler =
+            physical.and_then(|address| memory.read_instruction(address));
+        if handler.is_none() {
+            return false;
         }
 
-        // Finally, execute the instruction.
-        self.execute_instruction(instruction.unwrap(), memory);
+        // The handler runs standard code. It returns to the faulting
+        // instruction, or to the jump if that's in a delay slot, with the
+        // ISA mode to go back to in bit 0.
+        let restart = if self.branching {
+            self.branch_pc
+        } else {
+            exception.pc()
+        };
+        self.cp0.enter_exception(&exception,
+                                 restart | self.mode_bit(),
+                                 self.branching);
+        self.exception = None;
+        self.branching = false;
+        self.forbidden = false;
+        self.isa_mode = IsaMode::Standard;
+        self.rf.pc = vector;
+        true
     }
 
-    // This function is the source of the panics.
-    pub fn execute_instruction(&mut self,
-                               instruction: u32,
-                               memory: &mut crate::computer::memory::Memory) {
-        // We start by pulling the opcode and some commonly used parts of
-        // the instruction.
-        let opcode: i32 = (instruction >> OPCODE) as i32;
-
-        let rs: usize = (((instruction >> RS) as i32) & LOW5) as usize;
-        let rt: usize = (((instruction >> RT) as i32) & LOW5) as usize;
-        let rd: usize = (((instruction >> RD) as i32) & LOW5) as usize;
-
-        let imm16: i64 = ( instruction as i16 ) as i64;
-        
-        // Match on the opcode to find the category of instruction.
-        match opcode {
-            0 => {
-                // If the opcode is 0, we need to fetch the function bits
-                // and check those next.
-                let function: i32 = (instruction as i32) & LOW6;
-
-                if function == BREAK {
-
-                } else if (instruction as i32 & LOW11) == CLO && rt == 0 {
-                    let i: u64 = 0;
-                    for i in 0..32 {
-                        if ( (self.rf.registers[rs] & (0x1 << (31 - i)))
-                                    >> (31 - i)) == 1 {
-                            break;
-                        }
-                    }
-                    self.rf.registers[rd] = i;
-                } else if (instruction as i32 & LOW11) == CLZ && rt == 0 {
-                    let i: u64 = 0;
-                    for i in 0..32 {
-                        if ( (self.rf.registers[rs] ^ (0x1 << (31 - i)))
-                                    >> (31 - i)) & 0x1 == 1 {
-                            break;
-                        }
-                    }
-                    self.rf.registers[rd] = i;
-                } else if (instruction as i32 & LOW11) == DCLO && rt == 0 {
-                    let i: u64 = 0;
-                    for i in 0..64 {
-                        if ( (self.rf.registers[rs] & (0x1 << (63 - i)))
-                                    >> (63 - i)) == 1 {
-                            break;
-                        }
+    fn raise(&mut self, exception: Exception) {
+        self.exception = Some(exception);
+    }
+
+    // The byte order the CPU sees memory in: the memory's own, unless it's
+    // running reverse-endian.
+    fn endianness(&self, memory: &Memory) -> Endianness {
+        if self.cp0.reverse_endian() {
+            memory.endianness().reversed()
+        } else {
+            memory.endianness()
+        }
+    }
+
+    // Where a virtual address lands in physical memory, or the exception
+    // to raise if it can't be reached. Without 64-bit addressing, anything
+    // other than a sign-extended 32-bit address is an address error before
+    // the MMU even sees it. Instruction fetches count as loads.
+    fn translate(&self, memory: &mut Memory, address: u64, store: bool)
+        -> Result<u64, Exception> {
+        let physical = if !self.cp0.doubleword_addressing() &&
+                address as i32 as i64 as u64 != address {
+            Err(Fault::AddressError)
+        } else if self.cp0.tlb_enabled() {
+            self.cp0.map(address, store)
+        } else {
+            memory.translate_address(self.id, address)
+                .ok_or(Fault::AddressError)
+        };
+        let pc = self.rf.pc;
+        physical.map_err(|fault| match (fault, store) {
+            (Fault::AddressError, false) =>
+                Exception::AddressErrorLoad { pc, address },
+            (Fault::AddressError, true) =>
+                Exception::AddressErrorStore { pc, address },
+            (Fault::Refill, false) => Exception::TlbLoad { pc, address },
+            (Fault::Refill, true) => Exception::TlbStore { pc, address },
+            (Fault::Invalid, false) =>
+                Exception::TlbInvalidLoad { pc, address },
+            (Fault::Invalid, true) =>
+                Exception::TlbInvalidStore { pc, address },
+            (Fault::Modified, _) => Exception::TlbModified { pc, address },
+        })
+    }
+
+    // Reads `size` bytes from a virtual address, raising the appropriate
+    // exception if that can't be done. Unaligned addresses are dealt with
+    // as the unaligned policy says.
+    fn load(&mut self,
+            memory: &mut Memory,
+            address: u64,
+            size: u64) -> Option<u64> {
+        if address & (size - 1) != 0 {
+            self.unaligned_accesses += 1;
+            match self.unaligned_policy {
+                UnalignedPolicy::Trap => {
+                    let pc = self.rf.pc;
+                    self.raise(Exception::AddressErrorLoad { pc, address });
+                    return None;
+                },
+                UnalignedPolicy::Emulate => {
+                    let endianness = self.endianness(memory);
+                    let mut value = 0;
+                    for i in 0..size {
+                        let byte = self.read(memory, address.wrapping_add(i),
+                                             1)?;
+                        value |= byte << (8 * endianness.lane(i, size));
                     }
-                    self.rf.registers[rd] = i;
-                } else if (instruction as i32 & LOW11) == DCLZ && rt == 0 {
-                    let i: u64 = 0;
-                    for i in 0..64 {
-                        if ( (self.rf.registers[rs] ^ (0x1 << (63 - i)))
-                                    >> (63 - i)) & 0x1 == 1 {
-                            break;
+                    return Some(value);
+                },
+                UnalignedPolicy::Allow => {},
+            }
+        }
+        self.read(memory, address, size)
+    }
+
+    fn store(&mut self,
+             memory: &mut Memory,
+             address: u64,
+             value: u64,
+             size: u64) {
+        if address & (size - 1) != 0 {
+            self.unaligned_accesses += 1;
+            match self.unaligned_policy {
+                UnalignedPolicy::Trap => {
+                    let pc = self.rf.pc;
+                    self.raise(Exception::AddressErrorStore { pc, address });
+                    return;
+                },
+                UnalignedPolicy::Emulate => {
+                    let endianness = self.endianness(memory);
+                    for i in 0..size {
+                        let shift = 8 * endianness.lane(i, size);
+                        self.write(memory, address.wrapping_add(i),
+                                   value >> shift, 1);
+                        if self.exception.is_some() {
+                            return;
                         }
                     }
-                    self.rf.registers[rd] = i;
-                } else {
+                    return;
+                },
+                UnalignedPolicy::Allow => {},
+            }
+        }
+        self.write(memory, address, value, size);
+    }
 
-                    match function {
-                        ADD => {
-                            if (self.rf.registers[rs] as i32).checked_add(
-                                    self.rf.registers[rt] as i32) == None {
-                                self.exception = true;
-                                self.rf.pc -= 4;
-                            } else {
-                                self.rf.registers[rd] = (
-                                    self.rf.registers[rs] as i32 +
-                                    self.rf.registers[rt] as i32
-                                ) as i64 as u64;
-                            }
-                        },
-                        ADDU => {
-                            self.rf.registers[rd] = (
-                                self.rf.registers[rs] as i64 +
-                                self.rf.registers[rt] as i64
-                            ) as u64;
-                        },
-                        AND => {
-                            self.rf.registers[rd] = self.rf.registers[rs] &
-                                                    self.rf.registers[rt];
-                        },
-                        DADD => {
-                            if (self.rf.registers[rs] as i64).checked_add(
-                                    self.rf.registers[rt] as i64) == None {
-                                self.exception = true;
-                                self.rf.pc -= 4;
-                            } else {
-                                self.rf.registers[rd] = (
-                                    self.rf.registers[rs] as i64 +
-                                    self.rf.registers[rt] as i64
-                                ) as u64;
-                            }
-                        },
-                        DADDU => {
-                            self.rf.registers[rd] = (
-                                self.rf.registers[rs] as i64 +
-                                self.rf.registers[rt] as i64
-                            ) as u64;
-                        },
-                        DSUB => {
-                            if (self.rf.registers[rs] as i64).checked_sub(
-                                    self.rf.registers[rt] as i64) == None {
-                                self.exception = true;
-                                self.rf.pc -= 4;
-                            } else {
-                                self.rf.registers[rd] = (
-                                    self.rf.registers[rs] as i64 -
-                                    self.rf.registers[rt] as i64
-                                ) as u64;
-                            }
-                        },
-                        DSUBU => {
-                            self.rf.registers[rd] = (
-                                self.rf.registers[rs] as i64 -
-                                self.rf.registers[rt] as i64
-                            ) as u64;
-                        },
-                        NOR => {
-                            self.rf.registers[rd] = !(
-                                self.rf.registers[rs] |
-                                self.rf.registers[rt]
-                            );
-                        },
-                        OR => {
-                            self.rf.registers[rd] = self.rf.registers[rs] |
-                                                    self.rf.registers[rt];
-                        },
-                        SLT => {
-                            self.rf.registers[rd] = if (self.rf.registers[rs] as i64) <
-                                                      (self.rf.registers[rt] as i64)
-                                                        { 1 } else { 0 };
-                        },
-                        SLTU => {
-                            self.rf.registers[rd] = if self.rf.registers[rs] <
-                                                    self.rf.registers[rt]
-                                                        { 1 } else { 0 };
-                        },
-                        SUB => {
-                            if (self.rf.registers[rs] as i32).checked_sub(
-                                    self.rf.registers[rt] as i32) == None {
-                                self.exception = true;
-                                self.rf.pc -= 4;
-                            } else {
-                                self.rf.registers[rd] = (
-                                    self.rf.registers[rs] as i32 -
-                                    self.rf.registers[rt] as i32
-                                ) as i64 as u64;
-                            }
-                        },
-                        SUBU => {
-                            self.rf.registers[rd] = (
-                                self.rf.registers[rs] as i32 -
-                                self.rf.registers[rt] as i32
-                            ) as i64 as u64;
-                        },
-                        XOR => {
-                            self.rf.registers[rd] = self.rf.registers[rs] ^
-                                                    self.rf.registers[rt];
-                        },
-                        DSRL => { // DROTR
-                            let sa = (instruction as i32 >> 6) & LOW5;
-                            if (instruction >> 21) & 0x1 != 0 {
-                                self.rf.registers[rd] =
-                                    (self.rf.registers[rt] << (64 - sa)) |
-                                    (self.rf.registers[rt] >> sa);
-                            } else { // DSRL
-                                self.rf.registers[rd] = self.rf.registers[rt]
-                                    >> sa;
-                            }
-                        },
-                        DSRL32 => { // DROTR32
-                            let sa = ((instruction as i32 >> 6) & LOW5) + 32;
-                            if (instruction >> 21) & 0x1 != 0 {
-                                self.rf.registers[rd] =
-                                    (self.rf.registers[rt] << (64 - sa)) |
-                                    (self.rf.registers[rt] >> sa);
-                            } else { // DSLR32
-                                self.rf.registers[rd] =
-                                    self.rf.registers[rt] >> sa;
-                            }
-                        },
-                        DSRLV => { // DROTRV
-                            let sa = self.rf.registers[rs];
-                            if (instruction >> 6) & 0x1 != 0 {
-                                self.rf.registers[rd] =
-                                    (self.rf.registers[rt] << (64 - sa)) |
-                                    (self.rf.registers[rt] >> sa);
-                            } else {
-                                self.rf.registers[rd] =
-                                    self.rf.registers[rt] >> sa;
-                            }
-                        },
-                        DSLL => {
-                            let sa = (instruction as i32 >> 6) & LOW5;
-                            if instruction >> 21 == 0 {
-                                self.rf.registers[rd] = self.rf.registers[rt]
-                                    << sa;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        DSLL32 => {
-                            let sa = (instruction as i32 >> 6) & LOW5;
-                            if instruction >> 21 == 0 {
-                                self.rf.registers[rd] = self.rf.registers[rt]
-                                    << (sa + 32);
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        DSLLV => {
-                            if ( instruction >> 6 ) as i32 & LOW5 == 0 {
-                                self.rf.registers[rd] = self.rf.registers[rt]
-                                    << self.rf.registers[rs];
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        DSRA => {
-                            let sa = (instruction as i32 >> 6) & LOW5;
-                            if instruction >> 21 == 0 {
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    as i64 >> sa) as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        DSRA32 => {
-                            let sa = (instruction as i32 >> 6) & LOW5;
-                            if instruction >> 21 == 0 {
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    as i64 >> (sa + 32)) as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        DSRAV => {
-                            if (instruction as i32 >> 6) & LOW5 == 0 {
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    as i64 >> (self.rf.registers[rs] + 32)
-                                    ) as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SRL => {
-                            let sa = (instruction as i32 >> 6) & LOW5;
-                            if (instruction as i32 >> 21) & LOW5 == 0x1 { // ROTR
-                                self.rf.registers[rd] = (((self.rf.registers[rt]
-                                    << (32 - sa)) as i32) |
-                                    ((self.rf.registers[rt] as u32 >> sa) as i32))
-                                    as i64 as u64;
-                            } else { // SRL
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    as u32 >> sa) as u64;
-                            }
-                        },
-                        SRLV => {
-                            let sa = self.rf.registers[rs] as i32 & LOW5;
-                            if (instruction as i32 >> 6) & LOW5 == 0x1 { // ROTRV
-                                self.rf.registers[rd] = (((self.rf.registers[rt]
-                                    << (32 - sa)) as i32) |
-                                    ((self.rf.registers[rt] as u32 >> sa) as i32))
-                                    as i64 as u64;
-                            } else { // SRLV
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    as u32 >> sa) as u64;
-                            }
-                        },
-                        SLL => {
-                            let sa = (instruction as i32 >> 6) & LOW5;
-                            if (instruction as i32 >> 21) & LOW5 == 0x0 {
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    << sa) as i32 as i64 as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SLLV => {
-                            let sa = self.rf.registers[rs] as i32 & LOW5;
-                            if (instruction as i32 >> 6) & LOW5 == 0x0 {
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    << sa) as i32 as i64 as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SRA => {
-                            let sa = (instruction as i32 >> 6) & LOW5;
-                            if (instruction as i32 >> 21) & LOW5 == 0x0 {
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    as i32 >> sa) as i64 as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SRAV => {
-                            let sa = self.rf.registers[rs] as i32 & LOW5;
-                            if (instruction as i32 >> 6) & LOW5 == 0x0 {
-                                self.rf.registers[rd] = (self.rf.registers[rt]
-                                    as i32 >> sa) as i64 as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SOP30 => {
-                            if ( instruction as i32 >> 6 ) & LOW5 == MUL {
-                                self.rf.registers[rd] = (
-                                    self.rf.registers[rs] as i32
-                                    * self.rf.registers[rt] as i32)
-                                    as i64 as u64;
-                            } else if (instruction as i32 >> 6) & LOW5 == MUH {
-                                self.rf.registers [rd] = ((
-                                    (self.rf.registers[rs] as i32 as i64) *
-                                    (self.rf.registers[rt] as i32 as i64)
-                                    ) >> 32) as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SOP31 => {
-                            if ( instruction as i32 >> 6 ) & LOW5 == MULU {
-                                self.rf.registers[rd] = (
-                                    self.rf.registers[rs] as u32
-                                    * self.rf.registers[rt] as u32
-                                ) as u64;
-                            } else if (instruction as i32 >> 6) & LOW5 == MUHU {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as u32 as u64)
-                                    * (self.rf.registers[rt] as u32 as u64)
-                                ) >> 32;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SOP34 => {
-                            if (instruction as i32 >> 6) & LOW5 == DMUL {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as i64)
-                                    * (self.rf.registers[rt] as i64)
-                                ) as u64;
-                            } else if (instruction as i32 >> 6) & LOW5 == DMUH {
-                                self.rf.registers[rd] = ((
-                                    (self.rf.registers[rs] as i128)
-                                    * (self.rf.registers[rt] as i128)
-                                ) >> 64) as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SOP35 => {
-                            if (instruction as i32 >> 6) & LOW5 == DMULU {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as u64)
-                                    * (self.rf.registers[rt] as u64)
-                                ) as u64;
-                            } else if (instruction as i32 >> 6) & LOW5 == DMUHU {
-                                self.rf.registers[rd] = ((
-                                    (self.rf.registers[rs] as u128)
-                                    * (self.rf.registers[rt] as u128)
-                                ) >> 64) as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SOP32 => {
-                            if (instruction as i32 >> 6) & LOW5 == DIV {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as i32)
-                                    / (self.rf.registers[rt] as i32)
-                                ) as i64 as u64;
-                            } else if (instruction as i32 >> 6) & LOW5 == MOD {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as i32)
-                                    % (self.rf.registers[rt] as i32)
-                                ) as i64 as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SOP33 => {
-                            if (instruction as i32 >> 6) & LOW5 == DIVU {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as u32)
-                                    / (self.rf.registers[rt] as u32)
-                                ) as u64;
-                            } else if (instruction as i32 >> 6) & LOW5 == MODU {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as u32)
-                                    % (self.rf.registers[rt] as u32)
-                                ) as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SOP36 => {
-                            if (instruction as i32 >> 6) & LOW5 == DDIV {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as i64)
-                                    / (self.rf.registers[rt] as i64)
-                                ) as u64;
-                            } else if (instruction as i32 >> 6) & LOW5 == DMOD {
-                                self.rf.registers[rd] = (
-                                    (self.rf.registers[rs] as i64)
-                                    % (self.rf.registers[rt] as i64)
-                                ) as u64;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        SOP37 => {
-                            if (instruction as i32 >> 6) & LOW5 == DDIVU {
-                                self.rf.registers[rd] =
-                                    (self.rf.registers[rs])
-                                    / (self.rf.registers[rt]);
-                            } else if (instruction as i32 >> 6) & LOW5 == DMODU {
-                                self.rf.registers[rd] =
-                                    (self.rf.registers[rs])
-                                    % (self.rf.registers[rt]);
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        JALR => {
-                            if rd != 0 {
-                                self.rf.registers[rd] = self.rf.pc + 8;
-                            }
-                            self.rf.pc = self.rf.registers[rs];
-                        },
-                        _ => panic!("Uncovered type!"),
-                    }
+    // A single access to memory, whatever its alignment.
+    // Where the first and last bytes of an access are in memory. With a
+    // TLB, an unaligned access can run over into the next page, which is
+    // mapped on its own, so both pages have to be there before any of it is
+    // done.
+    fn translate_span(&mut self,
+                      memory: &mut Memory,
+                      address: u64,
+                      size: u64,
+                      store: bool) -> Option<(u64, u64)> {
+        let last = address.wrapping_add(size - 1);
+        let span = self.translate(memory, address, store).and_then(|first| {
+            if self.cp0.tlb_enabled() {
+                Ok((first, self.translate(memory, last, store)?))
+            } else {
+                Ok((first, first.wrapping_add(size - 1)))
+            }
+        });
+        match span {
+            Err(exception) => {
+                self.raise(exception);
+                None
+            },
+            Ok(span) => Some(span),
+        }
+    }
+
+    fn read(&mut self,
+            memory: &mut Memory,
+            address: u64,
+            size: u64) -> Option<u64> {
+        let pc = self.rf.pc;
+        let (first, last) = self.translate_span(memory, address, size, false)?;
+        let endianness = self.endianness(memory);
+        let value = if last == first.wrapping_add(size - 1) {
+            memory.read_ordered(first, size, endianness)
+        } else {
+            (0..size).try_fold(0, |value, i| {
+                let byte = split_byte(address, size, first, last, i);
+                let lane = endianness.lane(i, size);
+                Some(value | (memory.read_byte(byte)? as u64) << (8 * lane))
+            })
+        };
+        if value.is_none() {
+            self.raise(Exception::BusErrorData { pc, address });
+        }
+        value
+    }
+
+    fn write(&mut self,
+             memory: &mut Memory,
+             address: u64,
+             value: u64,
+             size: u64) {
+        let pc = self.rf.pc;
+        let (first, last) =
+            match self.translate_span(memory, address, size, true) {
+                None => return,
+                Some(span) => span,
+            };
+        let endianness = self.endianness(memory);
+        let written = if last == first.wrapping_add(size - 1) {
+            memory.write_ordered(first, value, size, endianness)
+        } else {
+            (0..size).all(|i| {
+                let byte = split_byte(address, size, first, last, i);
+                let lane = endianness.lane(i, size);
+                memory.write_byte(byte, (value >> (8 * lane)) as u8)
+            })
+        };
+        if !written {
+            self.raise(Exception::BusErrorData { pc, address });
+        }
+    }
+
+    // LL and friends: a load that also links the CPU to where it loaded
+    // from. They have to be naturally aligned.
+    fn load_linked(&mut self,
+                   memory: &mut Memory,
+                   address: u64,
+                   size: u64) -> Option<u64> {
+        if address & (size - 1) != 0 {
+            let pc = self.rf.pc;
+            self.raise(Exception::AddressErrorLoad { pc, address });
+            return None;
+        }
+        let value = self.load(memory, address, size)?;
+        let physical = self.translate(memory, address, false).ok()?;
+        memory.link(self.id, physical);
+        Some(value)
+    }
+
+    // SC and friends: stores only if nothing has broken the link since the
+    // last load-linked, and says whether it did.
+    fn store_conditional(&mut self,
+                         memory: &mut Memory,
+                         address: u64,
+                         value: u64,
+                         size: u64) -> Option<bool> {
+        if address & (size - 1) != 0 {
+            let pc = self.rf.pc;
+            self.raise(Exception::AddressErrorStore { pc, address });
+            return None;
+        }
+        let physical = match self.translate(memory, address, true) {
+            Err(exception) => {
+                self.raise(exception);
+                return None;
+            },
+            Ok(physical) => physical,
+        };
+        if !memory.take_link(self.id, physical) {
+            return Some(false);
+        }
+        self.store(memory, address, value, size);
+        if self.exception.is_some() {
+            None
+        } else {
+            Some(true)
+        }
+    }
+
+    // LWL and LWR load the bytes of an unaligned word that lie in the aligned
+    // word holding `address`, merging them into `value`; LDL and LDR do the
+    // same for doublewords.
+    fn load_partial(&mut self,
+                    memory: &mut Memory,
+                    address: u64,
+                    size: u64,
+                    left: bool,
+                    value: u64) -> Option<u64> {
+        let endianness = self.endianness(memory);
+        let (start, count, origin) = partial(endianness, address, size, left);
+        let mut value = value;
+        for i in 0..count {
+            let byte = self.load(memory, start + i, 1)?;
+            let index = (start + i).wrapping_sub(origin);
+            let shift = 8 * endianness.lane(index, size);
+            value = (value & !(0xff << shift)) | (byte << shift);
+        }
+        Some(value)
+    }
+
+    fn store_partial(&mut self,
+                     memory: &mut Memory,
+                     address: u64,
+                     size: u64,
+                     left: bool,
+                     value: u64) {
+        let endianness = self.endianness(memory);
+        let (start, count, origin) = partial(endianness, address, size, left);
+        for i in 0..count {
+            let index = (start + i).wrapping_sub(origin);
+            let shift = 8 * endianness.lane(index, size);
+            self.store(memory, start + i, value >> shift, 1);
+            if self.exception.is_some() {
+                return;
+            }
+        }
+    }
+
+    // Returns the exception if this step raised one. A CPU with an exception
+    // pending does nothing until the exception is cleared.
+    pub fn step(&mut self, memory: &mut Memory) -> Option<Exception> {
+        if self.exception.is_some() {
+            return None;
+        }
+
+        let pc = self.rf.pc;
+
+        // Get the real address from the memory's translation unit, or the
+        // TLB.
+        let pc_address = match self.translate(memory, pc, false) {
+            Err(exception) => {
+                self.raise(exception);
+                return self.exception;
+            },
+            Ok(address) => address,
+        };
+
+        // Get the actual instruction from memory.
+        let endianness = self.endianness(memory);
+        let (instruction, size) = match memory.read_instruction_ordered(
+                pc_address, self.isa_mode, endianness) {
+            None => {
+                self.raise(Exception::BusErrorInstruction { pc, address: pc });
+                return self.exception;
+            },
+            Some(fetched) => fetched,
+        };
+
+        // Finally, execute the instruction.
+        self.size = size;
+        let instruction = match self.isa_mode {
+            IsaMode::Standard => decoder::decode(instruction, self.revision),
+            IsaMode::MicroMips => micromips::decode(instruction, size),
+            IsaMode::Mips16 => mips16::decode(instruction, size),
+        };
+        self.run(instruction, memory);
+        self.exception
+    }
+
+    // Runs a word as a standard MIPS instruction, whatever the ISA mode.
+    pub fn execute_instruction(&mut self,
+                               instruction: u32,
+                               memory: &mut Memory) {
+        self.size = 4;
+        self.run(decoder::decode(instruction, self.revision), memory);
+    }
+
+    // Words that don't encode an instruction raise a Reserved Instruction
+    // exception rather than taking down the host, as does a branch or jump
+    // in a delay slot or forbidden slot.
+    fn run(&mut self, instruction: Instruction, memory: &mut Memory) {
+        self.cp0.tick();
+
+        // Control transfers aren't allowed in a delay or forbidden slot, and
+        // doubleword instructions need 64-bit operations enabled.
+        let reserved = ((self.branching || self.forbidden) &&
+                        instruction.is_control_transfer()) ||
+            (instruction.is_doubleword() && !self.cp0.doubleword_operations());
+        if reserved {
+            self.raise(Exception::ReservedInstruction { pc: self.rf.pc });
+        } else if instruction.is_vector() && !self.cp0.msa_enabled() {
+            self.raise(Exception::MsaDisabled { pc: self.rf.pc });
+        } else if instruction.is_dsp() && !self.cp0.dsp_enabled() {
+            self.raise(Exception::DspDisabled { pc: self.rf.pc });
+        } else {
+            self.execute(instruction, memory);
+        }
+
+        // The PC stays on the faulting instruction so the exception can
+        // report it. If it's in a delay slot, the branch is still pending.
+        if self.exception.is_some() {
+            self.next_branching = false;
+            self.next_forbidden = false;
+            return;
+        }
+        self.advance();
+    }
+
+    // Moves on to the next instruction once the current one is done.
+    fn advance(&mut self) {
+        if self.branching {
+            self.branching = false;
+            self.rf.pc = self.switch_isa_mode(self.branch_target);
+        } else {
+            self.rf.pc = self.next_pc();
+        }
+
+        if self.next_branching {
+            self.next_branching = false;
+            self.branching = true;
+        }
+        self.forbidden = self.next_forbidden;
+        self.next_forbidden = false;
+    }
+
+    // Sends the CPU to `target` once the current instruction is done.
+    fn jump(&mut self, target: u64) {
+        // The PC moves on past every instruction.
+        self.rf.pc = target.wrapping_sub(self.size);
+    }
+
+    // Where the next instruction is, which compact branches count their
+    // offsets from.
+    fn next_pc(&self) -> u64 {
+        self.rf.pc.wrapping_add(self.size)
+    }
+
+    // What PC-relative instructions count from. MIPS16e's round their own
+    // address down to a word, or the jump's if they're in its delay slot.
+    fn pc_relative_base(&self) -> u64 {
+        if self.isa_mode != IsaMode::Mips16 {
+            self.rf.pc
+        } else if self.branching {
+            self.branch_pc & !3
+        } else {
+            self.rf.pc & !3
+        }
+    }
+
+    // Bit 0 of the addresses that link or return to the current ISA mode.
+    fn mode_bit(&self) -> u64 {
+        (self.isa_mode != IsaMode::Standard) as u64
+    }
+
+    // What a compact branch or jump leaves in the link register: the next
+    // instruction, with the ISA mode in bit 0 so a register jump comes back
+    // to the same mode.
+    fn link_address(&self) -> u64 {
+        self.next_pc() | self.mode_bit()
+    }
+
+    // The same for a jump with a delay slot, which is returned past too.
+    // MIPS16e's delay slots are a halfword.
+    fn delayed_link_address(&self) -> u64 {
+        let slot = if self.isa_mode == IsaMode::Mips16 { 2 } else { 4 };
+        self.next_pc().wrapping_add(slot) | self.mode_bit()
+    }
+
+    // A jump to an address with bit 0 set goes to the compressed encoding,
+    // microMIPS on Release 6 and MIPS16e before it, and one with it clear
+    // to standard code. Returns the address to go to.
+    fn switch_isa_mode(&mut self, target: u64) -> u64 {
+        self.isa_mode = if target & 1 == 0 {
+            IsaMode::Standard
+        } else if self.revision == IsaRevision::Release6 {
+            IsaMode::MicroMips
+        } else {
+            IsaMode::Mips16
+        };
+        target & !1
+    }
+
+    // Sends the CPU to `target` once the instruction in the delay slot has
+    // run. Bit 0 of the target picks the ISA mode to carry on in.
+    fn delayed_jump(&mut self, target: u64) {
+        self.next_branching = true;
+        self.branch_target = target;
+        self.branch_pc = self.rf.pc;
+    }
+
+    // A branch-likely that isn't taken skips its delay slot instead. Any
+    // other branch still runs it, and it still can't hold a jump.
+    fn delayed_branch(&mut self, taken: bool, likely: bool, offset: i64) {
+        let pc = self.rf.pc;
+        if taken {
+            self.delayed_jump(pc.wrapping_add(4).wrapping_add(offset as u64));
+        } else if likely {
+            self.jump(pc.wrapping_add(8));
+        } else {
+            self.next_forbidden = true;
+        }
+    }
+
+    // DSP ASE instructions work on the low words of registers and leave
+    // sign-extended words behind.
+    fn word(&self, index: usize) -> u32 {
+        self.rf.registers[index] as u32
+    }
+
+    fn set_word(&mut self, index: usize, value: u32) {
+        self.set_register(index, value as i32 as i64 as u64);
+    }
+
+    fn vector(&self, index: usize) -> u128 {
+        self.cp1.read_vector(index)
+    }
+
+    fn condition(&self, condition: Condition, rs: usize, rt: usize) -> bool {
+        compare(condition, self.rf.registers[rs], self.rf.registers[rt])
+    }
+
+    fn execute(&mut self, instruction: Instruction, memory: &mut Memory) {
+        let pc = self.rf.pc;
+        match instruction {
+            Instruction::Memory { op, rt, base, offset } => {
+                let address =
+                    (self.rf.registers[base] as i64).wrapping_add(offset) as u64;
+                let value = self.rf.registers[rt];
+                let loaded = match op {
+                    MemoryOp::Lb => self.load(memory, address, 1)
+                        .map(|value| value as i8 as i64 as u64),
+                    MemoryOp::Lbu => self.load(memory, address, 1),
+                    MemoryOp::Ld => self.load(memory, address, 8),
+                    MemoryOp::Lh => self.load(memory, address, 2)
+                        .map(|value| value as i16 as i64 as u64),
+                    MemoryOp::Lhu => self.load(memory, address, 2),
+                    MemoryOp::Lw => self.load(memory, address, 4)
+                        .map(|value| value as i32 as i64 as u64),
+                    MemoryOp::Lwu => self.load(memory, address, 4),
+                    MemoryOp::Sb => {
+                        self.store(memory, address, value, 1);
+                        None
+                    },
+                    MemoryOp::Sd => {
+                        self.store(memory, address, value, 8);
+                        None
+                    },
+                    MemoryOp::Sh => {
+                        self.store(memory, address, value, 2);
+                        None
+                    },
+                    MemoryOp::Sw => {
+                        self.store(memory, address, value, 4);
+                        None
+                    },
+                    MemoryOp::Ldl =>
+                        self.load_partial(memory, address, 8, true, value),
+                    MemoryOp::Ldr =>
+                        self.load_partial(memory, address, 8, false, value),
+                    MemoryOp::Lwl =>
+                        self.load_partial(memory, address, 4, true, value)
+                            .map(|value| value as i32 as i64 as u64),
+                    MemoryOp::Lwr =>
+                        self.load_partial(memory, address, 4, false, value)
+                            .map(|value| value as i32 as i64 as u64),
+                    MemoryOp::Sdl => {
+                        self.store_partial(memory, address, 8, true, value);
+                        None
+                    },
+                    MemoryOp::Sdr => {
+                        self.store_partial(memory, address, 8, false, value);
+                        None
+                    },
+                    MemoryOp::Swl => {
+                        self.store_partial(memory, address, 4, true, value);
+                        None
+                    },
+                    MemoryOp::Swr => {
+                        self.store_partial(memory, address, 4, false, value);
+                        None
+                    },
+                };
+                if let Some(loaded) = loaded {
+                    self.set_register(rt, loaded);
+                }
+            },
+            Instruction::Linked { op, rt, base, offset } => {
+                let address =
+                    (self.rf.registers[base] as i64).wrapping_add(offset) as u64;
+                let value = self.rf.registers[rt];
+                let result = match op {
+                    LinkedOp::Ll => self.load_linked(memory, address, 4)
+                        .map(|value| value as i32 as i64 as u64),
+                    LinkedOp::Lld => self.load_linked(memory, address, 8),
+                    LinkedOp::Sc => self.store_conditional(memory, address,
+                                                           value, 4)
+                        .map(|stored| stored as u64),
+                    LinkedOp::Scd => self.store_conditional(memory, address,
+                                                            value, 8)
+                        .map(|stored| stored as u64),
+                };
+                if let Some(result) = result {
+                    self.set_register(rt, result);
+                }
+            },
+            Instruction::Llwp { rt, rd, base } => {
+                let address = self.rf.registers[base];
+                if let Some(pair) = self.load_linked(memory, address, 8) {
+                    self.set_register(rt, (pair >> 32) as i32 as i64 as u64);
+                    self.set_register(rd, pair as i32 as i64 as u64);
                 }
             },
-            LB | LBU | LD | LH | LHU | LW | LWU | SB | SD | SH | SW => {
-                match memory.translate_address(self.id,
-                        (self.rf.registers[rs] as i64 + imm16) as u64) {
-                    None => {
-                        self.exception = true;
+            Instruction::Scwp { rt, rd, base } => {
+                let address = self.rf.registers[base];
+                let pair = (self.rf.registers[rt] << 32) |
+                    (self.rf.registers[rd] & 0xffffffff);
+                if let Some(stored) =
+                        self.store_conditional(memory, address, pair, 8) {
+                    self.set_register(rt, stored as u64);
+                }
+            },
+            // Every load and store already reaches the shared memory in
+            // program order (see Computer::step), so there's nothing for
+            // SYNC to wait for.
+            Instruction::Sync { .. } => {},
+            // Nothing is cached, so there's nothing to fetch early and no
+            // stale instructions to throw away.
+            Instruction::Pref { .. } | Instruction::Synci { .. } => {},
+            Instruction::PcRelative { op, rs, offset } => {
+                let address = self.pc_relative_base()
+                    .wrapping_add(offset as u64);
+                let result = match op {
+                    PcRelativeOp::Addiupc | PcRelativeOp::Auipc =>
+                        Some(address),
+                    PcRelativeOp::Aluipc => Some(address & !0xffff),
+                    PcRelativeOp::Lwpc => self.load(memory, address, 4)
+                        .map(|value| value as i32 as i64 as u64),
+                    PcRelativeOp::Lwupc => self.load(memory, address, 4),
+                    PcRelativeOp::Ldpc => self.load(
+                        memory, (pc & !7).wrapping_add(offset as u64), 8),
+                };
+                if let Some(result) = result {
+                    self.set_register(rs, result);
+                }
+            },
+            Instruction::Immediate { op, rt, rs, immediate } => {
+                let source = self.rf.registers[rs];
+                let signed = immediate as i16 as i64;
+                let result = match op {
+                    ImmediateOp::Addi => (source as i32)
+                        .checked_add(signed as i32)
+                        .map(|sum| sum as i64 as u64),
+                    ImmediateOp::Addiu => Some((source as i32)
+                        .wrapping_add(signed as i32) as i64 as u64),
+                    ImmediateOp::Andi => Some(source & immediate as u64),
+                    ImmediateOp::Daddi => (source as i64).checked_add(signed)
+                        .map(|sum| sum as u64),
+                    ImmediateOp::Daddiu =>
+                        Some((source as i64).wrapping_add(signed) as u64),
+                    ImmediateOp::Lui => Some((signed << 16) as u64),
+                    ImmediateOp::Aui => Some((source as i32)
+                        .wrapping_add((signed << 16) as i32) as i64 as u64),
+                    ImmediateOp::Daui =>
+                        Some(source.wrapping_add((signed << 16) as u64)),
+                    ImmediateOp::Dahi =>
+                        Some(source.wrapping_add((signed << 32) as u64)),
+                    ImmediateOp::Dati =>
+                        Some(source.wrapping_add((signed << 48) as u64)),
+                    ImmediateOp::Ori => Some(source | immediate as u64),
+                    ImmediateOp::Slti =>
+                        Some(((source as i64) < signed) as u64),
+                    ImmediateOp::Sltiu => Some((source < signed as u64) as u64),
+                    ImmediateOp::Xori => Some(source ^ immediate as u64),
+                };
+                match result {
+                    None => self.raise(Exception::IntegerOverflow { pc }),
+                    Some(result) => self.set_register(rt, result),
+                }
+            },
+            Instruction::Register { op, rd, rs, rt } => {
+                let a = self.rf.registers[rs];
+                let b = self.rf.registers[rt];
+                let result = match op {
+                    RegisterOp::Add => (a as i32).checked_add(b as i32)
+                        .map(|sum| sum as i64 as u64),
+                    RegisterOp::Addu =>
+                        Some((a as i32).wrapping_add(b as i32) as i64 as u64),
+                    RegisterOp::And => Some(a & b),
+                    RegisterOp::Dadd => (a as i64).checked_add(b as i64)
+                        .map(|sum| sum as u64),
+                    RegisterOp::Daddu => Some(a.wrapping_add(b)),
+                    RegisterOp::Dsub => (a as i64).checked_sub(b as i64)
+                        .map(|difference| difference as u64),
+                    RegisterOp::Dsubu => Some(a.wrapping_sub(b)),
+                    RegisterOp::Nor => Some(!(a | b)),
+                    RegisterOp::Or => Some(a | b),
+                    RegisterOp::Slt => Some(((a as i64) < b as i64) as u64),
+                    RegisterOp::Sltu => Some((a < b) as u64),
+                    RegisterOp::Sub => (a as i32).checked_sub(b as i32)
+                        .map(|difference| difference as i64 as u64),
+                    RegisterOp::Subu =>
+                        Some((a as i32).wrapping_sub(b as i32) as i64 as u64),
+                    RegisterOp::Xor => Some(a ^ b),
+                    RegisterOp::Mul =>
+                        Some((a as i32).wrapping_mul(b as i32) as i64 as u64),
+                    RegisterOp::Muh => Some(((a as i32 as i64 * b as i32 as i64)
+                        >> 32) as u64),
+                    RegisterOp::Mulu =>
+                        Some((a as u32).wrapping_mul(b as u32) as i32 as i64
+                             as u64),
+                    RegisterOp::Muhu => Some(((a as u32 as u64 * b as u32 as u64)
+                        >> 32) as i32 as i64 as u64),
+                    RegisterOp::Dmul => Some(a.wrapping_mul(b)),
+                    RegisterOp::Dmuh => Some(((a as i64 as i128 * b as i64 as i128)
+                        >> 64) as u64),
+                    RegisterOp::Dmulu => Some(a.wrapping_mul(b)),
+                    RegisterOp::Dmuhu => Some(((a as u128 * b as u128) >> 64)
+                        as u64),
+                    // Dividing by zero gives an unpredictable result on real
+                    // hardware; here it's zero.
+                    RegisterOp::Div => Some((a as i32).checked_div(b as i32)
+                        .unwrap_or(0) as i64 as u64),
+                    RegisterOp::Mod => Some((a as i32).checked_rem(b as i32)
+                        .unwrap_or(0) as i64 as u64),
+                    RegisterOp::Divu => Some((a as u32).checked_div(b as u32)
+                        .unwrap_or(0) as i32 as i64 as u64),
+                    RegisterOp::Modu => Some((a as u32).checked_rem(b as u32)
+                        .unwrap_or(0) as i32 as i64 as u64),
+                    RegisterOp::Ddiv => Some((a as i64).checked_div(b as i64)
+                        .unwrap_or(0) as u64),
+                    RegisterOp::Dmod => Some((a as i64).checked_rem(b as i64)
+                        .unwrap_or(0) as u64),
+                    RegisterOp::Ddivu => Some(a.checked_div(b).unwrap_or(0)),
+                    RegisterOp::Dmodu => Some(a.checked_rem(b).unwrap_or(0)),
+                    RegisterOp::Seleqz => Some(if b == 0 { a } else { 0 }),
+                    RegisterOp::Selnez => Some(if b != 0 { a } else { 0 }),
+                };
+                match result {
+                    // Only the trapping adds and subtracts come back empty.
+                    None => self.raise(Exception::IntegerOverflow { pc }),
+                    Some(result) => self.set_register(rd, result),
+                }
+            },
+            Instruction::HiLo { op, ac, rs, rt } => {
+                let a = self.rf.registers[rs];
+                let b = self.rf.registers[rt];
+                // The word operations leave a sign-extended word in each of
+                // HI and LO.
+                let words = |value: u64| {
+                    ((value >> 32) as i32 as i64 as u64,
+                     value as i32 as i64 as u64)
+                };
+                let accumulator = self.accumulator(ac);
+                let signed =
+                    (a as i32 as i64).wrapping_mul(b as i32 as i64) as u64;
+                let unsigned = a as u32 as u64 * b as u32 as u64;
+                let (hi, lo) = match op {
+                    HiLoOp::Mult => words(signed),
+                    HiLoOp::Multu => words(unsigned),
+                    // As with DIV in Release 6, dividing by zero gives zero.
+                    HiLoOp::Div => (
+                        (a as i32).checked_rem(b as i32).unwrap_or(0) as i64
+                            as u64,
+                        (a as i32).checked_div(b as i32).unwrap_or(0) as i64
+                            as u64),
+                    HiLoOp::Divu => (
+                        (a as u32).checked_rem(b as u32).unwrap_or(0) as i32
+                            as i64 as u64,
+                        (a as u32).checked_div(b as u32).unwrap_or(0) as i32
+                            as i64 as u64),
+                    HiLoOp::Dmult => {
+                        let product = a as i64 as i128 * b as i64 as i128;
+                        ((product >> 64) as u64, product as u64)
                     },
-                    Some(address) => {
-                        match opcode {
-                            LB => {
-                                match memory.read_byte(address) {
-                                    None => {
-                                        self.exception = true;
-                                    },
-                                    Some(value) => {
-                                        self.rf.registers[rt] =
-                                            value as i8 as i64 as u64;
-                                    }
-                                }
-                            },
-                            LBU => {
-                                match memory.read_byte(address) {
-                                    None => {
-                                        self.exception = true;
-                                    },
-                                    Some(value) => {
-                                        self.rf.registers[rt] = value as u64;
-                                    }
-                                }
-                            },
-                            LD => {
-                                match memory.read_dword(address) {
-                                    None => {
-                                        self.exception = true;
-                                    },
-                                    Some(value) => {
-                                        self.rf.registers[rt] =
-                                            value;
-                                    }
-                                }
-                            },
-                            LH => {
-                                match memory.read_halfword(address) {
-                                    None => {
-                                        self.exception = true;
-                                    },
-                                    Some(value) => {
-                                        self.rf.registers[rt] =
-                                            value as i16 as i64 as u64;
-                                    }
-                                }
-                            },
-                            LHU => {
-                                match memory.read_halfword(address) {
-                                    None => {
-                                        self.exception = true;
-                                    },
-                                    Some(value) => {
-                                        self.rf.registers[rt] = value as u64;
-                                    }
-                                }
-                            },
-                            LW => {
-                                match memory.read_word(address) {
-                                    None => {
-                                        self.exception = true;
-                                    },
-                                    Some(value) => {
-                                        self.rf.registers[rt] =
-                                            value as i32 as i64 as u64;
-                                    }
-                                }
-                            },
-                            LWU => {
-                                match memory.read_word(address) {
-                                    None => {
-                                        self.exception = true;
-                                    },
-                                    Some(value) => {
-                                        self.rf.registers[rt] = value as u64;
-                                    }
-                                }
-                            },
-                            SB => {
-                                if !memory.write_byte(address,
-                                    self.rf.registers[rt] as u8) {
-                                    self.exception = true;
-                                }
-                            },
-                            SD => {
-                                if !memory.write_dword(address,
-                                    self.rf.registers[rt]) {
-                                    self.exception = true;
-                                }
-                            },
-                            SH => {
-                                if !memory.write_halfword(address,
-                                    self.rf.registers[rt] as u16) {
-                                    self.exception = true;
-                                }
-                            },
-                            SW => {
-                                if !memory.write_word(address,
-                                    self.rf.registers[rt] as u32) {
-                                    self.exception = true;
-                                }
-                            },
-                            _ => {
-                                panic!("This is impossible!");
-                            }
-                        }
+                    HiLoOp::Dmultu => {
+                        let product = a as u128 * b as u128;
+                        ((product >> 64) as u64, product as u64)
+                    },
+                    HiLoOp::Ddiv => (
+                        (a as i64).checked_rem(b as i64).unwrap_or(0) as u64,
+                        (a as i64).checked_div(b as i64).unwrap_or(0) as u64),
+                    HiLoOp::Ddivu => (a.checked_rem(b).unwrap_or(0),
+                                      a.checked_div(b).unwrap_or(0)),
+                    HiLoOp::Madd => words(accumulator.wrapping_add(signed)),
+                    HiLoOp::Maddu => words(accumulator.wrapping_add(unsigned)),
+                    HiLoOp::Msub => words(accumulator.wrapping_sub(signed)),
+                    HiLoOp::Msubu => words(accumulator.wrapping_sub(unsigned)),
+                };
+                self.rf.hi[ac] = hi;
+                self.rf.lo[ac] = lo;
+            },
+            Instruction::Mfhi { rd, ac } =>
+                self.set_register(rd, self.rf.hi[ac]),
+            Instruction::Mflo { rd, ac } =>
+                self.set_register(rd, self.rf.lo[ac]),
+            Instruction::Mthi { rs, ac } =>
+                self.rf.hi[ac] = self.rf.registers[rs],
+            Instruction::Mtlo { rs, ac } =>
+                self.rf.lo[ac] = self.rf.registers[rs],
+            Instruction::Movz { rd, rs, rt } => {
+                if self.rf.registers[rt] == 0 {
+                    self.set_register(rd, self.rf.registers[rs]);
+                }
+            },
+            Instruction::Movn { rd, rs, rt } => {
+                if self.rf.registers[rt] != 0 {
+                    self.set_register(rd, self.rf.registers[rs]);
+                }
+            },
+            Instruction::Movep { rd, re, rs, rt } => {
+                let (first, second) =
+                    (self.rf.registers[rs], self.rf.registers[rt]);
+                self.set_register(rd, first);
+                self.set_register(re, second);
+            },
+            // The registers go to consecutive words in ascending order.
+            Instruction::LoadMultiple { registers, base, offset } => {
+                let mut address =
+                    self.rf.registers[base].wrapping_add(offset as u64);
+                for register in (0..32).filter(|r| registers >> r & 1 != 0) {
+                    match self.load(memory, address, 4) {
+                        Some(value) => self.set_register(
+                            register, value as i32 as i64 as u64),
+                        None => break,
                     }
+                    address = address.wrapping_add(4);
                 }
             },
-            PCREL => {
-                if ((instruction >> 19) & 0x3) as i32 == LWPC {
-                    let address = self.rf.pc +
-                        (((instruction as i64 & LOW19 as i64)
-                                << 45) >> 43) as u64;
-                    match memory.translate_address(self.id, address) {
-                        None => {
-                            self.exception = true;
-                        }
-                        Some(address) => {
-                            match memory.read_word(address) {
-                                None => {
-                                    self.exception = true;
-                                },
-                                Some(value) => {
-                                    self.rf.registers[rs] =
-                                        value as i32 as i64 as u64;
-                                }
-                            }
-                        }
+            Instruction::StoreMultiple { registers, base, offset } => {
+                let mut address =
+                    self.rf.registers[base].wrapping_add(offset as u64);
+                for register in (0..32).filter(|r| registers >> r & 1 != 0) {
+                    self.store(memory, address, self.rf.registers[register], 4);
+                    if self.exception.is_some() {
+                        break;
                     }
-                } else if ((instruction >> 19) & 0x3) as i32 == LWUPC {
-                    let address = self.rf.pc +
-                        (((instruction as i64 & LOW19 as i64)
-                                << 45) >> 43) as u64;
-                    match memory.translate_address(self.id, address) {
-                        None => {
-                            self.exception = true;
-                        }
-                        Some(address) => {
-                            match memory.read_word(address) {
-                                None => {
-                                    self.exception = true;
-                                },
-                                Some(value) => {
-                                    self.rf.registers[rs] = value as u64;
-                                }
-                            }
-                        }
+                    address = address.wrapping_add(4);
+                }
+            },
+            Instruction::Save { arguments, registers, frame } => {
+                let sp = self.rf.registers[29];
+                for k in 0..arguments as usize {
+                    let address = sp.wrapping_add(4 * k as u64);
+                    self.store(memory, address, self.rf.registers[4 + k], 4);
+                    if self.exception.is_some() {
+                        return;
                     }
-                } else if ((instruction >> 18) & 0x7) as i32 == LDPC {
-                    let address = self.rf.pc +
-                        (((instruction as i64 & LOW18 as i64)
-                                << 46) >> 43) as u64;
-                    match memory.translate_address(self.id, address) {
-                        None => {
-                            self.exception = true;
-                        }
-                        Some(address) => {
-                            match memory.read_word(address) {
-                                None => {
-                                    self.exception = true;
-                                },
-                                Some(value) => {
-                                    self.rf.registers[rs] = value as u64;
-                                }
-                            }
-                        }
+                }
+                let mut address = sp;
+                let saved = (0..32).rev().filter(|r| registers >> r & 1 != 0);
+                for register in saved {
+                    address = address.wrapping_sub(4);
+                    self.store(memory, address, self.rf.registers[register], 4);
+                    if self.exception.is_some() {
+                        return;
                     }
-                } else {
-                    panic!("Unknown instruction.");
                 }
+                self.set_register(29, sp.wrapping_sub(frame));
             },
-            ADDIU => {
-                self.rf.registers[rt] = ((self.rf.registers[rs] as i32) +
-                                        (imm16 as i32)) as i64 as u64;
-            },
-            ANDI => {
-                self.rf.registers[rt] = self.rf.registers[rs] & 
-                                        (imm16 as u16 as u64);
-            },
-            DADDIU => {
-                self.rf.registers[rt] = ((self.rf.registers[rs] as i64) +
-                                        (imm16 as i64)) as u64;
-            },
-            LUI => {
-                self.rf.registers[rt] = (imm16 << 16) as u64;
-            },
-            ORI => {
-                self.rf.registers[rt] = (imm16 as u16 as u64) |
-                                        self.rf.registers[rs];
-            },
-            SLTI => {
-                self.rf.registers[rt] = if imm16 < (self.rf.registers[rs] as i64)
-                                        { 1 } else { 0 };
-            },
-            SLTIU => {
-                self.rf.registers[rt] = if (imm16 as u64) < self.rf.registers[rs]
-                                        { 1 } else { 0 };
-            },
-            XORI => {
-                self.rf.registers[rt] = (imm16 as u16 as u64) ^
-                                        self.rf.registers[rs];
-            },
-            SPECIAL3 => {
-                if rs != 0 {
-                    match instruction as i32 & LOW6 {
-                        BSHFL => {
-                            match ((instruction >> 8) & 0x7) as i32 {
-                                ALIGN => {
-                                    let bp = ((instruction >> 6) & 0x3) as u8;
-                                    self.rf.registers[rd] =
-                                        (self.rf.registers[rt] << (8 * bp)) |
-                                        (self.rf.registers[rs] >> (64 - 8 * bp));
-                                    if self.rf.registers[rd] & 0x80000000 != 0 {
-                                        self.rf.registers[rd] |= 0x1111111100000000;
-                                    } else {
-                                        self.rf.registers[rd] &= 0x0000000011111111;
-                                    }
-                                },
-                                _ => {
-                                    panic!("Unknown instruction!");
-                                }
-                            }
-                        },
-                        DBSHFL => {
-                            match ((instruction >> 9) & 0x3) as i32 {
-                                DALIGN => {
-                                    let bp = ((instruction >> 6) & 0x7) as u8;
-                                    self.rf.registers[rd] =
-                                        (self.rf.registers[rt] << (8 * bp)) |
-                                        (self.rf.registers[rs] >> (64 - 8 * bp));
-                                },
-                                _ => {
-                                    panic!("Unknown instruction!");
-                                }
-                            }
-                        },
-                        _ => {
-                            panic!("Unknown instruction!");
-                        }
-                    }
-                } else {
-                    match instruction as i32 & LOW6 {
-                        BSHFL => {
-                            if instruction as i32 & LOW5 == BITSWAP {
-                                let mut result: u64 = 0;
-                                for i in 0..4 {
-                                    let byte: u8 =
-                                        (self.rf.registers[rt] >> (8 * i))
-                                        as u8;
-                                    let mut obyte: u8 = 0;
-                                    for j in 0..8 {
-                                        if byte & (0x1 << j) != 0 {
-                                            obyte |= 0x80 >> j;
-                                        }
-                                    }
-                                    result |= (obyte as u64) << (i * 8);
-                                }
-                                if result & 0x80000000 != 0 {
-                                    result |= 0xffffffff00000000;
-                                } else {
-                                    result &= 0x00000000ffffffff;
-                                }
-                                self.rf.registers[rd] = result;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        DBSHFL => {
-                            if instruction as i32 & LOW5 == DBITSWAP {
-                                let mut result: u64 = 0;
-                                for i in 0..8 {
-                                    let byte: u8 =
-                                        (self.rf.registers[rt] >> (8 * i))
-                                        as u8;
-                                    let mut obyte: u8 = 0;
-                                    for j in 0..8 {
-                                        if byte & (0x1 << j) != 0 {
-                                            obyte |= 0x80 >> j;
-                                        }
-                                    }
-                                    result |= (obyte as u64) << (i * 8);
-                                }
-                                self.rf.registers[rd] = result;
-                            } else {
-                                panic!("Unknown instruction!");
-                            }
-                        },
-                        _ => panic!("Unknown instruction!"),
+            Instruction::Restore { registers, frame } => {
+                let top = self.rf.registers[29].wrapping_add(frame);
+                let mut address = top;
+                let saved = (0..32).rev().filter(|r| registers >> r & 1 != 0);
+                for register in saved {
+                    address = address.wrapping_sub(4);
+                    match self.load(memory, address, 4) {
+                        Some(value) => self.set_register(
+                            register, value as i32 as i64 as u64),
+                        None => return,
                     }
                 }
+                self.set_register(29, top);
             },
-            BC => {
-                self.rf.pc = ((((instruction as i32) << 6) >> 4) as i64 +
-                    (self.rf.pc as i64)) as u64;
+            Instruction::Count { op, rd, rs } => {
+                let value = self.rf.registers[rs];
+                let count = match op {
+                    CountOp::Clo => (value as u32).leading_ones(),
+                    CountOp::Clz => (value as u32).leading_zeros(),
+                    CountOp::Dclo => value.leading_ones(),
+                    CountOp::Dclz => value.leading_zeros(),
+                };
+                self.set_register(rd, count as u64);
             },
-            BALC => {
-                self.rf.registers[31] = self.rf.pc + 4;
-                self.rf.pc = ((((instruction as i32) << 6) >> 4) as i64 +
-                    (self.rf.pc as i64)) as u64;
+            Instruction::Shift { op, rd, rt, sa } => {
+                let value = self.rf.registers[rt];
+                let word = value as u32;
+                let result = match op {
+                    ShiftOp::Sll => (word << sa) as i32 as i64 as u64,
+                    ShiftOp::Srl => (word >> sa) as i32 as i64 as u64,
+                    ShiftOp::Sra => (word as i32 >> sa) as i64 as u64,
+                    ShiftOp::Rotr => word.rotate_right(sa) as i32 as i64 as u64,
+                    ShiftOp::Dsll => value << sa,
+                    ShiftOp::Dsrl => value >> sa,
+                    ShiftOp::Dsra => (value as i64 >> sa) as u64,
+                    ShiftOp::Drotr => value.rotate_right(sa),
+                    ShiftOp::Dsll32 => value << (sa + 32),
+                    ShiftOp::Dsrl32 => value >> (sa + 32),
+                    ShiftOp::Dsra32 => (value as i64 >> (sa + 32)) as u64,
+                    ShiftOp::Drotr32 => value.rotate_right(sa + 32),
+                };
+                self.set_register(rd, result);
             },
-            POP06 => {
-                if rs != 0 && rt != 0 && rs != rt { // BGEUC
-                    if self.rf.registers[rt] >= self.rf.registers[rs] {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs != 0 && rs == rt { // BGEZALC
-                    if self.rf.registers[rt] as i64 >= 0 {
-                        self.rf.registers[31] = self.rf.pc + 4;
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs == 0 && rt != 0 { // BLEZALC
-                    if self.rf.registers[rt] as i64 <= 0 {
-                        self.rf.registers[31] = self.rf.pc + 4;
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
+            Instruction::ShiftVariable { op, rd, rt, rs } => {
+                let value = self.rf.registers[rt];
+                let word = value as u32;
+                // Word shifts use the low 5 bits of rs, doubleword shifts the
+                // low 6.
+                let sa = self.rf.registers[rs] as u32;
+                let result = match op {
+                    ShiftVariableOp::Sllv =>
+                        (word << (sa & 0x1f)) as i32 as i64 as u64,
+                    ShiftVariableOp::Srlv =>
+                        (word >> (sa & 0x1f)) as i32 as i64 as u64,
+                    ShiftVariableOp::Srav =>
+                        (word as i32 >> (sa & 0x1f)) as i64 as u64,
+                    ShiftVariableOp::Rotrv =>
+                        word.rotate_right(sa & 0x1f) as i32 as i64 as u64,
+                    ShiftVariableOp::Dsllv => value << (sa & 0x3f),
+                    ShiftVariableOp::Dsrlv => value >> (sa & 0x3f),
+                    ShiftVariableOp::Dsrav => (value as i64 >> (sa & 0x3f)) as u64,
+                    ShiftVariableOp::Drotrv => value.rotate_right(sa & 0x3f),
+                };
+                self.set_register(rd, result);
+            },
+            Instruction::Align { rd, rs, rt, bp } => {
+                let rt_word = self.rf.registers[rt] as u32;
+                let rs_word = self.rf.registers[rs] as u32;
+                let result = if bp == 0 {
+                    rt_word
                 } else {
-                    panic!("Unknown instruction!");
-                }
+                    (rt_word << (8 * bp)) | (rs_word >> (32 - 8 * bp))
+                };
+                self.set_register(rd, result as i32 as i64 as u64);
             },
-            POP07 => {
-                if rs != 0 && rt != 0 && rs != rt { // BLTUC
-                    if self.rf.registers[rt] < self.rf.registers[rs] {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs != 0 && rs == rt { // BLTZALC
-                    if (self.rf.registers[rt] as i64) < 0 {
-                        self.rf.registers[31] = self.rf.pc + 4;
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs == 0 && rt != 0 { // BGTZALC
-                    if self.rf.registers[rt] as i64 > 0 {
-                        self.rf.registers[31] = self.rf.pc + 4;
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
+            Instruction::Dalign { rd, rs, rt, bp } => {
+                let result = if bp == 0 {
+                    self.rf.registers[rt]
                 } else {
-                    panic!("Unknown instruction!");
+                    (self.rf.registers[rt] << (8 * bp)) |
+                        (self.rf.registers[rs] >> (64 - 8 * bp))
+                };
+                self.set_register(rd, result);
+            },
+            Instruction::Bitswap { rd, rt } => {
+                // Reverses the bits in each byte.
+                let word = (self.rf.registers[rt] as u32).reverse_bits()
+                    .swap_bytes();
+                self.set_register(rd, word as i32 as i64 as u64);
+            },
+            Instruction::Dbitswap { rd, rt } => {
+                let value = self.rf.registers[rt].reverse_bits().swap_bytes();
+                self.set_register(rd, value);
+            },
+            Instruction::BitField { op, rt, rs, pos, size } => {
+                let source = self.rf.registers[rs];
+                let target = self.rf.registers[rt];
+                let mask = u64::MAX >> (64 - size);
+                let inserted = (target & !(mask << pos)) |
+                    ((source & mask) << pos);
+                let result = match op {
+                    BitFieldOp::Ext =>
+                        ((source >> pos) & mask) as i32 as i64 as u64,
+                    BitFieldOp::Ins => inserted as i32 as i64 as u64,
+                    BitFieldOp::Dext => (source >> pos) & mask,
+                    BitFieldOp::Dins => inserted,
+                };
+                self.set_register(rt, result);
+            },
+            Instruction::Shuffle { op, rd, rt } => {
+                let value = self.rf.registers[rt];
+                let swap_halfwords = |value: u64| {
+                    ((value & 0x00ff00ff00ff00ff) << 8) |
+                        ((value >> 8) & 0x00ff00ff00ff00ff)
+                };
+                let result = match op {
+                    ShuffleOp::Wsbh =>
+                        swap_halfwords(value) as i32 as i64 as u64,
+                    ShuffleOp::Seb => value as i8 as i64 as u64,
+                    ShuffleOp::Seh => value as i16 as i64 as u64,
+                    ShuffleOp::Dsbh => swap_halfwords(value),
+                    ShuffleOp::Dshd => swap_halfwords(value.swap_bytes()),
+                };
+                self.set_register(rd, result);
+            },
+            Instruction::Lsa { rd, rs, rt, sa } => {
+                let result = ((self.rf.registers[rs] as u32) << sa)
+                    .wrapping_add(self.rf.registers[rt] as u32);
+                self.set_register(rd, result as i32 as i64 as u64);
+            },
+            Instruction::Dlsa { rd, rs, rt, sa } => {
+                let result = (self.rf.registers[rs] << sa)
+                    .wrapping_add(self.rf.registers[rt]);
+                self.set_register(rd, result);
+            },
+            Instruction::Rdhwr { rt, rd } => {
+                let value = match rd {
+                    HWR_CPUNUM => self.cp0.cpu_number(),
+                    // There are no caches, so SYNCI is never needed.
+                    HWR_SYNCI_STEP => 0,
+                    HWR_CC => self.cp0.read(cp0::COUNT, 0) as i32 as i64 as u64,
+                    HWR_CCRES => 1,
+                    HWR_ULR => self.cp0.read(cp0::USERLOCAL, cp0::USERLOCAL_SEL),
+                    _ => {
+                        self.raise(Exception::ReservedInstruction { pc });
+                        return;
+                    },
+                };
+                self.set_register(rt, value);
+            },
+            Instruction::Bc { offset } => {
+                self.jump(self.next_pc().wrapping_add(offset as u64));
+            },
+            Instruction::Balc { offset } => {
+                self.set_register(31, self.link_address());
+                self.jump(self.next_pc().wrapping_add(offset as u64));
+            },
+            Instruction::Jic { rt, offset } => {
+                let target = self.rf.registers[rt].wrapping_add(offset as u64);
+                let target = self.switch_isa_mode(target);
+                self.jump(target);
+            },
+            Instruction::Jialc { rt, offset } => {
+                let link = self.link_address();
+                let target = self.rf.registers[rt].wrapping_add(offset as u64);
+                let target = self.switch_isa_mode(target);
+                self.set_register(31, link);
+                self.jump(target);
+            },
+            Instruction::Jalrc { rd, rs } => {
+                let link = self.link_address();
+                let target = self.switch_isa_mode(self.rf.registers[rs]);
+                self.set_register(rd, link);
+                self.jump(target);
+            },
+            Instruction::JrcAddiusp { immediate } => {
+                let target = self.switch_isa_mode(self.rf.registers[31]);
+                let sp = self.rf.registers[29].wrapping_add(immediate as u64);
+                self.set_register(29, sp);
+                self.jump(target);
+            },
+            // The instruction after a conditional compact branch is only
+            // run if the branch isn't taken. It's a forbidden slot: it can't
+            // be another branch or jump. Compressed code has no forbidden
+            // slots.
+            Instruction::CompactBranch { condition, link, rs, rt, offset } => {
+                // The link is written whether or not the branch is taken.
+                let taken = self.condition(condition, rs, rt);
+                if link {
+                    self.set_register(31, self.link_address());
+                }
+                if taken {
+                    self.jump(self.next_pc().wrapping_add(offset as u64));
+                } else if self.isa_mode == IsaMode::Standard {
+                    self.next_forbidden = true;
                 }
             },
-            POP10 => {
-                if rs != 0 && rt != 0 && rs < rt { // BEQC
-                    if self.rf.registers[rt] == self.rf.registers[rs] {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs == 0 && rs < rt { // BEQZALC
-                    if self.rf.registers[rt] == 0 {
-                        self.rf.registers[31] = self.rf.pc + 4;
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs >= rt { // BOVC
-                    if (self.rf.registers[rt] as i32)
-                        .checked_add(self.rf.registers[rs] as i32) == None {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else {
-                    panic!("Unknown instruction!");
+            Instruction::Branch { condition, link, likely, rs, rt, offset } => {
+                let taken = self.condition(condition, rs, rt);
+                self.delayed_branch(taken, likely, offset);
+                if link {
+                    self.set_register(31, pc.wrapping_add(8));
                 }
             },
-            POP30 => {
-                if rs != 0 && rt != 0 && rs < rt { // BNEC
-                    if self.rf.registers[rt] != self.rf.registers[rs] {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs == 0 && rs < rt { // BNEZALC
-                    if self.rf.registers[rt] != 0 {
-                        self.rf.registers[31] = self.rf.pc + 4;
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs >= rt { // BOVC
-                    if (self.rf.registers[rt] as i32)
-                        .checked_add(self.rf.registers[rs] as i32) != None {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
+            Instruction::Bc1 { condition, ft, offset } => {
+                let zero = self.cp1.read(ft) & 1 == 0;
+                self.delayed_branch(zero == (condition == Condition::Eqz),
+                                    false, offset);
+            },
+            Instruction::Bc1c { condition, ft, offset } => {
+                let zero = self.cp1.read(ft) & 1 == 0;
+                if zero == (condition == Condition::Eqz) {
+                    self.jump(self.next_pc().wrapping_add(offset as u64));
+                }
+            },
+            // J and JAL stay in the same ISA mode, and JALX switches.
+            Instruction::J { target } => {
+                let target = decoder::jump_target(pc, target);
+                self.delayed_jump(target | self.mode_bit());
+            },
+            Instruction::Jal { target } => {
+                let target = decoder::jump_target(pc, target);
+                self.set_register(31, self.delayed_link_address());
+                self.delayed_jump(target | self.mode_bit());
+            },
+            Instruction::Jalx { target } => {
+                let target = decoder::jump_target(pc, target);
+                self.set_register(31, self.delayed_link_address());
+                self.delayed_jump(target | (self.mode_bit() ^ 1));
+            },
+            Instruction::Jalr { rd, rs } => {
+                let target = self.rf.registers[rs];
+                self.set_register(rd, self.delayed_link_address());
+                self.delayed_jump(target);
+            },
+            // Outside kernel mode these need Status.CU0.
+            Instruction::Cop0 { .. } | Instruction::Tlb { .. } |
+            Instruction::Di { .. } | Instruction::Ei { .. } |
+            Instruction::Eret | Instruction::Deret | Instruction::Wait
+                if !self.cp0.usable() => {
+                self.raise(Exception::CoprocessorUnusable { pc, unit: 0 });
+            },
+            Instruction::Cop0 { op, rt, rd, sel } => {
+                match op {
+                    Cop0Op::Mfc0 => {
+                        let value = self.cp0.read(rd, sel) as i32 as i64 as u64;
+                        self.set_register(rt, value);
+                    },
+                    Cop0Op::Dmfc0 => {
+                        let value = self.cp0.read(rd, sel);
+                        self.set_register(rt, value);
+                    },
+                    Cop0Op::Mtc0 => {
+                        self.cp0.write(rd, sel,
+                            self.rf.registers[rt] as i32 as i64 as u64);
+                    },
+                    Cop0Op::Dmtc0 => {
+                        self.cp0.write(rd, sel, self.rf.registers[rt]);
+                    },
+                }
+            },
+            // Without a TLB there's nothing for these to work on.
+            Instruction::Tlb { .. } if !self.cp0.tlb_enabled() => {
+                self.raise(Exception::ReservedInstruction { pc });
+            },
+            Instruction::Tlb { op } => match op {
+                TlbOp::Tlbp => self.cp0.tlb_probe(),
+                TlbOp::Tlbr => self.cp0.tlb_read(),
+                TlbOp::Tlbwi => self.cp0.tlb_write(false),
+                TlbOp::Tlbwr => self.cp0.tlb_write(true),
+                TlbOp::Tlbinv => self.cp0.tlb_invalidate(false),
+                TlbOp::Tlbinvf => self.cp0.tlb_invalidate(true),
+            },
+            Instruction::Di { rt } | Instruction::Ei { rt } => {
+                let status = self.cp0.status();
+                self.set_register(rt, status as i32 as i64 as u64);
+                if let Instruction::Ei { .. } = instruction {
+                    self.cp0.set_status(status | cp0::STATUS_IE);
                 } else {
-                    panic!("Unknown instruction!");
+                    self.cp0.set_status(status & !cp0::STATUS_IE);
                 }
             },
-            POP66 => {
-                if (instruction as i32 >> 21) & LOW5 == JIC {
-                    self.rf.pc = (self.rf.registers[rt] as i64 + imm16 - 4)
-                        as u64;
-                } else { // BEQZC
-                    if self.rf.registers[rs] == 0 {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            ((((instruction as i32) << 11) >> 9) as i64 - 4)
-                            ) as u64;
-                    }
+            // Returning from an exception breaks any link, so an SC can't
+            // succeed across a context switch.
+            Instruction::Eret => {
+                memory.clear_link(self.id);
+                let target = self.cp0.exception_return();
+                let target = self.switch_isa_mode(target);
+                self.jump(target);
+            },
+            Instruction::Deret => {
+                let target = self.cp0.debug_return();
+                let target = self.switch_isa_mode(target);
+                self.jump(target);
+            },
+            Instruction::Wait => {},
+            Instruction::Syscall { .. } => {
+                self.raise(Exception::Syscall { pc });
+            },
+            Instruction::Break { code } => {
+                self.raise(Exception::Breakpoint { pc, code });
+            },
+            Instruction::Sdbbp { code } => {
+                self.raise(Exception::DebugBreakpoint { pc, code });
+            },
+            Instruction::Trap { condition, rs, rt, code } => {
+                if self.condition(condition, rs, rt) {
+                    self.raise(Exception::Trap { pc, code });
                 }
             },
-            POP76 => {
-                if (instruction as i32 >> 21) & LOW5 == JIALC {
-                    self.rf.registers[31] = self.rf.pc + 4;
-                    self.rf.pc = (self.rf.registers[rt] as i64 + imm16 - 4)
-                        as u64;
-                } else { // BNEZC
-                    if self.rf.registers[rs] != 0 {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            ((((instruction as i32) << 11) >> 9) as i64 - 4)
-                            ) as u64;
-                    }
+            Instruction::TrapImmediate { condition, rs, immediate } => {
+                if compare(condition, self.rf.registers[rs], immediate as u64) {
+                    self.raise(Exception::Trap { pc, code: 0 });
                 }
             },
-            POP26 => {
-                if rs == 0 && rt != 0 { // BLEZC
-                    if self.rf.registers[rt] as i64 <= 0 {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs != 0 && rt != 0 && rs == rt { // BGEZC
-                    if self.rf.registers[rt] as i64 >= 0 {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs != 0 && rt != 0 && rs != rt { // BGEC
-                    if self.rf.registers[rs] as i64 >=
-                        self.rf.registers[rt] as i64 {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
+            Instruction::Sigrie { .. } => {
+                self.raise(Exception::ReservedInstruction { pc });
+            },
+            Instruction::FloatMemory { op, ft, base, offset } => {
+                let address =
+                    (self.rf.registers[base] as i64).wrapping_add(offset) as u64;
+                let value = self.cp1.read(ft);
+                match op {
+                    // A single leaves the upper half of the register alone.
+                    FloatMemoryOp::Lwc1 => {
+                        if let Some(word) = self.load(memory, address, 4) {
+                            self.cp1.write(ft, (value & !0xffffffff) | word);
+                        }
+                    },
+                    FloatMemoryOp::Ldc1 => {
+                        if let Some(doubleword) = self.load(memory, address, 8) {
+                            self.cp1.write(ft, doubleword);
+                        }
+                    },
+                    FloatMemoryOp::Swc1 =>
+                        self.store(memory, address, value & 0xffffffff, 4),
+                    FloatMemoryOp::Sdc1 => self.store(memory, address, value, 8),
+                }
+            },
+            Instruction::Cop1 { op, rt, fs } => {
+                let value = self.rf.registers[rt];
+                let float = self.cp1.read(fs);
+                match op {
+                    Cop1Op::Mfc1 =>
+                        self.set_register(rt, float as i32 as i64 as u64),
+                    Cop1Op::Dmfc1 => self.set_register(rt, float),
+                    Cop1Op::Mfhc1 => self.set_register(
+                        rt, (float >> 32) as i32 as i64 as u64),
+                    Cop1Op::Mtc1 => self.cp1.write(
+                        fs, (float & !0xffffffff) | (value & 0xffffffff)),
+                    Cop1Op::Dmtc1 => self.cp1.write(fs, value),
+                    Cop1Op::Mthc1 => self.cp1.write(
+                        fs, (float & 0xffffffff) | (value << 32)),
+                    Cop1Op::Cfc1 => match self.cp1.read_control(fs) {
+                        None => self.raise(Exception::ReservedInstruction { pc }),
+                        Some(control) => self.set_register(
+                            rt, control as i32 as i64 as u64),
+                    },
+                    Cop1Op::Ctc1 => {
+                        if self.cp1.read_control(fs).is_none() {
+                            self.raise(Exception::ReservedInstruction { pc });
+                        } else if !self.cp1.write_control(fs, value as u32) {
+                            self.raise(Exception::FloatingPoint { pc });
+                        }
+                    },
+                }
+            },
+            Instruction::Float { op, fmt, fd, fs, ft } => {
+                if !self.cp1.operate(op, fmt, fd, fs, ft) {
+                    self.raise(Exception::FloatingPoint { pc });
+                }
+            },
+            Instruction::FloatUnary { op, fmt, fd, fs } => {
+                if !self.cp1.operate_unary(op, fmt, fd, fs) {
+                    self.raise(Exception::FloatingPoint { pc });
+                }
+            },
+            Instruction::FloatCompare { condition, fmt, fd, fs, ft } => {
+                if !self.cp1.compare(condition, fmt, fd, fs, ft) {
+                    self.raise(Exception::FloatingPoint { pc });
+                }
+            },
+            Instruction::ConvertFixed { to, from, fd, fs } => {
+                if !self.cp1.convert_fixed(to, from, fd, fs) {
+                    self.raise(Exception::FloatingPoint { pc });
+                }
+            },
+            Instruction::Reserved => {
+                self.raise(Exception::ReservedInstruction { pc });
+            },
+            // DSP ASE instructions
+            Instruction::Dsp { op, rd, rs, rt } => {
+                let value =
+                    dsp::operate(op, self.word(rs), self.word(rt),
+                                 &mut self.rf.dsp_control);
+                self.set_word(rd, value);
+            },
+            Instruction::DspUnary { op, rd, rt } => {
+                let value =
+                    dsp::unary(op, self.word(rt), &mut self.rf.dsp_control);
+                self.set_word(rd, value);
+            },
+            Instruction::Repl { op, rd, immediate } => {
+                let value = dsp::unary(op, immediate as u32,
+                                       &mut self.rf.dsp_control);
+                self.set_word(rd, value);
+            },
+            Instruction::DspShift { op, rd, rt, sa } => {
+                let value = dsp::shift(op, self.word(rt), sa,
+                                       &mut self.rf.dsp_control);
+                self.set_word(rd, value);
+            },
+            Instruction::DspShiftVariable { op, rd, rt, rs } => {
+                let value = dsp::shift(op, self.word(rt), self.word(rs),
+                                       &mut self.rf.dsp_control);
+                self.set_word(rd, value);
+            },
+            Instruction::DspCompare { op, condition, rd, rs, rt } => {
+                let value = dsp::compare(op, condition, self.word(rs),
+                                         self.word(rt),
+                                         &mut self.rf.dsp_control);
+                self.set_word(rd, value);
+            },
+            Instruction::PrecrSra { round, rt, rs, sa } => {
+                let value = dsp::precr_sra(round, self.word(rt),
+                                           self.word(rs), sa);
+                self.set_word(rt, value);
+            },
+            Instruction::DspAccumulate { op, ac, rs, rt } => {
+                let value = dsp::accumulate(op, ac, self.accumulator(ac) as i64,
+                                            self.word(rs), self.word(rt),
+                                            &mut self.rf.dsp_control);
+                self.set_accumulator(ac, value as u64);
+            },
+            Instruction::Extract { op, rt, ac, shift } => {
+                let value = dsp::extract(op, self.accumulator(ac) as i64,
+                                         shift, &mut self.rf.dsp_control);
+                if let Some(value) = value {
+                    self.set_register(rt, value);
+                }
+            },
+            Instruction::ExtractVariable { op, rt, ac, rs } => {
+                let value = dsp::extract(op, self.accumulator(ac) as i64,
+                                         self.word(rs),
+                                         &mut self.rf.dsp_control);
+                if let Some(value) = value {
+                    self.set_register(rt, value);
+                }
+            },
+            Instruction::Shilo { ac, shift } => {
+                let value = dsp::shilo(self.accumulator(ac) as i64, shift);
+                self.set_accumulator(ac, value as u64);
+            },
+            // Only the low six bits count, as a signed shift.
+            Instruction::Shilov { ac, rs } => {
+                let shift = (self.word(rs) << 26) as i32 >> 26;
+                let value =
+                    dsp::shilo(self.accumulator(ac) as i64, shift as i64);
+                self.set_accumulator(ac, value as u64);
+            },
+            Instruction::Mthlip { rs, ac } => {
+                let value = dsp::mthlip(self.accumulator(ac) as i64,
+                                        self.word(rs),
+                                        &mut self.rf.dsp_control);
+                self.set_accumulator(ac, value as u64);
+            },
+            Instruction::Wrdsp { rs, mask } => {
+                self.rf.dsp_control = dsp::write_control(
+                    self.rf.dsp_control, self.word(rs), mask);
+            },
+            Instruction::Rddsp { rd, mask } => {
+                self.set_word(rd, dsp::read_control(self.rf.dsp_control, mask));
+            },
+            Instruction::Insv { rt, rs } => {
+                let value = dsp::insv(self.word(rt), self.word(rs),
+                                      self.rf.dsp_control);
+                self.set_word(rt, value);
+            },
+            Instruction::Append { op, rt, rs, sa } => {
+                let value = dsp::append(op, self.word(rt), self.word(rs), sa);
+                self.set_word(rt, value);
+            },
+            Instruction::IndexedLoad { op, rd, base, index } => {
+                let address = self.rf.registers[base]
+                    .wrapping_add(self.rf.registers[index]);
+                let value = match op {
+                    MemoryOp::Lw => self.load(memory, address, 4)
+                        .map(|value| value as i32 as i64 as u64),
+                    MemoryOp::Lh => self.load(memory, address, 2)
+                        .map(|value| value as i16 as i64 as u64),
+                    _ => self.load(memory, address, 1),
+                };
+                if let Some(value) = value {
+                    self.set_register(rd, value);
+                }
+            },
+            Instruction::Bposge32 { offset } => {
+                let taken = dsp::pos(self.rf.dsp_control) >= 32;
+                self.delayed_branch(taken, false, offset);
+            },
+            // MSA instructions
+            Instruction::Vector { op, df, wd, ws, wt } => {
+                let value = msa::operate(op, df, self.vector(wd),
+                                         self.vector(ws), self.vector(wt));
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::VectorImmediate { op, df, wd, ws, immediate } => {
+                let value = msa::operate(op, df, self.vector(wd),
+                                         self.vector(ws),
+                                         msa::splat(df, immediate as u64));
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::VectorLogic { op, wd, ws, wt } => {
+                let value = msa::logic(op, self.vector(wd), self.vector(ws),
+                                       self.vector(wt));
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::VectorLogicImmediate { op, wd, ws, immediate } => {
+                let value = msa::logic(op, self.vector(wd), self.vector(ws),
+                                       msa::splat(VectorFormat::B,
+                                                  immediate as u64));
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::VectorCount { op, df, wd, ws } => {
+                let value = msa::count(op, df, self.vector(ws));
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::Ldi { df, wd, immediate } => {
+                self.cp1.write_vector(wd, msa::splat(df, immediate as u64));
+            },
+            Instruction::Shf { df, wd, ws, immediate } => {
+                let value = msa::shf(df, self.vector(ws), immediate);
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::Sld { df, wd, ws, rt } => {
+                let n = self.rf.registers[rt] as usize;
+                let value = msa::slide(df, self.vector(wd), self.vector(ws),
+                                       n);
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::Sldi { df, wd, ws, n } => {
+                let value = msa::slide(df, self.vector(wd), self.vector(ws),
+                                       n);
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::Splat { df, wd, ws, rt } => {
+                let n = self.rf.registers[rt] as usize % df.elements();
+                let value = msa::element(self.vector(ws), df, n);
+                self.cp1.write_vector(wd, msa::splat(df, value));
+            },
+            Instruction::Splati { df, wd, ws, n } => {
+                let value = msa::element(self.vector(ws), df, n);
+                self.cp1.write_vector(wd, msa::splat(df, value));
+            },
+            Instruction::Copy { signed, df, rd, ws, n } => {
+                let value = if signed {
+                    msa::signed_element(self.vector(ws), df, n) as u64
                 } else {
-                    panic!("Unknown instruction!");
+                    msa::element(self.vector(ws), df, n)
+                };
+                self.set_register(rd, value);
+            },
+            Instruction::Insert { df, wd, rs, n } => {
+                let value = msa::with_element(self.vector(wd), df, n,
+                                              self.rf.registers[rs]);
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::Insve { df, wd, ws, n } => {
+                let element = msa::element(self.vector(ws), df, 0);
+                let value = msa::with_element(self.vector(wd), df, n, element);
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::Fill { df, wd, rs } => {
+                let value = msa::splat(df, self.rf.registers[rs]);
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::MoveV { wd, ws } => {
+                self.cp1.write_vector(wd, self.vector(ws));
+            },
+            Instruction::Cfcmsa { rd, cs } => match self.cp1.read_msa_control(cs) {
+                None => self.raise(Exception::ReservedInstruction { pc }),
+                Some(control) =>
+                    self.set_register(rd, control as i32 as i64 as u64),
+            },
+            Instruction::Ctcmsa { cd, rs } => {
+                let value = self.rf.registers[rs] as u32;
+                if self.cp1.read_msa_control(cd).is_none() {
+                    self.raise(Exception::ReservedInstruction { pc });
+                } else if !self.cp1.write_msa_control(cd, value) {
+                    self.raise(Exception::MsaFloatingPoint { pc });
                 }
             },
-            POP27 => {
-                if rs == 0 && rt != 0 { // BGTZC
-                    if self.rf.registers[rt] as i64 > 0 {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
+            // Element by element, so the unaligned policy applies to each
+            // one, and the register isn't written if any of them faults.
+            Instruction::VectorLoad { df, wd, base, offset } => {
+                let address =
+                    (self.rf.registers[base] as i64).wrapping_add(offset) as u64;
+                let size = df.bits() as u64 / 8;
+                let mut value = 0;
+                for i in 0..df.elements() {
+                    let element = address.wrapping_add(i as u64 * size);
+                    match self.load(memory, element, size) {
+                        None => return,
+                        Some(element) =>
+                            value = msa::with_element(value, df, i, element),
                     }
-                } else if rs != 0 && rt != 0 && rs == rt { // BLTZC
-                    if (self.rf.registers[rt] as i64) < 0 {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
-                    }
-                } else if rs != 0 && rt != 0 && rs != rt { // BLTC
-                    if (self.rf.registers[rs] as i64) <
-                        self.rf.registers[rt] as i64 {
-                        self.rf.pc = ((self.rf.pc as i64) +
-                            imm16 - 4) as u64;
+                }
+                self.cp1.write_vector(wd, value);
+            },
+            Instruction::VectorStore { df, wd, base, offset } => {
+                let address =
+                    (self.rf.registers[base] as i64).wrapping_add(offset) as u64;
+                let size = df.bits() as u64 / 8;
+                let value = self.vector(wd);
+                for i in 0..df.elements() {
+                    let element = address.wrapping_add(i as u64 * size);
+                    self.store(memory, element, msa::element(value, df, i),
+                               size);
+                    if self.exception.is_some() {
+                        return;
                     }
-                } else {
-                    panic!("Unknown instruction!");
                 }
             },
-            J => {
-                self.rf.pc = (((self.rf.pc >> 28) << 28) |
-                    (((instruction << 6) >> 4) as u64)) - 4;
-            },
-            JAL => {
-                self.rf.registers[31] = self.rf.pc + 4;
-                self.rf.pc = (((self.rf.pc >> 28) << 28) |
-                    (((instruction << 6) >> 4) as u64)) - 4;
-            },
-            BEQ => {
-                if self.branching {
-                    self.exception = true;
-                } else if self.rf.registers[rs] == self.rf.registers[rt] {
-                    self.next_branching = true;
-                    self.branch_target = (self.rf.pc as i64 + (imm16 << 2))
-                                            as u64;
+            Instruction::VectorFloat { op, df, wd, ws, wt } => {
+                if !self.cp1.operate_vector(op, df, wd, ws, wt) {
+                    self.raise(Exception::MsaFloatingPoint { pc });
+                }
+            },
+            Instruction::VectorFloatUnary { op, df, wd, ws } => {
+                if !self.cp1.operate_vector_unary(op, df, wd, ws) {
+                    self.raise(Exception::MsaFloatingPoint { pc });
+                }
+            },
+            Instruction::VectorFloatCompare { condition, df, wd, ws, wt } => {
+                if !self.cp1.compare_vector(condition, df, wd, ws, wt) {
+                    self.raise(Exception::MsaFloatingPoint { pc });
                 }
             },
-            _ => panic!("Uncovered opcode!"),
+            Instruction::VectorBranch { nonzero, df, wt, offset } => {
+                let zero = msa::is_zero(df, self.vector(wt));
+                self.delayed_branch(zero != nonzero, false, offset);
+            },
         }
+    }
+}
 
-        if self.branching {
-            self.branching = false;
-            self.rf.pc = self.branch_target;
-        } else {
-            self.rf.pc += 4;
-        }
+// Where the part of an unaligned access at `address` that lies in its aligned
+// word or doubleword is: the address of its first byte, how many bytes there
+// are and the address the whole unaligned value starts at. The left part
+// holds the register's most significant bytes, so big-endian it's the bytes
+// from the address to the end of the aligned word, and little-endian the
+// ones from the start of the aligned word up to the address.
+fn partial(endianness: Endianness,
+           address: u64,
+           size: u64,
+           left: bool) -> (u64, u64, u64) {
+    let index = address & (size - 1);
+    if left == (endianness == Endianness::Big) {
+        (address, size - index, address)
+    } else {
+        (address - index, index + 1, address.wrapping_sub(size - 1))
+    }
+}
 
-        if self.next_branching {
-            self.next_branching = false;
-            self.branching = true;
-        }
+// Where byte `i` of an access at `address` that changes page is, given where
+// its first and last bytes are. Pages are at least 4KB, so it changes page
+// at the one 4KB boundary inside it.
+fn split_byte(address: u64, size: u64, first: u64, last: u64, i: u64) -> u64 {
+    let boundary = (address | 0xfff).wrapping_add(1);
+    if address.wrapping_add(i) < boundary {
+        first.wrapping_add(i)
+    } else {
+        last.wrapping_sub(size - 1 - i)
+    }
+}
+
+// Whether `a` and `b` pass a branch or trap condition. The ones against zero
+// only look at `a`.
+fn compare(condition: Condition, a: u64, b: u64) -> bool {
+    match condition {
+        Condition::Eq => a == b,
+        Condition::Ne => a != b,
+        Condition::Lt => (a as i64) < b as i64,
+        Condition::Ge => a as i64 >= b as i64,
+        Condition::Ltu => a < b,
+        Condition::Geu => a >= b,
+        Condition::Eqz => a == 0,
+        Condition::Nez => a != 0,
+        Condition::Ltz => (a as i64) < 0,
+        Condition::Gez => a as i64 >= 0,
+        Condition::Lez => a as i64 <= 0,
+        Condition::Gtz => a as i64 > 0,
+        Condition::Ov => (a as i32).checked_add(b as i32).is_none(),
+        Condition::Nv => (a as i32).checked_add(b as i32).is_some(),
     }
 }
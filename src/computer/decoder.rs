@@ -0,0 +1,2888 @@
+// Naturally Aligned CPU Load/Store Instructions
+pub(crate) const LB: i32 = 0x20;
+pub(crate) const LBU: i32 = 0x24;
+pub(crate) const LD: i32 = 0x37;
+pub(crate) const LH: i32 = 0x21;
+pub(crate) const LHU: i32 = 0x25;
+pub(crate) const LW: i32 = 0x23;
+pub(crate) const LWU: i32 = 0x27;
+pub(crate) const SB: i32 = 0x28;
+pub(crate) const SD: i32 = 0x3f;
+pub(crate) const SH: i32 = 0x29;
+pub(crate) const SW: i32 = 0x2b;
+
+// Load-Linked and Store-Conditional Instructions. Release 6 moved these to
+// SPECIAL3 with a 9-bit offset, and the paired-word forms set bit 6.
+pub(crate) const LL: i32 = 0x36;
+pub(crate) const LLD: i32 = 0x37;
+pub(crate) const SC: i32 = 0x26;
+pub(crate) const SCD: i32 = 0x27;
+pub(crate) const PAIRED: u32 = 0x1;
+pub(crate) const SYNC: i32 = 0x0f;
+
+// Cache Hints. SYNCI tells the CPU that instructions have been written to
+// memory, and PREF that some data is about to be used. Release 6 moved PREF
+// to SPECIAL3 with a 9-bit offset.
+pub(crate) const PREF: i32 = 0x35;
+pub(crate) const SYNCI: i32 = 0x1f;
+
+// PC-relative Instructions. Each is picked by the top two, three or five
+// bits of the rt field, and the rest of the word is its immediate.
+pub(crate) const PCREL: i32 = 0x3b;
+pub(crate) const ADDIUPC: i32 = 0x0;
+pub(crate) const LWPC: i32 = 0x1;
+pub(crate) const LWUPC: i32 = 0x2;
+pub(crate) const LDPC: i32 = 0x6;
+pub(crate) const AUIPC: i32 = 0x1e;
+pub(crate) const ALUIPC: i32 = 0x1f;
+
+// ALU Instructions with 16-bit Immediate Operand
+pub(crate) const ADDIU: i32 = 0x09;
+pub(crate) const ANDI: i32 = 0x0c;
+pub(crate) const DADDIU: i32 = 0x19;
+pub(crate) const LUI: i32 = 0x0f;
+pub(crate) const AUI: i32 = 0x0f;
+pub(crate) const DAUI: i32 = 0x1d;
+pub(crate) const DAHI: i32 = 0x06;
+pub(crate) const DATI: i32 = 0x1e;
+pub(crate) const ORI: i32 = 0x0d;
+pub(crate) const SLTI: i32 = 0x0a;
+pub(crate) const SLTIU: i32 = 0x0b;
+pub(crate) const XORI: i32 = 0x0e;
+
+// Three-Operand ALU Instructions
+pub(crate) const ADD: i32 = 0x20;
+pub(crate) const ADDU: i32 = 0x21;
+pub(crate) const AND: i32 = 0x24;
+pub(crate) const DADD: i32 = 0x2c;
+pub(crate) const DADDU: i32 = 0x2d;
+pub(crate) const DSUB: i32 = 0x2e;
+pub(crate) const DSUBU: i32 = 0x2f;
+pub(crate) const NOR: i32 = 0x27;
+pub(crate) const OR: i32 = 0x25;
+pub(crate) const SLT: i32 = 0x2a;
+pub(crate) const SLTU: i32 = 0x2b;
+pub(crate) const SUB: i32 = 0x22;
+pub(crate) const SUBU: i32 = 0x23;
+pub(crate) const XOR: i32 = 0x26;
+pub(crate) const LSA: i32 = 0x05;
+pub(crate) const DLSA: i32 = 0x15;
+pub(crate) const SELEQZ: i32 = 0x35;
+pub(crate) const SELNEZ: i32 = 0x37;
+
+// Two-Operand ALU Instructions
+pub(crate) const CLO: i32 = 0x51;
+pub(crate) const CLZ: i32 = 0x50;
+pub(crate) const DCLO: i32 = 0x53;
+pub(crate) const DCLZ: i32 = 0x52;
+
+// Shift Instructions
+pub(crate) const BSHFL: i32 = 0x20;
+pub(crate) const ALIGN: i32 = 0x2;
+pub(crate) const DBSHFL: i32 = 0x24;
+pub(crate) const DALIGN: i32 = 0x1;
+pub(crate) const BITSWAP: i32 = 0x00;
+pub(crate) const DBITSWAP: i32 = 0x00;
+pub(crate) const DSRL: i32 = 0x3a;
+pub(crate) const DSRL32: i32 = 0x3e;
+pub(crate) const DSRLV: i32 = 0x16;
+pub(crate) const DSLL: i32 = 0x38;
+pub(crate) const DSLL32: i32 = 0x3c;
+pub(crate) const DSLLV: i32 = 0x14;
+pub(crate) const DSRA: i32 = 0x3b;
+pub(crate) const DSRA32: i32 = 0x3f;
+pub(crate) const DSRAV: i32 = 0x17;
+pub(crate) const SRL: i32 = 0x02;
+pub(crate) const SRLV: i32 = 0x06;
+pub(crate) const SLL: i32 = 0x00;
+pub(crate) const SLLV: i32 = 0x04;
+pub(crate) const SRA: i32 = 0x03;
+pub(crate) const SRAV: i32 = 0x07;
+
+// Bit Field and Byte Shuffle Instructions. The doubleword extracts and
+// inserts have a form for each way a field can lie across bit 32.
+pub(crate) const EXT: i32 = 0x00;
+pub(crate) const DEXTM: i32 = 0x01;
+pub(crate) const DEXTU: i32 = 0x02;
+pub(crate) const DEXT: i32 = 0x03;
+pub(crate) const INS: i32 = 0x04;
+pub(crate) const DINSM: i32 = 0x05;
+pub(crate) const DINSU: i32 = 0x06;
+pub(crate) const DINS: i32 = 0x07;
+pub(crate) const WSBH: i32 = 0x02;
+pub(crate) const SEB: i32 = 0x10;
+pub(crate) const SEH: i32 = 0x18;
+pub(crate) const DSBH: i32 = 0x02;
+pub(crate) const DSHD: i32 = 0x05;
+
+// Hardware Register Access
+pub(crate) const RDHWR: i32 = 0x3b;
+pub(crate) const HWR_CPUNUM: usize = 0;
+pub(crate) const HWR_SYNCI_STEP: usize = 1;
+pub(crate) const HWR_CC: usize = 2;
+pub(crate) const HWR_CCRES: usize = 3;
+pub(crate) const HWR_ULR: usize = 29;
+
+// Same-Width Multiply and Divide Instructions
+pub(crate) const MUL: i32 = 0x02;
+pub(crate) const SOP30: i32 = 0x18;
+pub(crate) const MUH: i32 = 0x03;
+pub(crate) const MULU: i32 = 0x02;
+pub(crate) const MUHU: i32 = 0x03;
+pub(crate) const SOP31: i32 = 0x19;
+pub(crate) const DMUL: i32 = 0x02;
+pub(crate) const DMUH: i32 = 0x03;
+pub(crate) const SOP34: i32 = 0x1c;
+pub(crate) const DMULU: i32 = 0x02;
+pub(crate) const DMUHU: i32 = 0x03;
+pub(crate) const SOP35: i32 = 0x1d;
+pub(crate) const DIV: i32 = 0x02;
+pub(crate) const MOD: i32 = 0x03;
+pub(crate) const SOP32: i32 = 0x1a;
+pub(crate) const DIVU: i32 = 0x02;
+pub(crate) const MODU: i32 = 0x03;
+pub(crate) const SOP33: i32 = 0x1b;
+pub(crate) const DDIV: i32 = 0x02;
+pub(crate) const DMOD: i32 = 0x03;
+pub(crate) const SOP36: i32 = 0x1e;
+pub(crate) const DDIVU: i32 = 0x02;
+pub(crate) const DMODU: i32 = 0x03;
+pub(crate) const SOP37: i32 = 0x1f;
+
+// Release 6 Compact Branch and Jump Instructions
+pub(crate) const BC: i32 = 0x32;
+pub(crate) const BALC: i32 = 0x3a;
+pub(crate) const POP66: i32 = 0x36;
+pub(crate) const JIC: i32 = 0;
+pub(crate) const POP76: i32 = 0x3e;
+pub(crate) const JIALC: i32 = 0;
+pub(crate) const POP26: i32 = 0x16;
+pub(crate) const POP27: i32 = 0x17;
+pub(crate) const POP06: i32 = 0x06;
+pub(crate) const POP07: i32 = 0x07;
+pub(crate) const POP10: i32 = 0x08;
+pub(crate) const POP30: i32 = 0x18;
+
+// Delayed Branch Instructions
+pub(crate) const J: i32 = 0x02;
+pub(crate) const JAL: i32 = 0x03;
+pub(crate) const JALX: i32 = 0x1d;
+pub(crate) const JALR: i32 = 0x09;
+pub(crate) const BEQ: i32 = 0x04;
+pub(crate) const BNE: i32 = 0x05;
+pub(crate) const BLEZ: i32 = 0x06;
+pub(crate) const BGTZ: i32 = 0x07;
+pub(crate) const REGIMM: i32 = 0x01;
+pub(crate) const BLTZ: i32 = 0x00;
+pub(crate) const BGEZ: i32 = 0x01;
+pub(crate) const BLTZAL: i32 = 0x10;
+pub(crate) const BGEZAL: i32 = 0x11;
+
+// Instructions Removed in Release 6. MULT, MULTU, DIV, DIVU and their
+// doubleword forms share the SOP30 to SOP37 function codes with sa zero.
+pub(crate) const ADDI: i32 = 0x08;
+pub(crate) const DADDI: i32 = 0x18;
+pub(crate) const BEQL: i32 = 0x14;
+pub(crate) const BNEL: i32 = 0x15;
+pub(crate) const BLEZL: i32 = 0x16;
+pub(crate) const BGTZL: i32 = 0x17;
+pub(crate) const BLTZL: i32 = 0x02;
+pub(crate) const BGEZL: i32 = 0x03;
+pub(crate) const BLTZALL: i32 = 0x12;
+pub(crate) const BGEZALL: i32 = 0x13;
+pub(crate) const LDL: i32 = 0x1a;
+pub(crate) const LDR: i32 = 0x1b;
+pub(crate) const LWL: i32 = 0x22;
+pub(crate) const LWR: i32 = 0x26;
+pub(crate) const SDL: i32 = 0x2c;
+pub(crate) const SDR: i32 = 0x2d;
+pub(crate) const SWL: i32 = 0x2a;
+pub(crate) const SWR: i32 = 0x2e;
+pub(crate) const JR: i32 = 0x08;
+pub(crate) const MOVZ: i32 = 0x0a;
+pub(crate) const MOVN: i32 = 0x0b;
+pub(crate) const MFHI: i32 = 0x10;
+pub(crate) const MTHI: i32 = 0x11;
+pub(crate) const MFLO: i32 = 0x12;
+pub(crate) const MTLO: i32 = 0x13;
+pub(crate) const SPECIAL2: i32 = 0x1c;
+pub(crate) const MADD: i32 = 0x00;
+pub(crate) const MADDU: i32 = 0x01;
+pub(crate) const SPECIAL2_MUL: i32 = 0x02;
+pub(crate) const MSUB: i32 = 0x04;
+pub(crate) const MSUBU: i32 = 0x05;
+pub(crate) const SPECIAL2_CLZ: i32 = 0x20;
+pub(crate) const SPECIAL2_CLO: i32 = 0x21;
+pub(crate) const SPECIAL2_DCLZ: i32 = 0x24;
+pub(crate) const SPECIAL2_DCLO: i32 = 0x25;
+pub(crate) const LL_R2: i32 = 0x30;
+pub(crate) const LLD_R2: i32 = 0x34;
+pub(crate) const SC_R2: i32 = 0x38;
+pub(crate) const SCD_R2: i32 = 0x3c;
+pub(crate) const PREF_R2: i32 = 0x33;
+
+// Coprocessor 0 Instructions
+pub(crate) const COP0: i32 = 0x10;
+pub(crate) const MFC0: i32 = 0x00;
+pub(crate) const DMFC0: i32 = 0x01;
+pub(crate) const MTC0: i32 = 0x04;
+pub(crate) const DMTC0: i32 = 0x05;
+pub(crate) const MFMC0: i32 = 0x0b;
+pub(crate) const C0: i32 = 0x10;
+pub(crate) const TLBR: i32 = 0x01;
+pub(crate) const TLBWI: i32 = 0x02;
+pub(crate) const TLBINV: i32 = 0x03;
+pub(crate) const TLBINVF: i32 = 0x04;
+pub(crate) const TLBWR: i32 = 0x06;
+pub(crate) const TLBP: i32 = 0x08;
+pub(crate) const ERET: i32 = 0x18;
+pub(crate) const DERET: i32 = 0x1f;
+pub(crate) const WAIT: i32 = 0x20;
+
+// Floating Point Load/Store Instructions
+pub(crate) const LWC1: i32 = 0x31;
+pub(crate) const LDC1: i32 = 0x35;
+pub(crate) const SWC1: i32 = 0x39;
+pub(crate) const SDC1: i32 = 0x3d;
+
+// Floating Point Move and Branch Instructions
+pub(crate) const COP1: i32 = 0x11;
+pub(crate) const MFC1: i32 = 0x00;
+pub(crate) const DMFC1: i32 = 0x01;
+pub(crate) const CFC1: i32 = 0x02;
+pub(crate) const MFHC1: i32 = 0x03;
+pub(crate) const MTC1: i32 = 0x04;
+pub(crate) const DMTC1: i32 = 0x05;
+pub(crate) const CTC1: i32 = 0x06;
+pub(crate) const MTHC1: i32 = 0x07;
+pub(crate) const BC1EQZ: i32 = 0x09;
+pub(crate) const BC1NEZ: i32 = 0x0d;
+
+// Floating Point Formats. CMP.condn.S and CMP.condn.D are encoded with the
+// W and L formats.
+pub(crate) const FMT_S: i32 = 0x10;
+pub(crate) const FMT_D: i32 = 0x11;
+pub(crate) const FMT_W: i32 = 0x14;
+pub(crate) const FMT_L: i32 = 0x15;
+
+// Floating Point Arithmetic and Conversion Instructions
+pub(crate) const ADD_FMT: i32 = 0x00;
+pub(crate) const SUB_FMT: i32 = 0x01;
+pub(crate) const MUL_FMT: i32 = 0x02;
+pub(crate) const DIV_FMT: i32 = 0x03;
+pub(crate) const SQRT_FMT: i32 = 0x04;
+pub(crate) const ABS_FMT: i32 = 0x05;
+pub(crate) const MOV_FMT: i32 = 0x06;
+pub(crate) const NEG_FMT: i32 = 0x07;
+pub(crate) const ROUND_L_FMT: i32 = 0x08;
+pub(crate) const TRUNC_L_FMT: i32 = 0x09;
+pub(crate) const CEIL_L_FMT: i32 = 0x0a;
+pub(crate) const FLOOR_L_FMT: i32 = 0x0b;
+pub(crate) const ROUND_W_FMT: i32 = 0x0c;
+pub(crate) const TRUNC_W_FMT: i32 = 0x0d;
+pub(crate) const CEIL_W_FMT: i32 = 0x0e;
+pub(crate) const FLOOR_W_FMT: i32 = 0x0f;
+pub(crate) const SEL_FMT: i32 = 0x10;
+pub(crate) const SELEQZ_FMT: i32 = 0x14;
+pub(crate) const RECIP_FMT: i32 = 0x15;
+pub(crate) const RSQRT_FMT: i32 = 0x16;
+pub(crate) const SELNEZ_FMT: i32 = 0x17;
+pub(crate) const MADDF_FMT: i32 = 0x18;
+pub(crate) const MSUBF_FMT: i32 = 0x19;
+pub(crate) const RINT_FMT: i32 = 0x1a;
+pub(crate) const CLASS_FMT: i32 = 0x1b;
+pub(crate) const MIN_FMT: i32 = 0x1c;
+pub(crate) const MAX_FMT: i32 = 0x1d;
+pub(crate) const MINA_FMT: i32 = 0x1e;
+pub(crate) const MAXA_FMT: i32 = 0x1f;
+pub(crate) const CVT_S_FMT: i32 = 0x20;
+pub(crate) const CVT_D_FMT: i32 = 0x21;
+pub(crate) const CVT_W_FMT: i32 = 0x24;
+pub(crate) const CVT_L_FMT: i32 = 0x25;
+
+// MIPS SIMD Architecture. The function field is a minor opcode that picks
+// the format, and the format then picks the operation from the top bits.
+// The 2R and 2RF formats share VEC's minor opcode, and LD and ST put the
+// element format in the low two bits of theirs.
+pub(crate) const MSA: i32 = 0x1e;
+pub(crate) const MSA_I8_LOGIC: i32 = 0x00;
+pub(crate) const MSA_I8_SELECT: i32 = 0x01;
+pub(crate) const MSA_I8_SHF: i32 = 0x02;
+pub(crate) const MSA_I5_ARITHMETIC: i32 = 0x06;
+pub(crate) const MSA_I5_COMPARE: i32 = 0x07;
+pub(crate) const MSA_BIT_SHIFT: i32 = 0x09;
+pub(crate) const MSA_BIT_SATURATE: i32 = 0x0a;
+pub(crate) const MSA_3R_SHIFT: i32 = 0x0d;
+pub(crate) const MSA_3R_ADD: i32 = 0x0e;
+pub(crate) const MSA_3R_COMPARE: i32 = 0x0f;
+pub(crate) const MSA_3R_ADD_SATURATE: i32 = 0x10;
+pub(crate) const MSA_3R_SUBTRACT: i32 = 0x11;
+pub(crate) const MSA_3R_MULTIPLY: i32 = 0x12;
+pub(crate) const MSA_3R_DOT_PRODUCT: i32 = 0x13;
+pub(crate) const MSA_3R_SHUFFLE: i32 = 0x14;
+pub(crate) const MSA_3R_HORIZONTAL: i32 = 0x15;
+pub(crate) const MSA_ELM: i32 = 0x19;
+pub(crate) const MSA_3RF_COMPARE: i32 = 0x1a;
+pub(crate) const MSA_3RF_ARITHMETIC: i32 = 0x1b;
+pub(crate) const MSA_3RF_FIXED: i32 = 0x1c;
+pub(crate) const MSA_VEC: i32 = 0x1e;
+pub(crate) const MSA_LD: i32 = 0x20;
+pub(crate) const MSA_ST: i32 = 0x24;
+pub(crate) const MSA_2R: u32 = 0x18;
+pub(crate) const MSA_2RF: u32 = 0x19;
+pub(crate) const LDI: u32 = 0x6;
+// The ELM df/n field that picks CTCMSA, CFCMSA and MOVE.V instead.
+pub(crate) const MSA_ELM_CONTROL: u32 = 0x3e;
+pub(crate) const BZ_V: i32 = 0x0b;
+pub(crate) const BNZ_V: i32 = 0x0f;
+pub(crate) const BZ_DF: i32 = 0x18;
+pub(crate) const BNZ_DF: i32 = 0x1c;
+
+// DSP ASE, in SPECIAL3 before Release 6. The function field picks a group
+// and the sa field an operation within it. BPOSGE32 is in REGIMM.
+pub(crate) const LX_DSP: i32 = 0x0a;
+pub(crate) const INSV_DSP: i32 = 0x0c;
+pub(crate) const ADDU_QB_DSP: i32 = 0x10;
+pub(crate) const CMPU_EQ_QB_DSP: i32 = 0x11;
+pub(crate) const ABSQ_S_PH_DSP: i32 = 0x12;
+pub(crate) const SHLL_QB_DSP: i32 = 0x13;
+pub(crate) const ADDUH_QB_DSP: i32 = 0x18;
+pub(crate) const DPA_W_PH_DSP: i32 = 0x30;
+pub(crate) const APPEND_DSP: i32 = 0x31;
+pub(crate) const EXTR_W_DSP: i32 = 0x38;
+pub(crate) const BPOSGE32: i32 = 0x1c;
+
+// Trap Instructions. The immediate forms in REGIMM were removed in Release
+// 6, and SDBBP moved from SPECIAL2 to SPECIAL.
+pub(crate) const TGE: i32 = 0x30;
+pub(crate) const TGEU: i32 = 0x31;
+pub(crate) const TLT: i32 = 0x32;
+pub(crate) const TLTU: i32 = 0x33;
+pub(crate) const TEQ: i32 = 0x34;
+pub(crate) const TNE: i32 = 0x36;
+pub(crate) const TGEI: i32 = 0x08;
+pub(crate) const TGEIU: i32 = 0x09;
+pub(crate) const TLTI: i32 = 0x0a;
+pub(crate) const TLTIU: i32 = 0x0b;
+pub(crate) const TEQI: i32 = 0x0c;
+pub(crate) const TNEI: i32 = 0x0e;
+pub(crate) const SIGRIE: i32 = 0x17;
+pub(crate) const SDBBP: i32 = 0x0e;
+pub(crate) const SDBBP_R2: i32 = 0x3f;
+
+// Special Constants
+pub(crate) const SPECIAL3: i32 = 0x1f;
+pub(crate) const BREAK: i32 = 0x0d;
+pub(crate) const SYSCALL: i32 = 0x0c;
+pub(crate) const OPCODE: i32 = 26;
+pub(crate) const RS: i32 = 21;
+pub(crate) const RT: i32 = 16;
+pub(crate) const RD: i32 = 11;
+
+// Which release of the architecture a CPU implements. Release 6 gave new
+// instructions to opcodes that earlier releases used for branch-likely,
+// LWL and friends, so the same word can decode differently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IsaRevision {
+    Release2,
+    Release6,
+}
+
+// Which encoding the CPU is fetching. Bit 0 of a jump target picks between
+// the standard one and the compressed one the revision has: microMIPS on
+// Release 6 and MIPS16e before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IsaMode {
+    Standard,
+    MicroMips,
+    Mips16,
+}
+
+// Loads and stores: rt, offset(base). Before Release 6, LWL, LWR and the
+// rest move the part of an unaligned word that lies in one aligned word.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryOp {
+    Lb,
+    Lbu,
+    Ld,
+    Lh,
+    Lhu,
+    Lw,
+    Lwu,
+    Sb,
+    Sd,
+    Sh,
+    Sw,
+    Ldl,
+    Ldr,
+    Lwl,
+    Lwr,
+    Sdl,
+    Sdr,
+    Swl,
+    Swr,
+}
+
+// Loads and address calculations relative to the instruction's own address:
+// rs, offset. ALUIPC clears the low 16 bits of the address it works out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PcRelativeOp {
+    Addiupc,
+    Lwpc,
+    Lwupc,
+    Ldpc,
+    Auipc,
+    Aluipc,
+}
+
+// Load-linked and store-conditional: rt, offset(base)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkedOp {
+    Ll,
+    Lld,
+    Sc,
+    Scd,
+}
+
+// ALU instructions with a 16-bit immediate: rt, rs, immediate. AUI and the
+// rest add the immediate shifted up by 16, 32 or 48 bits. DAHI and DATI add
+// to rs, which is also rt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImmediateOp {
+    Addi,
+    Addiu,
+    Andi,
+    Daddi,
+    Daddiu,
+    Lui,
+    Aui,
+    Daui,
+    Dahi,
+    Dati,
+    Ori,
+    Slti,
+    Sltiu,
+    Xori,
+}
+
+// Three-operand ALU instructions, multiplies and divides: rd, rs, rt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegisterOp {
+    Add,
+    Addu,
+    And,
+    Dadd,
+    Daddu,
+    Dsub,
+    Dsubu,
+    Nor,
+    Or,
+    Slt,
+    Sltu,
+    Sub,
+    Subu,
+    Xor,
+    Mul,
+    Muh,
+    Mulu,
+    Muhu,
+    Dmul,
+    Dmuh,
+    Dmulu,
+    Dmuhu,
+    Div,
+    Mod,
+    Divu,
+    Modu,
+    Ddiv,
+    Dmod,
+    Ddivu,
+    Dmodu,
+    Seleqz,
+    Selnez,
+}
+
+// The multiplies and divides that leave their result in HI and LO: rs, rt.
+// MADD and the rest add to or subtract from HI and LO as one 64-bit value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HiLoOp {
+    Mult,
+    Multu,
+    Div,
+    Divu,
+    Dmult,
+    Dmultu,
+    Ddiv,
+    Ddivu,
+    Madd,
+    Maddu,
+    Msub,
+    Msubu,
+}
+
+// Leading one and zero counts: rd, rs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountOp {
+    Clo,
+    Clz,
+    Dclo,
+    Dclz,
+}
+
+// Shifts by a constant: rd, rt, sa
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShiftOp {
+    Sll,
+    Srl,
+    Sra,
+    Rotr,
+    Dsll,
+    Dsrl,
+    Dsra,
+    Drotr,
+    Dsll32,
+    Dsrl32,
+    Dsra32,
+    Drotr32,
+}
+
+// Shifts by a register: rd, rt, rs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShiftVariableOp {
+    Sllv,
+    Srlv,
+    Srav,
+    Rotrv,
+    Dsllv,
+    Dsrlv,
+    Dsrav,
+    Drotrv,
+}
+
+// Bit field extracts and inserts: rt, rs, pos, size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitFieldOp {
+    Ext,
+    Ins,
+    Dext,
+    Dins,
+}
+
+// Byte shuffles and sign extensions: rd, rt. WSBH and DSBH swap the bytes
+// in each halfword, and DSHD reverses the order of the halfwords.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShuffleOp {
+    Wsbh,
+    Seb,
+    Seh,
+    Dsbh,
+    Dshd,
+}
+
+// What a conditional branch tests. The ones ending in z compare a single
+// register against zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Ltu,
+    Geu,
+    Eqz,
+    Nez,
+    Ltz,
+    Gez,
+    Lez,
+    Gtz,
+    // Signed 32-bit addition overflows, or doesn't.
+    Ov,
+    Nv,
+}
+
+// Moves to and from coprocessor 0: rt, rd, sel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cop0Op {
+    Mfc0,
+    Dmfc0,
+    Mtc0,
+    Dmtc0,
+}
+
+// TLB maintenance, through EntryHi, EntryLo0, EntryLo1, PageMask and
+// Index in coprocessor 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlbOp {
+    Tlbp,
+    Tlbr,
+    Tlbwi,
+    Tlbwr,
+    Tlbinv,
+    Tlbinvf,
+}
+
+// Floating-point loads and stores: ft, offset(base)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatMemoryOp {
+    Lwc1,
+    Ldc1,
+    Swc1,
+    Sdc1,
+}
+
+// Moves to and from coprocessor 1: rt, fs. For CFC1 and CTC1, fs is a
+// control register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cop1Op {
+    Mfc1,
+    Dmfc1,
+    Cfc1,
+    Mfhc1,
+    Mtc1,
+    Dmtc1,
+    Ctc1,
+    Mthc1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatFormat {
+    S,
+    D,
+}
+
+// The integer formats the FPU converts from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FixedFormat {
+    W,
+    L,
+}
+
+// Floating-point operations on fs and ft. MADDF and MSUBF also add to fd,
+// and SEL uses fd to choose.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Maddf,
+    Msubf,
+    Min,
+    Mina,
+    Max,
+    Maxa,
+    Sel,
+    Seleqz,
+    Selnez,
+}
+
+// Floating-point operations on fs alone. The conversions to W and L leave
+// an integer in fd.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatUnaryOp {
+    Sqrt,
+    Abs,
+    Mov,
+    Neg,
+    Recip,
+    Rsqrt,
+    Rint,
+    Class,
+    RoundL,
+    TruncL,
+    CeilL,
+    FloorL,
+    RoundW,
+    TruncW,
+    CeilW,
+    FloorW,
+    CvtS,
+    CvtD,
+    CvtW,
+    CvtL,
+}
+
+// The CMP.condn.fmt conditions, numbered as they're encoded. The low three
+// bits pick unordered, equal and less than, bit 3 makes the comparison
+// signal on quiet NaNs too, and bit 4 negates it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatCondition {
+    Af = 0x00,
+    Un = 0x01,
+    Eq = 0x02,
+    Ueq = 0x03,
+    Lt = 0x04,
+    Ult = 0x05,
+    Le = 0x06,
+    Ule = 0x07,
+    Saf = 0x08,
+    Sun = 0x09,
+    Seq = 0x0a,
+    Sueq = 0x0b,
+    Slt = 0x0c,
+    Sult = 0x0d,
+    Sle = 0x0e,
+    Sule = 0x0f,
+    Or = 0x11,
+    Une = 0x12,
+    Ne = 0x13,
+    Sor = 0x19,
+    Sune = 0x1a,
+    Sne = 0x1b,
+}
+
+// The size of an MSA vector's elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorFormat {
+    B,
+    H,
+    W,
+    D,
+}
+
+impl VectorFormat {
+    pub fn bits(self) -> u32 {
+        8 << self as u32
+    }
+
+    pub fn elements(self) -> usize {
+        (128 / self.bits()) as usize
+    }
+
+    fn from_bits(bits: u32) -> VectorFormat {
+        match bits & 3 {
+            0 => VectorFormat::B,
+            1 => VectorFormat::H,
+            2 => VectorFormat::W,
+            _ => VectorFormat::D,
+        }
+    }
+}
+
+// MSA integer operations, lane by lane on ws and wt unless they say
+// otherwise. The immediate forms take the same ops with a constant for wt.
+// The dot products and horizontal ops combine adjacent pairs of elements
+// half the size of wd's, and the shuffles move whole elements around. The
+// _Q ops work on fixed-point fractions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorOp {
+    Sll,
+    Sra,
+    Srl,
+    Bclr,
+    Bset,
+    Bneg,
+    Binsl,
+    Binsr,
+    Addv,
+    Subv,
+    MaxS,
+    MaxU,
+    MinS,
+    MinU,
+    MaxA,
+    MinA,
+    Ceq,
+    CltS,
+    CltU,
+    CleS,
+    CleU,
+    AddA,
+    AddsA,
+    AddsS,
+    AddsU,
+    AveS,
+    AveU,
+    AverS,
+    AverU,
+    SubsS,
+    SubsU,
+    SubsusU,
+    SubsuuS,
+    AsubS,
+    AsubU,
+    Mulv,
+    Maddv,
+    Msubv,
+    DivS,
+    DivU,
+    ModS,
+    ModU,
+    DotpS,
+    DotpU,
+    DpaddS,
+    DpaddU,
+    DpsubS,
+    DpsubU,
+    Pckev,
+    Pckod,
+    Ilvl,
+    Ilvr,
+    Ilvev,
+    Ilvod,
+    Vshf,
+    Srar,
+    Srlr,
+    HaddS,
+    HaddU,
+    HsubS,
+    HsubU,
+    SatS,
+    SatU,
+    MulQ,
+    MaddQ,
+    MsubQ,
+    MulrQ,
+    MaddrQ,
+    MsubrQ,
+}
+
+// Bitwise operations on whole vectors. BMNZ and BMZ copy ws's bits into wd
+// where wt's are set or clear, and BSEL picks wt's where wd's are set and
+// ws's elsewhere.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogicOp {
+    And,
+    Or,
+    Nor,
+    Xor,
+    Bmnz,
+    Bmz,
+    Bsel,
+}
+
+// Counts of the set bits, leading ones and leading zeros in each element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorCountOp {
+    Pcnt,
+    Nloc,
+    Nlzc,
+}
+
+// MSA floating-point operations on ws and wt. FMADD and FMSUB also add to
+// wd. FEXP2 scales ws by 2 to the integer in wt. FEXDO and FTQ narrow the
+// elements of ws and wt into the two halves of wd, to floats or to
+// fixed-point fractions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorFloatOp {
+    Fadd,
+    Fsub,
+    Fmul,
+    Fdiv,
+    Fmadd,
+    Fmsub,
+    Fexp2,
+    Fexdo,
+    Ftq,
+    Fmin,
+    FminA,
+    Fmax,
+    FmaxA,
+}
+
+// MSA floating-point operations on ws alone. FEXUPL and FEXUPR widen the
+// left or right half of ws's elements, and FFQL and FFQR do the same from
+// fixed-point fractions. FLOG2 finds the integer part of the logarithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorFloatUnaryOp {
+    Fclass,
+    FtruncS,
+    FtruncU,
+    Fsqrt,
+    Frsqrt,
+    Frcp,
+    Frint,
+    Flog2,
+    Fexupl,
+    Fexupr,
+    Ffql,
+    Ffqr,
+    FtintS,
+    FtintU,
+    FfintS,
+    FfintU,
+}
+
+// DSP ASE operations on rs and rt, lane by lane on quad bytes (QB), paired
+// halfwords (PH) or whole words (W). The Q ops work on fixed-point
+// fractions, the _S ones saturate and the _R ones round. The ops with two
+// formats take their operands in the second and give a result in the
+// first, from the lanes on the left or right (L and R) or from alternate
+// ones (LA and RA).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DspOp {
+    AdduQb,
+    AdduSQb,
+    SubuQb,
+    SubuSQb,
+    AdduPh,
+    AdduSPh,
+    SubuPh,
+    SubuSPh,
+    AddqPh,
+    AddqSPh,
+    SubqPh,
+    SubqSPh,
+    AddqSW,
+    SubqSW,
+    Addsc,
+    Addwc,
+    Modsub,
+    AdduhQb,
+    AdduhRQb,
+    SubuhQb,
+    SubuhRQb,
+    AddqhPh,
+    AddqhRPh,
+    SubqhPh,
+    SubqhRPh,
+    AddqhW,
+    AddqhRW,
+    SubqhW,
+    SubqhRW,
+    MuleuSPhQbl,
+    MuleuSPhQbr,
+    MulqRsPh,
+    MulqSPh,
+    MuleqSWPhl,
+    MuleqSWPhr,
+    MulPh,
+    MulSPh,
+    MulqSW,
+    MulqRsW,
+    PickQb,
+    PickPh,
+    PackrlPh,
+    PrecrQbPh,
+    PrecrqQbPh,
+    PrecrqPhW,
+    PrecrqRsPhW,
+    PrecrquSQbPh,
+}
+
+// DSP ASE operations on one register. REPL copies an immediate to every
+// lane, and REPLV the low lane of a register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DspUnaryOp {
+    AbsqSQb,
+    AbsqSPh,
+    AbsqSW,
+    PreceqWPhl,
+    PreceqWPhr,
+    PrecequPhQbl,
+    PrecequPhQbr,
+    PrecequPhQbla,
+    PrecequPhQbra,
+    PreceuPhQbl,
+    PreceuPhQbr,
+    PreceuPhQbla,
+    PreceuPhQbra,
+    Bitrev,
+    ReplQb,
+    ReplPh,
+    RadduWQb,
+}
+
+// Lane by lane shifts. The variable forms take the same ops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DspShiftOp {
+    ShllQb,
+    ShllPh,
+    ShllSPh,
+    ShllSW,
+    ShrlQb,
+    ShrlPh,
+    ShraQb,
+    ShraRQb,
+    ShraPh,
+    ShraRPh,
+    ShraRW,
+}
+
+// CMPU compares unsigned bytes and CMP signed halfwords, setting the
+// DSPControl condition bits. CMPGU puts the results in rd instead, and
+// CMPGDU does both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DspCompareOp {
+    Cmpu,
+    Cmpgu,
+    Cmpgdu,
+    Cmp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DspCondition {
+    Eq,
+    Lt,
+    Le,
+}
+
+// Dot products and multiplies that add to or subtract from an
+// accumulator. The X ops cross the halfwords over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccumulateOp {
+    DpauHQbl,
+    DpauHQbr,
+    DpsuHQbl,
+    DpsuHQbr,
+    DpaWPh,
+    DpaxWPh,
+    DpsWPh,
+    DpsxWPh,
+    MulsaWPh,
+    DpaqSWPh,
+    DpaqxSWPh,
+    DpsqSWPh,
+    DpsqxSWPh,
+    MulsaqSWPh,
+    DpaqxSaWPh,
+    DpsqxSaWPh,
+    DpaqSaLW,
+    DpsqSaLW,
+    MaqSWPhl,
+    MaqSWPhr,
+    MaqSaWPhl,
+    MaqSaWPhr,
+}
+
+// Moves from an accumulator to a register. The variable forms take the
+// same ops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtractOp {
+    ExtrW,
+    ExtrRW,
+    ExtrRsW,
+    ExtrSH,
+    Extp,
+    Extpdp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppendOp {
+    Append,
+    Prepend,
+    Balign,
+}
+
+// An instruction with its operands pulled out of the encoding. Register
+// operands are register numbers, and branch offsets are in bytes, already
+// scaled and sign-extended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Memory { op: MemoryOp, rt: usize, base: usize, offset: i64 },
+    // microMIPS's LWP, LWM and the stores like them move each register set
+    // in the mask, lowest first, to or from consecutive words.
+    LoadMultiple { registers: u32, base: usize, offset: i64 },
+    StoreMultiple { registers: u32, base: usize, offset: i64 },
+    // SC and SCD leave 1 in rt if they stored and 0 if the link was broken.
+    // LLWP and SCWP move rt and rd as the high and low words of the
+    // doubleword at base, and SCWP leaves its result in rt.
+    Linked { op: LinkedOp, rt: usize, base: usize, offset: i64 },
+    Llwp { rt: usize, rd: usize, base: usize },
+    Scwp { rt: usize, rd: usize, base: usize },
+    Sync { stype: u32 },
+    // Neither does anything, as there are no caches.
+    Pref { hint: u32, base: usize, offset: i64 },
+    Synci { base: usize, offset: i64 },
+    // The offset is from the instruction's address, or for LDPC from that
+    // address rounded down to a doubleword. AUIPC and ALUIPC's offset is
+    // their immediate shifted up 16 bits.
+    PcRelative { op: PcRelativeOp, rs: usize, offset: i64 },
+    Immediate { op: ImmediateOp, rt: usize, rs: usize, immediate: u16 },
+    Register { op: RegisterOp, rd: usize, rs: usize, rt: usize },
+    Count { op: CountOp, rd: usize, rs: usize },
+    Shift { op: ShiftOp, rd: usize, rt: usize, sa: u32 },
+    ShiftVariable { op: ShiftVariableOp, rd: usize, rt: usize, rs: usize },
+    Align { rd: usize, rs: usize, rt: usize, bp: u32 },
+    Dalign { rd: usize, rs: usize, rt: usize, bp: u32 },
+    Bitswap { rd: usize, rt: usize },
+    Dbitswap { rd: usize, rt: usize },
+    // The field is `size` bits starting at bit `pos`, and always fits.
+    BitField { op: BitFieldOp, rt: usize, rs: usize, pos: u32, size: u32 },
+    Shuffle { op: ShuffleOp, rd: usize, rt: usize },
+    // Add rs shifted left by sa, which is 1 to 4, to rt.
+    Lsa { rd: usize, rs: usize, rt: usize, sa: u32 },
+    Dlsa { rd: usize, rs: usize, rt: usize, sa: u32 },
+    Rdhwr { rt: usize, rd: usize },
+    // With the DSP ASE there are four HI and LO pairs, and ac picks one.
+    HiLo { op: HiLoOp, ac: usize, rs: usize, rt: usize },
+    Mfhi { rd: usize, ac: usize },
+    Mflo { rd: usize, ac: usize },
+    Mthi { rs: usize, ac: usize },
+    Mtlo { rs: usize, ac: usize },
+    // Copy rs to rd if rt is zero, or isn't.
+    Movz { rd: usize, rs: usize, rt: usize },
+    Movn { rd: usize, rs: usize, rt: usize },
+    // microMIPS's MOVEP copies rs to rd and rt to re.
+    Movep { rd: usize, re: usize, rs: usize, rt: usize },
+    // MIPS16e's SAVE stores the first `arguments` argument registers at
+    // the bottom of the caller's frame, pushes the registers in the mask
+    // below $sp, highest first, and then drops $sp by the frame size.
+    // RESTORE pops them from the top of the frame the same way.
+    Save { arguments: u32, registers: u32, frame: u64 },
+    Restore { registers: u32, frame: u64 },
+    // Compact branches and jumps. Branch offsets are from the next
+    // instruction; JIC and JIALC add theirs to rt.
+    Bc { offset: i64 },
+    Balc { offset: i64 },
+    Jic { rt: usize, offset: i64 },
+    Jialc { rt: usize, offset: i64 },
+    // microMIPS only: JALR without a delay slot, and a return through $ra
+    // that also adds the immediate to $sp.
+    Jalrc { rd: usize, rs: usize },
+    JrcAddiusp { immediate: i64 },
+    // For conditions against zero the register is in rs and rt is unused.
+    CompactBranch {
+        condition: Condition,
+        link: bool,
+        rs: usize,
+        rt: usize,
+        offset: i64,
+    },
+    // Delayed branches and jumps. A jump's target is the low 28 bits of
+    // the address it goes to. Branches that link always do, taken or not,
+    // and a branch-likely that isn't taken skips its delay slot.
+    Branch {
+        condition: Condition,
+        link: bool,
+        likely: bool,
+        rs: usize,
+        rt: usize,
+        offset: i64,
+    },
+    J { target: u64 },
+    Jal { target: u64 },
+    // JAL that also switches between the standard encoding and MIPS16e.
+    Jalx { target: u64 },
+    Jalr { rd: usize, rs: usize },
+    Cop0 { op: Cop0Op, rt: usize, rd: usize, sel: usize },
+    Tlb { op: TlbOp },
+    Di { rt: usize },
+    Ei { rt: usize },
+    Eret,
+    Deret,
+    Wait,
+    Syscall { code: u32 },
+    Break { code: u32 },
+    Sdbbp { code: u32 },
+    // Trap if rs compares with rt, or with the sign-extended immediate, as
+    // the condition says.
+    Trap { condition: Condition, rs: usize, rt: usize, code: u32 },
+    TrapImmediate { condition: Condition, rs: usize, immediate: i64 },
+    // Always raises a Reserved Instruction exception.
+    Sigrie { code: u32 },
+    // Floating point. BC1EQZ and BC1NEZ test bit 0 of ft and have a delay
+    // slot like BEQ.
+    FloatMemory { op: FloatMemoryOp, ft: usize, base: usize, offset: i64 },
+    Cop1 { op: Cop1Op, rt: usize, fs: usize },
+    Float { op: FloatOp, fmt: FloatFormat, fd: usize, fs: usize, ft: usize },
+    FloatUnary { op: FloatUnaryOp, fmt: FloatFormat, fd: usize, fs: usize },
+    FloatCompare {
+        condition: FloatCondition,
+        fmt: FloatFormat,
+        fd: usize,
+        fs: usize,
+        ft: usize,
+    },
+    ConvertFixed { to: FloatFormat, from: FixedFormat, fd: usize, fs: usize },
+    Bc1 { condition: Condition, ft: usize, offset: i64 },
+    // microMIPS's compact BC1EQZC and BC1NEZC.
+    Bc1c { condition: Condition, ft: usize, offset: i64 },
+    // MSA. Vector registers overlay the floating-point ones. Element
+    // indexes count elements of the format df. The I5 immediates are
+    // sign-extended for the signed ops, and the bit ops' immediate is a
+    // bit number within an element. Vector loads and stores scale their
+    // offset by the element size.
+    Vector {
+        op: VectorOp,
+        df: VectorFormat,
+        wd: usize,
+        ws: usize,
+        wt: usize,
+    },
+    VectorImmediate {
+        op: VectorOp,
+        df: VectorFormat,
+        wd: usize,
+        ws: usize,
+        immediate: i64,
+    },
+    VectorLogic { op: LogicOp, wd: usize, ws: usize, wt: usize },
+    VectorLogicImmediate { op: LogicOp, wd: usize, ws: usize, immediate: u8 },
+    VectorCount { op: VectorCountOp, df: VectorFormat, wd: usize, ws: usize },
+    Ldi { df: VectorFormat, wd: usize, immediate: i64 },
+    // Each group of four elements is shuffled by the four two-bit indexes
+    // in the immediate.
+    Shf { df: VectorFormat, wd: usize, ws: usize, immediate: u8 },
+    // SLD slides wd and ws along by rt elements, and SPLAT copies ws's
+    // element rt to every element of wd.
+    Sld { df: VectorFormat, wd: usize, ws: usize, rt: usize },
+    Splat { df: VectorFormat, wd: usize, ws: usize, rt: usize },
+    Sldi { df: VectorFormat, wd: usize, ws: usize, n: usize },
+    Splati { df: VectorFormat, wd: usize, ws: usize, n: usize },
+    Copy { signed: bool, df: VectorFormat, rd: usize, ws: usize, n: usize },
+    Insert { df: VectorFormat, wd: usize, rs: usize, n: usize },
+    // Copies element 0 of ws.
+    Insve { df: VectorFormat, wd: usize, ws: usize, n: usize },
+    Fill { df: VectorFormat, wd: usize, rs: usize },
+    Ctcmsa { cd: usize, rs: usize },
+    Cfcmsa { rd: usize, cs: usize },
+    MoveV { wd: usize, ws: usize },
+    VectorLoad { df: VectorFormat, wd: usize, base: usize, offset: i64 },
+    VectorStore { df: VectorFormat, wd: usize, base: usize, offset: i64 },
+    // The format is the one the mnemonic names. That's wd's, which is the
+    // smaller one for FEXDO and FTQ and the larger one for FEXUPL and the
+    // rest that widen.
+    VectorFloat {
+        op: VectorFloatOp,
+        df: VectorFormat,
+        wd: usize,
+        ws: usize,
+        wt: usize,
+    },
+    VectorFloatUnary {
+        op: VectorFloatUnaryOp,
+        df: VectorFormat,
+        wd: usize,
+        ws: usize,
+    },
+    VectorFloatCompare {
+        condition: FloatCondition,
+        df: VectorFormat,
+        wd: usize,
+        ws: usize,
+        wt: usize,
+    },
+    // Delayed branches on whether all of wt is zero, or with a format,
+    // whether any of its elements is.
+    VectorBranch {
+        nonzero: bool,
+        df: Option<VectorFormat>,
+        wt: usize,
+        offset: i64,
+    },
+    // The DSP ASE. Its operations read only the low word of a register,
+    // and the result is sign-extended. RADDU.W.QB's operand is in the rs
+    // field, but it's rt here like the other unary ops'.
+    Dsp { op: DspOp, rd: usize, rs: usize, rt: usize },
+    DspUnary { op: DspUnaryOp, rd: usize, rt: usize },
+    Repl { op: DspUnaryOp, rd: usize, immediate: i64 },
+    DspShift { op: DspShiftOp, rd: usize, rt: usize, sa: u32 },
+    DspShiftVariable { op: DspShiftOp, rd: usize, rt: usize, rs: usize },
+    DspCompare {
+        op: DspCompareOp,
+        condition: DspCondition,
+        rd: usize,
+        rs: usize,
+        rt: usize,
+    },
+    // Packs rt and rs, shifted right by sa, into the halves of rt.
+    PrecrSra { round: bool, rt: usize, rs: usize, sa: u32 },
+    DspAccumulate { op: AccumulateOp, ac: usize, rs: usize, rt: usize },
+    Extract { op: ExtractOp, rt: usize, ac: usize, shift: u32 },
+    ExtractVariable { op: ExtractOp, rt: usize, ac: usize, rs: usize },
+    Shilo { ac: usize, shift: i64 },
+    Shilov { ac: usize, rs: usize },
+    Mthlip { rs: usize, ac: usize },
+    // The mask picks which DSPControl fields are read or written.
+    Wrdsp { rs: usize, mask: u32 },
+    Rddsp { rd: usize, mask: u32 },
+    Insv { rt: usize, rs: usize },
+    Append { op: AppendOp, rt: usize, rs: usize, sa: u32 },
+    // LWX, LHX and LBUX load from base plus index.
+    IndexedLoad { op: MemoryOp, rd: usize, base: usize, index: usize },
+    // A delayed branch taken if DSPControl.POS is at least 32.
+    Bposge32 { offset: i64 },
+    // Anything the CPU doesn't implement.
+    Reserved,
+}
+
+impl Instruction {
+    // Branches, jumps and exception returns. None of them can go in another
+    // branch's delay slot or a compact branch's forbidden slot.
+    pub fn is_control_transfer(&self) -> bool {
+        matches!(self,
+                 Instruction::Bc { .. } | Instruction::Balc { .. } |
+                 Instruction::Jic { .. } | Instruction::Jialc { .. } |
+                 Instruction::Jalrc { .. } | Instruction::JrcAddiusp { .. } |
+                 Instruction::CompactBranch { .. } |
+                 Instruction::Branch { .. } | Instruction::J { .. } |
+                 Instruction::Jal { .. } | Instruction::Jalx { .. } |
+                 Instruction::Jalr { .. } | Instruction::Bc1 { .. } |
+                 Instruction::Bc1c { .. } | Instruction::VectorBranch { .. } |
+                 Instruction::Bposge32 { .. } | Instruction::Eret |
+                 Instruction::Deret)
+    }
+
+    // DSP ASE instructions, which raise a DSP Disabled exception unless
+    // Status.MX is set. That includes the accumulator moves and multiplies
+    // on any accumulator but the original HI and LO.
+    pub fn is_dsp(&self) -> bool {
+        match *self {
+            Instruction::HiLo { ac, .. } | Instruction::Mfhi { ac, .. } |
+            Instruction::Mflo { ac, .. } | Instruction::Mthi { ac, .. } |
+            Instruction::Mtlo { ac, .. } => ac != 0,
+            Instruction::Dsp { .. } | Instruction::DspUnary { .. } |
+            Instruction::Repl { .. } | Instruction::DspShift { .. } |
+            Instruction::DspShiftVariable { .. } |
+            Instruction::DspCompare { .. } | Instruction::PrecrSra { .. } |
+            Instruction::DspAccumulate { .. } | Instruction::Extract { .. } |
+            Instruction::ExtractVariable { .. } | Instruction::Shilo { .. } |
+            Instruction::Shilov { .. } | Instruction::Mthlip { .. } |
+            Instruction::Wrdsp { .. } | Instruction::Rddsp { .. } |
+            Instruction::Insv { .. } | Instruction::Append { .. } |
+            Instruction::IndexedLoad { .. } |
+            Instruction::Bposge32 { .. } => true,
+            _ => false,
+        }
+    }
+
+    // MSA instructions, which raise an MSA Disabled exception unless MSA
+    // is enabled.
+    pub fn is_vector(&self) -> bool {
+        matches!(self,
+                 Instruction::Vector { .. } |
+                 Instruction::VectorImmediate { .. } |
+                 Instruction::VectorLogic { .. } |
+                 Instruction::VectorLogicImmediate { .. } |
+                 Instruction::VectorCount { .. } | Instruction::Ldi { .. } |
+                 Instruction::Shf { .. } | Instruction::Sld { .. } |
+                 Instruction::Splat { .. } | Instruction::Sldi { .. } |
+                 Instruction::Splati { .. } | Instruction::Copy { .. } |
+                 Instruction::Insert { .. } | Instruction::Insve { .. } |
+                 Instruction::Fill { .. } | Instruction::Ctcmsa { .. } |
+                 Instruction::Cfcmsa { .. } | Instruction::MoveV { .. } |
+                 Instruction::VectorLoad { .. } |
+                 Instruction::VectorStore { .. } |
+                 Instruction::VectorFloat { .. } |
+                 Instruction::VectorFloatUnary { .. } |
+                 Instruction::VectorFloatCompare { .. } |
+                 Instruction::VectorBranch { .. })
+    }
+
+    // Instructions that only make sense on 64-bit registers or addresses.
+    // They're reserved whenever 64-bit operations aren't enabled.
+    pub fn is_doubleword(&self) -> bool {
+        match *self {
+            Instruction::Memory { op, .. } =>
+                matches!(op, MemoryOp::Ld | MemoryOp::Lwu | MemoryOp::Sd |
+                             MemoryOp::Ldl | MemoryOp::Ldr | MemoryOp::Sdl |
+                             MemoryOp::Sdr),
+            Instruction::PcRelative { op, .. } =>
+                matches!(op, PcRelativeOp::Lwupc | PcRelativeOp::Ldpc),
+            Instruction::Linked { op, .. } =>
+                matches!(op, LinkedOp::Lld | LinkedOp::Scd),
+            Instruction::Immediate { op, .. } =>
+                matches!(op, ImmediateOp::Daddi | ImmediateOp::Daddiu |
+                             ImmediateOp::Daui | ImmediateOp::Dahi |
+                             ImmediateOp::Dati),
+            Instruction::Register { op, .. } =>
+                matches!(op, RegisterOp::Dadd | RegisterOp::Daddu |
+                             RegisterOp::Dsub | RegisterOp::Dsubu |
+                             RegisterOp::Dmul | RegisterOp::Dmuh |
+                             RegisterOp::Dmulu | RegisterOp::Dmuhu |
+                             RegisterOp::Ddiv | RegisterOp::Dmod |
+                             RegisterOp::Ddivu | RegisterOp::Dmodu),
+            Instruction::Count { op, .. } =>
+                matches!(op, CountOp::Dclo | CountOp::Dclz),
+            Instruction::Shift { op, .. } =>
+                matches!(op, ShiftOp::Dsll | ShiftOp::Dsrl | ShiftOp::Dsra |
+                             ShiftOp::Drotr | ShiftOp::Dsll32 |
+                             ShiftOp::Dsrl32 | ShiftOp::Dsra32 |
+                             ShiftOp::Drotr32),
+            Instruction::ShiftVariable { op, .. } =>
+                matches!(op, ShiftVariableOp::Dsllv | ShiftVariableOp::Dsrlv |
+                             ShiftVariableOp::Dsrav | ShiftVariableOp::Drotrv),
+            Instruction::BitField { op, .. } =>
+                matches!(op, BitFieldOp::Dext | BitFieldOp::Dins),
+            Instruction::Shuffle { op, .. } =>
+                matches!(op, ShuffleOp::Dsbh | ShuffleOp::Dshd),
+            Instruction::HiLo { op, .. } =>
+                matches!(op, HiLoOp::Dmult | HiLoOp::Dmultu |
+                             HiLoOp::Ddiv | HiLoOp::Ddivu),
+            Instruction::Cop0 { op, .. } =>
+                matches!(op, Cop0Op::Dmfc0 | Cop0Op::Dmtc0),
+            Instruction::Cop1 { op, .. } =>
+                matches!(op, Cop1Op::Dmfc1 | Cop1Op::Dmtc1),
+            // Moving doublewords between GPRs and vectors, and COPY_U.W,
+            // which zero-extends.
+            Instruction::Copy { df, signed, .. } =>
+                df == VectorFormat::D || (df == VectorFormat::W && !signed),
+            Instruction::Insert { df, .. } | Instruction::Fill { df, .. } =>
+                df == VectorFormat::D,
+            Instruction::Dalign { .. } | Instruction::Dbitswap { .. } |
+            Instruction::Dlsa { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+// Sign-extends the low `bits` bits of `value` and scales them by
+// 1 << `shift`.
+pub(crate) fn offset(value: u32, bits: u32, shift: u32) -> i64 {
+    (((value as i64) << (64 - bits)) >> (64 - bits)) << shift
+}
+
+// Where a J or JAL at `pc` goes: the target replaces the low 28 bits of the
+// delay slot's address.
+pub fn jump_target(pc: u64, target: u64) -> u64 {
+    (pc.wrapping_add(4) & !0xfffffff) | target
+}
+
+// Works out which instruction a word encodes for a given revision. The CPU
+// and the disassembler both go through here, so they always agree.
+pub fn decode(instruction: u32, revision: IsaRevision) -> Instruction {
+    decode_word(instruction, revision).unwrap_or(Instruction::Reserved)
+}
+
+fn decode_word(instruction: u32, revision: IsaRevision) -> Option<Instruction> {
+    let r6 = revision == IsaRevision::Release6;
+    let opcode = (instruction >> OPCODE) as i32;
+    let rs = ((instruction >> RS) & 0x1f) as usize;
+    let rt = ((instruction >> RT) & 0x1f) as usize;
+    let rd = ((instruction >> RD) & 0x1f) as usize;
+    let sa = (instruction >> 6) & 0x1f;
+    let function = (instruction & 0x3f) as i32;
+    let immediate = instruction as u16;
+    let offset16 = offset(instruction, 16, 2);
+
+    let decoded = match opcode {
+        0 => return decode_special(instruction, revision),
+        SPECIAL2 if !r6 => return decode_special2(instruction),
+        COP1 => return decode_cop1(instruction),
+        MSA => return decode_msa(instruction),
+        LWC1 | LDC1 | SWC1 | SDC1 => {
+            let op = match opcode {
+                LWC1 => FloatMemoryOp::Lwc1,
+                LDC1 => FloatMemoryOp::Ldc1,
+                SWC1 => FloatMemoryOp::Swc1,
+                _ => FloatMemoryOp::Sdc1,
+            };
+            Instruction::FloatMemory {
+                op,
+                ft: rt,
+                base: rs,
+                offset: immediate as i16 as i64,
+            }
+        },
+        LB | LBU | LD | LH | LHU | LW | LWU | SB | SD | SH | SW => {
+            let op = match opcode {
+                LB => MemoryOp::Lb,
+                LBU => MemoryOp::Lbu,
+                LD => MemoryOp::Ld,
+                LH => MemoryOp::Lh,
+                LHU => MemoryOp::Lhu,
+                LW => MemoryOp::Lw,
+                LWU => MemoryOp::Lwu,
+                SB => MemoryOp::Sb,
+                SD => MemoryOp::Sd,
+                SH => MemoryOp::Sh,
+                _ => MemoryOp::Sw,
+            };
+            Instruction::Memory {
+                op,
+                rt,
+                base: rs,
+                offset: immediate as i16 as i64,
+            }
+        },
+        LDL | LDR | LWL | LWR | SDL | SDR | SWL | SWR if !r6 => {
+            let op = match opcode {
+                LDL => MemoryOp::Ldl,
+                LDR => MemoryOp::Ldr,
+                LWL => MemoryOp::Lwl,
+                LWR => MemoryOp::Lwr,
+                SDL => MemoryOp::Sdl,
+                SDR => MemoryOp::Sdr,
+                SWL => MemoryOp::Swl,
+                _ => MemoryOp::Swr,
+            };
+            Instruction::Memory {
+                op,
+                rt,
+                base: rs,
+                offset: immediate as i16 as i64,
+            }
+        },
+        PCREL if r6 => return decode_pc_relative(instruction),
+        ADDIU | ANDI | DADDIU | LUI | ORI | SLTI | SLTIU | XORI => {
+            // LUI is AUI adding to $zero.
+            let op = match opcode {
+                ADDIU => ImmediateOp::Addiu,
+                ANDI => ImmediateOp::Andi,
+                DADDIU => ImmediateOp::Daddiu,
+                AUI if r6 && rs != 0 => ImmediateOp::Aui,
+                LUI => ImmediateOp::Lui,
+                ORI => ImmediateOp::Ori,
+                SLTI => ImmediateOp::Slti,
+                SLTIU => ImmediateOp::Sltiu,
+                _ => ImmediateOp::Xori,
+            };
+            Instruction::Immediate { op, rt, rs, immediate }
+        },
+        LL_R2 | LLD_R2 | SC_R2 | SCD_R2 if !r6 => {
+            let op = match opcode {
+                LL_R2 => LinkedOp::Ll,
+                LLD_R2 => LinkedOp::Lld,
+                SC_R2 => LinkedOp::Sc,
+                _ => LinkedOp::Scd,
+            };
+            Instruction::Linked {
+                op,
+                rt,
+                base: rs,
+                offset: immediate as i16 as i64,
+            }
+        },
+        DAUI if r6 && rs != 0 => Instruction::Immediate {
+            op: ImmediateOp::Daui,
+            rt,
+            rs,
+            immediate,
+        },
+        PREF_R2 if !r6 => Instruction::Pref {
+            hint: rt as u32,
+            base: rs,
+            offset: immediate as i16 as i64,
+        },
+        ADDI | DADDI if !r6 => {
+            let op = if opcode == ADDI {
+                ImmediateOp::Addi
+            } else {
+                ImmediateOp::Daddi
+            };
+            Instruction::Immediate { op, rt, rs, immediate }
+        },
+        SPECIAL3 => {
+            if function == RDHWR {
+                Instruction::Rdhwr { rt, rd }
+            } else if (EXT..=DINS).contains(&function) {
+                return decode_bit_field(function, rt, rs, rd as u32, sa);
+            } else if rs == 0 && matches!((function, sa as i32),
+                    (BSHFL, WSBH | SEB | SEH) | (DBSHFL, DSBH | DSHD)) {
+                let op = match (function, sa as i32) {
+                    (BSHFL, WSBH) => ShuffleOp::Wsbh,
+                    (BSHFL, SEB) => ShuffleOp::Seb,
+                    (BSHFL, _) => ShuffleOp::Seh,
+                    (_, DSBH) => ShuffleOp::Dsbh,
+                    _ => ShuffleOp::Dshd,
+                };
+                Instruction::Shuffle { op, rd, rt }
+            } else if !r6 {
+                return decode_dsp(instruction);
+            } else if (function == LL || function == SC) && sa == PAIRED {
+                if function == LL {
+                    Instruction::Llwp { rt, rd, base: rs }
+                } else {
+                    Instruction::Scwp { rt, rd, base: rs }
+                }
+            } else if matches!(function, LL | LLD | SC | SCD) &&
+                    instruction & 0x40 == 0 {
+                let op = match function {
+                    LL => LinkedOp::Ll,
+                    LLD => LinkedOp::Lld,
+                    SC => LinkedOp::Sc,
+                    _ => LinkedOp::Scd,
+                };
+                Instruction::Linked {
+                    op,
+                    rt,
+                    base: rs,
+                    offset: offset(instruction >> 7, 9, 0),
+                }
+            } else if function == PREF && instruction & 0x40 == 0 {
+                Instruction::Pref {
+                    hint: rt as u32,
+                    base: rs,
+                    offset: offset(instruction >> 7, 9, 0),
+                }
+            } else if function == BSHFL && sa as i32 == BITSWAP {
+                Instruction::Bitswap { rd, rt }
+            } else if function == BSHFL && (sa >> 2) as i32 == ALIGN {
+                Instruction::Align { rd, rs, rt, bp: sa & 0x3 }
+            } else if function == DBSHFL && sa as i32 == DBITSWAP {
+                Instruction::Dbitswap { rd, rt }
+            } else if function == DBSHFL && (sa >> 3) as i32 == DALIGN {
+                Instruction::Dalign { rd, rs, rt, bp: sa & 0x7 }
+            } else {
+                return None;
+            }
+        },
+        BC if r6 => Instruction::Bc { offset: offset(instruction, 26, 2) },
+        BALC if r6 => Instruction::Balc { offset: offset(instruction, 26, 2) },
+        // With rt zero these are still the delayed BLEZ and BGTZ.
+        BLEZ | BGTZ if rt == 0 => {
+            let condition = if opcode == BLEZ {
+                Condition::Lez
+            } else {
+                Condition::Gtz
+            };
+            Instruction::Branch {
+                condition,
+                link: false,
+                likely: false,
+                rs,
+                rt: 0,
+                offset: offset16,
+            }
+        },
+        POP06 | POP07 | POP26 | POP27 if r6 =>
+            return decode_compact_branch(opcode, rs, rt, offset16),
+        BEQL | BNEL | BLEZL | BGTZL if !r6 => {
+            let condition = match opcode {
+                BEQL => Condition::Eq,
+                BNEL => Condition::Ne,
+                BLEZL if rt == 0 => Condition::Lez,
+                BGTZL if rt == 0 => Condition::Gtz,
+                _ => return None,
+            };
+            Instruction::Branch {
+                condition,
+                link: false,
+                likely: true,
+                rs,
+                rt,
+                offset: offset16,
+            }
+        },
+        POP10 | POP30 if r6 =>
+            return decode_compact_branch(opcode, rs, rt, offset16),
+        POP66 | POP76 if r6 => {
+            let offset16 = immediate as i16 as i64;
+            if opcode == POP66 && rs as i32 == JIC {
+                Instruction::Jic { rt, offset: offset16 }
+            } else if opcode == POP76 && rs as i32 == JIALC {
+                Instruction::Jialc { rt, offset: offset16 }
+            } else {
+                let condition = if opcode == POP66 {
+                    Condition::Eqz
+                } else {
+                    Condition::Nez
+                };
+                Instruction::CompactBranch {
+                    condition,
+                    link: false,
+                    rs,
+                    rt: 0,
+                    offset: offset(instruction, 21, 2),
+                }
+            }
+        },
+        J => Instruction::J { target: ((instruction & 0x3ffffff) << 2) as u64 },
+        JAL => Instruction::Jal {
+            target: ((instruction & 0x3ffffff) << 2) as u64,
+        },
+        JALX if !r6 => Instruction::Jalx {
+            target: ((instruction & 0x3ffffff) << 2) as u64,
+        },
+        BEQ | BNE => {
+            let condition = if opcode == BEQ {
+                Condition::Eq
+            } else {
+                Condition::Ne
+            };
+            Instruction::Branch {
+                condition,
+                link: false,
+                likely: false,
+                rs,
+                rt,
+                offset: offset16,
+            }
+        },
+        REGIMM => {
+            // Release 6 only keeps the linking forms as NAL and BAL, which
+            // test $zero.
+            let (condition, link, likely) = match rt as i32 {
+                BLTZ => (Condition::Ltz, false, false),
+                BGEZ => (Condition::Gez, false, false),
+                BLTZAL | BGEZAL if r6 && rs != 0 => return None,
+                BLTZAL => (Condition::Ltz, true, false),
+                BGEZAL => (Condition::Gez, true, false),
+                BLTZL if !r6 => (Condition::Ltz, false, true),
+                BGEZL if !r6 => (Condition::Gez, false, true),
+                BLTZALL if !r6 => (Condition::Ltz, true, true),
+                BGEZALL if !r6 => (Condition::Gez, true, true),
+                SYNCI => {
+                    return Some(Instruction::Synci {
+                        base: rs,
+                        offset: immediate as i16 as i64,
+                    });
+                },
+                DAHI | DATI if r6 => {
+                    let op = if rt as i32 == DAHI {
+                        ImmediateOp::Dahi
+                    } else {
+                        ImmediateOp::Dati
+                    };
+                    return Some(Instruction::Immediate {
+                        op,
+                        rt: rs,
+                        rs,
+                        immediate,
+                    });
+                },
+                SIGRIE if r6 && rs == 0 => {
+                    return Some(Instruction::Sigrie { code: immediate as u32 });
+                },
+                TGEI | TGEIU | TLTI | TLTIU | TEQI | TNEI if !r6 => {
+                    let condition = match rt as i32 {
+                        TGEI => Condition::Ge,
+                        TGEIU => Condition::Geu,
+                        TLTI => Condition::Lt,
+                        TLTIU => Condition::Ltu,
+                        TEQI => Condition::Eq,
+                        _ => Condition::Ne,
+                    };
+                    return Some(Instruction::TrapImmediate {
+                        condition,
+                        rs,
+                        immediate: immediate as i16 as i64,
+                    });
+                },
+                BPOSGE32 if !r6 && rs == 0 => {
+                    return Some(Instruction::Bposge32 { offset: offset16 });
+                },
+                _ => return None,
+            };
+            Instruction::Branch {
+                condition,
+                link,
+                likely,
+                rs,
+                rt: 0,
+                offset: offset16,
+            }
+        },
+        COP0 => match rs as i32 {
+            MFC0 | DMFC0 | MTC0 | DMTC0 => {
+                let op = match rs as i32 {
+                    MFC0 => Cop0Op::Mfc0,
+                    DMFC0 => Cop0Op::Dmfc0,
+                    MTC0 => Cop0Op::Mtc0,
+                    _ => Cop0Op::Dmtc0,
+                };
+                Instruction::Cop0 {
+                    op,
+                    rt,
+                    rd,
+                    sel: (instruction & 0x7) as usize,
+                }
+            },
+            MFMC0 => {
+                if (instruction >> 5) & 0x1 != 0 {
+                    Instruction::Ei { rt }
+                } else {
+                    Instruction::Di { rt }
+                }
+            },
+            C0..=0x1f => match function {
+                TLBP => Instruction::Tlb { op: TlbOp::Tlbp },
+                TLBR => Instruction::Tlb { op: TlbOp::Tlbr },
+                TLBWI => Instruction::Tlb { op: TlbOp::Tlbwi },
+                TLBWR => Instruction::Tlb { op: TlbOp::Tlbwr },
+                TLBINV => Instruction::Tlb { op: TlbOp::Tlbinv },
+                TLBINVF => Instruction::Tlb { op: TlbOp::Tlbinvf },
+                ERET => Instruction::Eret,
+                DERET => Instruction::Deret,
+                WAIT => Instruction::Wait,
+                _ => return None,
+            },
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// The PCREL opcode. rs is the destination, and the top bits of the rt
+// field pick the instruction.
+pub(crate) fn decode_pc_relative(instruction: u32) -> Option<Instruction> {
+    let rs = ((instruction >> RS) & 0x1f) as usize;
+    let rt = ((instruction >> RT) & 0x1f) as usize;
+    let (op, offset) = match rt as i32 {
+        AUIPC => (PcRelativeOp::Auipc, offset(instruction, 16, 16)),
+        ALUIPC => (PcRelativeOp::Aluipc, offset(instruction, 16, 16)),
+        _ if (rt >> 3) as i32 == ADDIUPC =>
+            (PcRelativeOp::Addiupc, offset(instruction, 19, 2)),
+        _ if (rt >> 3) as i32 == LWPC =>
+            (PcRelativeOp::Lwpc, offset(instruction, 19, 2)),
+        _ if (rt >> 3) as i32 == LWUPC =>
+            (PcRelativeOp::Lwupc, offset(instruction, 19, 2)),
+        _ if (rt >> 2) as i32 == LDPC =>
+            (PcRelativeOp::Ldpc, offset(instruction, 18, 3)),
+        _ => return None,
+    };
+    Some(Instruction::PcRelative { op, rs, offset })
+}
+
+// The Release 6 compact branches that share an opcode. Which comparison
+// one is depends on which of rs and rt are $zero and whether they're the
+// same register, or for POP10 and POP30, which is the larger.
+pub(crate) fn decode_compact_branch(opcode: i32,
+                                    rs: usize,
+                                    rt: usize,
+                                    offset: i64) -> Option<Instruction> {
+    let (condition, link, rs, rt) = match opcode {
+        POP06 if rs != 0 && rt != 0 && rs != rt =>
+            (Condition::Geu, false, rs, rt),
+        POP06 if rs != 0 && rs == rt => (Condition::Gez, true, rt, 0),
+        POP06 if rs == 0 && rt != 0 => (Condition::Lez, true, rt, 0),
+        POP07 if rs != 0 && rt != 0 && rs != rt =>
+            (Condition::Ltu, false, rs, rt),
+        POP07 if rs != 0 && rs == rt => (Condition::Ltz, true, rt, 0),
+        POP07 if rs == 0 && rt != 0 => (Condition::Gtz, true, rt, 0),
+        POP26 if rs != 0 && rt != 0 && rs != rt =>
+            (Condition::Ge, false, rs, rt),
+        POP26 if rs != 0 && rs == rt => (Condition::Gez, false, rt, 0),
+        POP26 if rs == 0 && rt != 0 => (Condition::Lez, false, rt, 0),
+        POP27 if rs != 0 && rt != 0 && rs != rt =>
+            (Condition::Lt, false, rs, rt),
+        POP27 if rs != 0 && rs == rt => (Condition::Ltz, false, rt, 0),
+        POP27 if rs == 0 && rt != 0 => (Condition::Gtz, false, rt, 0),
+        POP10 | POP30 if rs != 0 && rt != 0 && rs < rt => {
+            let condition = if opcode == POP10 {
+                Condition::Eq
+            } else {
+                Condition::Ne
+            };
+            (condition, false, rs, rt)
+        },
+        POP10 | POP30 if rs == 0 && rt != 0 => {
+            let condition = if opcode == POP10 {
+                Condition::Eqz
+            } else {
+                Condition::Nez
+            };
+            (condition, true, rt, 0)
+        },
+        POP10 => (Condition::Ov, false, rs, rt),
+        POP30 => (Condition::Nv, false, rs, rt),
+        _ => return None,
+    };
+    Some(Instruction::CompactBranch { condition, link, rs, rt, offset })
+}
+
+// EXT, INS and their doubleword forms give the field as its first bit and
+// either its size or its last bit, with 32 added to one or both in the
+// forms for fields that lie across or above bit 32. A field that runs off
+// the end of the register isn't an instruction.
+pub(crate) fn decode_bit_field(function: i32,
+                               rt: usize,
+                               rs: usize,
+                               msb: u32,
+                               lsb: u32) -> Option<Instruction> {
+    let (op, pos, size) = match function {
+        EXT => (BitFieldOp::Ext, lsb, msb + 1),
+        DEXTM => (BitFieldOp::Dext, lsb, msb + 33),
+        DEXTU => (BitFieldOp::Dext, lsb + 32, msb + 1),
+        DEXT => (BitFieldOp::Dext, lsb, msb + 1),
+        INS => (BitFieldOp::Ins, lsb, (msb + 1).checked_sub(lsb)?),
+        DINSM => (BitFieldOp::Dins, lsb, (msb + 33).checked_sub(lsb)?),
+        DINSU => (BitFieldOp::Dins, lsb + 32, (msb + 1).checked_sub(lsb)?),
+        _ => (BitFieldOp::Dins, lsb, (msb + 1).checked_sub(lsb)?),
+    };
+    let width = match op {
+        BitFieldOp::Ext | BitFieldOp::Ins => 32,
+        BitFieldOp::Dext | BitFieldOp::Dins => 64,
+    };
+    if size == 0 || pos + size > width {
+        return None;
+    }
+    Some(Instruction::BitField { op, rt, rs, pos, size })
+}
+
+// The SPECIAL opcode, where the function field picks the instruction.
+fn decode_special(instruction: u32, revision: IsaRevision)
+    -> Option<Instruction> {
+    let r6 = revision == IsaRevision::Release6;
+    let rs = ((instruction >> RS) & 0x1f) as usize;
+    let rt = ((instruction >> RT) & 0x1f) as usize;
+    let rd = ((instruction >> RD) & 0x1f) as usize;
+    let sa = (instruction >> 6) & 0x1f;
+    let function = (instruction & 0x3f) as i32;
+
+    if function == BREAK {
+        return Some(Instruction::Break { code: (instruction >> 6) & 0xfffff });
+    }
+    if function == SDBBP && r6 {
+        return Some(Instruction::Sdbbp { code: (instruction >> 6) & 0xfffff });
+    }
+    if function == SYNC && instruction >> 11 == 0 {
+        return Some(Instruction::Sync { stype: sa });
+    }
+    if function == SYSCALL {
+        return Some(Instruction::Syscall {
+            code: (instruction >> 6) & 0xfffff,
+        });
+    }
+    if r6 && rt == 0 {
+        let op = match (instruction & 0x7ff) as i32 {
+            CLO => Some(CountOp::Clo),
+            CLZ => Some(CountOp::Clz),
+            DCLO => Some(CountOp::Dclo),
+            DCLZ => Some(CountOp::Dclz),
+            _ => None,
+        };
+        if let Some(op) = op {
+            return Some(Instruction::Count { op, rd, rs });
+        }
+    }
+
+    let register = |op| Some(Instruction::Register { op, rd, rs, rt });
+    let trap = |condition| {
+        Some(Instruction::Trap {
+            condition,
+            rs,
+            rt,
+            code: (instruction >> 6) & 0x3ff,
+        })
+    };
+    // Shifts by a constant leave rs zero, apart from the bit that makes
+    // them rotates. Shifts by a register do the same with sa.
+    let shift = |op, required_rs: usize| {
+        if rs == required_rs {
+            Some(Instruction::Shift { op, rd, rt, sa })
+        } else {
+            None
+        }
+    };
+    let shift_variable = |op, required_sa: u32| {
+        if sa == required_sa {
+            Some(Instruction::ShiftVariable { op, rd, rt, rs })
+        } else {
+            None
+        }
+    };
+    // The same-width multiplies and divides use sa to pick between the low
+    // and high halves, or the quotient and remainder.
+    // Before Release 6 they write HI and LO instead, and sa is zero. MULT
+    // and MULTU can pick a DSP accumulator with the low bits of rd.
+    let sop = |low, high, hi_lo| {
+        let ac = if matches!(hi_lo, HiLoOp::Mult | HiLoOp::Multu) {
+            rd & 0x3
+        } else {
+            0
+        };
+        if !r6 && sa == 0 && rd == ac {
+            Some(Instruction::HiLo { op: hi_lo, ac, rs, rt })
+        } else if !r6 {
+            None
+        } else if sa as i32 == MUL {
+            register(low)
+        } else if sa as i32 == MUH {
+            register(high)
+        } else {
+            None
+        }
+    };
+    // MFHI and MFLO only use rd, and MTHI and MTLO only rs, along with the
+    // low bits of the other one to pick a DSP accumulator. Every other
+    // field is zero.
+    let hi_lo = |used: i32, accumulator: i32, register| {
+        let others = instruction & 0x3ffffff & !(0x1f << used) &
+            !(0x3 << accumulator);
+        if !r6 && others as i32 == function {
+            Some(register)
+        } else {
+            None
+        }
+    };
+
+    match function {
+        ADD => register(RegisterOp::Add),
+        ADDU => register(RegisterOp::Addu),
+        AND => register(RegisterOp::And),
+        DADD => register(RegisterOp::Dadd),
+        DADDU => register(RegisterOp::Daddu),
+        DSUB => register(RegisterOp::Dsub),
+        DSUBU => register(RegisterOp::Dsubu),
+        NOR => register(RegisterOp::Nor),
+        OR => register(RegisterOp::Or),
+        SLT => register(RegisterOp::Slt),
+        SLTU => register(RegisterOp::Sltu),
+        SUB => register(RegisterOp::Sub),
+        SUBU => register(RegisterOp::Subu),
+        XOR => register(RegisterOp::Xor),
+        SLL => shift(ShiftOp::Sll, 0),
+        SRL => shift(ShiftOp::Srl, 0).or_else(|| shift(ShiftOp::Rotr, 1)),
+        SRA => shift(ShiftOp::Sra, 0),
+        DSLL => shift(ShiftOp::Dsll, 0),
+        DSRL => shift(ShiftOp::Dsrl, 0).or_else(|| shift(ShiftOp::Drotr, 1)),
+        DSRA => shift(ShiftOp::Dsra, 0),
+        DSLL32 => shift(ShiftOp::Dsll32, 0),
+        DSRL32 => shift(ShiftOp::Dsrl32, 0)
+            .or_else(|| shift(ShiftOp::Drotr32, 1)),
+        DSRA32 => shift(ShiftOp::Dsra32, 0),
+        SLLV => shift_variable(ShiftVariableOp::Sllv, 0),
+        SRLV => shift_variable(ShiftVariableOp::Srlv, 0)
+            .or_else(|| shift_variable(ShiftVariableOp::Rotrv, 1)),
+        SRAV => shift_variable(ShiftVariableOp::Srav, 0),
+        DSLLV => shift_variable(ShiftVariableOp::Dsllv, 0),
+        DSRLV => shift_variable(ShiftVariableOp::Dsrlv, 0)
+            .or_else(|| shift_variable(ShiftVariableOp::Drotrv, 1)),
+        DSRAV => shift_variable(ShiftVariableOp::Dsrav, 0),
+        SOP30 => sop(RegisterOp::Mul, RegisterOp::Muh, HiLoOp::Mult),
+        SOP31 => sop(RegisterOp::Mulu, RegisterOp::Muhu, HiLoOp::Multu),
+        SOP32 => sop(RegisterOp::Div, RegisterOp::Mod, HiLoOp::Div),
+        SOP33 => sop(RegisterOp::Divu, RegisterOp::Modu, HiLoOp::Divu),
+        SOP34 => sop(RegisterOp::Dmul, RegisterOp::Dmuh, HiLoOp::Dmult),
+        SOP35 => sop(RegisterOp::Dmulu, RegisterOp::Dmuhu, HiLoOp::Dmultu),
+        SOP36 => sop(RegisterOp::Ddiv, RegisterOp::Dmod, HiLoOp::Ddiv),
+        SOP37 => sop(RegisterOp::Ddivu, RegisterOp::Dmodu, HiLoOp::Ddivu),
+        MFHI => hi_lo(RD, RS, Instruction::Mfhi { rd, ac: rs & 0x3 }),
+        MFLO => hi_lo(RD, RS, Instruction::Mflo { rd, ac: rs & 0x3 }),
+        MTHI => hi_lo(RS, RD, Instruction::Mthi { rs, ac: rd & 0x3 }),
+        MTLO => hi_lo(RS, RD, Instruction::Mtlo { rs, ac: rd & 0x3 }),
+        MOVZ if !r6 && sa == 0 => Some(Instruction::Movz { rd, rs, rt }),
+        MOVN if !r6 && sa == 0 => Some(Instruction::Movn { rd, rs, rt }),
+        // Release 6 writes JR as JALR with rd zero.
+        JR if !r6 => Some(Instruction::Jalr { rd: 0, rs }),
+        JALR => Some(Instruction::Jalr { rd, rs }),
+        // The shift amount is one more than the sa field.
+        LSA | DLSA if r6 && sa >> 2 == 0 => {
+            let sa = sa + 1;
+            if function == LSA {
+                Some(Instruction::Lsa { rd, rs, rt, sa })
+            } else {
+                Some(Instruction::Dlsa { rd, rs, rt, sa })
+            }
+        },
+        SELEQZ if r6 && sa == 0 => register(RegisterOp::Seleqz),
+        SELNEZ if r6 && sa == 0 => register(RegisterOp::Selnez),
+        TGE => trap(Condition::Ge),
+        TGEU => trap(Condition::Geu),
+        TLT => trap(Condition::Lt),
+        TLTU => trap(Condition::Ltu),
+        TEQ => trap(Condition::Eq),
+        TNE => trap(Condition::Ne),
+        _ => None,
+    }
+}
+
+// The SPECIAL2 opcode, which Release 6 removed. It held the multiply-adds,
+// a MUL that only writes rd, the leading zero and one counts, and SDBBP.
+fn decode_special2(instruction: u32) -> Option<Instruction> {
+    let rs = ((instruction >> RS) & 0x1f) as usize;
+    let rt = ((instruction >> RT) & 0x1f) as usize;
+    let rd = ((instruction >> RD) & 0x1f) as usize;
+    let sa = (instruction >> 6) & 0x1f;
+    let function = (instruction & 0x3f) as i32;
+
+    if function == SDBBP_R2 {
+        return Some(Instruction::Sdbbp { code: (instruction >> 6) & 0xfffff });
+    }
+    if sa != 0 {
+        return None;
+    }
+    // The low bits of rd pick a DSP accumulator.
+    let hi_lo = |op| {
+        if rd < 4 {
+            Some(Instruction::HiLo { op, ac: rd, rs, rt })
+        } else {
+            None
+        }
+    };
+    match function {
+        MADD => hi_lo(HiLoOp::Madd),
+        MADDU => hi_lo(HiLoOp::Maddu),
+        MSUB => hi_lo(HiLoOp::Msub),
+        MSUBU => hi_lo(HiLoOp::Msubu),
+        SPECIAL2_MUL =>
+            Some(Instruction::Register { op: RegisterOp::Mul, rd, rs, rt }),
+        SPECIAL2_CLZ => Some(Instruction::Count { op: CountOp::Clz, rd, rs }),
+        SPECIAL2_CLO => Some(Instruction::Count { op: CountOp::Clo, rd, rs }),
+        SPECIAL2_DCLZ => Some(Instruction::Count { op: CountOp::Dclz, rd, rs }),
+        SPECIAL2_DCLO => Some(Instruction::Count { op: CountOp::Dclo, rd, rs }),
+        _ => None,
+    }
+}
+
+// The DSP ASE's groups in SPECIAL3. Accumulators are picked by the low
+// bits of rd, and shift amounts and the like go in rs or rd.
+fn decode_dsp(instruction: u32) -> Option<Instruction> {
+    let rs = ((instruction >> RS) & 0x1f) as usize;
+    let rt = ((instruction >> RT) & 0x1f) as usize;
+    let rd = ((instruction >> RD) & 0x1f) as usize;
+    let op = (instruction >> 6) & 0x1f;
+    let function = (instruction & 0x3f) as i32;
+    let ac = rd & 0x3;
+
+    let dsp = |op| Some(Instruction::Dsp { op, rd, rs, rt });
+    let unary = |op| if rs == 0 {
+        Some(Instruction::DspUnary { op, rd, rt })
+    } else {
+        None
+    };
+    let compare = |op, condition| {
+        let rd = if op == DspCompareOp::Cmpu || op == DspCompareOp::Cmp {
+            0
+        } else {
+            rd
+        };
+        Some(Instruction::DspCompare { op, condition, rd, rs, rt })
+    };
+    // The shift amount is only as wide as it needs to be for the lanes.
+    let shift = |op, variable: bool, bits: usize| if variable {
+        Some(Instruction::DspShiftVariable { op, rd, rt, rs })
+    } else if rs < bits {
+        Some(Instruction::DspShift { op, rd, rt, sa: rs as u32 })
+    } else {
+        None
+    };
+    let accumulate = |op| if rd == ac {
+        Some(Instruction::DspAccumulate { op, ac, rs, rt })
+    } else {
+        None
+    };
+    let extract = |op, variable: bool| if rd != ac {
+        None
+    } else if variable {
+        Some(Instruction::ExtractVariable { op, rt, ac, rs })
+    } else {
+        Some(Instruction::Extract { op, rt, ac, shift: rs as u32 })
+    };
+    let load = |op| Some(Instruction::IndexedLoad {
+        op,
+        rd,
+        base: rs,
+        index: rt,
+    });
+
+    match function {
+        ADDU_QB_DSP => match op {
+            0x00 => dsp(DspOp::AdduQb),
+            0x01 => dsp(DspOp::SubuQb),
+            0x04 => dsp(DspOp::AdduSQb),
+            0x05 => dsp(DspOp::SubuSQb),
+            0x06 => dsp(DspOp::MuleuSPhQbl),
+            0x07 => dsp(DspOp::MuleuSPhQbr),
+            0x08 => dsp(DspOp::AdduPh),
+            0x09 => dsp(DspOp::SubuPh),
+            0x0a => dsp(DspOp::AddqPh),
+            0x0b => dsp(DspOp::SubqPh),
+            0x0c => dsp(DspOp::AdduSPh),
+            0x0d => dsp(DspOp::SubuSPh),
+            0x0e => dsp(DspOp::AddqSPh),
+            0x0f => dsp(DspOp::SubqSPh),
+            0x10 => dsp(DspOp::Addsc),
+            0x11 => dsp(DspOp::Addwc),
+            0x12 => dsp(DspOp::Modsub),
+            0x14 if rt == 0 => Some(Instruction::DspUnary {
+                op: DspUnaryOp::RadduWQb,
+                rd,
+                rt: rs,
+            }),
+            0x16 => dsp(DspOp::AddqSW),
+            0x17 => dsp(DspOp::SubqSW),
+            0x1c => dsp(DspOp::MuleqSWPhl),
+            0x1d => dsp(DspOp::MuleqSWPhr),
+            0x1e => dsp(DspOp::MulqSPh),
+            0x1f => dsp(DspOp::MulqRsPh),
+            _ => None,
+        },
+        ADDUH_QB_DSP => match op {
+            0x00 => dsp(DspOp::AdduhQb),
+            0x01 => dsp(DspOp::SubuhQb),
+            0x02 => dsp(DspOp::AdduhRQb),
+            0x03 => dsp(DspOp::SubuhRQb),
+            0x08 => dsp(DspOp::AddqhPh),
+            0x09 => dsp(DspOp::SubqhPh),
+            0x0a => dsp(DspOp::AddqhRPh),
+            0x0b => dsp(DspOp::SubqhRPh),
+            0x0c => dsp(DspOp::MulPh),
+            0x0e => dsp(DspOp::MulSPh),
+            0x10 => dsp(DspOp::AddqhW),
+            0x11 => dsp(DspOp::SubqhW),
+            0x12 => dsp(DspOp::AddqhRW),
+            0x13 => dsp(DspOp::SubqhRW),
+            0x16 => dsp(DspOp::MulqSW),
+            0x17 => dsp(DspOp::MulqRsW),
+            _ => None,
+        },
+        ABSQ_S_PH_DSP => match op {
+            0x01 => unary(DspUnaryOp::AbsqSQb),
+            0x02 if instruction >> 24 & 0x3 == 0 => Some(Instruction::Repl {
+                op: DspUnaryOp::ReplQb,
+                rd,
+                immediate: ((instruction >> 16) & 0xff) as i64,
+            }),
+            0x03 => unary(DspUnaryOp::ReplQb),
+            0x04 => unary(DspUnaryOp::PrecequPhQbl),
+            0x05 => unary(DspUnaryOp::PrecequPhQbr),
+            0x06 => unary(DspUnaryOp::PrecequPhQbla),
+            0x07 => unary(DspUnaryOp::PrecequPhQbra),
+            0x09 => unary(DspUnaryOp::AbsqSPh),
+            0x0a => Some(Instruction::Repl {
+                op: DspUnaryOp::ReplPh,
+                rd,
+                immediate: offset(instruction >> 16, 10, 0),
+            }),
+            0x0b => unary(DspUnaryOp::ReplPh),
+            0x0c => unary(DspUnaryOp::PreceqWPhl),
+            0x0d => unary(DspUnaryOp::PreceqWPhr),
+            0x11 => unary(DspUnaryOp::AbsqSW),
+            0x1b => unary(DspUnaryOp::Bitrev),
+            0x1c => unary(DspUnaryOp::PreceuPhQbl),
+            0x1d => unary(DspUnaryOp::PreceuPhQbr),
+            0x1e => unary(DspUnaryOp::PreceuPhQbla),
+            0x1f => unary(DspUnaryOp::PreceuPhQbra),
+            _ => None,
+        },
+        CMPU_EQ_QB_DSP => match op {
+            0x00 => compare(DspCompareOp::Cmpu, DspCondition::Eq),
+            0x01 => compare(DspCompareOp::Cmpu, DspCondition::Lt),
+            0x02 => compare(DspCompareOp::Cmpu, DspCondition::Le),
+            0x03 => dsp(DspOp::PickQb),
+            0x04 => compare(DspCompareOp::Cmpgu, DspCondition::Eq),
+            0x05 => compare(DspCompareOp::Cmpgu, DspCondition::Lt),
+            0x06 => compare(DspCompareOp::Cmpgu, DspCondition::Le),
+            0x08 => compare(DspCompareOp::Cmp, DspCondition::Eq),
+            0x09 => compare(DspCompareOp::Cmp, DspCondition::Lt),
+            0x0a => compare(DspCompareOp::Cmp, DspCondition::Le),
+            0x0b => dsp(DspOp::PickPh),
+            0x0c => dsp(DspOp::PrecrqQbPh),
+            0x0d => dsp(DspOp::PrecrQbPh),
+            0x0e => dsp(DspOp::PackrlPh),
+            0x0f => dsp(DspOp::PrecrquSQbPh),
+            0x14 => dsp(DspOp::PrecrqPhW),
+            0x15 => dsp(DspOp::PrecrqRsPhW),
+            0x18 => compare(DspCompareOp::Cmpgdu, DspCondition::Eq),
+            0x19 => compare(DspCompareOp::Cmpgdu, DspCondition::Lt),
+            0x1a => compare(DspCompareOp::Cmpgdu, DspCondition::Le),
+            0x1e | 0x1f => Some(Instruction::PrecrSra {
+                round: op == 0x1f,
+                rt,
+                rs,
+                sa: rd as u32,
+            }),
+            _ => None,
+        },
+        // Bit 1 picks the variable form.
+        SHLL_QB_DSP => {
+            let variable = op & 0x2 != 0;
+            match op & !0x2 {
+                0x00 => shift(DspShiftOp::ShllQb, variable, 8),
+                0x01 => shift(DspShiftOp::ShrlQb, variable, 8),
+                0x04 => shift(DspShiftOp::ShraQb, variable, 8),
+                0x05 => shift(DspShiftOp::ShraRQb, variable, 8),
+                0x08 => shift(DspShiftOp::ShllPh, variable, 16),
+                0x09 => shift(DspShiftOp::ShraPh, variable, 16),
+                0x0c => shift(DspShiftOp::ShllSPh, variable, 16),
+                0x0d => shift(DspShiftOp::ShraRPh, variable, 16),
+                0x14 => shift(DspShiftOp::ShllSW, variable, 32),
+                0x15 => shift(DspShiftOp::ShraRW, variable, 32),
+                0x19 => shift(DspShiftOp::ShrlPh, variable, 16),
+                _ => None,
+            }
+        },
+        DPA_W_PH_DSP => match op {
+            0x00 => accumulate(AccumulateOp::DpaWPh),
+            0x01 => accumulate(AccumulateOp::DpsWPh),
+            0x02 => accumulate(AccumulateOp::MulsaWPh),
+            0x03 => accumulate(AccumulateOp::DpauHQbl),
+            0x04 => accumulate(AccumulateOp::DpaqSWPh),
+            0x05 => accumulate(AccumulateOp::DpsqSWPh),
+            0x06 => accumulate(AccumulateOp::MulsaqSWPh),
+            0x07 => accumulate(AccumulateOp::DpauHQbr),
+            0x08 => accumulate(AccumulateOp::DpaxWPh),
+            0x09 => accumulate(AccumulateOp::DpsxWPh),
+            0x0b => accumulate(AccumulateOp::DpsuHQbl),
+            0x0c => accumulate(AccumulateOp::DpaqSaLW),
+            0x0d => accumulate(AccumulateOp::DpsqSaLW),
+            0x0f => accumulate(AccumulateOp::DpsuHQbr),
+            0x10 => accumulate(AccumulateOp::MaqSaWPhl),
+            0x12 => accumulate(AccumulateOp::MaqSaWPhr),
+            0x14 => accumulate(AccumulateOp::MaqSWPhl),
+            0x16 => accumulate(AccumulateOp::MaqSWPhr),
+            0x18 => accumulate(AccumulateOp::DpaqxSWPh),
+            0x19 => accumulate(AccumulateOp::DpsqxSWPh),
+            0x1a => accumulate(AccumulateOp::DpaqxSaWPh),
+            0x1b => accumulate(AccumulateOp::DpsqxSaWPh),
+            _ => None,
+        },
+        // Bit 0 picks the variable form of the extracts and SHILO.
+        EXTR_W_DSP => {
+            let variable = op & 0x1 != 0;
+            match op {
+                0x00 | 0x01 => extract(ExtractOp::ExtrW, variable),
+                0x02 | 0x03 => extract(ExtractOp::Extp, variable),
+                0x04 | 0x05 => extract(ExtractOp::ExtrRW, variable),
+                0x06 | 0x07 => extract(ExtractOp::ExtrRsW, variable),
+                0x0a | 0x0b => extract(ExtractOp::Extpdp, variable),
+                0x0e | 0x0f => extract(ExtractOp::ExtrSH, variable),
+                0x12 => Some(Instruction::Rddsp {
+                    rd,
+                    mask: (instruction >> 16) & 0x3ff,
+                }),
+                0x13 => Some(Instruction::Wrdsp {
+                    rs,
+                    mask: (instruction >> 11) & 0x3ff,
+                }),
+                0x1a if rd == ac && rt & 0xf == 0 => Some(Instruction::Shilo {
+                    ac,
+                    shift: offset(instruction >> 20, 6, 0),
+                }),
+                0x1b if rd == ac && rt == 0 =>
+                    Some(Instruction::Shilov { ac, rs }),
+                0x1f if rd == ac && rt == 0 =>
+                    Some(Instruction::Mthlip { rs, ac }),
+                _ => None,
+            }
+        },
+        APPEND_DSP => match op {
+            0x00 => Some(Instruction::Append {
+                op: AppendOp::Append,
+                rt,
+                rs,
+                sa: rd as u32,
+            }),
+            0x01 => Some(Instruction::Append {
+                op: AppendOp::Prepend,
+                rt,
+                rs,
+                sa: rd as u32,
+            }),
+            0x10 if rd == ac => Some(Instruction::Append {
+                op: AppendOp::Balign,
+                rt,
+                rs,
+                sa: ac as u32,
+            }),
+            _ => None,
+        },
+        INSV_DSP if op == 0 && rd == 0 => Some(Instruction::Insv { rt, rs }),
+        LX_DSP => match op {
+            0x00 => load(MemoryOp::Lw),
+            0x04 => load(MemoryOp::Lh),
+            0x06 => load(MemoryOp::Lbu),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The COP1 opcode, where the fmt field picks a move, a branch, or the
+// format of an arithmetic instruction picked by the function field.
+fn decode_cop1(instruction: u32) -> Option<Instruction> {
+    let fmt = ((instruction >> RS) & 0x1f) as i32;
+    let ft = ((instruction >> RT) & 0x1f) as usize;
+    let fs = ((instruction >> RD) & 0x1f) as usize;
+    let fd = ((instruction >> 6) & 0x1f) as usize;
+    let function = (instruction & 0x3f) as i32;
+
+    let format = match fmt {
+        MFC1 | DMFC1 | CFC1 | MFHC1 | MTC1 | DMTC1 | CTC1 | MTHC1 => {
+            if instruction & 0x7ff != 0 {
+                return None;
+            }
+            let op = match fmt {
+                MFC1 => Cop1Op::Mfc1,
+                DMFC1 => Cop1Op::Dmfc1,
+                CFC1 => Cop1Op::Cfc1,
+                MFHC1 => Cop1Op::Mfhc1,
+                MTC1 => Cop1Op::Mtc1,
+                DMTC1 => Cop1Op::Dmtc1,
+                CTC1 => Cop1Op::Ctc1,
+                _ => Cop1Op::Mthc1,
+            };
+            return Some(Instruction::Cop1 { op, rt: ft, fs });
+        },
+        BC1EQZ | BC1NEZ => {
+            let condition = if fmt == BC1EQZ {
+                Condition::Eqz
+            } else {
+                Condition::Nez
+            };
+            return Some(Instruction::Bc1 {
+                condition,
+                ft,
+                offset: offset(instruction, 16, 2),
+            });
+        },
+        BZ_V | BNZ_V | BZ_DF..=0x1f => {
+            let df = if fmt >= BZ_DF {
+                Some(VectorFormat::from_bits(fmt as u32))
+            } else {
+                None
+            };
+            return Some(Instruction::VectorBranch {
+                nonzero: fmt == BNZ_V || fmt >= BNZ_DF,
+                df,
+                wt: ft,
+                offset: offset(instruction, 16, 2),
+            });
+        },
+        FMT_W | FMT_L if function < CVT_S_FMT => {
+            let fmt = if fmt == FMT_W {
+                FloatFormat::S
+            } else {
+                FloatFormat::D
+            };
+            return Some(Instruction::FloatCompare {
+                condition: float_condition(function)?,
+                fmt,
+                fd,
+                fs,
+                ft,
+            });
+        },
+        FMT_W | FMT_L => {
+            let from = if fmt == FMT_W {
+                FixedFormat::W
+            } else {
+                FixedFormat::L
+            };
+            let to = match function {
+                CVT_S_FMT => FloatFormat::S,
+                CVT_D_FMT => FloatFormat::D,
+                _ => return None,
+            };
+            if ft != 0 {
+                return None;
+            }
+            return Some(Instruction::ConvertFixed { to, from, fd, fs });
+        },
+        FMT_S => FloatFormat::S,
+        FMT_D => FloatFormat::D,
+        _ => return None,
+    };
+
+    let binary = |op| Some(Instruction::Float { op, fmt: format, fd, fs, ft });
+    // Operations on a single register leave ft zero.
+    let unary = |op| {
+        if ft == 0 {
+            Some(Instruction::FloatUnary { op, fmt: format, fd, fs })
+        } else {
+            None
+        }
+    };
+
+    match function {
+        ADD_FMT => binary(FloatOp::Add),
+        SUB_FMT => binary(FloatOp::Sub),
+        MUL_FMT => binary(FloatOp::Mul),
+        DIV_FMT => binary(FloatOp::Div),
+        MADDF_FMT => binary(FloatOp::Maddf),
+        MSUBF_FMT => binary(FloatOp::Msubf),
+        MIN_FMT => binary(FloatOp::Min),
+        MINA_FMT => binary(FloatOp::Mina),
+        MAX_FMT => binary(FloatOp::Max),
+        MAXA_FMT => binary(FloatOp::Maxa),
+        SEL_FMT => binary(FloatOp::Sel),
+        SELEQZ_FMT => binary(FloatOp::Seleqz),
+        SELNEZ_FMT => binary(FloatOp::Selnez),
+        SQRT_FMT => unary(FloatUnaryOp::Sqrt),
+        ABS_FMT => unary(FloatUnaryOp::Abs),
+        MOV_FMT => unary(FloatUnaryOp::Mov),
+        NEG_FMT => unary(FloatUnaryOp::Neg),
+        RECIP_FMT => unary(FloatUnaryOp::Recip),
+        RSQRT_FMT => unary(FloatUnaryOp::Rsqrt),
+        RINT_FMT => unary(FloatUnaryOp::Rint),
+        CLASS_FMT => unary(FloatUnaryOp::Class),
+        ROUND_L_FMT => unary(FloatUnaryOp::RoundL),
+        TRUNC_L_FMT => unary(FloatUnaryOp::TruncL),
+        CEIL_L_FMT => unary(FloatUnaryOp::CeilL),
+        FLOOR_L_FMT => unary(FloatUnaryOp::FloorL),
+        ROUND_W_FMT => unary(FloatUnaryOp::RoundW),
+        TRUNC_W_FMT => unary(FloatUnaryOp::TruncW),
+        CEIL_W_FMT => unary(FloatUnaryOp::CeilW),
+        FLOOR_W_FMT => unary(FloatUnaryOp::FloorW),
+        CVT_S_FMT if format == FloatFormat::D => unary(FloatUnaryOp::CvtS),
+        CVT_D_FMT if format == FloatFormat::S => unary(FloatUnaryOp::CvtD),
+        CVT_W_FMT => unary(FloatUnaryOp::CvtW),
+        CVT_L_FMT => unary(FloatUnaryOp::CvtL),
+        _ => None,
+    }
+}
+
+pub(crate) fn float_condition(function: i32) -> Option<FloatCondition> {
+    Some(match function {
+        0x00 => FloatCondition::Af,
+        0x01 => FloatCondition::Un,
+        0x02 => FloatCondition::Eq,
+        0x03 => FloatCondition::Ueq,
+        0x04 => FloatCondition::Lt,
+        0x05 => FloatCondition::Ult,
+        0x06 => FloatCondition::Le,
+        0x07 => FloatCondition::Ule,
+        0x08 => FloatCondition::Saf,
+        0x09 => FloatCondition::Sun,
+        0x0a => FloatCondition::Seq,
+        0x0b => FloatCondition::Sueq,
+        0x0c => FloatCondition::Slt,
+        0x0d => FloatCondition::Sult,
+        0x0e => FloatCondition::Sle,
+        0x0f => FloatCondition::Sule,
+        0x11 => FloatCondition::Or,
+        0x12 => FloatCondition::Une,
+        0x13 => FloatCondition::Ne,
+        0x19 => FloatCondition::Sor,
+        0x1a => FloatCondition::Sune,
+        0x1b => FloatCondition::Sne,
+        _ => return None,
+    })
+}
+
+// The df/m field of the BIT format: a format marked by where its leading
+// zero is, and a bit number that fills the rest.
+fn bit_format(field: u32) -> (VectorFormat, u32) {
+    match field {
+        0x00..=0x3f => (VectorFormat::D, field),
+        0x40..=0x5f => (VectorFormat::W, field & 0x1f),
+        0x60..=0x6f => (VectorFormat::H, field & 0xf),
+        _ => (VectorFormat::B, field & 0x7),
+    }
+}
+
+// The df/n field of the ELM format works the same way for an element
+// index.
+fn element_format(field: u32) -> Option<(VectorFormat, usize)> {
+    let n = field as usize;
+    Some(match field {
+        0x00..=0x0f => (VectorFormat::B, n),
+        0x20..=0x27 => (VectorFormat::H, n & 0x7),
+        0x30..=0x33 => (VectorFormat::W, n & 0x3),
+        0x38..=0x39 => (VectorFormat::D, n & 0x1),
+        _ => return None,
+    })
+}
+
+fn decode_msa(instruction: u32) -> Option<Instruction> {
+    let minor = (instruction & 0x3f) as i32;
+    let wt = ((instruction >> 16) & 0x1f) as usize;
+    let ws = ((instruction >> 11) & 0x1f) as usize;
+    let wd = ((instruction >> 6) & 0x1f) as usize;
+    // The 3R, I5 and I10 formats put a three-bit operation above the
+    // format, and the 3RF and ELM formats a four-bit one.
+    let operation = (instruction >> 23) & 0x7;
+    let df = VectorFormat::from_bits(instruction >> 21);
+    let operation4 = (instruction >> 22) & 0xf;
+    // 3RF takes W and D, or H and W for the fixed-point formats.
+    let float_format = if instruction & (1 << 21) == 0 {
+        VectorFormat::W
+    } else {
+        VectorFormat::D
+    };
+    let fixed_format = if instruction & (1 << 21) == 0 {
+        VectorFormat::H
+    } else {
+        VectorFormat::W
+    };
+    let i8 = (instruction >> 16) as u8;
+
+    let vector = |op| Some(Instruction::Vector { op, df, wd, ws, wt });
+    let wide = |op| if df == VectorFormat::B { None } else { vector(op) };
+    let immediate = |op, immediate| {
+        Some(Instruction::VectorImmediate { op, df, wd, ws, immediate })
+    };
+    let signed5 = offset(instruction >> 16, 5, 0);
+    let unsigned5 = wt as i64;
+    let bit = |op| {
+        let (df, m) = bit_format((instruction >> 16) & 0x7f);
+        Some(Instruction::VectorImmediate {
+            op,
+            df,
+            wd,
+            ws,
+            immediate: m as i64,
+        })
+    };
+    let float = |op| {
+        Some(Instruction::VectorFloat { op, df: float_format, wd, ws, wt })
+    };
+    let fixed = |op| {
+        Some(Instruction::Vector { op, df: fixed_format, wd, ws, wt })
+    };
+
+    match minor {
+        MSA_I8_LOGIC | MSA_I8_SELECT => {
+            let op = match (minor, instruction >> 24 & 0x3) {
+                (MSA_I8_LOGIC, 0) => LogicOp::And,
+                (MSA_I8_LOGIC, 1) => LogicOp::Or,
+                (MSA_I8_LOGIC, 2) => LogicOp::Nor,
+                (MSA_I8_LOGIC, _) => LogicOp::Xor,
+                (_, 0) => LogicOp::Bmnz,
+                (_, 1) => LogicOp::Bmz,
+                (_, 2) => LogicOp::Bsel,
+                _ => return None,
+            };
+            Some(Instruction::VectorLogicImmediate { op, wd, ws, immediate: i8 })
+        },
+        MSA_I8_SHF => {
+            let df = VectorFormat::from_bits(instruction >> 24);
+            if instruction >> 24 & 0x3 == 3 {
+                return None;
+            }
+            Some(Instruction::Shf { df, wd, ws, immediate: i8 })
+        },
+        MSA_I5_ARITHMETIC => match operation {
+            0 => immediate(VectorOp::Addv, unsigned5),
+            1 => immediate(VectorOp::Subv, unsigned5),
+            2 => immediate(VectorOp::MaxS, signed5),
+            3 => immediate(VectorOp::MaxU, unsigned5),
+            4 => immediate(VectorOp::MinS, signed5),
+            5 => immediate(VectorOp::MinU, unsigned5),
+            _ => None,
+        },
+        MSA_I5_COMPARE => match operation {
+            0 => immediate(VectorOp::Ceq, signed5),
+            2 => immediate(VectorOp::CltS, signed5),
+            3 => immediate(VectorOp::CltU, unsigned5),
+            4 => immediate(VectorOp::CleS, signed5),
+            5 => immediate(VectorOp::CleU, unsigned5),
+            LDI => Some(Instruction::Ldi {
+                df,
+                wd,
+                immediate: offset(instruction >> 11, 10, 0),
+            }),
+            _ => None,
+        },
+        MSA_BIT_SHIFT => match operation {
+            0 => bit(VectorOp::Sll),
+            1 => bit(VectorOp::Sra),
+            2 => bit(VectorOp::Srl),
+            3 => bit(VectorOp::Bclr),
+            4 => bit(VectorOp::Bset),
+            5 => bit(VectorOp::Bneg),
+            6 => bit(VectorOp::Binsl),
+            _ => bit(VectorOp::Binsr),
+        },
+        MSA_BIT_SATURATE => match operation {
+            0 => bit(VectorOp::SatS),
+            1 => bit(VectorOp::SatU),
+            2 => bit(VectorOp::Srar),
+            3 => bit(VectorOp::Srlr),
+            _ => None,
+        },
+        MSA_3R_SHIFT => match operation {
+            0 => vector(VectorOp::Sll),
+            1 => vector(VectorOp::Sra),
+            2 => vector(VectorOp::Srl),
+            3 => vector(VectorOp::Bclr),
+            4 => vector(VectorOp::Bset),
+            5 => vector(VectorOp::Bneg),
+            6 => vector(VectorOp::Binsl),
+            _ => vector(VectorOp::Binsr),
+        },
+        MSA_3R_ADD => match operation {
+            0 => vector(VectorOp::Addv),
+            1 => vector(VectorOp::Subv),
+            2 => vector(VectorOp::MaxS),
+            3 => vector(VectorOp::MaxU),
+            4 => vector(VectorOp::MinS),
+            5 => vector(VectorOp::MinU),
+            6 => vector(VectorOp::MaxA),
+            _ => vector(VectorOp::MinA),
+        },
+        MSA_3R_COMPARE => match operation {
+            0 => vector(VectorOp::Ceq),
+            2 => vector(VectorOp::CltS),
+            3 => vector(VectorOp::CltU),
+            4 => vector(VectorOp::CleS),
+            5 => vector(VectorOp::CleU),
+            _ => None,
+        },
+        MSA_3R_ADD_SATURATE => match operation {
+            0 => vector(VectorOp::AddA),
+            1 => vector(VectorOp::AddsA),
+            2 => vector(VectorOp::AddsS),
+            3 => vector(VectorOp::AddsU),
+            4 => vector(VectorOp::AveS),
+            5 => vector(VectorOp::AveU),
+            6 => vector(VectorOp::AverS),
+            _ => vector(VectorOp::AverU),
+        },
+        MSA_3R_SUBTRACT => match operation {
+            0 => vector(VectorOp::SubsS),
+            1 => vector(VectorOp::SubsU),
+            2 => vector(VectorOp::SubsusU),
+            3 => vector(VectorOp::SubsuuS),
+            4 => vector(VectorOp::AsubS),
+            5 => vector(VectorOp::AsubU),
+            _ => None,
+        },
+        MSA_3R_MULTIPLY => match operation {
+            0 => vector(VectorOp::Mulv),
+            1 => vector(VectorOp::Maddv),
+            2 => vector(VectorOp::Msubv),
+            4 => vector(VectorOp::DivS),
+            5 => vector(VectorOp::DivU),
+            6 => vector(VectorOp::ModS),
+            7 => vector(VectorOp::ModU),
+            _ => None,
+        },
+        MSA_3R_DOT_PRODUCT => match operation {
+            0 => wide(VectorOp::DotpS),
+            1 => wide(VectorOp::DotpU),
+            2 => wide(VectorOp::DpaddS),
+            3 => wide(VectorOp::DpaddU),
+            4 => wide(VectorOp::DpsubS),
+            5 => wide(VectorOp::DpsubU),
+            _ => None,
+        },
+        MSA_3R_SHUFFLE => match operation {
+            0 => Some(Instruction::Sld { df, wd, ws, rt: wt }),
+            1 => Some(Instruction::Splat { df, wd, ws, rt: wt }),
+            2 => vector(VectorOp::Pckev),
+            3 => vector(VectorOp::Pckod),
+            4 => vector(VectorOp::Ilvl),
+            5 => vector(VectorOp::Ilvr),
+            6 => vector(VectorOp::Ilvev),
+            _ => vector(VectorOp::Ilvod),
+        },
+        MSA_3R_HORIZONTAL => match operation {
+            0 => vector(VectorOp::Vshf),
+            1 => vector(VectorOp::Srar),
+            2 => vector(VectorOp::Srlr),
+            4 => wide(VectorOp::HaddS),
+            5 => wide(VectorOp::HaddU),
+            6 => wide(VectorOp::HsubS),
+            7 => wide(VectorOp::HsubU),
+            _ => None,
+        },
+        MSA_ELM => {
+            let field = (instruction >> 16) & 0x3f;
+            if field == MSA_ELM_CONTROL {
+                return match operation4 {
+                    0 => Some(Instruction::Ctcmsa { cd: wd, rs: ws }),
+                    1 => Some(Instruction::Cfcmsa { rd: wd, cs: ws }),
+                    2 => Some(Instruction::MoveV { wd, ws }),
+                    _ => None,
+                };
+            }
+            let (df, n) = element_format(field)?;
+            match operation4 {
+                0 => Some(Instruction::Sldi { df, wd, ws, n }),
+                1 => Some(Instruction::Splati { df, wd, ws, n }),
+                2 => Some(Instruction::Copy { signed: true, df, rd: wd, ws, n }),
+                3 if df != VectorFormat::D => {
+                    Some(Instruction::Copy { signed: false, df, rd: wd, ws, n })
+                },
+                4 => Some(Instruction::Insert { df, wd, rs: ws, n }),
+                5 => Some(Instruction::Insve { df, wd, ws, n }),
+                _ => None,
+            }
+        },
+        MSA_3RF_COMPARE => Some(Instruction::VectorFloatCompare {
+            condition: float_condition(operation4 as i32)?,
+            df: float_format,
+            wd,
+            ws,
+            wt,
+        }),
+        MSA_3RF_ARITHMETIC => match operation4 {
+            0 => float(VectorFloatOp::Fadd),
+            1 => float(VectorFloatOp::Fsub),
+            2 => float(VectorFloatOp::Fmul),
+            3 => float(VectorFloatOp::Fdiv),
+            4 => float(VectorFloatOp::Fmadd),
+            5 => float(VectorFloatOp::Fmsub),
+            7 => float(VectorFloatOp::Fexp2),
+            8 | 10 => {
+                let op = if operation4 == 8 {
+                    VectorFloatOp::Fexdo
+                } else {
+                    VectorFloatOp::Ftq
+                };
+                Some(Instruction::VectorFloat {
+                    op,
+                    df: fixed_format,
+                    wd,
+                    ws,
+                    wt,
+                })
+            },
+            12 => float(VectorFloatOp::Fmin),
+            13 => float(VectorFloatOp::FminA),
+            14 => float(VectorFloatOp::Fmax),
+            15 => float(VectorFloatOp::FmaxA),
+            _ => None,
+        },
+        // The compares here are the ones with bit 4 of the condition set.
+        MSA_3RF_FIXED => match operation4 {
+            1..=3 | 9..=11 => Some(Instruction::VectorFloatCompare {
+                condition: float_condition(0x10 | operation4 as i32)?,
+                df: float_format,
+                wd,
+                ws,
+                wt,
+            }),
+            4 => fixed(VectorOp::MulQ),
+            5 => fixed(VectorOp::MaddQ),
+            6 => fixed(VectorOp::MsubQ),
+            12 => fixed(VectorOp::MulrQ),
+            13 => fixed(VectorOp::MaddrQ),
+            14 => fixed(VectorOp::MsubrQ),
+            _ => None,
+        },
+        MSA_VEC => {
+            let op = match (instruction >> 21) & 0x1f {
+                0 => LogicOp::And,
+                1 => LogicOp::Or,
+                2 => LogicOp::Nor,
+                3 => LogicOp::Xor,
+                4 => LogicOp::Bmnz,
+                5 => LogicOp::Bmz,
+                6 => LogicOp::Bsel,
+                MSA_2R => return decode_msa_2r(instruction),
+                MSA_2RF => return decode_msa_2rf(instruction),
+                _ => return None,
+            };
+            Some(Instruction::VectorLogic { op, wd, ws, wt })
+        },
+        _ if minor & !0x7 == MSA_LD => {
+            let df = VectorFormat::from_bits(instruction);
+            let offset = offset(instruction >> 16, 10, df as u32);
+            if minor & !0x3 == MSA_LD {
+                Some(Instruction::VectorLoad { df, wd, base: ws, offset })
+            } else {
+                Some(Instruction::VectorStore { df, wd, base: ws, offset })
+            }
+        },
+        _ => None,
+    }
+}
+
+fn decode_msa_2r(instruction: u32) -> Option<Instruction> {
+    let df = VectorFormat::from_bits(instruction >> 16);
+    let ws = ((instruction >> 11) & 0x1f) as usize;
+    let wd = ((instruction >> 6) & 0x1f) as usize;
+    let op = match (instruction >> 18) & 0x7 {
+        0 => return Some(Instruction::Fill { df, wd, rs: ws }),
+        1 => VectorCountOp::Pcnt,
+        2 => VectorCountOp::Nloc,
+        3 => VectorCountOp::Nlzc,
+        _ => return None,
+    };
+    Some(Instruction::VectorCount { op, df, wd, ws })
+}
+
+fn decode_msa_2rf(instruction: u32) -> Option<Instruction> {
+    let df = if instruction & (1 << 16) == 0 {
+        VectorFormat::W
+    } else {
+        VectorFormat::D
+    };
+    let ws = ((instruction >> 11) & 0x1f) as usize;
+    let wd = ((instruction >> 6) & 0x1f) as usize;
+    let op = match (instruction >> 17) & 0xf {
+        0x0 => VectorFloatUnaryOp::Fclass,
+        0x1 => VectorFloatUnaryOp::FtruncS,
+        0x2 => VectorFloatUnaryOp::FtruncU,
+        0x3 => VectorFloatUnaryOp::Fsqrt,
+        0x4 => VectorFloatUnaryOp::Frsqrt,
+        0x5 => VectorFloatUnaryOp::Frcp,
+        0x6 => VectorFloatUnaryOp::Frint,
+        0x7 => VectorFloatUnaryOp::Flog2,
+        0x8 => VectorFloatUnaryOp::Fexupl,
+        0x9 => VectorFloatUnaryOp::Fexupr,
+        0xa => VectorFloatUnaryOp::Ffql,
+        0xb => VectorFloatUnaryOp::Ffqr,
+        0xc => VectorFloatUnaryOp::FtintS,
+        0xd => VectorFloatUnaryOp::FtintU,
+        0xe => VectorFloatUnaryOp::FfintS,
+        _ => VectorFloatUnaryOp::FfintU,
+    };
+    Some(Instruction::VectorFloatUnary { op, df, wd, ws })
+}
@@ -0,0 +1,605 @@
+use std::fmt::Debug;
+
+use crate::computer::assembler::REGISTER_NAMES;
+use crate::computer::decoder::{self, BitFieldOp, Condition, Cop1Op,
+                               DspCompareOp, DspUnaryOp, FloatCondition,
+                               FloatUnaryOp, ImmediateOp, Instruction,
+                               IsaRevision, PcRelativeOp, VectorFormat,
+                               VectorOp};
+use crate::computer::memory::Memory;
+use crate::computer::{micromips, mips16};
+
+// Each op is named after its mnemonic.
+fn mnemonic(op: impl Debug) -> String {
+    format!("{:?}", op).to_lowercase()
+}
+
+// MSA ops put an underscore before their suffix: MaxS is max_s.
+fn vector_mnemonic(op: impl Debug) -> String {
+    let mut name = String::new();
+    for (i, c) in format!("{:?}", op).chars().enumerate() {
+        if i > 0 && c.is_ascii_uppercase() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+// The immediate forms add an i before the suffix, except for SAT_S and
+// SAT_U, which only have immediate forms.
+fn vector_immediate_mnemonic(op: VectorOp) -> String {
+    let name = vector_mnemonic(op);
+    match name.split_once('_') {
+        _ if matches!(op, VectorOp::SatS | VectorOp::SatU) => name,
+        Some((name, suffix)) => format!("{}i_{}", name, suffix),
+        None => name + "i",
+    }
+}
+
+// The signaling compares start fs and the rest fc, so FCEQ is fc with eq
+// and FSEQ is f with seq.
+fn vector_compare_mnemonic(condition: FloatCondition) -> String {
+    if condition as u32 & 0x8 == 0 {
+        format!("fc{}", mnemonic(condition))
+    } else {
+        format!("f{}", mnemonic(condition))
+    }
+}
+
+// DSP ASE ops join their words with dots, except that the saturating and
+// rounding variants take an underscore: MuleuSPhQbl is muleu_s.ph.qbl.
+fn dsp_mnemonic(op: impl Debug) -> String {
+    let name = format!("{:?}", op);
+    let mut words = Vec::new();
+    for (i, c) in name.char_indices().skip(1) {
+        if c.is_ascii_uppercase() {
+            words.push(i);
+        }
+    }
+    let mut text = String::new();
+    let mut start = 0;
+    for end in words.into_iter().chain([name.len()]) {
+        let word = name[start..end].to_lowercase();
+        if start != 0 {
+            let variant = matches!(word.as_str(), "s" | "r" | "rs" | "sa");
+            text.push(if variant { '_' } else { '.' });
+        }
+        text.push_str(&word);
+        start = end;
+    }
+    text
+}
+
+// The forms that take an amount from a register add a v to the name's
+// first word: shllv_s.ph and extpv.
+fn dsp_variable_mnemonic(op: impl Debug) -> String {
+    let mut name = dsp_mnemonic(op);
+    let end = name.find(['_', '.']).unwrap_or(name.len());
+    name.insert(end, 'v');
+    name
+}
+
+fn accumulator(number: usize) -> String {
+    format!("$ac{}", number)
+}
+
+fn register(number: usize) -> String {
+    format!("${}", REGISTER_NAMES[number])
+}
+
+fn float_register(number: usize) -> String {
+    format!("$f{}", number)
+}
+
+fn vector_register(number: usize) -> String {
+    format!("$w{}", number)
+}
+
+// An instruction name with an element format, like addv.w.
+fn vector_name(name: String, df: VectorFormat) -> String {
+    format!("{}.{}", name, mnemonic(df))
+}
+
+// The rounding and conversion ops name their result's format.
+fn float_unary_mnemonic(op: FloatUnaryOp) -> &'static str {
+    match op {
+        FloatUnaryOp::Sqrt => "sqrt",
+        FloatUnaryOp::Abs => "abs",
+        FloatUnaryOp::Mov => "mov",
+        FloatUnaryOp::Neg => "neg",
+        FloatUnaryOp::Recip => "recip",
+        FloatUnaryOp::Rsqrt => "rsqrt",
+        FloatUnaryOp::Rint => "rint",
+        FloatUnaryOp::Class => "class",
+        FloatUnaryOp::RoundL => "round.l",
+        FloatUnaryOp::TruncL => "trunc.l",
+        FloatUnaryOp::CeilL => "ceil.l",
+        FloatUnaryOp::FloorL => "floor.l",
+        FloatUnaryOp::RoundW => "round.w",
+        FloatUnaryOp::TruncW => "trunc.w",
+        FloatUnaryOp::CeilW => "ceil.w",
+        FloatUnaryOp::FloorW => "floor.w",
+        FloatUnaryOp::CvtS => "cvt.s",
+        FloatUnaryOp::CvtD => "cvt.d",
+        FloatUnaryOp::CvtW => "cvt.w",
+        FloatUnaryOp::CvtL => "cvt.l",
+    }
+}
+
+// Offsets go in hex, the way the assembler reads them back.
+fn offset(value: i64) -> String {
+    if value < 0 {
+        format!("-0x{:x}", value.unsigned_abs())
+    } else {
+        format!("0x{:x}", value)
+    }
+}
+
+fn zero_condition(condition: Condition) -> bool {
+    matches!(condition, Condition::Eqz | Condition::Nez | Condition::Ltz |
+             Condition::Gez | Condition::Lez | Condition::Gtz)
+}
+
+// Compressed instructions the CPU wouldn't execute, as .hword directives.
+fn halfwords(instruction: u32, size: u64) -> String {
+    if size == 2 {
+        format!(".hword 0x{:04x}", instruction)
+    } else {
+        format!(".hword 0x{:04x}, 0x{:04x}", instruction >> 16,
+                instruction & 0xffff)
+    }
+}
+
+// LWM and friends list each register they move.
+fn register_list(registers: u32) -> String {
+    (0..32).filter(|number| registers & (1 << number) != 0)
+        .map(register)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Turns an instruction into assembly text. Release 6 text is what the
+// assembler accepts. `pc` is where the instruction is; jumps need it to show
+// their target. Words the CPU wouldn't execute come out as a .word directive.
+pub fn disassemble(instruction: u32, pc: u64, revision: IsaRevision) -> String {
+    text(decoder::decode(instruction, revision), pc)
+        .unwrap_or_else(|| format!(".word 0x{:08x}", instruction))
+}
+
+// The same for a microMIPS instruction of `size` bytes, laid out the way
+// micromips::decode takes it. The text is that of the standard instruction
+// it does the same as.
+pub fn disassemble_micromips(instruction: u32, size: u64, pc: u64) -> String {
+    text(micromips::decode(instruction, size), pc)
+        .unwrap_or_else(|| halfwords(instruction, size))
+}
+
+// And for a MIPS16e instruction, laid out the way mips16::decode takes it.
+pub fn disassemble_mips16(instruction: u32, size: u64, pc: u64) -> String {
+    text(mips16::decode(instruction, size), pc)
+        .unwrap_or_else(|| halfwords(instruction, size))
+}
+
+fn text(instruction: Instruction, pc: u64) -> Option<String> {
+    let text = match instruction {
+        Instruction::Memory { op, rt, base, offset } =>
+            format!("{} {}, {}({})", mnemonic(op), register(rt), offset,
+                    register(base)),
+        // LWP and SWP come out as the LWM32 or SWM32 that does the same.
+        Instruction::LoadMultiple { registers, base, offset } =>
+            format!("lwm32 {}, {}({})", register_list(registers), offset,
+                    register(base)),
+        Instruction::StoreMultiple { registers, base, offset } =>
+            format!("swm32 {}, {}({})", register_list(registers), offset,
+                    register(base)),
+        // The argument registers SAVE stores come before the frame size.
+        Instruction::Save { arguments, registers, frame } => {
+            let arguments = register_list(((1 << arguments) - 1) << 4);
+            let operands = [arguments, frame.to_string(),
+                            register_list(registers)];
+            let operands = operands.into_iter()
+                .filter(|operand| !operand.is_empty())
+                .collect::<Vec<_>>();
+            format!("save {}", operands.join(", "))
+        },
+        Instruction::Restore { registers: 0, frame } =>
+            format!("restore {}", frame),
+        Instruction::Restore { registers, frame } =>
+            format!("restore {}, {}", frame, register_list(registers)),
+        Instruction::Linked { op, rt, base, offset } =>
+            format!("{} {}, {}({})", mnemonic(op), register(rt), offset,
+                    register(base)),
+        Instruction::Llwp { rt, rd, base } =>
+            format!("llwp {}, {}, ({})", register(rt), register(rd),
+                    register(base)),
+        Instruction::Scwp { rt, rd, base } =>
+            format!("scwp {}, {}, ({})", register(rt), register(rd),
+                    register(base)),
+        Instruction::Sync { stype: 0 } => "sync".to_string(),
+        Instruction::Sync { stype } => format!("sync {}", stype),
+        Instruction::Pref { hint, base, offset } =>
+            format!("pref {}, {}({})", hint, offset, register(base)),
+        Instruction::Synci { base, offset } =>
+            format!("synci {}({})", offset, register(base)),
+        Instruction::PcRelative {
+            op: op @ (PcRelativeOp::Auipc | PcRelativeOp::Aluipc), rs, offset,
+        } => format!("{} {}, 0x{:x}", mnemonic(op), register(rs),
+                     (offset >> 16) as u16),
+        Instruction::PcRelative { op, rs, offset: value } =>
+            format!("{} {}, {}", mnemonic(op), register(rs), offset(value)),
+        Instruction::Immediate { op: ImmediateOp::Lui, rt, immediate, .. } =>
+            format!("lui {}, 0x{:x}", register(rt), immediate),
+        Instruction::Immediate {
+            op: op @ (ImmediateOp::Dahi | ImmediateOp::Dati), rs, immediate, ..
+        } => format!("{} {}, 0x{:x}", mnemonic(op), register(rs), immediate),
+        Instruction::Immediate { op, rt, rs, immediate } => {
+            let immediate = match op {
+                ImmediateOp::Andi | ImmediateOp::Ori | ImmediateOp::Xori |
+                ImmediateOp::Aui | ImmediateOp::Daui =>
+                    format!("0x{:x}", immediate),
+                _ => (immediate as i16).to_string(),
+            };
+            format!("{} {}, {}, {}", mnemonic(op), register(rt), register(rs),
+                    immediate)
+        },
+        Instruction::Register { op, rd, rs, rt } =>
+            format!("{} {}, {}, {}", mnemonic(op), register(rd), register(rs),
+                    register(rt)),
+        Instruction::Count { op, rd, rs } =>
+            format!("{} {}, {}", mnemonic(op), register(rd), register(rs)),
+        Instruction::Shift { op: decoder::ShiftOp::Sll, rd: 0, rt: 0, sa: 0 } =>
+            "nop".to_string(),
+        Instruction::Shift { op, rd, rt, sa } =>
+            format!("{} {}, {}, {}", mnemonic(op), register(rd), register(rt),
+                    sa),
+        Instruction::ShiftVariable { op, rd, rt, rs } =>
+            format!("{} {}, {}, {}", mnemonic(op), register(rd), register(rt),
+                    register(rs)),
+        Instruction::Align { rd, rs, rt, bp } =>
+            format!("align {}, {}, {}, {}", register(rd), register(rs),
+                    register(rt), bp),
+        Instruction::Dalign { rd, rs, rt, bp } =>
+            format!("dalign {}, {}, {}, {}", register(rd), register(rs),
+                    register(rt), bp),
+        Instruction::Bitswap { rd, rt } =>
+            format!("bitswap {}, {}", register(rd), register(rt)),
+        Instruction::Dbitswap { rd, rt } =>
+            format!("dbitswap {}, {}", register(rd), register(rt)),
+        // The doubleword forms are named for where the field lies.
+        Instruction::BitField { op, rt, rs, pos, size } => {
+            let name = match op {
+                BitFieldOp::Dext | BitFieldOp::Dins if pos >= 32 =>
+                    format!("{}u", mnemonic(op)),
+                BitFieldOp::Dext if size > 32 => "dextm".to_string(),
+                BitFieldOp::Dins if pos + size > 32 => "dinsm".to_string(),
+                _ => mnemonic(op),
+            };
+            format!("{} {}, {}, {}, {}", name, register(rt), register(rs), pos,
+                    size)
+        },
+        Instruction::Shuffle { op, rd, rt } =>
+            format!("{} {}, {}", mnemonic(op), register(rd), register(rt)),
+        Instruction::Lsa { rd, rs, rt, sa } =>
+            format!("lsa {}, {}, {}, {}", register(rd), register(rs),
+                    register(rt), sa),
+        Instruction::Dlsa { rd, rs, rt, sa } =>
+            format!("dlsa {}, {}, {}, {}", register(rd), register(rs),
+                    register(rt), sa),
+        Instruction::Rdhwr { rt, rd } =>
+            format!("rdhwr {}, ${}", register(rt), rd),
+        // The DSP ASE's other accumulators are named, but HI and LO aren't.
+        Instruction::HiLo { op, ac: 0, rs, rt } =>
+            format!("{} {}, {}", mnemonic(op), register(rs), register(rt)),
+        Instruction::HiLo { op, ac, rs, rt } =>
+            format!("{} {}, {}, {}", mnemonic(op), accumulator(ac),
+                    register(rs), register(rt)),
+        Instruction::Mfhi { rd, ac: 0 } => format!("mfhi {}", register(rd)),
+        Instruction::Mflo { rd, ac: 0 } => format!("mflo {}", register(rd)),
+        Instruction::Mthi { rs, ac: 0 } => format!("mthi {}", register(rs)),
+        Instruction::Mtlo { rs, ac: 0 } => format!("mtlo {}", register(rs)),
+        Instruction::Mfhi { rd, ac } =>
+            format!("mfhi {}, {}", register(rd), accumulator(ac)),
+        Instruction::Mflo { rd, ac } =>
+            format!("mflo {}, {}", register(rd), accumulator(ac)),
+        Instruction::Mthi { rs, ac } =>
+            format!("mthi {}, {}", register(rs), accumulator(ac)),
+        Instruction::Mtlo { rs, ac } =>
+            format!("mtlo {}, {}", register(rs), accumulator(ac)),
+        Instruction::Movz { rd, rs, rt } =>
+            format!("movz {}, {}, {}", register(rd), register(rs),
+                    register(rt)),
+        Instruction::Movn { rd, rs, rt } =>
+            format!("movn {}, {}, {}", register(rd), register(rs),
+                    register(rt)),
+        Instruction::Movep { rd, re, rs, rt } =>
+            format!("movep {}, {}, {}, {}", register(rd), register(re),
+                    register(rs), register(rt)),
+        Instruction::Bc { offset: value } => format!("bc {}", offset(value)),
+        Instruction::Balc { offset: value } => format!("balc {}", offset(value)),
+        Instruction::Jic { rt, offset } =>
+            format!("jic {}, {}", register(rt), offset),
+        Instruction::Jialc { rt, offset } =>
+            format!("jialc {}, {}", register(rt), offset),
+        Instruction::Jalrc { rd: 31, rs } => format!("jalrc {}", register(rs)),
+        Instruction::Jalrc { rd, rs } =>
+            format!("jalrc {}, {}", register(rd), register(rs)),
+        Instruction::JrcAddiusp { immediate } =>
+            format!("jrcaddiusp {}", immediate),
+        Instruction::CompactBranch { condition, link, rs, rt, offset: value } => {
+            let name = format!("b{}{}c", mnemonic(condition),
+                               if link { "al" } else { "" });
+            if zero_condition(condition) {
+                format!("{} {}, {}", name, register(rs), offset(value))
+            } else {
+                format!("{} {}, {}, {}", name, register(rs), register(rt),
+                        offset(value))
+            }
+        },
+        Instruction::Branch { condition: Condition::Eq, link: false,
+                              likely: false, rs: 0, rt: 0, offset: value } =>
+            format!("b {}", offset(value)),
+        Instruction::Branch { condition: Condition::Ltz, link: true,
+                              likely: false, rs: 0, offset: 0, .. } =>
+            "nal".to_string(),
+        Instruction::Branch { condition: Condition::Gez, link: true,
+                              likely: false, rs: 0, offset: value, .. } =>
+            format!("bal {}", offset(value)),
+        Instruction::Branch { condition, link, likely, rs, rt,
+                              offset: value } => {
+            let name = format!("b{}{}{}", mnemonic(condition),
+                               if link { "al" } else { "" },
+                               if likely { "l" } else { "" });
+            if zero_condition(condition) {
+                format!("{} {}, {}", name, register(rs), offset(value))
+            } else {
+                format!("{} {}, {}, {}", name, register(rs), register(rt),
+                        offset(value))
+            }
+        },
+        Instruction::J { target } =>
+            format!("j 0x{:x}", decoder::jump_target(pc, target)),
+        Instruction::Jal { target } =>
+            format!("jal 0x{:x}", decoder::jump_target(pc, target)),
+        Instruction::Jalx { target } =>
+            format!("jalx 0x{:x}", decoder::jump_target(pc, target)),
+        Instruction::Jalr { rd: 0, rs } => format!("jr {}", register(rs)),
+        Instruction::Jalr { rd: 31, rs } => format!("jalr {}", register(rs)),
+        Instruction::Jalr { rd, rs } =>
+            format!("jalr {}, {}", register(rd), register(rs)),
+        Instruction::Cop0 { op, rt, rd, sel } => {
+            if sel == 0 {
+                format!("{} {}, ${}", mnemonic(op), register(rt), rd)
+            } else {
+                format!("{} {}, ${}, {}", mnemonic(op), register(rt), rd, sel)
+            }
+        },
+        Instruction::Di { rt: 0 } => "di".to_string(),
+        Instruction::Di { rt } => format!("di {}", register(rt)),
+        Instruction::Ei { rt: 0 } => "ei".to_string(),
+        Instruction::Ei { rt } => format!("ei {}", register(rt)),
+        Instruction::Tlb { op } => mnemonic(op),
+        Instruction::Eret => "eret".to_string(),
+        Instruction::Deret => "deret".to_string(),
+        Instruction::Wait => "wait".to_string(),
+        Instruction::Syscall { code: 0 } => "syscall".to_string(),
+        Instruction::Syscall { code } => format!("syscall {}", code),
+        // BREAK's code is written as its two 10-bit halves.
+        Instruction::Break { code: 0 } => "break".to_string(),
+        Instruction::Break { code } if code & 0x3ff == 0 =>
+            format!("break {}", code >> 10),
+        Instruction::Break { code } =>
+            format!("break {}, {}", code >> 10, code & 0x3ff),
+        Instruction::Sdbbp { code: 0 } => "sdbbp".to_string(),
+        Instruction::Sdbbp { code } => format!("sdbbp {}", code),
+        Instruction::Trap { condition, rs, rt, code: 0 } =>
+            format!("t{} {}, {}", mnemonic(condition), register(rs),
+                    register(rt)),
+        Instruction::Trap { condition, rs, rt, code } =>
+            format!("t{} {}, {}, {}", mnemonic(condition), register(rs),
+                    register(rt), code),
+        // The unsigned ones are TGEIU and TLTIU.
+        Instruction::TrapImmediate { condition, rs, immediate } => {
+            let name = match condition {
+                Condition::Geu => "tgeiu".to_string(),
+                Condition::Ltu => "tltiu".to_string(),
+                _ => format!("t{}i", mnemonic(condition)),
+            };
+            format!("{} {}, {}", name, register(rs), immediate)
+        },
+        Instruction::Sigrie { code } => format!("sigrie 0x{:x}", code),
+        Instruction::FloatMemory { op, ft, base, offset } =>
+            format!("{} {}, {}({})", mnemonic(op), float_register(ft), offset,
+                    register(base)),
+        // The control registers only go by number.
+        Instruction::Cop1 { op: op @ (Cop1Op::Cfc1 | Cop1Op::Ctc1), rt, fs } =>
+            format!("{} {}, ${}", mnemonic(op), register(rt), fs),
+        Instruction::Cop1 { op, rt, fs } =>
+            format!("{} {}, {}", mnemonic(op), register(rt), float_register(fs)),
+        Instruction::Float { op, fmt, fd, fs, ft } =>
+            format!("{}.{} {}, {}, {}", mnemonic(op), mnemonic(fmt),
+                    float_register(fd), float_register(fs), float_register(ft)),
+        Instruction::FloatUnary { op, fmt, fd, fs } =>
+            format!("{}.{} {}, {}", float_unary_mnemonic(op), mnemonic(fmt),
+                    float_register(fd), float_register(fs)),
+        Instruction::FloatCompare { condition, fmt, fd, fs, ft } =>
+            format!("cmp.{}.{} {}, {}, {}", mnemonic(condition), mnemonic(fmt),
+                    float_register(fd), float_register(fs), float_register(ft)),
+        Instruction::ConvertFixed { to, from, fd, fs } =>
+            format!("cvt.{}.{} {}, {}", mnemonic(to), mnemonic(from),
+                    float_register(fd), float_register(fs)),
+        Instruction::Bc1 { condition, ft, offset: value } =>
+            format!("bc1{} {}, {}", mnemonic(condition), float_register(ft),
+                    offset(value)),
+        Instruction::Bc1c { condition, ft, offset: value } =>
+            format!("bc1{}c {}, {}", mnemonic(condition), float_register(ft),
+                    offset(value)),
+        Instruction::Vector { op, df, wd, ws, wt } =>
+            format!("{} {}, {}, {}", vector_name(vector_mnemonic(op), df),
+                    vector_register(wd), vector_register(ws),
+                    vector_register(wt)),
+        Instruction::VectorImmediate { op, df, wd, ws, immediate } =>
+            format!("{} {}, {}, {}",
+                    vector_name(vector_immediate_mnemonic(op), df),
+                    vector_register(wd), vector_register(ws), immediate),
+        Instruction::VectorLogic { op, wd, ws, wt } =>
+            format!("{}.v {}, {}, {}", mnemonic(op), vector_register(wd),
+                    vector_register(ws), vector_register(wt)),
+        Instruction::VectorLogicImmediate { op, wd, ws, immediate } =>
+            format!("{}i.b {}, {}, {}", mnemonic(op), vector_register(wd),
+                    vector_register(ws), immediate),
+        Instruction::VectorCount { op, df, wd, ws } =>
+            format!("{} {}, {}", vector_name(mnemonic(op), df),
+                    vector_register(wd), vector_register(ws)),
+        Instruction::Ldi { df, wd, immediate } =>
+            format!("ldi.{} {}, {}", mnemonic(df), vector_register(wd),
+                    immediate),
+        Instruction::Shf { df, wd, ws, immediate } =>
+            format!("shf.{} {}, {}, {}", mnemonic(df), vector_register(wd),
+                    vector_register(ws), immediate),
+        Instruction::Sld { df, wd, ws, rt } =>
+            format!("sld.{} {}, {}[{}]", mnemonic(df), vector_register(wd),
+                    vector_register(ws), register(rt)),
+        Instruction::Splat { df, wd, ws, rt } =>
+            format!("splat.{} {}, {}[{}]", mnemonic(df), vector_register(wd),
+                    vector_register(ws), register(rt)),
+        Instruction::Sldi { df, wd, ws, n } =>
+            format!("sldi.{} {}, {}[{}]", mnemonic(df), vector_register(wd),
+                    vector_register(ws), n),
+        Instruction::Splati { df, wd, ws, n } =>
+            format!("splati.{} {}, {}[{}]", mnemonic(df), vector_register(wd),
+                    vector_register(ws), n),
+        Instruction::Copy { signed, df, rd, ws, n } =>
+            format!("copy_{}.{} {}, {}[{}]", if signed { "s" } else { "u" },
+                    mnemonic(df), register(rd), vector_register(ws), n),
+        Instruction::Insert { df, wd, rs, n } =>
+            format!("insert.{} {}[{}], {}", mnemonic(df), vector_register(wd),
+                    n, register(rs)),
+        Instruction::Insve { df, wd, ws, n } =>
+            format!("insve.{} {}[{}], {}[0]", mnemonic(df), vector_register(wd),
+                    n, vector_register(ws)),
+        Instruction::Fill { df, wd, rs } =>
+            format!("fill.{} {}, {}", mnemonic(df), vector_register(wd),
+                    register(rs)),
+        // Like CFC1 and CTC1, the control registers only go by number.
+        Instruction::Ctcmsa { cd, rs } =>
+            format!("ctcmsa ${}, {}", cd, register(rs)),
+        Instruction::Cfcmsa { rd, cs } =>
+            format!("cfcmsa {}, ${}", register(rd), cs),
+        Instruction::MoveV { wd, ws } =>
+            format!("move.v {}, {}", vector_register(wd), vector_register(ws)),
+        Instruction::VectorLoad { df, wd, base, offset } =>
+            format!("ld.{} {}, {}({})", mnemonic(df), vector_register(wd),
+                    offset, register(base)),
+        Instruction::VectorStore { df, wd, base, offset } =>
+            format!("st.{} {}, {}({})", mnemonic(df), vector_register(wd),
+                    offset, register(base)),
+        Instruction::VectorFloat { op, df, wd, ws, wt } =>
+            format!("{} {}, {}, {}", vector_name(vector_mnemonic(op), df),
+                    vector_register(wd), vector_register(ws),
+                    vector_register(wt)),
+        Instruction::VectorFloatUnary { op, df, wd, ws } =>
+            format!("{} {}, {}", vector_name(vector_mnemonic(op), df),
+                    vector_register(wd), vector_register(ws)),
+        Instruction::VectorFloatCompare { condition, df, wd, ws, wt } =>
+            format!("{} {}, {}, {}",
+                    vector_name(vector_compare_mnemonic(condition), df),
+                    vector_register(wd), vector_register(ws),
+                    vector_register(wt)),
+        Instruction::VectorBranch { nonzero, df, wt, offset: value } => {
+            let format = df.map_or("v".to_string(), mnemonic);
+            format!("b{}z.{} {}, {}", if nonzero { "n" } else { "" }, format,
+                    vector_register(wt), offset(value))
+        },
+        Instruction::Dsp { op, rd, rs, rt } =>
+            format!("{} {}, {}, {}", dsp_mnemonic(op), register(rd),
+                    register(rs), register(rt)),
+        // REPLV.QB and REPLV.PH share their ops with REPL.
+        Instruction::DspUnary { op, rd, rt } => {
+            let name = match op {
+                DspUnaryOp::ReplQb | DspUnaryOp::ReplPh =>
+                    dsp_variable_mnemonic(op),
+                _ => dsp_mnemonic(op),
+            };
+            format!("{} {}, {}", name, register(rd), register(rt))
+        },
+        Instruction::Repl { op, rd, immediate } =>
+            format!("{} {}, {}", dsp_mnemonic(op), register(rd), immediate),
+        Instruction::DspShift { op, rd, rt, sa } =>
+            format!("{} {}, {}, {}", dsp_mnemonic(op), register(rd),
+                    register(rt), sa),
+        Instruction::DspShiftVariable { op, rd, rt, rs } =>
+            format!("{} {}, {}, {}", dsp_variable_mnemonic(op), register(rd),
+                    register(rt), register(rs)),
+        // CMPU and CMP only write DSPControl.
+        Instruction::DspCompare { op, condition, rd, rs, rt } => {
+            let format = if op == DspCompareOp::Cmp { "ph" } else { "qb" };
+            let name = format!("{}.{}.{}", mnemonic(op), mnemonic(condition),
+                               format);
+            match op {
+                DspCompareOp::Cmpu | DspCompareOp::Cmp =>
+                    format!("{} {}, {}", name, register(rs), register(rt)),
+                _ => format!("{} {}, {}, {}", name, register(rd),
+                             register(rs), register(rt)),
+            }
+        },
+        Instruction::PrecrSra { round, rt, rs, sa } =>
+            format!("precr_sra{}.ph.w {}, {}, {}",
+                    if round { "_r" } else { "" }, register(rt), register(rs),
+                    sa),
+        Instruction::DspAccumulate { op, ac, rs, rt } =>
+            format!("{} {}, {}, {}", dsp_mnemonic(op), accumulator(ac),
+                    register(rs), register(rt)),
+        Instruction::Extract { op, rt, ac, shift } =>
+            format!("{} {}, {}, {}", dsp_mnemonic(op), register(rt),
+                    accumulator(ac), shift),
+        Instruction::ExtractVariable { op, rt, ac, rs } =>
+            format!("{} {}, {}, {}", dsp_variable_mnemonic(op), register(rt),
+                    accumulator(ac), register(rs)),
+        Instruction::Shilo { ac, shift } =>
+            format!("shilo {}, {}", accumulator(ac), shift),
+        Instruction::Shilov { ac, rs } =>
+            format!("shilov {}, {}", accumulator(ac), register(rs)),
+        Instruction::Mthlip { rs, ac } =>
+            format!("mthlip {}, {}", register(rs), accumulator(ac)),
+        Instruction::Wrdsp { rs, mask } =>
+            format!("wrdsp {}, {}", register(rs), mask),
+        Instruction::Rddsp { rd, mask } =>
+            format!("rddsp {}, {}", register(rd), mask),
+        Instruction::Insv { rt, rs } =>
+            format!("insv {}, {}", register(rt), register(rs)),
+        Instruction::Append { op, rt, rs, sa } =>
+            format!("{} {}, {}, {}", mnemonic(op), register(rt), register(rs),
+                    sa),
+        Instruction::IndexedLoad { op, rd, base, index } =>
+            format!("{}x {}, {}({})", mnemonic(op), register(rd),
+                    register(index), register(base)),
+        Instruction::Bposge32 { offset: value } =>
+            format!("bposge32 {}", offset(value)),
+        Instruction::Reserved => return None,
+    };
+    Some(text)
+}
+
+// Disassembles the words from `start` up to `end` as a CPU would see them,
+// one line per word with its address and encoding. Stops early at the first
+// address the CPU can't fetch from.
+pub fn disassemble_memory(memory: &mut Memory,
+                          cpu: u64,
+                          revision: IsaRevision,
+                          start: u64,
+                          end: u64) -> String {
+    let mut text = String::new();
+    let mut pc = start & !3;
+    while pc < end {
+        let instruction = match memory.translate_address(cpu, pc)
+                .and_then(|address| memory.read_instruction(address)) {
+            None => break,
+            Some(instruction) => instruction,
+        };
+        text += &format!("{:016x}: {:08x}  {}\n", pc, instruction,
+                         disassemble(instruction, pc, revision));
+        pc += 4;
+    }
+    text
+}
@@ -0,0 +1,529 @@
+use crate::computer::decoder::{AccumulateOp, AppendOp, DspCompareOp,
+                               DspCondition, DspOp, DspShiftOp, DspUnaryOp,
+                               ExtractOp};
+
+// DSPControl fields. EXTP and friends extract from POS and INSV inserts
+// there, SCOUNT bits long. EFI says the last EXTP ran out of bits, and the
+// condition bits are set by the compares and read by PICK.
+const POS: u32 = 0x3f;
+const SCOUNT_SHIFT: u32 = 7;
+const SCOUNT: u32 = 0x3f << SCOUNT_SHIFT;
+const CARRY: u32 = 0x1 << 13;
+const EFI: u32 = 0x1 << 14;
+const OUFLAG: u32 = 0xff << 16;
+const CCOND_SHIFT: u32 = 24;
+const CCOND: u32 = 0xff << CCOND_SHIFT;
+
+// The overflow flag each kind of operation sets. Nothing but WRDSP clears
+// them. The accumulator ops set the one for their accumulator.
+const ACCUMULATOR_OVERFLOW: u32 = 16;
+const ARITHMETIC_OVERFLOW: u32 = 20;
+const MULTIPLY_OVERFLOW: u32 = 21;
+const SHIFT_OVERFLOW: u32 = 22;
+const EXTRACT_OVERFLOW: u32 = 23;
+
+// The fields WRDSP and RDDSP pick with each bit of their mask.
+const MASK_FIELDS: [u32; 6] = [POS, SCOUNT, CARRY, OUFLAG, CCOND, EFI];
+
+// How a register splits into lanes. Lanes are numbered from the least
+// significant end, so lane 1 of a PH is its left halfword.
+#[derive(Clone, Copy)]
+struct Lanes {
+    bits: u32,
+    signed: bool,
+}
+
+const QB: Lanes = Lanes { bits: 8, signed: false };
+const QB_SIGNED: Lanes = Lanes { bits: 8, signed: true };
+const PH: Lanes = Lanes { bits: 16, signed: true };
+const PH_UNSIGNED: Lanes = Lanes { bits: 16, signed: false };
+const W: Lanes = Lanes { bits: 32, signed: true };
+
+impl Lanes {
+    fn count(self) -> u32 {
+        32 / self.bits
+    }
+
+    fn get(self, value: u32, index: u32) -> i64 {
+        let lane = (value as i64 >> (index * self.bits)) & mask(self.bits);
+        if self.signed {
+            (lane << (64 - self.bits)) >> (64 - self.bits)
+        } else {
+            lane
+        }
+    }
+
+    fn limits(self) -> (i64, i64) {
+        if self.signed {
+            (-(1 << (self.bits - 1)), (1 << (self.bits - 1)) - 1)
+        } else {
+            (0, mask(self.bits))
+        }
+    }
+}
+
+fn mask(bits: u32) -> i64 {
+    (1 << bits) - 1
+}
+
+// Builds a register from its lanes, truncating each one to fit.
+fn from_lanes(lanes: Lanes, mut lane: impl FnMut(u32) -> i64) -> u32 {
+    (0..lanes.count()).fold(0, |value, i| {
+        value | (((lane(i) & mask(lanes.bits)) as u32) << (i * lanes.bits))
+    })
+}
+
+fn map(a: u32, lanes: Lanes, mut f: impl FnMut(i64) -> i64) -> u32 {
+    from_lanes(lanes, |i| f(lanes.get(a, i)))
+}
+
+fn lanewise(a: u32,
+            b: u32,
+            lanes: Lanes,
+            mut f: impl FnMut(i64, i64) -> i64) -> u32 {
+    from_lanes(lanes, |i| f(lanes.get(a, i), lanes.get(b, i)))
+}
+
+// Notes in `overflow` whether a result doesn't fit its lane, and clamps it
+// if the op saturates. Otherwise it's left to wrap.
+fn fit(value: i64, lanes: Lanes, saturating: bool, overflow: &mut bool)
+    -> i64 {
+    let (low, high) = lanes.limits();
+    if value < low || value > high {
+        *overflow = true;
+    }
+    if saturating { value.clamp(low, high) } else { value }
+}
+
+// A right shift that rounds to nearest by adding the last bit shifted out.
+fn rounded_shift(value: i128, shift: u32) -> i128 {
+    if shift == 0 {
+        value
+    } else {
+        (value >> shift) + ((value >> (shift - 1)) & 1)
+    }
+}
+
+// Q15 and Q31 fractions multiply into ones twice as wide. Minus one times
+// minus one doesn't fit, so it saturates.
+fn q15_product(a: i64, b: i64, overflow: &mut bool) -> i64 {
+    if a == -0x8000 && b == -0x8000 {
+        *overflow = true;
+        0x7fffffff
+    } else {
+        (a * b) << 1
+    }
+}
+
+fn q31_product(a: i64, b: i64, overflow: &mut bool) -> i64 {
+    if a == -0x80000000 && b == -0x80000000 {
+        *overflow = true;
+        i64::MAX
+    } else {
+        (a * b) << 1
+    }
+}
+
+fn set_flag(control: &mut u32, flag: u32, overflow: bool) {
+    if overflow {
+        *control |= 1 << flag;
+    }
+}
+
+pub fn pos(control: u32) -> u32 {
+    control & POS
+}
+
+// What rd gets from an operation on rs and rt.
+pub fn operate(op: DspOp, a: u32, b: u32, control: &mut u32) -> u32 {
+    let mut overflow = false;
+    let o = &mut overflow;
+    let halves = |value: u32| (PH.get(value, 1), PH.get(value, 0));
+    let result = match op {
+        DspOp::AdduQb => lanewise(a, b, QB, |x, y| fit(x + y, QB, false, o)),
+        DspOp::AdduSQb => lanewise(a, b, QB, |x, y| fit(x + y, QB, true, o)),
+        DspOp::SubuQb => lanewise(a, b, QB, |x, y| fit(x - y, QB, false, o)),
+        DspOp::SubuSQb => lanewise(a, b, QB, |x, y| fit(x - y, QB, true, o)),
+        DspOp::AdduPh => lanewise(a, b, PH_UNSIGNED,
+                                  |x, y| fit(x + y, PH_UNSIGNED, false, o)),
+        DspOp::AdduSPh => lanewise(a, b, PH_UNSIGNED,
+                                   |x, y| fit(x + y, PH_UNSIGNED, true, o)),
+        DspOp::SubuPh => lanewise(a, b, PH_UNSIGNED,
+                                  |x, y| fit(x - y, PH_UNSIGNED, false, o)),
+        DspOp::SubuSPh => lanewise(a, b, PH_UNSIGNED,
+                                   |x, y| fit(x - y, PH_UNSIGNED, true, o)),
+        DspOp::AddqPh => lanewise(a, b, PH, |x, y| fit(x + y, PH, false, o)),
+        DspOp::AddqSPh => lanewise(a, b, PH, |x, y| fit(x + y, PH, true, o)),
+        DspOp::SubqPh => lanewise(a, b, PH, |x, y| fit(x - y, PH, false, o)),
+        DspOp::SubqSPh => lanewise(a, b, PH, |x, y| fit(x - y, PH, true, o)),
+        DspOp::AddqSW => lanewise(a, b, W, |x, y| fit(x + y, W, true, o)),
+        DspOp::SubqSW => lanewise(a, b, W, |x, y| fit(x - y, W, true, o)),
+        DspOp::Addsc => {
+            let sum = a as u64 + b as u64;
+            *control = (*control & !CARRY) | ((sum >> 32) as u32 * CARRY);
+            sum as u32
+        },
+        DspOp::Addwc => {
+            let carry = (*control & CARRY != 0) as i64;
+            fit(W.get(a, 0) + W.get(b, 0) + carry, W, false, o) as u32
+        },
+        // A circular buffer index in rs steps down by the low byte of rt,
+        // and wraps from zero to the last index in the 16 bits above it.
+        DspOp::Modsub => if a == 0 {
+            (b >> 8) & 0xffff
+        } else {
+            a.wrapping_sub(b & 0xff)
+        },
+        DspOp::AdduhQb => lanewise(a, b, QB, |x, y| (x + y) >> 1),
+        DspOp::AdduhRQb => lanewise(a, b, QB, |x, y| (x + y + 1) >> 1),
+        DspOp::SubuhQb => lanewise(a, b, QB, |x, y| (x - y) >> 1),
+        DspOp::SubuhRQb => lanewise(a, b, QB, |x, y| (x - y + 1) >> 1),
+        DspOp::AddqhPh => lanewise(a, b, PH, |x, y| (x + y) >> 1),
+        DspOp::AddqhRPh => lanewise(a, b, PH, |x, y| (x + y + 1) >> 1),
+        DspOp::SubqhPh => lanewise(a, b, PH, |x, y| (x - y) >> 1),
+        DspOp::SubqhRPh => lanewise(a, b, PH, |x, y| (x - y + 1) >> 1),
+        DspOp::AddqhW => lanewise(a, b, W, |x, y| (x + y) >> 1),
+        DspOp::AddqhRW => lanewise(a, b, W, |x, y| (x + y + 1) >> 1),
+        DspOp::SubqhW => lanewise(a, b, W, |x, y| (x - y) >> 1),
+        DspOp::SubqhRW => lanewise(a, b, W, |x, y| (x - y + 1) >> 1),
+        DspOp::MuleuSPhQbl | DspOp::MuleuSPhQbr => {
+            let first = if op == DspOp::MuleuSPhQbl { 2 } else { 0 };
+            from_lanes(PH_UNSIGNED, |i| {
+                let product = QB.get(a, first + i) * PH_UNSIGNED.get(b, i);
+                fit(product, PH_UNSIGNED, true, o)
+            })
+        },
+        DspOp::MulqRsPh => lanewise(a, b, PH, |x, y| {
+            ((q15_product(x, y, o) + 0x8000) >> 16).min(0x7fff)
+        }),
+        DspOp::MulqSPh =>
+            lanewise(a, b, PH, |x, y| q15_product(x, y, o) >> 16),
+        DspOp::MuleqSWPhl => q15_product(halves(a).0, halves(b).0, o) as u32,
+        DspOp::MuleqSWPhr => q15_product(halves(a).1, halves(b).1, o) as u32,
+        DspOp::MulPh => lanewise(a, b, PH, |x, y| fit(x * y, PH, false, o)),
+        DspOp::MulSPh => lanewise(a, b, PH, |x, y| fit(x * y, PH, true, o)),
+        DspOp::MulqSW =>
+            (q31_product(W.get(a, 0), W.get(b, 0), o) >> 32) as u32,
+        DspOp::MulqRsW => {
+            let product = q31_product(W.get(a, 0), W.get(b, 0), o);
+            ((product as i128 + 0x80000000) >> 32).min(0x7fffffff) as u32
+        },
+        // The condition bits pick lanes from rs and clear ones from rt.
+        DspOp::PickQb | DspOp::PickPh => {
+            let lanes = if op == DspOp::PickQb { QB } else { PH };
+            let conditions = *control >> CCOND_SHIFT;
+            from_lanes(lanes, |i| if conditions & (1 << i) != 0 {
+                lanes.get(a, i)
+            } else {
+                lanes.get(b, i)
+            })
+        },
+        DspOp::PackrlPh => (a << 16) | (b >> 16),
+        DspOp::PrecrQbPh => (QB.get(a, 2) << 24 | QB.get(a, 0) << 16 |
+                             QB.get(b, 2) << 8 | QB.get(b, 0)) as u32,
+        DspOp::PrecrqQbPh => (QB.get(a, 3) << 24 | QB.get(a, 1) << 16 |
+                              QB.get(b, 3) << 8 | QB.get(b, 1)) as u32,
+        DspOp::PrecrqPhW => (a & 0xffff0000) | (b >> 16),
+        DspOp::PrecrqRsPhW => {
+            let round = |word: u32, o: &mut bool| {
+                fit((W.get(word, 0) + 0x8000) >> 16, PH, true, o) as u32
+            };
+            (round(a, o) << 16) | (round(b, o) & 0xffff)
+        },
+        // Q15 fractions to unsigned bytes. Negative ones saturate to zero,
+        // and ones above 0x7f80 to 0xff.
+        DspOp::PrecrquSQbPh => {
+            let halves = [PH.get(b, 0), PH.get(b, 1), PH.get(a, 0),
+                          PH.get(a, 1)];
+            from_lanes(QB, |i| {
+                let half = halves[i as usize];
+                if !(0..=0x7f80).contains(&half) {
+                    *o = true;
+                }
+                (half >> 7).clamp(0, 0xff)
+            })
+        },
+    };
+    let flag = match op {
+        DspOp::MuleuSPhQbl | DspOp::MuleuSPhQbr | DspOp::MulqRsPh |
+        DspOp::MulqSPh | DspOp::MuleqSWPhl | DspOp::MuleqSWPhr |
+        DspOp::MulPh | DspOp::MulSPh | DspOp::MulqSW | DspOp::MulqRsW =>
+            MULTIPLY_OVERFLOW,
+        DspOp::PrecrqRsPhW | DspOp::PrecrquSQbPh => SHIFT_OVERFLOW,
+        _ => ARITHMETIC_OVERFLOW,
+    };
+    set_flag(control, flag, overflow);
+    result
+}
+
+// What rd gets from an operation on one register.
+pub fn unary(op: DspUnaryOp, value: u32, control: &mut u32) -> u32 {
+    let mut overflow = false;
+    let o = &mut overflow;
+    let bytes = |first: u32, second: u32, shift: u32| {
+        (QB.get(value, first) << (16 + shift) |
+         QB.get(value, second) << shift) as u32
+    };
+    let result = match op {
+        DspUnaryOp::AbsqSQb =>
+            map(value, QB_SIGNED, |x| fit(x.abs(), QB_SIGNED, true, o)),
+        DspUnaryOp::AbsqSPh => map(value, PH, |x| fit(x.abs(), PH, true, o)),
+        DspUnaryOp::AbsqSW => map(value, W, |x| fit(x.abs(), W, true, o)),
+        DspUnaryOp::PreceqWPhl => value & 0xffff0000,
+        DspUnaryOp::PreceqWPhr => value << 16,
+        DspUnaryOp::PrecequPhQbl => bytes(3, 2, 7),
+        DspUnaryOp::PrecequPhQbr => bytes(1, 0, 7),
+        DspUnaryOp::PrecequPhQbla => bytes(3, 1, 7),
+        DspUnaryOp::PrecequPhQbra => bytes(2, 0, 7),
+        DspUnaryOp::PreceuPhQbl => bytes(3, 2, 0),
+        DspUnaryOp::PreceuPhQbr => bytes(1, 0, 0),
+        DspUnaryOp::PreceuPhQbla => bytes(3, 1, 0),
+        DspUnaryOp::PreceuPhQbra => bytes(2, 0, 0),
+        DspUnaryOp::Bitrev => (value as u16).reverse_bits() as u32,
+        DspUnaryOp::ReplQb => (value & 0xff) * 0x01010101,
+        DspUnaryOp::ReplPh => (value & 0xffff) * 0x00010001,
+        DspUnaryOp::RadduWQb => (0..4).map(|i| QB.get(value, i) as u32).sum(),
+    };
+    set_flag(control, ARITHMETIC_OVERFLOW, overflow);
+    result
+}
+
+// Compares each lane of rs with rt's, returning a bit for each lane with
+// lane 0's in bit 0. CMP compares signed halfwords and the others unsigned
+// bytes. All but CMPGU also leave the bits in DSPControl.
+pub fn compare(op: DspCompareOp,
+               condition: DspCondition,
+               a: u32,
+               b: u32,
+               control: &mut u32) -> u32 {
+    let lanes = if op == DspCompareOp::Cmp { PH } else { QB };
+    let result = (0..lanes.count()).fold(0, |result, i| {
+        let (x, y) = (lanes.get(a, i), lanes.get(b, i));
+        let holds = match condition {
+            DspCondition::Eq => x == y,
+            DspCondition::Lt => x < y,
+            DspCondition::Le => x <= y,
+        };
+        result | ((holds as u32) << i)
+    });
+    if op != DspCompareOp::Cmpgu {
+        let field = ((1 << lanes.count()) - 1) << CCOND_SHIFT;
+        *control = (*control & !field) | (result << CCOND_SHIFT);
+    }
+    result
+}
+
+// Shifts each lane of rt. Only as many bits of the shift amount as it
+// takes to shift a lane are used. Left shifts note any bits they lose, and
+// the _S ones saturate.
+pub fn shift(op: DspShiftOp, value: u32, sa: u32, control: &mut u32) -> u32 {
+    let lanes = match op {
+        DspShiftOp::ShllQb | DspShiftOp::ShrlQb => QB,
+        DspShiftOp::ShraQb | DspShiftOp::ShraRQb => QB_SIGNED,
+        DspShiftOp::ShrlPh => PH_UNSIGNED,
+        DspShiftOp::ShllPh | DspShiftOp::ShllSPh | DspShiftOp::ShraPh |
+        DspShiftOp::ShraRPh => PH,
+        DspShiftOp::ShllSW | DspShiftOp::ShraRW => W,
+    };
+    let sa = sa & (lanes.bits - 1);
+    let mut overflow = false;
+    let o = &mut overflow;
+    let result = match op {
+        DspShiftOp::ShllQb | DspShiftOp::ShllPh =>
+            map(value, lanes, |x| fit(x << sa, lanes, false, o)),
+        DspShiftOp::ShllSPh | DspShiftOp::ShllSW =>
+            map(value, lanes, |x| fit(x << sa, lanes, true, o)),
+        DspShiftOp::ShrlQb | DspShiftOp::ShrlPh | DspShiftOp::ShraQb |
+        DspShiftOp::ShraPh => map(value, lanes, |x| x >> sa),
+        DspShiftOp::ShraRQb | DspShiftOp::ShraRPh | DspShiftOp::ShraRW =>
+            map(value, lanes, |x| rounded_shift(x as i128, sa) as i64),
+    };
+    set_flag(control, SHIFT_OVERFLOW, overflow);
+    result
+}
+
+// PRECR_SRA.PH.W packs rt and rs shifted right, or with rounding, into
+// the left and right halves of rt.
+pub fn precr_sra(round: bool, rt: u32, rs: u32, sa: u32) -> u32 {
+    let shift = |word: u32| {
+        let word = W.get(word, 0) as i128;
+        let shifted = if round {
+            rounded_shift(word, sa)
+        } else {
+            word >> sa
+        };
+        shifted as u32 & 0xffff
+    };
+    (shift(rt) << 16) | shift(rs)
+}
+
+// Adds to or subtracts from an accumulator. The _SA ops saturate the
+// result to a Q31 fraction, or DPAQ_SA.L.W and DPSQ_SA.L.W to a Q63 one.
+pub fn accumulate(op: AccumulateOp,
+                  ac: usize,
+                  accumulator: i64,
+                  a: u32,
+                  b: u32,
+                  control: &mut u32) -> i64 {
+    let mut overflow = false;
+    let o = &mut overflow;
+    let (a1, a0, b1, b0) = (PH.get(a, 1), PH.get(a, 0), PH.get(b, 1),
+                            PH.get(b, 0));
+    let bytes = |first: u32| {
+        QB.get(a, first + 1) * QB.get(b, first + 1) +
+            QB.get(a, first) * QB.get(b, first)
+    };
+    let saturate = |value: i128, bits: u32, o: &mut bool| {
+        let limit = 1i128 << (bits - 1);
+        if value < -limit || value >= limit {
+            *o = true;
+        }
+        value.clamp(-limit, limit - 1)
+    };
+    let acc = accumulator as i128;
+    let result = match op {
+        AccumulateOp::DpauHQbl => acc + bytes(2) as i128,
+        AccumulateOp::DpauHQbr => acc + bytes(0) as i128,
+        AccumulateOp::DpsuHQbl => acc - bytes(2) as i128,
+        AccumulateOp::DpsuHQbr => acc - bytes(0) as i128,
+        AccumulateOp::DpaWPh => acc + (a1 * b1 + a0 * b0) as i128,
+        AccumulateOp::DpaxWPh => acc + (a1 * b0 + a0 * b1) as i128,
+        AccumulateOp::DpsWPh => acc - (a1 * b1 + a0 * b0) as i128,
+        AccumulateOp::DpsxWPh => acc - (a1 * b0 + a0 * b1) as i128,
+        AccumulateOp::MulsaWPh => acc + (a1 * b1 - a0 * b0) as i128,
+        AccumulateOp::DpaqSWPh => acc + (q15_product(a1, b1, o) +
+                                         q15_product(a0, b0, o)) as i128,
+        AccumulateOp::DpaqxSWPh => acc + (q15_product(a1, b0, o) +
+                                          q15_product(a0, b1, o)) as i128,
+        AccumulateOp::DpsqSWPh => acc - (q15_product(a1, b1, o) +
+                                         q15_product(a0, b0, o)) as i128,
+        AccumulateOp::DpsqxSWPh => acc - (q15_product(a1, b0, o) +
+                                          q15_product(a0, b1, o)) as i128,
+        AccumulateOp::MulsaqSWPh => acc + (q15_product(a1, b1, o) -
+                                           q15_product(a0, b0, o)) as i128,
+        AccumulateOp::DpaqxSaWPh => {
+            let sum = q15_product(a1, b0, o) + q15_product(a0, b1, o);
+            saturate(acc + sum as i128, 32, o)
+        },
+        AccumulateOp::DpsqxSaWPh => {
+            let sum = q15_product(a1, b0, o) + q15_product(a0, b1, o);
+            saturate(acc - sum as i128, 32, o)
+        },
+        AccumulateOp::DpaqSaLW => {
+            let product = q31_product(W.get(a, 0), W.get(b, 0), o);
+            saturate(acc + product as i128, 64, o)
+        },
+        AccumulateOp::DpsqSaLW => {
+            let product = q31_product(W.get(a, 0), W.get(b, 0), o);
+            saturate(acc - product as i128, 64, o)
+        },
+        AccumulateOp::MaqSWPhl => acc + q15_product(a1, b1, o) as i128,
+        AccumulateOp::MaqSWPhr => acc + q15_product(a0, b0, o) as i128,
+        AccumulateOp::MaqSaWPhl =>
+            saturate(acc + q15_product(a1, b1, o) as i128, 32, o),
+        AccumulateOp::MaqSaWPhr =>
+            saturate(acc + q15_product(a0, b0, o) as i128, 32, o),
+    };
+    set_flag(control, ACCUMULATOR_OVERFLOW + ac as u32, overflow);
+    result as i64
+}
+
+// What rt gets from an accumulator. The EXTR ops shift it right by
+// `amount` and note when the result, or its rounded version, doesn't fit.
+// EXTP and EXTPDP take amount plus one bits from POS down, and EXTPDP
+// moves POS past them. They come back empty, with EFI set, if there
+// aren't enough bits below POS.
+pub fn extract(op: ExtractOp, accumulator: i64, amount: u32, control: &mut u32)
+    -> Option<u64> {
+    let amount = amount & 0x1f;
+    let acc = accumulator as i128;
+    let truncated = acc >> amount;
+    let rounded = rounded_shift(acc, amount);
+    let word = |value: i128| value as i32 as i64 as u64;
+    let fits = |value: i128| value == value as i32 as i128;
+    let mut overflow = !fits(truncated) || !fits(rounded);
+    let result = match op {
+        ExtractOp::ExtrW => word(truncated),
+        ExtractOp::ExtrRW => word(rounded),
+        ExtractOp::ExtrRsW =>
+            word(rounded.clamp(i32::MIN as i128, i32::MAX as i128)),
+        ExtractOp::ExtrSH => {
+            overflow = truncated != truncated as i16 as i128;
+            truncated.clamp(i16::MIN as i128, i16::MAX as i128) as u64
+        },
+        ExtractOp::Extp | ExtractOp::Extpdp => {
+            let pos = pos(*control);
+            if pos < amount {
+                *control |= EFI;
+                return None;
+            }
+            *control &= !EFI;
+            if op == ExtractOp::Extpdp {
+                let pos = pos.wrapping_sub(amount + 1) & POS;
+                *control = (*control & !POS) | pos;
+            }
+            let bits = (accumulator as u64) >> (pos - amount);
+            return Some(bits & mask(amount + 1) as u64);
+        },
+    };
+    set_flag(control, EXTRACT_OVERFLOW, overflow);
+    Some(result)
+}
+
+// SHILO shifts the whole accumulator right, or left for a negative shift.
+pub fn shilo(accumulator: i64, shift: i64) -> i64 {
+    let value = accumulator as u64;
+    if shift >= 0 {
+        (value >> shift) as i64
+    } else {
+        (value << -shift) as i64
+    }
+}
+
+// MTHLIP moves LO up to HI and rs into LO, and moves POS along to match.
+pub fn mthlip(accumulator: i64, value: u32, control: &mut u32) -> i64 {
+    let pos = pos(*control);
+    if pos <= 32 {
+        *control = (*control & !POS) | (pos + 32);
+    }
+    (accumulator << 32) | value as i64
+}
+
+pub fn read_control(control: u32, mask: u32) -> u32 {
+    control & fields(mask)
+}
+
+pub fn write_control(control: u32, value: u32, mask: u32) -> u32 {
+    let fields = fields(mask);
+    (control & !fields) | (value & fields)
+}
+
+fn fields(mask: u32) -> u32 {
+    (0..MASK_FIELDS.len())
+        .filter(|i| mask & (1 << i) != 0)
+        .fold(0, |fields, i| fields | MASK_FIELDS[i])
+}
+
+// INSV puts the low SCOUNT bits of rs into rt at POS. A field that
+// doesn't fit leaves rt alone.
+pub fn insv(rt: u32, rs: u32, control: u32) -> u32 {
+    let pos = pos(control);
+    let size = (control & SCOUNT) >> SCOUNT_SHIFT;
+    if size == 0 || pos + size > 32 {
+        return rt;
+    }
+    let field = (mask(size) as u32) << pos;
+    (rt & !field) | ((rs << pos) & field)
+}
+
+// APPEND shifts rt left and fills it from the bottom of rs, PREPEND shifts
+// it right and fills it from the top, and BALIGN does the same as APPEND
+// a byte at a time from the top of rs.
+pub fn append(op: AppendOp, rt: u32, rs: u32, sa: u32) -> u32 {
+    match op {
+        _ if sa == 0 => rt,
+        AppendOp::Append => (rt << sa) | (rs & mask(sa) as u32),
+        AppendOp::Prepend => (rs << (32 - sa)) | (rt >> sa),
+        AppendOp::Balign => (rt << (8 * sa)) | (rs >> (8 * (4 - sa))),
+    }
+}
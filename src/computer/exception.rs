@@ -0,0 +1,172 @@
+use std::fmt;
+
+// Everything that can stop a CPU in its tracks. Each exception remembers the
+// address of the instruction that caused it, and the ones caused by a memory
+// access also remember the virtual address that was being accessed. Traps
+// and breakpoints carry the code field of the instruction that raised them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Exception {
+    // Instruction fetches count as loads.
+    AddressErrorLoad { pc: u64, address: u64 },
+    AddressErrorStore { pc: u64, address: u64 },
+    // The address translated fine but there's no memory behind it.
+    BusErrorInstruction { pc: u64, address: u64 },
+    BusErrorData { pc: u64, address: u64 },
+    // A TLB miss, and then a hit on a page that isn't valid, or a store to
+    // one that isn't dirty.
+    TlbLoad { pc: u64, address: u64 },
+    TlbStore { pc: u64, address: u64 },
+    TlbInvalidLoad { pc: u64, address: u64 },
+    TlbInvalidStore { pc: u64, address: u64 },
+    TlbModified { pc: u64, address: u64 },
+    ReservedInstruction { pc: u64 },
+    // A coprocessor instruction run without the right to use the unit.
+    CoprocessorUnusable { pc: u64, unit: u32 },
+    IntegerOverflow { pc: u64 },
+    Syscall { pc: u64 },
+    Breakpoint { pc: u64, code: u32 },
+    Trap { pc: u64, code: u32 },
+    FloatingPoint { pc: u64 },
+    MsaFloatingPoint { pc: u64 },
+    MsaDisabled { pc: u64 },
+    DspDisabled { pc: u64 },
+    // SDBBP, which goes to the debug handler rather than the general one.
+    DebugBreakpoint { pc: u64, code: u32 },
+}
+
+impl Exception {
+    pub fn pc(&self) -> u64 {
+        match *self {
+            Exception::AddressErrorLoad { pc, .. } |
+            Exception::AddressErrorStore { pc, .. } |
+            Exception::BusErrorInstruction { pc, .. } |
+            Exception::BusErrorData { pc, .. } |
+            Exception::TlbLoad { pc, .. } |
+            Exception::TlbStore { pc, .. } |
+            Exception::TlbInvalidLoad { pc, .. } |
+            Exception::TlbInvalidStore { pc, .. } |
+            Exception::TlbModified { pc, .. } |
+            Exception::ReservedInstruction { pc } |
+            Exception::CoprocessorUnusable { pc, .. } |
+            Exception::IntegerOverflow { pc } |
+            Exception::Syscall { pc } |
+            Exception::Breakpoint { pc, .. } |
+            Exception::Trap { pc, .. } |
+            Exception::FloatingPoint { pc } |
+            Exception::MsaFloatingPoint { pc } |
+            Exception::MsaDisabled { pc } |
+            Exception::DspDisabled { pc } |
+            Exception::DebugBreakpoint { pc, .. } => pc,
+        }
+    }
+
+    // The code field of a BREAK, SDBBP or trap, which tells a debugger or
+    // the program's handler why it stopped.
+    pub fn trap_code(&self) -> Option<u32> {
+        match *self {
+            Exception::Breakpoint { code, .. } |
+            Exception::Trap { code, .. } |
+            Exception::DebugBreakpoint { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    // The ExcCode value CP0's Cause register uses for this exception. Debug
+    // exceptions leave Cause alone; SDBBP is closest to a breakpoint.
+    pub fn code(&self) -> u64 {
+        match self {
+            Exception::TlbModified { .. } => 0x01,
+            Exception::TlbLoad { .. } |
+            Exception::TlbInvalidLoad { .. } => 0x02,
+            Exception::TlbStore { .. } |
+            Exception::TlbInvalidStore { .. } => 0x03,
+            Exception::AddressErrorLoad { .. } => 0x04,
+            Exception::AddressErrorStore { .. } => 0x05,
+            Exception::BusErrorInstruction { .. } => 0x06,
+            Exception::BusErrorData { .. } => 0x07,
+            Exception::Syscall { .. } => 0x08,
+            Exception::Breakpoint { .. } |
+            Exception::DebugBreakpoint { .. } => 0x09,
+            Exception::ReservedInstruction { .. } => 0x0a,
+            Exception::CoprocessorUnusable { .. } => 0x0b,
+            Exception::IntegerOverflow { .. } => 0x0c,
+            Exception::Trap { .. } => 0x0d,
+            Exception::MsaFloatingPoint { .. } => 0x0e,
+            Exception::FloatingPoint { .. } => 0x0f,
+            Exception::MsaDisabled { .. } => 0x15,
+            Exception::DspDisabled { .. } => 0x1a,
+        }
+    }
+
+    // The virtual address the exception was caused by, if there is one.
+    pub fn address(&self) -> Option<u64> {
+        match *self {
+            Exception::BusErrorInstruction { address, .. } |
+            Exception::BusErrorData { address, .. } => Some(address),
+            _ => self.bad_address(),
+        }
+    }
+
+    // What goes in CP0's BadVAddr. Bus errors leave it alone.
+    pub fn bad_address(&self) -> Option<u64> {
+        match *self {
+            Exception::AddressErrorLoad { address, .. } |
+            Exception::AddressErrorStore { address, .. } |
+            Exception::TlbLoad { address, .. } |
+            Exception::TlbStore { address, .. } |
+            Exception::TlbInvalidLoad { address, .. } |
+            Exception::TlbInvalidStore { address, .. } |
+            Exception::TlbModified { address, .. } => Some(address),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Exception::AddressErrorLoad { .. } => "address error on load",
+            Exception::AddressErrorStore { .. } => "address error on store",
+            Exception::BusErrorInstruction { .. } =>
+                "bus error on instruction fetch",
+            Exception::BusErrorData { .. } => "bus error on data access",
+            Exception::TlbLoad { .. } => "TLB miss on load",
+            Exception::TlbStore { .. } => "TLB miss on store",
+            Exception::TlbInvalidLoad { .. } => "invalid TLB entry on load",
+            Exception::TlbInvalidStore { .. } => "invalid TLB entry on store",
+            Exception::TlbModified { .. } => "store to a clean TLB entry",
+            Exception::ReservedInstruction { .. } => "reserved instruction",
+            Exception::CoprocessorUnusable { .. } => "coprocessor unusable",
+            Exception::IntegerOverflow { .. } => "integer overflow",
+            Exception::Syscall { .. } => "syscall",
+            Exception::Breakpoint { .. } => "breakpoint",
+            Exception::Trap { .. } => "trap",
+            Exception::FloatingPoint { .. } => "floating-point exception",
+            Exception::MsaFloatingPoint { .. } =>
+                "MSA floating-point exception",
+            Exception::MsaDisabled { .. } => "MSA disabled",
+            Exception::DspDisabled { .. } => "DSP disabled",
+            Exception::DebugBreakpoint { .. } => "debug breakpoint",
+        };
+        write!(f, "{}", name)?;
+        match *self {
+            // BREAK's code is written as its two 10-bit halves, like the
+            // assembler takes it.
+            Exception::Breakpoint { code: 0, .. } => {},
+            Exception::Breakpoint { code, .. } if code & 0x3ff == 0 =>
+                write!(f, " {}", code >> 10)?,
+            Exception::Breakpoint { code, .. } =>
+                write!(f, " {}, {}", code >> 10, code & 0x3ff)?,
+            Exception::CoprocessorUnusable { unit, .. } =>
+                write!(f, " ({})", unit)?,
+            _ => if let Some(code) = self.trap_code().filter(|&c| c != 0) {
+                write!(f, " {}", code)?;
+            },
+        }
+        write!(f, " at pc {:#x}", self.pc())?;
+        if let Some(address) = self.address() {
+            write!(f, " (address {:#x})", address)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,756 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::computer::cpu::Cpu;
+use crate::computer::memory::Memory;
+use crate::computer::program::{Program, ProgramError};
+use crate::computer::syscall::{SyscallHandler, SyscallResult};
+
+// Registers
+const V0: usize = 2;
+const A0: usize = 4;
+const A1: usize = 5;
+const A2: usize = 6;
+const A3: usize = 7;
+const A4: usize = 8;
+const A5: usize = 9;
+const SP: usize = 29;
+
+// n64 syscall numbers, passed in $v0
+const READ: u64 = 5000;
+const WRITE: u64 = 5001;
+const OPEN: u64 = 5002;
+const CLOSE: u64 = 5003;
+const STAT: u64 = 5004;
+const FSTAT: u64 = 5005;
+const LSTAT: u64 = 5006;
+const LSEEK: u64 = 5008;
+const MMAP: u64 = 5009;
+const MPROTECT: u64 = 5010;
+const MUNMAP: u64 = 5011;
+const BRK: u64 = 5012;
+const RT_SIGACTION: u64 = 5013;
+const RT_SIGPROCMASK: u64 = 5014;
+const IOCTL: u64 = 5015;
+const READV: u64 = 5018;
+const WRITEV: u64 = 5019;
+const ACCESS: u64 = 5020;
+const SCHED_YIELD: u64 = 5023;
+const MREMAP: u64 = 5024;
+const MADVISE: u64 = 5027;
+const NANOSLEEP: u64 = 5034;
+const GETPID: u64 = 5038;
+const EXIT: u64 = 5058;
+const UNAME: u64 = 5061;
+const GETCWD: u64 = 5077;
+const GETTIMEOFDAY: u64 = 5094;
+const GETUID: u64 = 5100;
+const GETGID: u64 = 5102;
+const GETEUID: u64 = 5105;
+const GETEGID: u64 = 5106;
+const GETPPID: u64 = 5108;
+const SIGALTSTACK: u64 = 5129;
+const GETTID: u64 = 5178;
+const TKILL: u64 = 5192;
+const FUTEX: u64 = 5194;
+const EXIT_GROUP: u64 = 5205;
+const SET_TID_ADDRESS: u64 = 5212;
+const CLOCK_GETTIME: u64 = 5222;
+const CLOCK_NANOSLEEP: u64 = 5224;
+const TGKILL: u64 = 5225;
+const SET_THREAD_AREA: u64 = 5242;
+const OPENAT: u64 = 5247;
+const NEWFSTATAT: u64 = 5252;
+const READLINKAT: u64 = 5257;
+const FACCESSAT: u64 = 5259;
+const PRLIMIT64: u64 = 5297;
+const GETRANDOM: u64 = 5313;
+
+// Error numbers. The low ones are the same on every Linux architecture, but
+// MIPS has its own numbering past 34.
+const ENOENT: u64 = 2;
+const EIO: u64 = 5;
+const EBADF: u64 = 9;
+const ENOMEM: u64 = 12;
+const EACCES: u64 = 13;
+const EFAULT: u64 = 14;
+const EEXIST: u64 = 17;
+const ENOTDIR: u64 = 20;
+const EINVAL: u64 = 22;
+const ENOTTY: u64 = 25;
+const ESPIPE: u64 = 29;
+const ENOSYS: u64 = 89;
+
+// open() flags, MIPS values
+const O_ACCMODE: u64 = 0x3;
+const O_RDONLY: u64 = 0x0;
+const O_WRONLY: u64 = 0x1;
+const O_RDWR: u64 = 0x2;
+const O_APPEND: u64 = 0x8;
+const O_CREAT: u64 = 0x100;
+const O_TRUNC: u64 = 0x200;
+const O_EXCL: u64 = 0x400;
+
+// Other syscall constants
+const AT_FDCWD: u64 = -100i64 as u64;
+const AT_EMPTY_PATH: u64 = 0x1000;
+const MAP_FIXED: u64 = 0x10;
+const MAP_ANONYMOUS: u64 = 0x800;
+const SEEK_SET: u64 = 0;
+const SEEK_CUR: u64 = 1;
+const SEEK_END: u64 = 2;
+const S_IFCHR: u64 = 0o020000;
+const S_IFDIR: u64 = 0o040000;
+const S_IFREG: u64 = 0o100000;
+const RLIM_INFINITY: u64 = !0;
+
+// Auxiliary vector entry types
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_BASE: u64 = 7;
+const AT_FLAGS: u64 = 8;
+const AT_ENTRY: u64 = 9;
+const AT_UID: u64 = 11;
+const AT_EUID: u64 = 12;
+const AT_GID: u64 = 13;
+const AT_EGID: u64 = 14;
+const AT_PLATFORM: u64 = 15;
+const AT_HWCAP: u64 = 16;
+const AT_CLKTCK: u64 = 17;
+const AT_SECURE: u64 = 23;
+const AT_RANDOM: u64 = 25;
+const AT_EXECFN: u64 = 31;
+
+// Sizes of the structures we fill in for the n64 ABI
+const STAT_SIZE: usize = 104;
+const UTSNAME_FIELD_SIZE: usize = 65;
+const SIGACTION_SIZE: usize = 32;
+// MIPS has 128 signals.
+const SIGSET_SIZE: u64 = 16;
+
+// The standard file descriptors
+const STDIN: u64 = 0;
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const PAGE_SIZE: u64 = 4096;
+// Room kept free for the stack at the top of memory; mmap hands out memory
+// from just below it.
+const STACK_SIZE: u64 = 1024 * 1024;
+// There is only one process and one thread.
+const PID: u64 = 1000;
+const CLOCK_TICKS: u64 = 100;
+
+const UTSNAME: [&str; 6] = ["Linux", "mips", "6.1.0", "#1", "mips64", "(none)"];
+
+fn page_align(value: u64) -> Option<u64> {
+    value.checked_add(PAGE_SIZE - 1).map(|value| value & !(PAGE_SIZE - 1))
+}
+
+fn errno(error: io::Error) -> u64 {
+    match error.raw_os_error() {
+        Some(code) if (1..=34).contains(&code) => code as u64,
+        _ => match error.kind() {
+            ErrorKind::NotFound => ENOENT,
+            ErrorKind::PermissionDenied => EACCES,
+            ErrorKind::AlreadyExists => EEXIST,
+            ErrorKind::InvalidInput => EINVAL,
+            _ => EIO,
+        },
+    }
+}
+
+// Runs statically linked MIPS64 Linux programs the way qemu-user does: the
+// program gets a Linux-style initial stack, and its syscalls are carried out
+// on the host. Paths the program uses are resolved inside the root directory.
+pub struct Linux {
+    root: PathBuf,
+    args: Vec<String>,
+    env: Vec<String>,
+    files: HashMap<u64, File>,
+    // Where each open file is on the host, so the *at syscalls can look up
+    // paths relative to a directory.
+    paths: HashMap<u64, PathBuf>,
+    next_fd: u64,
+    brk_start: u64,
+    brk: u64,
+    // The lowest address mmap has handed out so far.
+    mmap_bottom: u64,
+    random: u64,
+}
+
+pub fn new(root: PathBuf, args: Vec<String>, env: Vec<String>) -> Linux {
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or(0);
+    Linux {
+        root,
+        args,
+        env,
+        files: HashMap::new(),
+        paths: HashMap::new(),
+        next_fd: STDERR + 1,
+        brk_start: 0,
+        brk: 0,
+        mmap_bottom: 0,
+        random: seed | 1,
+    }
+}
+
+impl Linux {
+    // xorshift64; good enough for AT_RANDOM and getrandom.
+    fn next_random(&mut self) -> u64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        self.random
+    }
+
+    fn random_bytes(&mut self, size: usize) -> Vec<u8> {
+        (0..size).map(|_| self.next_random() as u8).collect()
+    }
+
+    // Absolute paths start at the root directory, and relative ones at
+    // `directory`. Nothing can climb back out of the root.
+    fn resolve(&self, directory: &Path, path: &[u8]) -> Result<PathBuf, u64> {
+        let path = str::from_utf8(path).map_err(|_| ENOENT)?;
+        let mut resolved = if path.starts_with('/') {
+            self.root.clone()
+        } else {
+            directory.to_path_buf()
+        };
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::RootDir | Component::CurDir => {},
+                Component::ParentDir => return Err(EACCES),
+                Component::Prefix(_) => return Err(ENOENT),
+            }
+        }
+        if !self.inside_root(&resolved) {
+            return Err(EACCES);
+        }
+        Ok(resolved)
+    }
+
+    // Symlinks inside the root can still lead out of it, so wherever the
+    // part of the path that exists really is has to be under the root too.
+    // A dangling symlink could be created through, so that's out as well.
+    fn inside_root(&self, path: &Path) -> bool {
+        let root = self.root.canonicalize()
+            .unwrap_or_else(|_| self.root.clone());
+        for ancestor in path.ancestors() {
+            match ancestor.canonicalize() {
+                Ok(real) => return real.starts_with(&root),
+                Err(_) if fs::symlink_metadata(ancestor).is_ok() =>
+                    return false,
+                Err(_) => {},
+            }
+        }
+        false
+    }
+
+    // Relative paths for the *at syscalls start at the directory `dirfd`
+    // refers to, or the root for AT_FDCWD.
+    fn read_path(&self,
+                 memory: &mut Memory,
+                 id: u64,
+                 dirfd: u64,
+                 address: u64) -> Result<PathBuf, u64> {
+        let path = memory.read_string(id, address).ok_or(EFAULT)?;
+        if dirfd == AT_FDCWD || path.first() == Some(&b'/') {
+            return self.resolve(&self.root, &path);
+        }
+        let directory = self.paths.get(&dirfd).ok_or(EBADF)?;
+        if !directory.is_dir() {
+            return Err(ENOTDIR);
+        }
+        self.resolve(directory, &path)
+    }
+
+    fn open(&mut self, path: PathBuf, flags: u64) -> Result<u64, u64> {
+        let access = flags & O_ACCMODE;
+        let mut options = OpenOptions::new();
+        options.read(access == O_RDONLY || access == O_RDWR)
+            .write(access == O_WRONLY || access == O_RDWR)
+            .append(flags & O_APPEND != 0)
+            .truncate(flags & O_TRUNC != 0);
+        if flags & O_CREAT != 0 {
+            if flags & O_EXCL != 0 {
+                options.create_new(true);
+            } else {
+                options.create(true);
+            }
+        }
+        let file = options.open(&path).map_err(errno)?;
+        let fd = self.next_fd;
+        self.next_fd += 1;
+        self.files.insert(fd, file);
+        self.paths.insert(fd, path);
+        Ok(fd)
+    }
+
+    fn read(&mut self, fd: u64, size: u64) -> Result<Vec<u8>, u64> {
+        let mut buffer = vec![0; size as usize];
+        let count = match fd {
+            STDIN => io::stdin().read(&mut buffer),
+            STDOUT | STDERR => return Err(EBADF),
+            _ => self.files.get_mut(&fd).ok_or(EBADF)?.read(&mut buffer),
+        }.map_err(errno)?;
+        buffer.truncate(count);
+        Ok(buffer)
+    }
+
+    fn write(&mut self, fd: u64, bytes: &[u8]) -> Result<u64, u64> {
+        match fd {
+            STDIN => return Err(EBADF),
+            STDOUT => io::stdout().write_all(bytes)
+                .and_then(|_| io::stdout().flush()),
+            STDERR => io::stderr().write_all(bytes),
+            _ => self.files.get_mut(&fd).ok_or(EBADF)?.write_all(bytes),
+        }.map_err(errno)?;
+        Ok(bytes.len() as u64)
+    }
+
+    // The iovec array readv and writev take: pairs of base and length.
+    fn read_iovecs(memory: &mut Memory,
+                   id: u64,
+                   address: u64,
+                   count: u64) -> Result<Vec<(u64, u64)>, u64> {
+        let mut iovecs = Vec::new();
+        for i in 0..count {
+            let iovec = address.wrapping_add(i * 16);
+            let base = memory.read_virtual_value(id, iovec, 8).ok_or(EFAULT)?;
+            let size = memory.read_virtual_value(id, iovec + 8, 8)
+                .ok_or(EFAULT)?;
+            iovecs.push((base, size));
+        }
+        Ok(iovecs)
+    }
+
+    fn write_stat(memory: &mut Memory,
+                  id: u64,
+                  address: u64,
+                  mode: u64,
+                  size: u64) -> Result<u64, u64> {
+        let fields = [
+            (24, mode, 4),
+            (28, 1, 4), // st_nlink
+            (56, size, 8),
+            (88, PAGE_SIZE, 4), // st_blksize
+            (96, size.div_ceil(512), 8), // st_blocks
+        ];
+        if !memory.write_virtual(id, address, &[0; STAT_SIZE]) {
+            return Err(EFAULT);
+        }
+        for (offset, value, size) in fields {
+            memory.write_virtual_value(id, address + offset, value, size);
+        }
+        Ok(0)
+    }
+
+    fn stat_path(memory: &mut Memory,
+                 id: u64,
+                 path: PathBuf,
+                 address: u64) -> Result<u64, u64> {
+        let metadata = fs::metadata(path).map_err(errno)?;
+        let mode = if metadata.is_dir() {
+            S_IFDIR | 0o755
+        } else {
+            S_IFREG | 0o644
+        };
+        Linux::write_stat(memory, id, address, mode, metadata.len())
+    }
+
+    fn stat_fd(&mut self,
+               memory: &mut Memory,
+               id: u64,
+               fd: u64,
+               address: u64) -> Result<u64, u64> {
+        match fd {
+            STDIN | STDOUT | STDERR =>
+                Linux::write_stat(memory, id, address, S_IFCHR | 0o620, 0),
+            _ => {
+                let metadata = self.files.get(&fd).ok_or(EBADF)?
+                    .metadata().map_err(errno)?;
+                let mode = if metadata.is_dir() {
+                    S_IFDIR | 0o755
+                } else {
+                    S_IFREG | 0o644
+                };
+                Linux::write_stat(memory, id, address, mode, metadata.len())
+            },
+        }
+    }
+
+    fn lseek(&mut self, fd: u64, offset: u64, whence: u64) -> Result<u64, u64> {
+        let file = self.files.get_mut(&fd).ok_or(match fd {
+            STDIN | STDOUT | STDERR => ESPIPE,
+            _ => EBADF,
+        })?;
+        let position = match whence {
+            SEEK_SET => SeekFrom::Start(offset),
+            SEEK_CUR => SeekFrom::Current(offset as i64),
+            SEEK_END => SeekFrom::End(offset as i64),
+            _ => return Err(EINVAL),
+        };
+        file.seek(position).map_err(errno)
+    }
+
+    fn brk(&mut self, memory: &mut Memory, id: u64, address: u64) -> u64 {
+        if address >= self.brk_start && address <= self.mmap_bottom {
+            // Memory handed back and then taken again has to come back
+            // zeroed.
+            if address > self.brk {
+                if let Some(physical) = memory.translate_address(id, self.brk) {
+                    memory.fill(physical, 0, address - self.brk);
+                }
+            }
+            self.brk = address;
+        }
+        self.brk
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn mmap(&mut self,
+            memory: &mut Memory,
+            id: u64,
+            address: u64,
+            size: u64,
+            flags: u64,
+            fd: u64,
+            offset: u64) -> Result<u64, u64> {
+        let size = match page_align(size) {
+            Some(size) if size != 0 => size,
+            _ => return Err(EINVAL),
+        };
+        let address = if flags & MAP_FIXED != 0 {
+            address
+        } else {
+            let start = self.mmap_bottom.checked_sub(size).ok_or(ENOMEM)?;
+            if start < self.brk {
+                return Err(ENOMEM);
+            }
+            self.mmap_bottom = start;
+            start
+        };
+
+        let physical = memory.translate_address(id, address).ok_or(ENOMEM)?;
+        if memory.translate_address(id, address.wrapping_add(size - 1))
+                .is_none() || !memory.fill(physical, 0, size) {
+            return Err(ENOMEM);
+        }
+
+        if flags & MAP_ANONYMOUS == 0 {
+            // Read the file without disturbing its position.
+            let file = self.files.get_mut(&fd).ok_or(EBADF)?;
+            let position = file.stream_position().map_err(errno)?;
+            let mut data = Vec::new();
+            file.seek(SeekFrom::Start(offset)).map_err(errno)?;
+            file.take(size).read_to_end(&mut data).map_err(errno)?;
+            let file = self.files.get_mut(&fd).ok_or(EBADF)?;
+            file.seek(SeekFrom::Start(position)).map_err(errno)?;
+            memory.write_virtual(id, address, &data);
+        }
+        Ok(address)
+    }
+
+    fn uname(memory: &mut Memory, id: u64, address: u64) -> Result<u64, u64> {
+        for (i, field) in UTSNAME.iter().enumerate() {
+            let mut bytes = [0; UTSNAME_FIELD_SIZE];
+            bytes[..field.len()].copy_from_slice(field.as_bytes());
+            let offset = (i * UTSNAME_FIELD_SIZE) as u64;
+            if !memory.write_virtual(id, address + offset, &bytes) {
+                return Err(EFAULT);
+            }
+        }
+        Ok(0)
+    }
+
+    // Writes a timespec, or a timeval if `micro` is set.
+    fn write_time(memory: &mut Memory,
+                  id: u64,
+                  address: u64,
+                  micro: bool) -> Result<u64, u64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let fraction = if micro {
+            now.subsec_micros() as u64
+        } else {
+            now.subsec_nanos() as u64
+        };
+        if memory.write_virtual_value(id, address, now.as_secs(), 8) &&
+            memory.write_virtual_value(id, address + 8, fraction, 8) {
+            Ok(0)
+        } else {
+            Err(EFAULT)
+        }
+    }
+
+    // Copies bytes onto the stack, returning their address.
+    fn push(memory: &mut Memory, id: u64, sp: &mut u64, bytes: &[u8])
+        -> Result<u64, ProgramError> {
+        *sp = sp.checked_sub(bytes.len() as u64)
+            .ok_or(ProgramError::Stack(*sp))?;
+        if !memory.write_virtual(id, *sp, bytes) {
+            return Err(ProgramError::Stack(*sp));
+        }
+        Ok(*sp)
+    }
+
+    // Copies a NUL-terminated string onto the stack, returning its address.
+    fn push_string(memory: &mut Memory, id: u64, sp: &mut u64, string: &[u8])
+        -> Result<u64, ProgramError> {
+        Linux::push(memory, id, sp, &[string, &[0]].concat())
+    }
+}
+
+impl SyscallHandler for Linux {
+    // Lays out the stack the kernel would: argc, then the argv and envp
+    // arrays, then the auxiliary vector, with the strings they point to
+    // above them at the top of the stack.
+    fn load(&mut self, program: &Program, cpus: &mut [Cpu], memory: &mut Memory)
+        -> Result<(), ProgramError> {
+        let id = match cpus.first() {
+            None => return Ok(()),
+            Some(cpu) => cpu.id(),
+        };
+        let top = cpus[0].register(SP);
+
+        let (_, high) = program.bounds();
+        self.brk_start = page_align(high).unwrap_or(high);
+        self.brk = self.brk_start;
+        self.mmap_bottom = (top.saturating_sub(STACK_SIZE) & !(PAGE_SIZE - 1))
+            .max(self.brk_start);
+
+        let mut sp = top;
+        let random = self.random_bytes(16);
+        let random = Linux::push(memory, id, &mut sp, &random)?;
+        let platform = Linux::push_string(memory, id, &mut sp,
+                                          UTSNAME[4].as_bytes())?;
+        let name = self.args.first().cloned().unwrap_or_default();
+        let execfn = Linux::push_string(memory, id, &mut sp, name.as_bytes())?;
+        let argv = self.args.iter()
+            .map(|arg| Linux::push_string(memory, id, &mut sp, arg.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let envp = self.env.iter()
+            .map(|var| Linux::push_string(memory, id, &mut sp, var.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let auxv = [
+            (AT_PHDR, program.headers.unwrap_or(0)),
+            (AT_PHENT, program.header_size),
+            (AT_PHNUM, program.header_count),
+            (AT_PAGESZ, PAGE_SIZE),
+            (AT_BASE, 0),
+            (AT_FLAGS, 0),
+            (AT_ENTRY, program.entry),
+            (AT_UID, 0),
+            (AT_EUID, 0),
+            (AT_GID, 0),
+            (AT_EGID, 0),
+            (AT_PLATFORM, platform),
+            (AT_HWCAP, 0),
+            (AT_CLKTCK, CLOCK_TICKS),
+            (AT_SECURE, 0),
+            (AT_RANDOM, random),
+            (AT_EXECFN, execfn),
+            (AT_NULL, 0),
+        ];
+
+        let mut words = vec![argv.len() as u64];
+        words.extend(argv);
+        words.push(0);
+        words.extend(envp);
+        words.push(0);
+        for (kind, value) in auxv {
+            words.push(kind);
+            words.push(value);
+        }
+
+        sp = sp.checked_sub(words.len() as u64 * 8)
+            .ok_or(ProgramError::Stack(sp))? & !15;
+        for (i, word) in words.iter().enumerate() {
+            if !memory.write_virtual_value(id, sp + i as u64 * 8, *word, 8) {
+                return Err(ProgramError::Stack(sp));
+            }
+        }
+        for cpu in cpus.iter_mut() {
+            cpu.set_register(SP, sp);
+        }
+        Ok(())
+    }
+
+    fn syscall(&mut self, cpu: &mut Cpu, memory: &mut Memory) -> SyscallResult {
+        let id = cpu.id();
+        let a0 = cpu.register(A0);
+        let a1 = cpu.register(A1);
+        let a2 = cpu.register(A2);
+        let a3 = cpu.register(A3);
+        let a4 = cpu.register(A4);
+        let a5 = cpu.register(A5);
+
+        let result = match cpu.register(V0) {
+            READ if !memory.mapped(id, a1, a2) => Err(EFAULT),
+            READ => self.read(a0, a2).and_then(|bytes| {
+                if memory.write_virtual(id, a1, &bytes) {
+                    Ok(bytes.len() as u64)
+                } else {
+                    Err(EFAULT)
+                }
+            }),
+            WRITE => match memory.read_virtual(id, a1, a2) {
+                None => Err(EFAULT),
+                Some(bytes) => self.write(a0, &bytes),
+            },
+            READV => Linux::read_iovecs(memory, id, a1, a2).and_then(|iovecs| {
+                let mut total = 0;
+                for (base, size) in iovecs {
+                    if !memory.mapped(id, base, size) {
+                        return Err(EFAULT);
+                    }
+                    let bytes = self.read(a0, size)?;
+                    if !memory.write_virtual(id, base, &bytes) {
+                        return Err(EFAULT);
+                    }
+                    total += bytes.len() as u64;
+                    if (bytes.len() as u64) < size {
+                        break;
+                    }
+                }
+                Ok(total)
+            }),
+            WRITEV => Linux::read_iovecs(memory, id, a1, a2).and_then(|iovecs| {
+                let mut total = 0;
+                for (base, size) in iovecs {
+                    let bytes = memory.read_virtual(id, base, size)
+                        .ok_or(EFAULT)?;
+                    total += self.write(a0, &bytes)?;
+                }
+                Ok(total)
+            }),
+            OPEN => self.read_path(memory, id, AT_FDCWD, a0)
+                .and_then(|path| self.open(path, a1)),
+            OPENAT => self.read_path(memory, id, a0, a1)
+                .and_then(|path| self.open(path, a2)),
+            CLOSE => match self.files.remove(&a0) {
+                Some(_) => {
+                    self.paths.remove(&a0);
+                    Ok(0)
+                },
+                None if a0 <= STDERR => Ok(0),
+                None => Err(EBADF),
+            },
+            STAT | LSTAT => self.read_path(memory, id, AT_FDCWD, a0)
+                .and_then(|path| Linux::stat_path(memory, id, path, a1)),
+            FSTAT => self.stat_fd(memory, id, a0, a1),
+            NEWFSTATAT => {
+                let empty = memory.read_string(id, a1)
+                    .map(|path| path.is_empty()).unwrap_or(false);
+                if empty && a3 & AT_EMPTY_PATH != 0 {
+                    self.stat_fd(memory, id, a0, a2)
+                } else {
+                    self.read_path(memory, id, a0, a1)
+                        .and_then(|path| Linux::stat_path(memory, id, path, a2))
+                }
+            },
+            ACCESS => self.read_path(memory, id, AT_FDCWD, a0)
+                .and_then(|path| fs::metadata(path).map(|_| 0).map_err(errno)),
+            FACCESSAT => self.read_path(memory, id, a0, a1)
+                .and_then(|path| fs::metadata(path).map(|_| 0).map_err(errno)),
+            LSEEK => self.lseek(a0, a1, a2),
+            BRK => Ok(self.brk(memory, id, a0)),
+            MMAP => self.mmap(memory, id, a0, a1, a3, a4, a5),
+            MUNMAP | MPROTECT | MADVISE => Ok(0),
+            MREMAP => Err(ENOMEM),
+            IOCTL => Err(ENOTTY),
+            RT_SIGACTION => {
+                if a2 != 0 {
+                    memory.write_virtual(id, a2, &[0; SIGACTION_SIZE]);
+                }
+                Ok(0)
+            },
+            RT_SIGPROCMASK if a3 != SIGSET_SIZE => Err(EINVAL),
+            RT_SIGPROCMASK => {
+                if a2 == 0 || memory.write_virtual(id, a2,
+                                                   &[0; SIGSET_SIZE as usize]) {
+                    Ok(0)
+                } else {
+                    Err(EFAULT)
+                }
+            },
+            SIGALTSTACK | SCHED_YIELD | FUTEX | NANOSLEEP |
+            CLOCK_NANOSLEEP => Ok(0),
+            GETPID | GETTID | SET_TID_ADDRESS => Ok(PID),
+            GETPPID | GETUID | GETGID | GETEUID | GETEGID => Ok(0),
+            SET_THREAD_AREA => {
+                cpu.set_user_local(a0);
+                Ok(0)
+            },
+            UNAME => Linux::uname(memory, id, a0),
+            GETCWD => {
+                if a1 < 2 {
+                    Err(EINVAL)
+                } else if memory.write_virtual(id, a0, b"/\0") {
+                    Ok(2)
+                } else {
+                    Err(EFAULT)
+                }
+            },
+            CLOCK_GETTIME => Linux::write_time(memory, id, a1, false),
+            GETTIMEOFDAY => {
+                if a0 == 0 {
+                    Ok(0)
+                } else {
+                    Linux::write_time(memory, id, a0, true)
+                }
+            },
+            GETRANDOM if !memory.mapped(id, a0, a1) => Err(EFAULT),
+            GETRANDOM => {
+                let bytes = self.random_bytes(a1 as usize);
+                if memory.write_virtual(id, a0, &bytes) {
+                    Ok(a1)
+                } else {
+                    Err(EFAULT)
+                }
+            },
+            PRLIMIT64 => {
+                if a3 != 0 {
+                    memory.write_virtual_value(id, a3, RLIM_INFINITY, 8);
+                    memory.write_virtual_value(id, a3.wrapping_add(8),
+                                              RLIM_INFINITY, 8);
+                }
+                Ok(0)
+            },
+            READLINKAT => Err(ENOENT),
+            // Only the low byte of the status gets back to the parent.
+            EXIT | EXIT_GROUP =>
+                return SyscallResult::Exit((a0 & 0xff) as i32),
+            // A signal sent to ourselves, most likely SIGABRT from abort().
+            // There are no signal handlers, so it kills the process.
+            TKILL => return SyscallResult::Exit(128 + a1 as i32),
+            TGKILL => return SyscallResult::Exit(128 + a2 as i32),
+            _ => Err(ENOSYS),
+        };
+
+        // Errors come back as a positive errno in $v0 with $a3 set.
+        match result {
+            Ok(value) => {
+                cpu.set_register(V0, value);
+                cpu.set_register(A3, 0);
+            },
+            Err(error) => {
+                cpu.set_register(V0, error);
+                cpu.set_register(A3, 1);
+            },
+        }
+        SyscallResult::Resume
+    }
+}
@@ -1,58 +1,195 @@
+use crate::computer::decoder::IsaMode;
+use crate::computer::{micromips, mips16};
+
+// In 32-bit mode only sign-extended 32-bit addresses are valid. Address
+// arithmetic on sign-extended registers already wraps around at 4 GiB the
+// way a MIPS32 CPU does, so anything that falls outside that range came from
+// an address calculation that overflowed and faults instead.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AddressMode {
+    Bits32,
+    Bits64,
+}
+
+// The order the bytes of a value are laid out in memory. It applies to
+// instruction fetches as much as to data, the same way it does on a real
+// core, so a program has to be built for the order it gets run in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+impl Endianness {
+    pub fn reversed(self) -> Endianness {
+        match self {
+            Endianness::Big => Endianness::Little,
+            Endianness::Little => Endianness::Big,
+        }
+    }
+
+    // Which byte of a `size` byte value, counting up from the least
+    // significant, sits `index` bytes into it in memory. It's its own
+    // inverse, so it also says where a given byte of the value goes.
+    pub fn lane(self, index: u64, size: u64) -> u64 {
+        match self {
+            Endianness::Big => size - 1 - index,
+            Endianness::Little => index,
+        }
+    }
+}
+
+// A load-linked watches the aligned doubleword it read from. Any store that
+// touches that doubleword breaks the link.
+const LINK_BLOCK: u64 = 8;
+
 pub struct MemoryManagementUnit {
     base: u64,
     limit: u64,
+    mode: AddressMode,
 }
 
 pub struct Memory {
     memory: Vec<u8>,
     mmus: Vec<MemoryManagementUnit>,
+    // Each CPU's link from its last load-linked: the physical address of
+    // the block it's watching.
+    links: Vec<Option<u64>>,
+    endianness: Endianness,
 }
 
+// Memory starts out big-endian, which is what MIPS cores come out of reset
+// as unless they're configured otherwise.
 pub fn new(size: u64, mmus: u64) -> Memory {
     let mut mem = Memory {
-        memory: Vec::with_capacity(size as usize),
+        memory: vec![0; size as usize],
         mmus: Vec::new(),
+        links: vec![None; mmus as usize],
+        endianness: Endianness::Big,
     };
     for _ in 0..mmus {
         mem.mmus.push(MemoryManagementUnit {
             base: 0,
             limit: 0,
+            mode: AddressMode::Bits64,
         });
     }
     mem
 }
 
 impl Memory {
+    pub fn size(&self) -> u64 {
+        self.memory.len() as u64
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
+    }
+
+    // Addresses below the base wrap around to the top of the address space,
+    // so a negative base can be used to map a high image down to zero.
+    pub fn set_mmu(&mut self,
+                   cpu_id: u64,
+                   base: u64,
+                   limit: u64,
+                   mode: AddressMode) {
+        self.mmus[cpu_id as usize] = MemoryManagementUnit { base, limit, mode };
+    }
+
     pub fn translate_address(&mut self,
                              cpu_id: u64,
                              address: u64) -> Option<u64> {
-        if address > self.mmus[cpu_id as usize].limit {
+        let mmu = &self.mmus[cpu_id as usize];
+        let canonical = mmu.mode == AddressMode::Bits64 ||
+            address as i32 as i64 as u64 == address;
+        if !canonical || address > mmu.limit {
             None
         } else {
-            Some(address + self.mmus[cpu_id as usize].base)
+            Some(address.wrapping_add(mmu.base))
+        }
+    }
+
+    // Load-linked: starts watching the block holding the physical `address`
+    // for `cpu_id`. A CPU only has one link at a time.
+    pub fn link(&mut self, cpu_id: u64, address: u64) {
+        self.links[cpu_id as usize] = Some(address & !(LINK_BLOCK - 1));
+    }
+
+    // Store-conditional: whether `cpu_id` still has an unbroken link to the
+    // block holding `address`. The link is used up either way.
+    pub fn take_link(&mut self, cpu_id: u64, address: u64) -> bool {
+        self.links[cpu_id as usize].take() ==
+            Some(address & !(LINK_BLOCK - 1))
+    }
+
+    pub fn clear_link(&mut self, cpu_id: u64) {
+        self.links[cpu_id as usize] = None;
+    }
+
+    // Every store breaks the links to the blocks it touches, whether it came
+    // from another CPU, a syscall or the linked CPU itself.
+    fn break_links(&mut self, address: u64, size: u64) {
+        if size == 0 {
+            return;
+        }
+        let first = address & !(LINK_BLOCK - 1);
+        let last = address.wrapping_add(size - 1) & !(LINK_BLOCK - 1);
+        for link in self.links.iter_mut() {
+            if matches!(*link, Some(block) if block >= first && block <= last) {
+                *link = None;
+            }
+        }
+    }
+
+    fn contains(&self, address: u64, size: u64) -> bool {
+        match address.checked_add(size) {
+            None => false,
+            Some(end) => end <= self.memory.len() as u64,
         }
     }
 
     pub fn read(&mut self, address: u64, size: u64) -> Option<u64> {
-        if (address + size - 1) as usize > self.memory.len() {
+        self.read_ordered(address, size, self.endianness)
+    }
+
+    pub fn write(&mut self, address: u64, value: u64, size: u64) -> bool {
+        self.write_ordered(address, value, size, self.endianness)
+    }
+
+    // Like `read` and `write`, but in the given byte order rather than the
+    // memory's own. A CPU running reverse-endian needs these.
+    pub fn read_ordered(&mut self,
+                        address: u64,
+                        size: u64,
+                        endianness: Endianness) -> Option<u64> {
+        if !self.contains(address, size) {
             None
         } else {
             let mut value: u64 = 0;
             for i in 0..size {
-                value |= (self.memory[(address + i) as usize] as u64)
-                            << (i * 8);
+                let byte = address + endianness.lane(i, size);
+                value |= (self.memory[byte as usize] as u64) << (i * 8);
             }
             Some(value)
         }
     }
 
-    pub fn write(&mut self, address: u64, value: u64, size: u64) -> bool {
-        if (address + size - 1) as usize > self.memory.len() {
+    pub fn write_ordered(&mut self,
+                         address: u64,
+                         value: u64,
+                         size: u64,
+                         endianness: Endianness) -> bool {
+        if !self.contains(address, size) {
             false
         } else {
+            self.break_links(address, size);
             for i in 0..size {
-                self.memory[(address + i) as usize] =
-                    (value >> (i * 8)) as u8;
+                let byte = address + endianness.lane(i, size);
+                self.memory[byte as usize] = (value >> (i * 8)) as u8;
             }
             true
         }
@@ -67,10 +204,7 @@ impl Memory {
     }
 
     pub fn read_word(&mut self, address: u64) -> Option<u32> {
-        match self.read(address, 4) {
-            None => None,
-            Some(value) => Some(value as u32),
-        }
+        self.read(address, 4).map(|value| value as u32)
     }
 
     pub fn write_word(&mut self, address: u64, value: u32) -> bool {
@@ -78,10 +212,7 @@ impl Memory {
     }
 
     pub fn read_halfword(&mut self, address: u64) -> Option<u16> {
-        match self.read(address, 2) {
-            None => None,
-            Some(value) => Some(value as u16),
-        }
+        self.read(address, 2).map(|value| value as u16)
     }
 
     pub fn write_halfword(&mut self, address: u64, value: u16) -> bool {
@@ -89,26 +220,157 @@ impl Memory {
     }
 
     pub fn read_byte(&mut self, address: u64) -> Option<u8> {
-        match self.read(address, 1) {
-            None => None,
-            Some(value) => Some(value as u8),
-        }
+        self.read(address, 1).map(|value| value as u8)
     }
 
     pub fn write_byte(&mut self, address: u64, value: u8) -> bool {
         self.write(address, value as u64, 1)
     }
 
-    pub fn read_instruction(&mut self, address: u64) -> Option<u32> {
-        if (address + 3) as usize > self.memory.len() {
-            None
+    pub fn write_bytes(&mut self, address: u64, bytes: &[u8]) -> bool {
+        if !self.contains(address, bytes.len() as u64) {
+            false
+        } else {
+            self.break_links(address, bytes.len() as u64);
+            let start = address as usize;
+            self.memory[start..start + bytes.len()].copy_from_slice(bytes);
+            true
+        }
+    }
+
+    pub fn fill(&mut self, address: u64, value: u8, size: u64) -> bool {
+        if !self.contains(address, size) {
+            false
         } else {
-            let mut value: u32 = 0;
-            for i in 0..4 {
-                value |= (self.memory[(address + i) as usize] as u32)
-                            << (24 - (i * 8));
+            self.break_links(address, size);
+            let start = address as usize;
+            self.memory[start..start + size as usize].fill(value);
+            true
+        }
+    }
+
+    // Whether a CPU can reach both ends of `size` bytes from `address`. Never
+    // true of more bytes than there is memory, so syscalls can check a size
+    // the program passed them before making a buffer that big.
+    pub fn mapped(&mut self, cpu_id: u64, address: u64, size: u64) -> bool {
+        if size == 0 {
+            return true;
+        }
+        let last = match address.checked_add(size - 1) {
+            None => return false,
+            Some(last) => last,
+        };
+        size <= self.size() &&
+            self.translate_address(cpu_id, address).is_some() &&
+            self.translate_address(cpu_id, last).is_some()
+    }
+
+    // Copies bytes out of a CPU's virtual address space, one byte at a time
+    // so the copy can cross from one mapping into another.
+    pub fn read_virtual(&mut self,
+                        cpu_id: u64,
+                        address: u64,
+                        size: u64) -> Option<Vec<u8>> {
+        if !self.mapped(cpu_id, address, size) {
+            return None;
+        }
+        let mut bytes = Vec::with_capacity(size as usize);
+        for i in 0..size {
+            let physical =
+                self.translate_address(cpu_id, address.wrapping_add(i))?;
+            bytes.push(self.read_byte(physical)?);
+        }
+        Some(bytes)
+    }
+
+    pub fn write_virtual(&mut self,
+                         cpu_id: u64,
+                         address: u64,
+                         bytes: &[u8]) -> bool {
+        for (i, byte) in bytes.iter().enumerate() {
+            let physical =
+                match self.translate_address(cpu_id,
+                                             address.wrapping_add(i as u64)) {
+                    None => return false,
+                    Some(physical) => physical,
+                };
+            if !self.write_byte(physical, *byte) {
+                return false;
             }
-            Some(value)
         }
+        true
+    }
+
+    // Reads a `size` byte value from a CPU's virtual address space in the
+    // same byte order the CPU itself would see it.
+    pub fn read_virtual_value(&mut self,
+                              cpu_id: u64,
+                              address: u64,
+                              size: u64) -> Option<u64> {
+        self.translate_address(cpu_id, address.wrapping_add(size - 1))?;
+        let physical = self.translate_address(cpu_id, address)?;
+        self.read(physical, size)
+    }
+
+    pub fn write_virtual_value(&mut self,
+                               cpu_id: u64,
+                               address: u64,
+                               value: u64,
+                               size: u64) -> bool {
+        if self.translate_address(cpu_id, address.wrapping_add(size - 1))
+            .is_none() {
+            return false;
+        }
+        match self.translate_address(cpu_id, address) {
+            None => false,
+            Some(physical) => self.write(physical, value, size),
+        }
+    }
+
+    // Reads a NUL-terminated string from a CPU's virtual address space,
+    // without the terminator.
+    pub fn read_string(&mut self,
+                       cpu_id: u64,
+                       address: u64) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        loop {
+            let physical = self.translate_address(
+                cpu_id, address.wrapping_add(bytes.len() as u64))?;
+            match self.read_byte(physical)? {
+                0 => return Some(bytes),
+                byte => bytes.push(byte),
+            }
+        }
+    }
+
+    pub fn read_instruction(&mut self, address: u64) -> Option<u32> {
+        self.read_word(address)
+    }
+
+    // Fetches an instruction in the given byte order, with how many bytes
+    // it takes. Compressed ones are one or two halfwords, each in the byte
+    // order, and the first halfword says which. A 32-bit one comes back
+    // with its first halfword on top, and a 16-bit one in the low half.
+    pub fn read_instruction_ordered(&mut self,
+                                    address: u64,
+                                    mode: IsaMode,
+                                    endianness: Endianness)
+        -> Option<(u32, u64)> {
+        if mode == IsaMode::Standard {
+            let instruction = self.read_ordered(address, 4, endianness)?;
+            return Some((instruction as u32, 4));
+        }
+        let first = self.read_ordered(address, 2, endianness)? as u32;
+        let size = if mode == IsaMode::MicroMips {
+            micromips::size(first as u16)
+        } else {
+            mips16::size(first as u16)
+        };
+        if size == 2 {
+            return Some((first, 2));
+        }
+        let second =
+            self.read_ordered(address.wrapping_add(2), 2, endianness)? as u32;
+        Some((first << 16 | second, 4))
     }
 }
@@ -0,0 +1,919 @@
+// microMIPS32 Release 6, the compressed encoding of the instruction set.
+// An instruction is one or two halfwords, and the major opcode in the top
+// six bits of the first says which. Everything decodes to the instructions
+// the standard encoding has, so the CPU runs both the same way. Branch
+// offsets count halfwords rather than words, and the 16-bit forms only
+// reach some registers.
+use crate::computer::decoder::{self, Condition, Cop0Op, Cop1Op, CountOp,
+                               FixedFormat, FloatFormat, FloatMemoryOp,
+                               FloatOp, FloatUnaryOp, ImmediateOp,
+                               Instruction, LinkedOp, MemoryOp, RegisterOp,
+                               ShiftOp, ShiftVariableOp, ShuffleOp, TlbOp};
+
+// 16-bit major opcodes
+const POOL16A: i32 = 0x01;
+const POOL16B: i32 = 0x09;
+const POOL16C: i32 = 0x11;
+const POOL16D: i32 = 0x13;
+const POOL16E: i32 = 0x1b;
+const LBU16: i32 = 0x02;
+const LHU16: i32 = 0x0a;
+const LW16: i32 = 0x1a;
+const LWSP: i32 = 0x12;
+const LWGP: i32 = 0x19;
+const SB16: i32 = 0x22;
+const SH16: i32 = 0x2a;
+const SW16: i32 = 0x3a;
+const SWSP: i32 = 0x32;
+const MOVE16: i32 = 0x03;
+const ANDI16: i32 = 0x0b;
+const LI16: i32 = 0x3b;
+const BC16: i32 = 0x33;
+const BEQZC16: i32 = 0x23;
+const BNEZC16: i32 = 0x2b;
+
+// POOL16C, by its low four bits, then its low five for the jumps and six
+// for the breakpoints. Bit 2 set means MOVEP whatever the rest is.
+const NOT16: u32 = 0x0;
+const AND16: u32 = 0x1;
+const LWM16: u32 = 0x2;
+const XOR16: u32 = 0x8;
+const OR16: u32 = 0x9;
+const SWM16: u32 = 0xa;
+const JRC16: u32 = 0x03;
+const JALRC16: u32 = 0x0b;
+const JRCADDIUSP: u32 = 0x13;
+const BREAK16: u32 = 0x1b;
+const SDBBP16: u32 = 0x3b;
+
+// 32-bit major opcodes
+const POOL32A: i32 = 0x00;
+const POOL32B: i32 = 0x08;
+const POOL32C: i32 = 0x18;
+const POOL32F: i32 = 0x15;
+const POOL32I: i32 = 0x10;
+const PCREL: i32 = 0x1e;
+const AUI: i32 = 0x04;
+const ADDIU: i32 = 0x0c;
+const ANDI: i32 = 0x34;
+const ORI: i32 = 0x14;
+const SLTI: i32 = 0x24;
+const SLTIU: i32 = 0x2c;
+const XORI: i32 = 0x1c;
+const LB: i32 = 0x07;
+const LBU: i32 = 0x05;
+const LH: i32 = 0x0f;
+const LHU: i32 = 0x0d;
+const LW: i32 = 0x3f;
+const SB: i32 = 0x06;
+const SH: i32 = 0x0e;
+const SW: i32 = 0x3e;
+const LWC1: i32 = 0x27;
+const LDC1: i32 = 0x2f;
+const SWC1: i32 = 0x26;
+const SDC1: i32 = 0x2e;
+const BC: i32 = 0x25;
+const BALC: i32 = 0x2d;
+// BEQZC and BNEZC, or JIALC and JIC when the register is $zero.
+const POP40: i32 = 0x20;
+const POP50: i32 = 0x28;
+// The rest of the compact branches that share an opcode. Each works the
+// way one of the standard encoding's does.
+const POP35: i32 = 0x1d;
+const POP37: i32 = 0x1f;
+const POP60: i32 = 0x30;
+const POP65: i32 = 0x35;
+const POP70: i32 = 0x38;
+const POP75: i32 = 0x3d;
+
+// POOL32A, by its low ten bits or for the ones with more operands, six.
+const SLL: u32 = 0x000;
+const SRL: u32 = 0x040;
+const SRA: u32 = 0x080;
+const ROTR: u32 = 0x0c0;
+const SELEQZ: u32 = 0x140;
+const SELNEZ: u32 = 0x180;
+const RDHWR: u32 = 0x1c0;
+const SLLV: u32 = 0x010;
+const SRLV: u32 = 0x050;
+const SRAV: u32 = 0x090;
+const ROTRV: u32 = 0x0d0;
+const ADD: u32 = 0x110;
+const ADDU: u32 = 0x150;
+const SUB: u32 = 0x190;
+const SUBU: u32 = 0x1d0;
+const AND: u32 = 0x250;
+const OR: u32 = 0x290;
+const NOR: u32 = 0x2d0;
+const XOR: u32 = 0x310;
+const SLT: u32 = 0x350;
+const SLTU: u32 = 0x390;
+const MUL: u32 = 0x018;
+const MUH: u32 = 0x058;
+const MULU: u32 = 0x098;
+const MUHU: u32 = 0x0d8;
+const DIV: u32 = 0x118;
+const MOD: u32 = 0x158;
+const DIVU: u32 = 0x198;
+const MODU: u32 = 0x1d8;
+const EXT: u32 = 0x2c;
+const INS: u32 = 0x0c;
+const LSA: u32 = 0x0f;
+const ALIGN: u32 = 0x1f;
+const BREAK: u32 = 0x07;
+const POOL32AXF: u32 = 0x3c;
+
+// POOL32Axf, by bits 15 to 6. The traps only use the low six of those and
+// put their code in the rest, and MFC0 and MTC0 the low five with the
+// select above them.
+const CLO: u32 = 0x12c;
+const CLZ: u32 = 0x16c;
+const SEB: u32 = 0x0ac;
+const SEH: u32 = 0x0ec;
+const WSBH: u32 = 0x1ec;
+const BITSWAP: u32 = 0x02c;
+const JALRC: u32 = 0x03c;
+const JALRC_HB: u32 = 0x07c;
+const SYNC: u32 = 0x1ad;
+const SYSCALL: u32 = 0x22d;
+const SDBBP: u32 = 0x36d;
+const WAIT: u32 = 0x24d;
+const TLBP: u32 = 0x00d;
+const TLBR: u32 = 0x04d;
+const TLBWI: u32 = 0x08d;
+const TLBWR: u32 = 0x0cd;
+const TLBINV: u32 = 0x10d;
+const TLBINVF: u32 = 0x14d;
+const ERET: u32 = 0x3cd;
+const DERET: u32 = 0x38d;
+const DI: u32 = 0x11d;
+const EI: u32 = 0x15d;
+const TEQ: u32 = 0x00;
+const TGE: u32 = 0x08;
+const TGEU: u32 = 0x10;
+const TLT: u32 = 0x20;
+const TLTU: u32 = 0x28;
+const TNE: u32 = 0x30;
+const MFC0: u32 = 0x03;
+const MTC0: u32 = 0x0b;
+
+// POOL32B and POOL32C, by bits 15 to 12.
+const LWP: u32 = 0x1;
+const LWM32: u32 = 0x5;
+const SWP: u32 = 0x9;
+const SWM32: u32 = 0xd;
+const PREF: u32 = 0x2;
+const LL: u32 = 0x3;
+const SC: u32 = 0xb;
+
+// POOL32I, by the rt field.
+const BC1EQZC: usize = 0x08;
+const BC1NEZC: usize = 0x09;
+const SYNCI: usize = 0x0c;
+
+// POOL32F, by its low six bits. The arithmetic ops pick the operation
+// with the bits above those.
+const ARITHMETIC: u32 = 0x30;
+const SELECT: u32 = 0x38;
+const MIN: u32 = 0x03;
+const MAX: u32 = 0x0b;
+const MINA: u32 = 0x23;
+const MAXA: u32 = 0x2b;
+const RINT_CLASS: u32 = 0x20;
+const CMP_S: u32 = 0x05;
+const CMP_D: u32 = 0x15;
+const POOL32FXF: u32 = 0x3b;
+
+// POOL32Fxf, by bits 15 to 6. Most take a format in bit 8, but ABS, NEG,
+// MOV and the conversions to floating point have two bits of it in 8 and
+// 7.
+const MFC1: u32 = 0x80;
+const MTC1: u32 = 0xa0;
+const MFHC1: u32 = 0xc0;
+const MTHC1: u32 = 0xe0;
+const CFC1: u32 = 0x40;
+const CTC1: u32 = 0x60;
+const SQRT: u32 = 0x28;
+const RSQRT: u32 = 0x08;
+const RECIP: u32 = 0x48;
+const CVT_W: u32 = 0x24;
+const CVT_L: u32 = 0x04;
+const ROUND_W: u32 = 0xec;
+const TRUNC_W: u32 = 0xac;
+const CEIL_W: u32 = 0x6c;
+const FLOOR_W: u32 = 0x2c;
+const ROUND_L: u32 = 0xcc;
+const TRUNC_L: u32 = 0x8c;
+const CEIL_L: u32 = 0x4c;
+const FLOOR_L: u32 = 0x0c;
+const ABS: u32 = 0x0d;
+const NEG: u32 = 0x2d;
+const MOV: u32 = 0x01;
+const CVT_D: u32 = 0x4d;
+const CVT_S: u32 = 0x6d;
+
+const GP: usize = 28;
+const SP: usize = 29;
+const RA: usize = 31;
+
+// The registers the 16-bit forms' three-bit fields name. Stores have
+// $zero in place of $s0, so they can store zero.
+const REGISTERS: [usize; 8] = [16, 17, 2, 3, 4, 5, 6, 7];
+const STORE_REGISTERS: [usize; 8] = [0, 17, 2, 3, 4, 5, 6, 7];
+
+const ANDI16_IMMEDIATES: [u16; 16] = [
+    128, 1, 2, 3, 4, 7, 8, 15, 16, 31, 32, 63, 64, 255, 32768, 65535,
+];
+const ADDIUR2_IMMEDIATES: [i16; 8] = [1, 4, 8, 12, 16, 20, 24, -1];
+
+// MOVEP's pairs of destinations, and the registers it copies from.
+const MOVEP_DESTINATIONS: [(usize, usize); 8] = [
+    (5, 6), (5, 7), (6, 7), (4, 21), (4, 22), (4, 5), (4, 6), (4, 7),
+];
+const MOVEP_SOURCES: [usize; 8] = [0, 17, 2, 3, 16, 18, 19, 20];
+
+// How many bytes the instruction starting with the halfword `first` takes.
+pub fn size(first: u16) -> u64 {
+    if matches!((first >> 10) & 0x7, 1..=3) {
+        2
+    } else {
+        4
+    }
+}
+
+// Works out which instruction is encoded in `size` bytes. A 32-bit
+// instruction's first halfword is its high half, and a 16-bit one is in
+// the low half of `instruction`.
+pub fn decode(instruction: u32, size: u64) -> Instruction {
+    let decoded = if size == 2 {
+        decode16(instruction as u16)
+    } else {
+        decode32(instruction)
+    };
+    decoded.unwrap_or(Instruction::Reserved)
+}
+
+// LWM and SWM's register lists: the first `count` of $s0 to $s7 and then
+// $fp, and $ra if asked for.
+fn register_list(count: u32, ra: bool) -> Option<u32> {
+    let registers = match count {
+        0..=8 => ((1 << count) - 1) << 16,
+        9 => 0xff << 16 | 1 << 30,
+        _ => return None,
+    };
+    let registers = registers | (ra as u32) << RA;
+    if registers == 0 {
+        None
+    } else {
+        Some(registers)
+    }
+}
+
+fn decode16(instruction: u16) -> Option<Instruction> {
+    let instruction = instruction as u32;
+    let major = (instruction >> 10) as i32;
+    let r7 = REGISTERS[((instruction >> 7) & 0x7) as usize];
+    let r4 = REGISTERS[((instruction >> 4) & 0x7) as usize];
+    let r1 = REGISTERS[((instruction >> 1) & 0x7) as usize];
+    let r5 = ((instruction >> 5) & 0x1f) as usize;
+    let r0 = (instruction & 0x1f) as usize;
+    let offset4 = (instruction & 0xf) as i64;
+
+    let decoded = match major {
+        POOL16A => {
+            let op = if instruction & 0x1 == 0 {
+                RegisterOp::Addu
+            } else {
+                RegisterOp::Subu
+            };
+            Instruction::Register { op, rd: r1, rs: r7, rt: r4 }
+        },
+        POOL16B => {
+            let op = if instruction & 0x1 == 0 {
+                ShiftOp::Sll
+            } else {
+                ShiftOp::Srl
+            };
+            let sa = match (instruction >> 1) & 0x7 {
+                0 => 8,
+                sa => sa,
+            };
+            Instruction::Shift { op, rd: r7, rt: r4, sa }
+        },
+        POOL16C => return decode_pool16c(instruction),
+        POOL16D if instruction & 0x1 == 0 => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: r5,
+            rs: r5,
+            immediate: decoder::offset(instruction >> 1, 4, 0) as u16,
+        },
+        POOL16D => {
+            // The immediates that would fit in ADDIUS5 are traded for
+            // larger ones.
+            let encoded = ((instruction >> 1) & 0x1ff) as i64;
+            let immediate = match encoded {
+                0..=1 => encoded + 256,
+                2..=255 => encoded,
+                256..=509 => encoded - 512,
+                _ => encoded - 768,
+            };
+            Instruction::Immediate {
+                op: ImmediateOp::Addiu,
+                rt: SP,
+                rs: SP,
+                immediate: (immediate << 2) as u16,
+            }
+        },
+        POOL16E if instruction & 0x1 == 0 => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: r7,
+            rs: r4,
+            immediate: ADDIUR2_IMMEDIATES[((instruction >> 1) & 0x7) as usize]
+                as u16,
+        },
+        POOL16E => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: r7,
+            rs: SP,
+            immediate: (((instruction >> 1) & 0x3f) << 2) as u16,
+        },
+        // LBU16 takes an offset of all ones to mean -1.
+        LBU16 if offset4 == 0xf => Instruction::Memory {
+            op: MemoryOp::Lbu,
+            rt: r7,
+            base: r4,
+            offset: -1,
+        },
+        LBU16 | LHU16 | LW16 => {
+            let (op, shift) = match major {
+                LBU16 => (MemoryOp::Lbu, 0),
+                LHU16 => (MemoryOp::Lhu, 1),
+                _ => (MemoryOp::Lw, 2),
+            };
+            let offset = offset4 << shift;
+            Instruction::Memory { op, rt: r7, base: r4, offset }
+        },
+        SB16 | SH16 | SW16 => {
+            let (op, shift) = match major {
+                SB16 => (MemoryOp::Sb, 0),
+                SH16 => (MemoryOp::Sh, 1),
+                _ => (MemoryOp::Sw, 2),
+            };
+            Instruction::Memory {
+                op,
+                rt: STORE_REGISTERS[((instruction >> 7) & 0x7) as usize],
+                base: r4,
+                offset: offset4 << shift,
+            }
+        },
+        LWSP | SWSP => {
+            let op = if major == LWSP { MemoryOp::Lw } else { MemoryOp::Sw };
+            Instruction::Memory {
+                op,
+                rt: r5,
+                base: SP,
+                offset: ((instruction & 0x1f) << 2) as i64,
+            }
+        },
+        LWGP => Instruction::Memory {
+            op: MemoryOp::Lw,
+            rt: r7,
+            base: GP,
+            offset: ((instruction & 0x7f) << 2) as i64,
+        },
+        MOVE16 => Instruction::Register {
+            op: RegisterOp::Or,
+            rd: r5,
+            rs: r0,
+            rt: 0,
+        },
+        ANDI16 => Instruction::Immediate {
+            op: ImmediateOp::Andi,
+            rt: r7,
+            rs: r4,
+            immediate: ANDI16_IMMEDIATES[(instruction & 0xf) as usize],
+        },
+        LI16 => {
+            let immediate = match instruction & 0x7f {
+                0x7f => -1,
+                value => value as i16,
+            };
+            Instruction::Immediate {
+                op: ImmediateOp::Addiu,
+                rt: r7,
+                rs: 0,
+                immediate: immediate as u16,
+            }
+        },
+        BC16 => Instruction::Bc { offset: decoder::offset(instruction, 10, 1) },
+        BEQZC16 | BNEZC16 => {
+            let condition = if major == BEQZC16 {
+                Condition::Eqz
+            } else {
+                Condition::Nez
+            };
+            Instruction::CompactBranch {
+                condition,
+                link: false,
+                rs: r7,
+                rt: 0,
+                offset: decoder::offset(instruction, 7, 1),
+            }
+        },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// POOL16C: logic on the three-bit registers, the 16-bit register jumps and
+// breakpoints, LWM16 and SWM16, and MOVEP.
+fn decode_pool16c(instruction: u32) -> Option<Instruction> {
+    let rt = REGISTERS[((instruction >> 7) & 0x7) as usize];
+    let rs = REGISTERS[((instruction >> 4) & 0x7) as usize];
+    let r5 = ((instruction >> 5) & 0x1f) as usize;
+
+    if instruction & 0x4 != 0 {
+        let (rd, re) = MOVEP_DESTINATIONS[((instruction >> 7) & 0x7) as usize];
+        let source = ((instruction >> 1) & 0x4) | (instruction & 0x3);
+        return Some(Instruction::Movep {
+            rd,
+            re,
+            rs: MOVEP_SOURCES[source as usize],
+            rt: MOVEP_SOURCES[((instruction >> 4) & 0x7) as usize],
+        });
+    }
+
+    let logic = |op| Some(Instruction::Register { op, rd: rt, rs: rt, rt: rs });
+    match instruction & 0xf {
+        NOT16 => return Some(Instruction::Register {
+            op: RegisterOp::Nor,
+            rd: rt,
+            rs,
+            rt: 0,
+        }),
+        AND16 => return logic(RegisterOp::And),
+        XOR16 => return logic(RegisterOp::Xor),
+        OR16 => return logic(RegisterOp::Or),
+        LWM16 | SWM16 => {
+            let registers = register_list(((instruction >> 8) & 0x3) + 1, true)?;
+            let base = SP;
+            let offset = (((instruction >> 4) & 0xf) << 2) as i64;
+            return Some(if instruction & 0xf == LWM16 {
+                Instruction::LoadMultiple { registers, base, offset }
+            } else {
+                Instruction::StoreMultiple { registers, base, offset }
+            });
+        },
+        _ => {},
+    }
+
+    let code = (instruction >> 6) & 0xf;
+    match instruction & 0x3f {
+        SDBBP16 => return Some(Instruction::Sdbbp { code }),
+        BREAK16 => return Some(Instruction::Break { code }),
+        _ => {},
+    }
+
+    Some(match instruction & 0x1f {
+        JRC16 => Instruction::Jic { rt: r5, offset: 0 },
+        JALRC16 => Instruction::Jalrc { rd: RA, rs: r5 },
+        JRCADDIUSP => Instruction::JrcAddiusp {
+            immediate: (r5 << 2) as i64,
+        },
+        _ => return None,
+    })
+}
+
+// The 32-bit instructions name their register fields the way the microMIPS
+// manual does, with rt at the top and rs below it, the other way around
+// from the standard encoding.
+fn decode32(instruction: u32) -> Option<Instruction> {
+    let major = (instruction >> 26) as i32;
+    let rt = ((instruction >> 21) & 0x1f) as usize;
+    let rs = ((instruction >> 16) & 0x1f) as usize;
+    let immediate = instruction as u16;
+    let offset16 = immediate as i16 as i64;
+
+    let decoded = match major {
+        POP35 | POP37 | POP60 | POP65 | POP70 | POP75 => {
+            let opcode = match major {
+                POP35 => decoder::POP10,
+                POP37 => decoder::POP30,
+                POP60 => decoder::POP06,
+                POP65 => decoder::POP27,
+                POP70 => decoder::POP07,
+                _ => decoder::POP26,
+            };
+            let offset = decoder::offset(instruction, 16, 1);
+            return decoder::decode_compact_branch(opcode, rs, rt, offset);
+        },
+        POOL32A => return decode_pool32a(instruction),
+        POOL32B | POOL32C => return decode_pool32bc(instruction),
+        POOL32F => return decode_pool32f(instruction),
+        POOL32I => match rt {
+            BC1EQZC | BC1NEZC => {
+                let condition = if rt == BC1EQZC {
+                    Condition::Eqz
+                } else {
+                    Condition::Nez
+                };
+                Instruction::Bc1c {
+                    condition,
+                    ft: rs,
+                    offset: decoder::offset(instruction, 16, 1),
+                }
+            },
+            SYNCI => Instruction::Synci { base: rs, offset: offset16 },
+            _ => return None,
+        },
+        // The sub-opcode is in the same place as the standard encoding's.
+        PCREL => return decoder::decode_pc_relative(instruction),
+        AUI | ADDIU | ANDI | ORI | SLTI | SLTIU | XORI => {
+            // LUI is AUI adding to $zero.
+            let op = match major {
+                AUI if rs == 0 => ImmediateOp::Lui,
+                AUI => ImmediateOp::Aui,
+                ADDIU => ImmediateOp::Addiu,
+                ANDI => ImmediateOp::Andi,
+                ORI => ImmediateOp::Ori,
+                SLTI => ImmediateOp::Slti,
+                SLTIU => ImmediateOp::Sltiu,
+                _ => ImmediateOp::Xori,
+            };
+            Instruction::Immediate { op, rt, rs, immediate }
+        },
+        LB | LBU | LH | LHU | LW | SB | SH | SW => {
+            let op = match major {
+                LB => MemoryOp::Lb,
+                LBU => MemoryOp::Lbu,
+                LH => MemoryOp::Lh,
+                LHU => MemoryOp::Lhu,
+                LW => MemoryOp::Lw,
+                SB => MemoryOp::Sb,
+                SH => MemoryOp::Sh,
+                _ => MemoryOp::Sw,
+            };
+            Instruction::Memory { op, rt, base: rs, offset: offset16 }
+        },
+        LWC1 | LDC1 | SWC1 | SDC1 => {
+            let op = match major {
+                LWC1 => FloatMemoryOp::Lwc1,
+                LDC1 => FloatMemoryOp::Ldc1,
+                SWC1 => FloatMemoryOp::Swc1,
+                _ => FloatMemoryOp::Sdc1,
+            };
+            Instruction::FloatMemory { op, ft: rt, base: rs, offset: offset16 }
+        },
+        BC => Instruction::Bc { offset: decoder::offset(instruction, 26, 1) },
+        BALC => Instruction::Balc {
+            offset: decoder::offset(instruction, 26, 1),
+        },
+        POP40 | POP50 if rt != 0 => {
+            let condition = if major == POP40 {
+                Condition::Eqz
+            } else {
+                Condition::Nez
+            };
+            Instruction::CompactBranch {
+                condition,
+                link: false,
+                rs: rt,
+                rt: 0,
+                offset: decoder::offset(instruction, 21, 1),
+            }
+        },
+        POP40 => Instruction::Jialc { rt: rs, offset: offset16 },
+        POP50 => Instruction::Jic { rt: rs, offset: offset16 },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// POOL32A: the register-to-register operations, picked by the low ten
+// bits, or six for the ones that need the room for more operands.
+fn decode_pool32a(instruction: u32) -> Option<Instruction> {
+    let rt = ((instruction >> 21) & 0x1f) as usize;
+    let rs = ((instruction >> 16) & 0x1f) as usize;
+    let rd = ((instruction >> 11) & 0x1f) as usize;
+    let sa = rd as u32;
+
+    match instruction & 0x3f {
+        EXT => return decoder::decode_bit_field(decoder::EXT, rt, rs, sa,
+                                                (instruction >> 6) & 0x1f),
+        INS => return decoder::decode_bit_field(decoder::INS, rt, rs, sa,
+                                                (instruction >> 6) & 0x1f),
+        LSA => return Some(Instruction::Lsa {
+            rd,
+            rs,
+            rt,
+            sa: ((instruction >> 9) & 0x3) + 1,
+        }),
+        ALIGN => return Some(Instruction::Align {
+            rd,
+            rs,
+            rt,
+            bp: (instruction >> 9) & 0x3,
+        }),
+        BREAK => return Some(Instruction::Break {
+            code: (instruction >> 6) & 0xfffff,
+        }),
+        POOL32AXF => return decode_pool32axf(instruction),
+        _ => {},
+    }
+
+    let minor = instruction & 0x3ff;
+    let decoded = match minor {
+        SLL | SRL | SRA | ROTR => {
+            let op = match minor {
+                SLL => ShiftOp::Sll,
+                SRL => ShiftOp::Srl,
+                SRA => ShiftOp::Sra,
+                _ => ShiftOp::Rotr,
+            };
+            Instruction::Shift { op, rd: rt, rt: rs, sa }
+        },
+        SLLV | SRLV | SRAV | ROTRV => {
+            let op = match minor {
+                SLLV => ShiftVariableOp::Sllv,
+                SRLV => ShiftVariableOp::Srlv,
+                SRAV => ShiftVariableOp::Srav,
+                _ => ShiftVariableOp::Rotrv,
+            };
+            Instruction::ShiftVariable { op, rd, rt, rs }
+        },
+        RDHWR => Instruction::Rdhwr { rt, rd: rs },
+        _ => {
+            let op = match minor {
+                SELEQZ => RegisterOp::Seleqz,
+                SELNEZ => RegisterOp::Selnez,
+                ADD => RegisterOp::Add,
+                ADDU => RegisterOp::Addu,
+                SUB => RegisterOp::Sub,
+                SUBU => RegisterOp::Subu,
+                AND => RegisterOp::And,
+                OR => RegisterOp::Or,
+                NOR => RegisterOp::Nor,
+                XOR => RegisterOp::Xor,
+                SLT => RegisterOp::Slt,
+                SLTU => RegisterOp::Sltu,
+                MUL => RegisterOp::Mul,
+                MUH => RegisterOp::Muh,
+                MULU => RegisterOp::Mulu,
+                MUHU => RegisterOp::Muhu,
+                DIV => RegisterOp::Div,
+                MOD => RegisterOp::Mod,
+                DIVU => RegisterOp::Divu,
+                MODU => RegisterOp::Modu,
+                _ => return None,
+            };
+            Instruction::Register { op, rd, rs, rt }
+        },
+    };
+    Some(decoded)
+}
+
+// POOL32Axf: operations on fewer registers, picked by bits 15 to 6.
+fn decode_pool32axf(instruction: u32) -> Option<Instruction> {
+    let rt = ((instruction >> 21) & 0x1f) as usize;
+    let rs = ((instruction >> 16) & 0x1f) as usize;
+    let extension = (instruction >> 6) & 0x3ff;
+
+    if extension >> 8 == 0 && matches!(extension & 0x1f, MFC0 | MTC0) {
+        let op = if extension & 0x1f == MFC0 {
+            Cop0Op::Mfc0
+        } else {
+            Cop0Op::Mtc0
+        };
+        let sel = ((instruction >> 11) & 0x7) as usize;
+        return Some(Instruction::Cop0 { op, rt, rd: rs, sel });
+    }
+
+    let condition = match extension & 0x3f {
+        TEQ => Some(Condition::Eq),
+        TGE => Some(Condition::Ge),
+        TGEU => Some(Condition::Geu),
+        TLT => Some(Condition::Lt),
+        TLTU => Some(Condition::Ltu),
+        TNE => Some(Condition::Ne),
+        _ => None,
+    };
+    if let Some(condition) = condition {
+        let code = extension >> 6;
+        return Some(Instruction::Trap { condition, rs, rt, code });
+    }
+
+    let decoded = match extension {
+        CLO => Instruction::Count { op: CountOp::Clo, rd: rt, rs },
+        CLZ => Instruction::Count { op: CountOp::Clz, rd: rt, rs },
+        SEB => Instruction::Shuffle { op: ShuffleOp::Seb, rd: rt, rt: rs },
+        SEH => Instruction::Shuffle { op: ShuffleOp::Seh, rd: rt, rt: rs },
+        WSBH => Instruction::Shuffle { op: ShuffleOp::Wsbh, rd: rt, rt: rs },
+        // Unlike its neighbours, BITSWAP's result goes in rs.
+        BITSWAP => Instruction::Bitswap { rd: rs, rt },
+        // There are no hazards to clear.
+        JALRC | JALRC_HB => Instruction::Jalrc { rd: rt, rs },
+        SYNC => Instruction::Sync { stype: rs as u32 },
+        SYSCALL => Instruction::Syscall { code: (instruction >> 16) & 0x3ff },
+        SDBBP => Instruction::Sdbbp { code: (instruction >> 16) & 0x3ff },
+        WAIT => Instruction::Wait,
+        TLBP => Instruction::Tlb { op: TlbOp::Tlbp },
+        TLBR => Instruction::Tlb { op: TlbOp::Tlbr },
+        TLBWI => Instruction::Tlb { op: TlbOp::Tlbwi },
+        TLBWR => Instruction::Tlb { op: TlbOp::Tlbwr },
+        TLBINV => Instruction::Tlb { op: TlbOp::Tlbinv },
+        TLBINVF => Instruction::Tlb { op: TlbOp::Tlbinvf },
+        ERET => Instruction::Eret,
+        DERET => Instruction::Deret,
+        DI => Instruction::Di { rt: rs },
+        EI => Instruction::Ei { rt: rs },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// POOL32B and POOL32C: the loads and stores with smaller offsets, picked by
+// bits 15 to 12.
+fn decode_pool32bc(instruction: u32) -> Option<Instruction> {
+    let major = (instruction >> 26) as i32;
+    let rt = ((instruction >> 21) & 0x1f) as usize;
+    let base = ((instruction >> 16) & 0x1f) as usize;
+    let minor = (instruction >> 12) & 0xf;
+    let offset12 = decoder::offset(instruction, 12, 0);
+
+    let decoded = match (major, minor) {
+        (POOL32B, LWP | SWP | LWM32 | SWM32) => {
+            // LWP and SWP move a register and the one after it.
+            let registers = match minor {
+                LWP | SWP if rt != RA => 0x3 << rt,
+                LWP | SWP => return None,
+                _ => register_list(rt as u32 & 0xf, rt & 0x10 != 0)?,
+            };
+            if matches!(minor, LWP | LWM32) {
+                Instruction::LoadMultiple { registers, base, offset: offset12 }
+            } else {
+                Instruction::StoreMultiple { registers, base, offset: offset12 }
+            }
+        },
+        (POOL32C, PREF) => Instruction::Pref {
+            hint: rt as u32,
+            base,
+            offset: offset12,
+        },
+        (POOL32C, LL | SC) if (instruction >> 9) & 0x7 == 0 => {
+            let op = if minor == LL { LinkedOp::Ll } else { LinkedOp::Sc };
+            Instruction::Linked {
+                op,
+                rt,
+                base,
+                offset: decoder::offset(instruction, 9, 0),
+            }
+        },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// POOL32F: the FPU's arithmetic, compares and moves.
+fn decode_pool32f(instruction: u32) -> Option<Instruction> {
+    let ft = ((instruction >> 21) & 0x1f) as usize;
+    let fs = ((instruction >> 16) & 0x1f) as usize;
+    let fd = ((instruction >> 11) & 0x1f) as usize;
+    let format = |bits| match bits {
+        0 => Some(FloatFormat::S),
+        1 => Some(FloatFormat::D),
+        _ => None,
+    };
+    // Most of them have the format in bits 10 and 9.
+    let fmt = format((instruction >> 9) & 0x3);
+
+    let decoded = match instruction & 0x3f {
+        ARITHMETIC => {
+            let op = match (instruction >> 6) & 0x3 {
+                0 => FloatOp::Add,
+                1 => FloatOp::Sub,
+                2 => FloatOp::Mul,
+                _ => FloatOp::Div,
+            };
+            let fmt = format((instruction >> 8) & 0x3)?;
+            Instruction::Float { op, fmt, fd, fs, ft }
+        },
+        SELECT => {
+            let op = match (instruction >> 6) & 0x7 {
+                0 => FloatOp::Seleqz,
+                1 => FloatOp::Selnez,
+                2 => FloatOp::Sel,
+                6 => FloatOp::Maddf,
+                7 => FloatOp::Msubf,
+                _ => return None,
+            };
+            Instruction::Float { op, fmt: fmt?, fd, fs, ft }
+        },
+        MIN | MAX | MINA | MAXA => {
+            let op = match instruction & 0x3f {
+                MIN => FloatOp::Min,
+                MAX => FloatOp::Max,
+                MINA => FloatOp::Mina,
+                _ => FloatOp::Maxa,
+            };
+            Instruction::Float { op, fmt: fmt?, fd, fs, ft }
+        },
+        // These have their source at the top and their result below it.
+        RINT_CLASS => {
+            let op = match (instruction >> 6) & 0x7 {
+                0 => FloatUnaryOp::Rint,
+                1 => FloatUnaryOp::Class,
+                _ => return None,
+            };
+            Instruction::FloatUnary { op, fmt: fmt?, fd: fs, fs: ft }
+        },
+        CMP_S | CMP_D => {
+            let fmt = if instruction & 0x3f == CMP_S {
+                FloatFormat::S
+            } else {
+                FloatFormat::D
+            };
+            let condition =
+                decoder::float_condition(((instruction >> 6) & 0x1f) as i32)?;
+            Instruction::FloatCompare { condition, fmt, fd, fs, ft }
+        },
+        POOL32FXF => return decode_pool32fxf(instruction),
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// POOL32Fxf: moves to and from the FPU and the operations on one register,
+// which put their result at the top.
+fn decode_pool32fxf(instruction: u32) -> Option<Instruction> {
+    let rt = ((instruction >> 21) & 0x1f) as usize;
+    let fs = ((instruction >> 16) & 0x1f) as usize;
+    let extension = (instruction >> 6) & 0x3ff;
+    let unary = |op, fmt| Some(Instruction::FloatUnary { op, fmt, fd: rt, fs });
+
+    let op = match extension {
+        MFC1 => Some(Cop1Op::Mfc1),
+        MTC1 => Some(Cop1Op::Mtc1),
+        MFHC1 => Some(Cop1Op::Mfhc1),
+        MTHC1 => Some(Cop1Op::Mthc1),
+        CFC1 => Some(Cop1Op::Cfc1),
+        CTC1 => Some(Cop1Op::Ctc1),
+        _ => None,
+    };
+    if let Some(op) = op {
+        return Some(Instruction::Cop1 { op, rt, fs });
+    }
+
+    let fmt = if extension & 0x100 == 0 {
+        FloatFormat::S
+    } else {
+        FloatFormat::D
+    };
+    let op = match extension & 0xff {
+        SQRT => Some(FloatUnaryOp::Sqrt),
+        RSQRT => Some(FloatUnaryOp::Rsqrt),
+        RECIP => Some(FloatUnaryOp::Recip),
+        CVT_W => Some(FloatUnaryOp::CvtW),
+        CVT_L => Some(FloatUnaryOp::CvtL),
+        ROUND_W => Some(FloatUnaryOp::RoundW),
+        TRUNC_W => Some(FloatUnaryOp::TruncW),
+        CEIL_W => Some(FloatUnaryOp::CeilW),
+        FLOOR_W => Some(FloatUnaryOp::FloorW),
+        ROUND_L => Some(FloatUnaryOp::RoundL),
+        TRUNC_L => Some(FloatUnaryOp::TruncL),
+        CEIL_L => Some(FloatUnaryOp::CeilL),
+        FLOOR_L => Some(FloatUnaryOp::FloorL),
+        _ => None,
+    };
+    if let (Some(op), 0) = (op, extension >> 9) {
+        return unary(op, fmt);
+    }
+
+    let op = extension & 0x7f;
+    match (op, extension >> 7) {
+        (ABS | NEG | MOV, format) => {
+            let op = match op {
+                ABS => FloatUnaryOp::Abs,
+                NEG => FloatUnaryOp::Neg,
+                _ => FloatUnaryOp::Mov,
+            };
+            let fmt = match format {
+                0 => FloatFormat::S,
+                1 => FloatFormat::D,
+                _ => return None,
+            };
+            unary(op, fmt)
+        },
+        (CVT_D, 0) => unary(FloatUnaryOp::CvtD, FloatFormat::S),
+        (CVT_S, 0) => unary(FloatUnaryOp::CvtS, FloatFormat::D),
+        (CVT_D | CVT_S, 1 | 2) => {
+            let to = if op == CVT_D { FloatFormat::D } else { FloatFormat::S };
+            let from = if extension >> 7 == 1 {
+                FixedFormat::W
+            } else {
+                FixedFormat::L
+            };
+            Some(Instruction::ConvertFixed { to, from, fd: rt, fs })
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,578 @@
+// MIPS16e, the compressed encoding that comes before Release 6. Almost
+// every instruction is a halfword with its major opcode in the top five
+// bits. An EXTEND halfword in front of one widens its immediate to 16
+// bits, and JAL and JALX take two halfwords of their own. Everything
+// decodes to the instructions the standard encoding has, so the CPU runs
+// both the same way. Branches have no delay slot, but jumps do. Only the
+// MIPS32 instructions are here; the doubleword ones are reserved.
+use crate::computer::decoder::{self, Condition, HiLoOp, ImmediateOp,
+                               Instruction, MemoryOp, PcRelativeOp,
+                               RegisterOp, ShiftOp, ShiftVariableOp,
+                               ShuffleOp};
+
+// Major opcodes
+const ADDIUSP: i32 = 0x00;
+const ADDIUPC: i32 = 0x01;
+const B: i32 = 0x02;
+const JAL: i32 = 0x03;
+const BEQZ: i32 = 0x04;
+const BNEZ: i32 = 0x05;
+const SHIFT: i32 = 0x06;
+const RRIA: i32 = 0x08;
+const ADDIU8: i32 = 0x09;
+const SLTI: i32 = 0x0a;
+const SLTIU: i32 = 0x0b;
+const I8: i32 = 0x0c;
+const LI: i32 = 0x0d;
+const CMPI: i32 = 0x0e;
+const LB: i32 = 0x10;
+const LH: i32 = 0x11;
+const LWSP: i32 = 0x12;
+const LW: i32 = 0x13;
+const LBU: i32 = 0x14;
+const LHU: i32 = 0x15;
+const LWPC: i32 = 0x16;
+const SB: i32 = 0x18;
+const SH: i32 = 0x19;
+const SWSP: i32 = 0x1a;
+const SW: i32 = 0x1b;
+const RRR: i32 = 0x1c;
+const RR: i32 = 0x1d;
+const EXTEND: i32 = 0x1e;
+
+// I8, by bits 10 to 8.
+const BTEQZ: u32 = 0x0;
+const BTNEZ: u32 = 0x1;
+const SWRASP: u32 = 0x2;
+const ADJSP: u32 = 0x3;
+const SVRS: u32 = 0x4;
+const MOV32R: u32 = 0x5;
+const MOVR32: u32 = 0x7;
+
+// RR, by its low five bits.
+const JR: u32 = 0x00;
+const SDBBP: u32 = 0x01;
+const SLT: u32 = 0x02;
+const SLTU: u32 = 0x03;
+const SLLV: u32 = 0x04;
+const BREAK: u32 = 0x05;
+const SRLV: u32 = 0x06;
+const SRAV: u32 = 0x07;
+const CMP: u32 = 0x0a;
+const NEG: u32 = 0x0b;
+const AND: u32 = 0x0c;
+const OR: u32 = 0x0d;
+const XOR: u32 = 0x0e;
+const NOT: u32 = 0x0f;
+const MFHI: u32 = 0x10;
+const CNVT: u32 = 0x11;
+const MFLO: u32 = 0x12;
+const MULT: u32 = 0x18;
+const MULTU: u32 = 0x19;
+const DIV: u32 = 0x1a;
+const DIVU: u32 = 0x1b;
+
+// CNVT, by bits 7 to 5.
+const ZEB: u32 = 0x0;
+const ZEH: u32 = 0x1;
+const SEB: u32 = 0x4;
+const SEH: u32 = 0x5;
+
+// The comparisons leave their result in $t8, which the BTEQZ and BTNEZ
+// branches test.
+const T8: usize = 24;
+const SP: usize = 29;
+const RA: usize = 31;
+
+// The registers the three-bit fields name.
+const REGISTERS: [usize; 8] = [16, 17, 2, 3, 4, 5, 6, 7];
+
+// How many bytes the instruction starting with the halfword `first` takes.
+pub fn size(first: u16) -> u64 {
+    match (first >> 11) as i32 {
+        EXTEND | JAL => 4,
+        _ => 2,
+    }
+}
+
+// Works out which instruction is encoded in `size` bytes. A 32-bit one's
+// first halfword is its high half, and a 16-bit one is in the low half of
+// `instruction`.
+pub fn decode(instruction: u32, size: u64) -> Instruction {
+    let decoded = if size == 2 {
+        decode16(instruction)
+    } else if (instruction >> 27) as i32 == JAL {
+        Some(decode_jal(instruction))
+    } else {
+        decode_extended(instruction >> 16 & 0x7ff, instruction & 0xffff)
+    };
+    decoded.unwrap_or(Instruction::Reserved)
+}
+
+// The loads and stores through rx, with how far their 5-bit offset is
+// shifted when it isn't extended.
+fn memory_op(major: i32) -> Option<(MemoryOp, u32)> {
+    Some(match major {
+        LB => (MemoryOp::Lb, 0),
+        LBU => (MemoryOp::Lbu, 0),
+        LH => (MemoryOp::Lh, 1),
+        LHU => (MemoryOp::Lhu, 1),
+        LW => (MemoryOp::Lw, 2),
+        SB => (MemoryOp::Sb, 0),
+        SH => (MemoryOp::Sh, 1),
+        SW => (MemoryOp::Sw, 2),
+        _ => return None,
+    })
+}
+
+fn shift_op(instruction: u32) -> Option<ShiftOp> {
+    match instruction & 0x3 {
+        0 => Some(ShiftOp::Sll),
+        2 => Some(ShiftOp::Srl),
+        3 => Some(ShiftOp::Sra),
+        _ => None,
+    }
+}
+
+fn zero_condition(equal: bool) -> Condition {
+    if equal {
+        Condition::Eqz
+    } else {
+        Condition::Nez
+    }
+}
+
+fn decode16(instruction: u32) -> Option<Instruction> {
+    let major = (instruction >> 11) as i32;
+    let rx = REGISTERS[((instruction >> 8) & 0x7) as usize];
+    let ry = REGISTERS[((instruction >> 5) & 0x7) as usize];
+    let immediate = (instruction & 0xff) as u16;
+    let offset8 = decoder::offset(instruction, 8, 1);
+
+    let decoded = match major {
+        ADDIUSP => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: rx,
+            rs: SP,
+            immediate: immediate << 2,
+        },
+        ADDIUPC => Instruction::PcRelative {
+            op: PcRelativeOp::Addiupc,
+            rs: rx,
+            offset: (immediate << 2) as i64,
+        },
+        B => Instruction::Bc { offset: decoder::offset(instruction, 11, 1) },
+        BEQZ | BNEZ => Instruction::CompactBranch {
+            condition: zero_condition(major == BEQZ),
+            link: false,
+            rs: rx,
+            rt: 0,
+            offset: offset8,
+        },
+        // A shift of 0 means 8.
+        SHIFT => Instruction::Shift {
+            op: shift_op(instruction)?,
+            rd: rx,
+            rt: ry,
+            sa: match (instruction >> 2) & 0x7 {
+                0 => 8,
+                sa => sa,
+            },
+        },
+        RRIA if instruction & 0x10 == 0 => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: ry,
+            rs: rx,
+            immediate: decoder::offset(instruction, 4, 0) as u16,
+        },
+        ADDIU8 => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: rx,
+            rs: rx,
+            immediate: decoder::offset(instruction, 8, 0) as u16,
+        },
+        // Unextended, the comparisons' immediates are zero-extended.
+        SLTI | SLTIU => Instruction::Immediate {
+            op: if major == SLTI {
+                ImmediateOp::Slti
+            } else {
+                ImmediateOp::Sltiu
+            },
+            rt: T8,
+            rs: rx,
+            immediate,
+        },
+        I8 => return decode_i8(instruction),
+        LI => Instruction::Immediate {
+            op: ImmediateOp::Ori,
+            rt: rx,
+            rs: 0,
+            immediate,
+        },
+        CMPI => Instruction::Immediate {
+            op: ImmediateOp::Xori,
+            rt: T8,
+            rs: rx,
+            immediate,
+        },
+        LWSP | SWSP => Instruction::Memory {
+            op: if major == LWSP { MemoryOp::Lw } else { MemoryOp::Sw },
+            rt: rx,
+            base: SP,
+            offset: (immediate << 2) as i64,
+        },
+        LWPC => Instruction::PcRelative {
+            op: PcRelativeOp::Lwpc,
+            rs: rx,
+            offset: (immediate << 2) as i64,
+        },
+        RRR => {
+            let op = match instruction & 0x3 {
+                1 => RegisterOp::Addu,
+                3 => RegisterOp::Subu,
+                _ => return None,
+            };
+            let rz = REGISTERS[((instruction >> 2) & 0x7) as usize];
+            Instruction::Register { op, rd: rz, rs: rx, rt: ry }
+        },
+        RR => return decode_rr(instruction),
+        _ => {
+            let (op, shift) = memory_op(major)?;
+            Instruction::Memory {
+                op,
+                rt: ry,
+                base: rx,
+                offset: ((instruction & 0x1f) << shift) as i64,
+            }
+        },
+    };
+    Some(decoded)
+}
+
+fn decode_i8(instruction: u32) -> Option<Instruction> {
+    let funct = (instruction >> 8) & 0x7;
+    let ry = REGISTERS[((instruction >> 5) & 0x7) as usize];
+
+    let decoded = match funct {
+        BTEQZ | BTNEZ => Instruction::CompactBranch {
+            condition: zero_condition(funct == BTEQZ),
+            link: false,
+            rs: T8,
+            rt: 0,
+            offset: decoder::offset(instruction, 8, 1),
+        },
+        SWRASP => Instruction::Memory {
+            op: MemoryOp::Sw,
+            rt: RA,
+            base: SP,
+            offset: ((instruction & 0xff) << 2) as i64,
+        },
+        ADJSP => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: SP,
+            rs: SP,
+            immediate: decoder::offset(instruction, 8, 3) as u16,
+        },
+        // A frame of 0 means 128 bytes.
+        SVRS => {
+            let frame = match instruction & 0xf {
+                0 => 128,
+                size => size << 3,
+            };
+            return save_restore(instruction, 0, 0, frame as u64);
+        },
+        // The 32-bit register's number has its low three bits on top.
+        MOV32R => {
+            let field = (instruction >> 3) & 0x1f;
+            Instruction::Register {
+                op: RegisterOp::Or,
+                rd: (field >> 2 | (field & 0x3) << 3) as usize,
+                rs: REGISTERS[(instruction & 0x7) as usize],
+                rt: 0,
+            }
+        },
+        MOVR32 => Instruction::Register {
+            op: RegisterOp::Or,
+            rd: ry,
+            rs: (instruction & 0x1f) as usize,
+            rt: 0,
+        },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// SAVE and RESTORE, with bit 7 of `instruction` picking SAVE and bits 6
+// to 4 asking for $ra, $s0 and $s1. `xsregs` adds that many more static
+// registers from $s2 up, with 7 meaning $s2 to $s7 and $fp. `aregs` says
+// how many argument registers are stored in the caller's frame and how
+// many, from $a3 down, are pushed with the static registers.
+fn save_restore(instruction: u32,
+                xsregs: u32,
+                aregs: u32,
+                frame: u64) -> Option<Instruction> {
+    let (arguments, statics) = match aregs {
+        0..=3 => (0, aregs),
+        4..=7 => (1, aregs - 4),
+        8..=10 => (2, aregs - 8),
+        11 => (0, 4),
+        12 | 13 => (3, aregs - 12),
+        14 => (4, 0),
+        _ => return None,
+    };
+    let extra = match xsregs {
+        7 => 0x3f << 18 | 1 << 30,
+        count => ((1 << count) - 1) << 18,
+    };
+    let registers = ((1 << statics) - 1) << (8 - statics) | extra |
+        ((instruction >> 5) & 1) << 16 | ((instruction >> 4) & 1) << 17 |
+        ((instruction >> 6) & 1) << RA;
+    if instruction & 0x80 != 0 {
+        Some(Instruction::Save { arguments, registers, frame })
+    } else {
+        Some(Instruction::Restore { registers, frame })
+    }
+}
+
+fn decode_rr(instruction: u32) -> Option<Instruction> {
+    let rx = REGISTERS[((instruction >> 8) & 0x7) as usize];
+    let ry = REGISTERS[((instruction >> 5) & 0x7) as usize];
+    let code = (instruction >> 5) & 0x3f;
+
+    let decoded = match instruction & 0x1f {
+        JR => return decode_jr(instruction, rx),
+        SDBBP => Instruction::Sdbbp { code },
+        BREAK => Instruction::Break { code },
+        SLT => Instruction::Register {
+            op: RegisterOp::Slt,
+            rd: T8,
+            rs: rx,
+            rt: ry,
+        },
+        SLTU => Instruction::Register {
+            op: RegisterOp::Sltu,
+            rd: T8,
+            rs: rx,
+            rt: ry,
+        },
+        CMP => Instruction::Register {
+            op: RegisterOp::Xor,
+            rd: T8,
+            rs: rx,
+            rt: ry,
+        },
+        // The variable shifts move ry by rx.
+        function @ (SLLV | SRLV | SRAV) => {
+            let op = match function {
+                SLLV => ShiftVariableOp::Sllv,
+                SRLV => ShiftVariableOp::Srlv,
+                _ => ShiftVariableOp::Srav,
+            };
+            Instruction::ShiftVariable { op, rd: ry, rt: ry, rs: rx }
+        },
+        NEG => Instruction::Register {
+            op: RegisterOp::Subu,
+            rd: rx,
+            rs: 0,
+            rt: ry,
+        },
+        function @ (AND | OR | XOR) => {
+            let op = match function {
+                AND => RegisterOp::And,
+                OR => RegisterOp::Or,
+                _ => RegisterOp::Xor,
+            };
+            Instruction::Register { op, rd: rx, rs: rx, rt: ry }
+        },
+        NOT => Instruction::Register {
+            op: RegisterOp::Nor,
+            rd: rx,
+            rs: ry,
+            rt: 0,
+        },
+        MFHI => Instruction::Mfhi { rd: rx, ac: 0 },
+        MFLO => Instruction::Mflo { rd: rx, ac: 0 },
+        CNVT => match (instruction >> 5) & 0x7 {
+            ZEB | ZEH => Instruction::Immediate {
+                op: ImmediateOp::Andi,
+                rt: rx,
+                rs: rx,
+                immediate: if (instruction >> 5) & 0x7 == ZEB {
+                    0xff
+                } else {
+                    0xffff
+                },
+            },
+            SEB => Instruction::Shuffle { op: ShuffleOp::Seb, rd: rx, rt: rx },
+            SEH => Instruction::Shuffle { op: ShuffleOp::Seh, rd: rx, rt: rx },
+            _ => return None,
+        },
+        function @ (MULT | MULTU | DIV | DIVU) => {
+            let op = match function {
+                MULT => HiLoOp::Mult,
+                MULTU => HiLoOp::Multu,
+                DIV => HiLoOp::Div,
+                _ => HiLoOp::Divu,
+            };
+            Instruction::HiLo { op, ac: 0, rs: rx, rt: ry }
+        },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// JR, JALR and their compact forms. Bit 7 drops the delay slot, bit 6
+// links through $ra and bit 5 jumps through $ra rather than rx.
+fn decode_jr(instruction: u32, rx: usize) -> Option<Instruction> {
+    let rs = if instruction & 0x20 != 0 { RA } else { rx };
+    let decoded = match (instruction >> 5) & 0x7 {
+        0 | 1 => Instruction::Jalr { rd: 0, rs },
+        2 => Instruction::Jalr { rd: RA, rs },
+        4 | 5 => Instruction::Jic { rt: rs, offset: 0 },
+        6 => Instruction::Jalrc { rd: RA, rs },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+// JAL and JALX, whose 26-bit target has its top ten bits in the first
+// halfword, the five below the top first.
+fn decode_jal(instruction: u32) -> Instruction {
+    let target = (instruction >> 16 & 0x1f) << 21 |
+        (instruction >> 21 & 0x1f) << 16 | (instruction & 0xffff);
+    let target = (target as u64) << 2;
+    if instruction & (1 << 26) != 0 {
+        Instruction::Jalx { target }
+    } else {
+        Instruction::Jal { target }
+    }
+}
+
+// An instruction behind EXTEND, whose eleven bits are `extend`. Most take
+// a 16-bit immediate made of its low five bits on top, then the six above
+// them, then the low five bits of `instruction`, and don't scale it.
+fn decode_extended(extend: u32, instruction: u32) -> Option<Instruction> {
+    let major = (instruction >> 11) as i32;
+    let funct = (instruction >> 8) & 0x7;
+    let rx = REGISTERS[((instruction >> 8) & 0x7) as usize];
+    let ry = REGISTERS[((instruction >> 5) & 0x7) as usize];
+    let immediate = (extend & 0x1f) << 11 | (extend >> 5) << 5 |
+        (instruction & 0x1f);
+    let immediate = immediate as u16;
+    let offset = immediate as i16 as i64;
+
+    let decoded = match major {
+        ADDIUSP => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: rx,
+            rs: SP,
+            immediate,
+        },
+        ADDIUPC => Instruction::PcRelative {
+            op: PcRelativeOp::Addiupc,
+            rs: rx,
+            offset,
+        },
+        B => Instruction::Bc { offset: offset << 1 },
+        BEQZ | BNEZ => Instruction::CompactBranch {
+            condition: zero_condition(major == BEQZ),
+            link: false,
+            rs: rx,
+            rt: 0,
+            offset: offset << 1,
+        },
+        SHIFT => Instruction::Shift {
+            op: shift_op(instruction)?,
+            rd: rx,
+            rt: ry,
+            sa: (extend >> 6) & 0x1f,
+        },
+        // A 15-bit immediate: bits 10 to 4 of `extend`, then its low four
+        // bits, on top of the low four of `instruction`.
+        RRIA if instruction & 0x10 == 0 => {
+            let immediate = (extend & 0xf) << 11 | (extend >> 4) << 4 |
+                (instruction & 0xf);
+            Instruction::Immediate {
+                op: ImmediateOp::Addiu,
+                rt: ry,
+                rs: rx,
+                immediate: decoder::offset(immediate, 15, 0) as u16,
+            }
+        },
+        ADDIU8 => Instruction::Immediate {
+            op: ImmediateOp::Addiu,
+            rt: rx,
+            rs: rx,
+            immediate,
+        },
+        SLTI | SLTIU => Instruction::Immediate {
+            op: if major == SLTI {
+                ImmediateOp::Slti
+            } else {
+                ImmediateOp::Sltiu
+            },
+            rt: T8,
+            rs: rx,
+            immediate,
+        },
+        I8 => match funct {
+            BTEQZ | BTNEZ => Instruction::CompactBranch {
+                condition: zero_condition(funct == BTEQZ),
+                link: false,
+                rs: T8,
+                rt: 0,
+                offset: offset << 1,
+            },
+            SWRASP => Instruction::Memory {
+                op: MemoryOp::Sw,
+                rt: RA,
+                base: SP,
+                offset,
+            },
+            ADJSP => Instruction::Immediate {
+                op: ImmediateOp::Addiu,
+                rt: SP,
+                rs: SP,
+                immediate,
+            },
+            // The frame is eight bits of doublewords, the top four in
+            // `extend`, and can be 0.
+            SVRS => {
+                let frame = ((extend >> 4) & 0xf) << 4 | (instruction & 0xf);
+                return save_restore(instruction,
+                                    extend >> 8,
+                                    extend & 0xf,
+                                    (frame << 3) as u64);
+            },
+            _ => return None,
+        },
+        LI => Instruction::Immediate {
+            op: ImmediateOp::Ori,
+            rt: rx,
+            rs: 0,
+            immediate,
+        },
+        CMPI => Instruction::Immediate {
+            op: ImmediateOp::Xori,
+            rt: T8,
+            rs: rx,
+            immediate,
+        },
+        LWSP | SWSP => Instruction::Memory {
+            op: if major == LWSP { MemoryOp::Lw } else { MemoryOp::Sw },
+            rt: rx,
+            base: SP,
+            offset,
+        },
+        LWPC => Instruction::PcRelative {
+            op: PcRelativeOp::Lwpc,
+            rs: rx,
+            offset,
+        },
+        _ => {
+            let (op, _) = memory_op(major)?;
+            Instruction::Memory { op, rt: ry, base: rx, offset }
+        },
+    };
+    Some(decoded)
+}
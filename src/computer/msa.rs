@@ -0,0 +1,308 @@
+use crate::computer::decoder::{LogicOp, VectorCountOp, VectorFormat, VectorOp};
+
+fn mask(bits: u32) -> u64 {
+    if bits == 64 { !0 } else { (1 << bits) - 1 }
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    ((value << (64 - bits)) as i64) >> (64 - bits)
+}
+
+// Elements are numbered from the least significant end of the vector, so
+// element 0 of a D vector is the floating-point register it overlays.
+fn get(vector: u128, bits: u32, index: usize) -> u64 {
+    (vector >> (index as u32 * bits)) as u64 & mask(bits)
+}
+
+pub fn element(vector: u128, df: VectorFormat, index: usize) -> u64 {
+    get(vector, df.bits(), index)
+}
+
+pub fn signed_element(vector: u128, df: VectorFormat, index: usize) -> i64 {
+    sign_extend(element(vector, df, index), df.bits())
+}
+
+pub fn with_element(vector: u128,
+                    df: VectorFormat,
+                    index: usize,
+                    value: u64) -> u128 {
+    let shift = index as u32 * df.bits();
+    let field = (mask(df.bits()) as u128) << shift;
+    (vector & !field) | (((value & mask(df.bits())) as u128) << shift)
+}
+
+// Builds a vector from its elements, truncating each one to fit.
+pub fn from_elements(df: VectorFormat,
+                     mut element: impl FnMut(usize) -> u64) -> u128 {
+    (0..df.elements()).fold(0, |vector, i| with_element(vector, df, i,
+                                                        element(i)))
+}
+
+pub fn splat(df: VectorFormat, value: u64) -> u128 {
+    from_elements(df, |_| value)
+}
+
+fn saturate_signed(value: i128, bits: u32) -> i128 {
+    value.clamp(-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+}
+
+fn saturate_unsigned(value: i128, bits: u32) -> i128 {
+    value.clamp(0, (1 << bits) - 1)
+}
+
+// A right shift that rounds to nearest by adding the last bit shifted out.
+fn rounded_shift(value: i128, shift: u32) -> i128 {
+    if shift == 0 {
+        value
+    } else {
+        (value >> shift) + ((value >> (shift - 1)) & 1)
+    }
+}
+
+// The _Q ops treat elements as fractions with the binary point just below
+// the sign bit. Products keep the top half, rounded for the R forms, and
+// saturate.
+fn fixed_point(op: VectorOp, bits: u32, d: i128, s: i128, t: i128) -> i128 {
+    let product = s * t;
+    let (sum, rounded) = match op {
+        VectorOp::MulQ => (product, false),
+        VectorOp::MulrQ => (product, true),
+        VectorOp::MaddQ => ((d << (bits - 1)) + product, false),
+        VectorOp::MaddrQ => ((d << (bits - 1)) + product, true),
+        VectorOp::MsubQ => ((d << (bits - 1)) - product, false),
+        _ => ((d << (bits - 1)) - product, true),
+    };
+    let round = if rounded { 1 << (bits - 2) } else { 0 };
+    saturate_signed((sum + round) >> (bits - 1), bits)
+}
+
+// One element of an op that works lane by lane. Everything is worked out
+// in i128 from both the signed and unsigned readings of the elements, so
+// nothing overflows before the result is truncated.
+fn lane(op: VectorOp, bits: u32, d: u64, s: u64, t: u64) -> u64 {
+    let (sd, ss, st) = (sign_extend(d, bits) as i128,
+                        sign_extend(s, bits) as i128,
+                        sign_extend(t, bits) as i128);
+    let (ud, us, ut) = (d as i128, s as i128, t as i128);
+    let ones = mask(bits) as i128;
+    let flag = |condition: bool| if condition { ones } else { 0 };
+    // Shifts and bit numbers only use as many bits of t as they need.
+    let n = (t % bits as u64) as u32;
+    let value = match op {
+        VectorOp::Sll => us << n,
+        VectorOp::Sra => ss >> n,
+        VectorOp::Srl => us >> n,
+        VectorOp::Srar => rounded_shift(ss, n),
+        VectorOp::Srlr => rounded_shift(us, n),
+        VectorOp::Bclr => us & !(1 << n),
+        VectorOp::Bset => us | (1 << n),
+        VectorOp::Bneg => us ^ (1 << n),
+        // Copy the top or bottom n + 1 bits of s into d.
+        VectorOp::Binsl => {
+            let high = ones ^ (ones >> (n + 1));
+            (ud & !high) | (us & high)
+        },
+        VectorOp::Binsr => {
+            let low = (1 << (n + 1)) - 1;
+            (ud & !low) | (us & low)
+        },
+        VectorOp::Addv => us + ut,
+        VectorOp::Subv => us - ut,
+        VectorOp::Mulv => ss * st,
+        VectorOp::Maddv => sd + ss * st,
+        VectorOp::Msubv => sd - ss * st,
+        VectorOp::MaxS => ss.max(st),
+        VectorOp::MaxU => us.max(ut),
+        VectorOp::MinS => ss.min(st),
+        VectorOp::MinU => us.min(ut),
+        VectorOp::MaxA => if ss.abs() > st.abs() { ss } else { st },
+        VectorOp::MinA => if ss.abs() < st.abs() { ss } else { st },
+        VectorOp::Ceq => flag(s == t),
+        VectorOp::CltS => flag(ss < st),
+        VectorOp::CltU => flag(us < ut),
+        VectorOp::CleS => flag(ss <= st),
+        VectorOp::CleU => flag(us <= ut),
+        VectorOp::AddA => ss.abs() + st.abs(),
+        VectorOp::AddsA => saturate_signed(ss.abs() + st.abs(), bits),
+        VectorOp::AddsS => saturate_signed(ss + st, bits),
+        VectorOp::AddsU => saturate_unsigned(us + ut, bits),
+        VectorOp::AveS => (ss + st) >> 1,
+        VectorOp::AveU => (us + ut) >> 1,
+        VectorOp::AverS => (ss + st + 1) >> 1,
+        VectorOp::AverU => (us + ut + 1) >> 1,
+        VectorOp::SubsS => saturate_signed(ss - st, bits),
+        VectorOp::SubsU => saturate_unsigned(us - ut, bits),
+        VectorOp::SubsusU => saturate_unsigned(us - st, bits),
+        VectorOp::SubsuuS => saturate_signed(us - ut, bits),
+        VectorOp::AsubS => (ss - st).abs(),
+        VectorOp::AsubU => (us - ut).abs(),
+        // Dividing by zero gives what the hardware does rather than
+        // trapping.
+        VectorOp::DivS if st == 0 => if ss >= 0 { -1 } else { 1 },
+        VectorOp::DivS => ss / st,
+        VectorOp::DivU if ut == 0 => ones,
+        VectorOp::DivU => us / ut,
+        VectorOp::ModS if st == 0 => ss,
+        VectorOp::ModS => ss % st,
+        VectorOp::ModU if ut == 0 => us,
+        VectorOp::ModU => us % ut,
+        // t is the bit number of the sign of the narrower format.
+        VectorOp::SatS => saturate_signed(ss, n + 1),
+        VectorOp::SatU => saturate_unsigned(us, n + 1),
+        VectorOp::MulQ | VectorOp::MulrQ | VectorOp::MaddQ |
+        VectorOp::MaddrQ | VectorOp::MsubQ | VectorOp::MsubrQ =>
+            fixed_point(op, bits, sd, ss, st),
+        _ => unreachable!("{:?} doesn't work lane by lane", op),
+    };
+    value as u64
+}
+
+// Dot products and horizontal ops: each element of the result comes from
+// the even and odd elements, half its size, in the same place in ws and wt.
+fn widening(op: VectorOp, df: VectorFormat, d: u128, s: u128, t: u128)
+    -> u128 {
+    let bits = df.bits();
+    let half = bits / 2;
+    let signed = matches!(op, VectorOp::DotpS | VectorOp::DpaddS |
+                              VectorOp::DpsubS | VectorOp::HaddS |
+                              VectorOp::HsubS);
+    let extend = |vector: u128, index: usize| {
+        let value = get(vector, half, index);
+        if signed {
+            sign_extend(value, half) as i128
+        } else {
+            value as i128
+        }
+    };
+    from_elements(df, |i| {
+        let (s_even, s_odd) = (extend(s, 2 * i), extend(s, 2 * i + 1));
+        let (t_even, t_odd) = (extend(t, 2 * i), extend(t, 2 * i + 1));
+        let d = get(d, bits, i) as i128;
+        let dot = s_even * t_even + s_odd * t_odd;
+        let value = match op {
+            VectorOp::DotpS | VectorOp::DotpU => dot,
+            VectorOp::DpaddS | VectorOp::DpaddU => d + dot,
+            VectorOp::DpsubS | VectorOp::DpsubU => d - dot,
+            VectorOp::HaddS | VectorOp::HaddU => s_odd + t_even,
+            _ => s_odd - t_even,
+        };
+        value as u64
+    })
+}
+
+// Ops that pick whole elements out of ws and wt. The left half of a vector
+// is its most significant half.
+fn shuffle(op: VectorOp, df: VectorFormat, d: u128, s: u128, t: u128)
+    -> u128 {
+    let count = df.elements();
+    let half = count / 2;
+    let from = |vector: u128, index: usize| element(vector, df, index);
+    from_elements(df, |i| match op {
+        VectorOp::Pckev if i < half => from(t, 2 * i),
+        VectorOp::Pckev => from(s, 2 * (i - half)),
+        VectorOp::Pckod if i < half => from(t, 2 * i + 1),
+        VectorOp::Pckod => from(s, 2 * (i - half) + 1),
+        VectorOp::Ilvl if i % 2 == 0 => from(t, half + i / 2),
+        VectorOp::Ilvl => from(s, half + i / 2),
+        VectorOp::Ilvr if i % 2 == 0 => from(t, i / 2),
+        VectorOp::Ilvr => from(s, i / 2),
+        VectorOp::Ilvev if i % 2 == 0 => from(t, i),
+        VectorOp::Ilvev => from(s, i - 1),
+        VectorOp::Ilvod if i % 2 == 0 => from(t, i + 1),
+        VectorOp::Ilvod => from(s, i),
+        // Each element of wd indexes into wt followed by ws, and indexes
+        // with either of the top two bits set give zero.
+        _ => {
+            let index = from(d, i);
+            if index & 0xc0 != 0 {
+                0
+            } else {
+                let index = index as usize % (2 * count);
+                if index < count {
+                    from(t, index)
+                } else {
+                    from(s, index - count)
+                }
+            }
+        },
+    })
+}
+
+pub fn operate(op: VectorOp, df: VectorFormat, d: u128, s: u128, t: u128)
+    -> u128 {
+    match op {
+        VectorOp::DotpS | VectorOp::DotpU | VectorOp::DpaddS |
+        VectorOp::DpaddU | VectorOp::DpsubS | VectorOp::DpsubU |
+        VectorOp::HaddS | VectorOp::HaddU | VectorOp::HsubS |
+        VectorOp::HsubU => widening(op, df, d, s, t),
+        VectorOp::Pckev | VectorOp::Pckod | VectorOp::Ilvl | VectorOp::Ilvr |
+        VectorOp::Ilvev | VectorOp::Ilvod | VectorOp::Vshf =>
+            shuffle(op, df, d, s, t),
+        _ => from_elements(df, |i| lane(op, df.bits(), element(d, df, i),
+                                        element(s, df, i), element(t, df, i))),
+    }
+}
+
+pub fn logic(op: LogicOp, d: u128, s: u128, t: u128) -> u128 {
+    match op {
+        LogicOp::And => s & t,
+        LogicOp::Or => s | t,
+        LogicOp::Nor => !(s | t),
+        LogicOp::Xor => s ^ t,
+        LogicOp::Bmnz => (s & t) | (d & !t),
+        LogicOp::Bmz => (s & !t) | (d & t),
+        LogicOp::Bsel => (s & !d) | (t & d),
+    }
+}
+
+pub fn count(op: VectorCountOp, df: VectorFormat, s: u128) -> u128 {
+    let unused = 64 - df.bits();
+    from_elements(df, |i| {
+        let value = element(s, df, i);
+        let count = match op {
+            VectorCountOp::Pcnt => value.count_ones(),
+            VectorCountOp::Nloc =>
+                (!value & mask(df.bits())).leading_zeros() - unused,
+            VectorCountOp::Nlzc => value.leading_zeros() - unused,
+        };
+        count as u64
+    })
+}
+
+// Shuffles each group of four elements by the two-bit indexes in the
+// immediate, lowest first.
+pub fn shf(df: VectorFormat, s: u128, immediate: u8) -> u128 {
+    from_elements(df, |i| {
+        let index = (immediate >> (2 * (i % 4))) as usize & 3;
+        element(s, df, (i & !3) + index)
+    })
+}
+
+// SLD and SLDI. The vector is split into slices as many bytes long as it
+// has elements, and each slice of ws followed by the same slice of wd
+// slides along by n bytes into wd.
+pub fn slide(df: VectorFormat, d: u128, s: u128, n: usize) -> u128 {
+    let size = df.elements();
+    let n = n % size;
+    let mut result = 0;
+    for slice in (0..16).step_by(size) {
+        for i in 0..size {
+            let byte = if i + n < size {
+                get(s, 8, slice + i + n)
+            } else {
+                get(d, 8, slice + i + n - size)
+            };
+            result |= (byte as u128) << (8 * (slice + i));
+        }
+    }
+    result
+}
+
+// The condition for BZ and BNZ: with a format, whether any element is
+// zero, and without one, whether the whole vector is.
+pub fn is_zero(df: Option<VectorFormat>, vector: u128) -> bool {
+    match df {
+        None => vector == 0,
+        Some(df) => (0..df.elements()).any(|i| element(vector, df, i) == 0),
+    }
+}
@@ -1,3 +1,290 @@
-struct Program {
+use std::fmt;
 
+use crate::computer::memory::Endianness;
+
+// ELF identification
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const EI_NIDENT: usize = 16;
+const EI_CLASS: usize = 4;
+const EI_DATA: usize = 5;
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+// ELF header values
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const EM_MIPS: u16 = 8;
+const PT_LOAD: u32 = 1;
+const PT_PHDR: u32 = 6;
+const PF_X: u64 = 0x1;
+
+// Header sizes
+const ELF32_EHDR_SIZE: usize = 52;
+const ELF32_PHDR_SIZE: u64 = 32;
+const ELF64_EHDR_SIZE: usize = 64;
+const ELF64_PHDR_SIZE: u64 = 56;
+
+// Both o32 and n32 programs are ELF32 and so run with 32-bit addresses;
+// only n64 programs get the full 64-bit address space.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Elf32,
+    Elf64,
+}
+
+#[derive(Debug)]
+pub enum ProgramError {
+    Truncated,
+    NotElf,
+    UnsupportedClass(u8),
+    UnsupportedEndianness(u8),
+    UnsupportedType(u16),
+    NotMips(u16),
+    NoLoadableSegments,
+    TooLarge { needed: u64, available: u64 },
+    Mapped(u64),
+    Stack(u64),
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgramError::Truncated => write!(f, "file is truncated"),
+            ProgramError::NotElf => write!(f, "not an ELF file"),
+            ProgramError::UnsupportedClass(class) =>
+                write!(f, "unsupported ELF class {}", class),
+            ProgramError::UnsupportedEndianness(data) =>
+                write!(f, "unsupported ELF data encoding {}", data),
+            ProgramError::UnsupportedType(kind) =>
+                write!(f, "unsupported ELF type {}", kind),
+            ProgramError::NotMips(machine) =>
+                write!(f, "not a MIPS program (machine {})", machine),
+            ProgramError::NoLoadableSegments =>
+                write!(f, "program has no loadable segments"),
+            ProgramError::TooLarge { needed, available } =>
+                write!(f, "program needs {} bytes of memory but only {} \
+                           are available", needed, available),
+            ProgramError::Mapped(address) =>
+                write!(f, "segment at 0x{:x} isn't in an unmapped kernel \
+                           segment", address),
+            ProgramError::Stack(address) =>
+                write!(f, "no room for the initial stack at 0x{:x}", address),
+        }
+    }
+}
+
+// A PT_LOAD segment. Anything past the end of `data` up to `size` is BSS
+// and gets zero-filled when the program is loaded.
+pub struct Segment {
+    pub address: u64,
+    pub size: u64,
+    pub data: Vec<u8>,
+    pub executable: bool,
+}
+
+pub struct Program {
+    pub class: Class,
+    // The byte order the program was built for, and so the one memory has
+    // to use to run it.
+    pub endianness: Endianness,
+    pub entry: u64,
+    pub segments: Vec<Segment>,
+    // Where the program headers end up in memory, if they get loaded at all,
+    // along with their size and count. A Linux program finds its own headers
+    // through the auxiliary vector.
+    pub headers: Option<u64>,
+    pub header_size: u64,
+    pub header_count: u64,
+}
+
+impl Program {
+    // The lowest and one-past-the-highest virtual address any segment
+    // occupies.
+    pub fn bounds(&self) -> (u64, u64) {
+        let low = self.segments.iter()
+            .map(|segment| segment.address)
+            .min()
+            .unwrap_or(0);
+        let high = self.segments.iter()
+            .map(|segment| segment.address.saturating_add(segment.size))
+            .max()
+            .unwrap_or(0);
+        (low, high)
+    }
+}
+
+// Reads fields out of the file in whatever byte order the file says it uses.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    endianness: Endianness,
+    class: Class,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&self, offset: u64, size: u64) -> Result<u64, ProgramError> {
+        let start = offset as usize;
+        let end = start.checked_add(size as usize)
+            .ok_or(ProgramError::Truncated)?;
+        let field = self.bytes.get(start..end)
+            .ok_or(ProgramError::Truncated)?;
+        let mut value: u64 = 0;
+        for i in 0..field.len() {
+            let byte = match self.endianness {
+                Endianness::Little => field[field.len() - 1 - i],
+                Endianness::Big => field[i],
+            };
+            value = (value << 8) | byte as u64;
+        }
+        Ok(value)
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, ProgramError> {
+        self.read(offset, 2).map(|value| value as u16)
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, ProgramError> {
+        self.read(offset, 4).map(|value| value as u32)
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64, ProgramError> {
+        self.read(offset, 8)
+    }
+
+    // A native-sized offset or size field.
+    fn word(&self, offset: u64) -> Result<u64, ProgramError> {
+        match self.class {
+            Class::Elf32 => self.u32(offset).map(|value| value as u64),
+            Class::Elf64 => self.u64(offset),
+        }
+    }
+
+    // A native-sized address field. 32-bit addresses are sign-extended the
+    // same way a MIPS64 CPU sees them, so 0x80000000 becomes
+    // 0xffffffff80000000.
+    fn address(&self, offset: u64) -> Result<u64, ProgramError> {
+        match self.class {
+            Class::Elf32 =>
+                self.u32(offset).map(|value| value as i32 as i64 as u64),
+            Class::Elf64 => self.u64(offset),
+        }
+    }
+
+    fn slice(&self, offset: u64, size: u64) -> Result<&'a [u8], ProgramError> {
+        let start = offset as usize;
+        let end = start.checked_add(size as usize)
+            .ok_or(ProgramError::Truncated)?;
+        self.bytes.get(start..end).ok_or(ProgramError::Truncated)
+    }
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Program, ProgramError> {
+    if bytes.len() < EI_NIDENT || bytes[0..4] != ELF_MAGIC {
+        return Err(ProgramError::NotElf);
+    }
+
+    let endianness = match bytes[EI_DATA] {
+        ELFDATA2LSB => Endianness::Little,
+        ELFDATA2MSB => Endianness::Big,
+        data => return Err(ProgramError::UnsupportedEndianness(data)),
+    };
+    let class = match bytes[EI_CLASS] {
+        ELFCLASS32 => Class::Elf32,
+        ELFCLASS64 => Class::Elf64,
+        class => return Err(ProgramError::UnsupportedClass(class)),
+    };
+    let header_size = match class {
+        Class::Elf32 => ELF32_EHDR_SIZE,
+        Class::Elf64 => ELF64_EHDR_SIZE,
+    };
+    if bytes.len() < header_size {
+        return Err(ProgramError::Truncated);
+    }
+
+    let reader = Reader { bytes, endianness, class };
+
+    let kind = reader.u16(16)?;
+    if kind != ET_EXEC && kind != ET_DYN {
+        return Err(ProgramError::UnsupportedType(kind));
+    }
+    let machine = reader.u16(18)?;
+    if machine != EM_MIPS {
+        return Err(ProgramError::NotMips(machine));
+    }
+
+    // Field offsets past e_entry depend on the size of a native word.
+    let (phoff, phentsize, phnum, phdr_size) = match class {
+        Class::Elf32 => (reader.word(28)?, reader.u16(42)?, reader.u16(44)?,
+                         ELF32_PHDR_SIZE),
+        Class::Elf64 => (reader.word(32)?, reader.u16(54)?, reader.u16(56)?,
+                         ELF64_PHDR_SIZE),
+    };
+    let entry = reader.address(24)?;
+    let phentsize = phentsize as u64;
+    let phnum = phnum as u64;
+    if phentsize < phdr_size {
+        return Err(ProgramError::Truncated);
+    }
+
+    let mut segments = Vec::new();
+    let mut headers = None;
+    for i in 0..phnum {
+        let header = match phoff.checked_add(i * phentsize) {
+            Some(header) if header < bytes.len() as u64 => header,
+            _ => return Err(ProgramError::Truncated),
+        };
+        let kind = reader.u32(header)?;
+        if kind != PT_LOAD && kind != PT_PHDR {
+            continue;
+        }
+        let (offset, address, filesz, memsz, flags) = match class {
+            Class::Elf32 => (reader.word(header + 4)?,
+                             reader.address(header + 8)?,
+                             reader.word(header + 16)?,
+                             reader.word(header + 20)?,
+                             reader.u32(header + 24)? as u64),
+            Class::Elf64 => (reader.word(header + 8)?,
+                             reader.address(header + 16)?,
+                             reader.word(header + 32)?,
+                             reader.word(header + 40)?,
+                             reader.u32(header + 4)? as u64),
+        };
+        if kind == PT_PHDR {
+            headers = Some(address);
+            continue;
+        }
+        // Without a PT_PHDR, the headers are loaded if they happen to fall
+        // inside a loaded part of the file.
+        if headers.is_none() && offset <= phoff &&
+            phoff < offset.saturating_add(filesz) {
+            headers = Some(address.wrapping_add(phoff - offset));
+        }
+        if memsz == 0 {
+            continue;
+        }
+        // A file size larger than the memory size isn't valid ELF, so
+        // just drop whatever doesn't fit.
+        let data = reader.slice(offset, filesz.min(memsz))?.to_vec();
+        segments.push(Segment {
+            address,
+            size: memsz,
+            data,
+            executable: flags & PF_X != 0,
+        });
+    }
+
+    if segments.is_empty() {
+        return Err(ProgramError::NoLoadableSegments);
+    }
+
+    Ok(Program {
+        class,
+        endianness,
+        entry,
+        segments,
+        headers,
+        header_size: phentsize,
+        header_count: phnum,
+    })
 }
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::computer::cpu::Cpu;
+use crate::computer::memory::Memory;
+use crate::computer::program::{Program, ProgramError};
+use crate::computer::syscall::{SyscallHandler, SyscallResult};
+
+// Registers
+const V0: usize = 2;
+const A0: usize = 4;
+const A1: usize = 5;
+const A2: usize = 6;
+const F0: usize = 0;
+const F12: usize = 12;
+
+// Syscall numbers, passed in $v0
+const PRINT_INT: u64 = 1;
+const PRINT_FLOAT: u64 = 2;
+const PRINT_DOUBLE: u64 = 3;
+const PRINT_STRING: u64 = 4;
+const READ_INT: u64 = 5;
+const READ_FLOAT: u64 = 6;
+const READ_DOUBLE: u64 = 7;
+const READ_STRING: u64 = 8;
+const SBRK: u64 = 9;
+const EXIT: u64 = 10;
+const PRINT_CHAR: u64 = 11;
+const READ_CHAR: u64 = 12;
+const OPEN: u64 = 13;
+const READ: u64 = 14;
+const WRITE: u64 = 15;
+const CLOSE: u64 = 16;
+const EXIT2: u64 = 17;
+
+// Flags for OPEN, as MARS defines them
+const OPEN_READ: u64 = 0;
+const OPEN_WRITE: u64 = 1;
+const OPEN_APPEND: u64 = 9;
+
+// The standard file descriptors
+const STDIN: u64 = 0;
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+// The classic SPIM/MARS syscall services. Console I/O goes to the host's
+// stdin and stdout, and files are only opened inside the sandbox directory.
+pub struct Spim {
+    sandbox: PathBuf,
+    files: HashMap<u64, File>,
+    next_fd: u64,
+    brk: u64,
+}
+
+pub fn new(sandbox: PathBuf) -> Spim {
+    Spim {
+        sandbox,
+        files: HashMap::new(),
+        next_fd: STDERR + 1,
+        brk: 0,
+    }
+}
+
+impl Spim {
+    // Only relative paths that stay inside the sandbox are allowed.
+    fn resolve(&self, name: &[u8]) -> Option<PathBuf> {
+        let name = std::str::from_utf8(name).ok()?;
+        let path = Path::new(name);
+        for component in path.components() {
+            match component {
+                Component::Normal(_) | Component::CurDir => {},
+                _ => return None,
+            }
+        }
+        Some(self.sandbox.join(path))
+    }
+
+    fn open(&mut self, name: &[u8], flags: u64) -> Option<u64> {
+        let path = self.resolve(name)?;
+        let file = match flags {
+            OPEN_READ => File::open(path),
+            OPEN_WRITE => File::create(path),
+            OPEN_APPEND => OpenOptions::new().append(true).create(true)
+                .open(path),
+            _ => return None,
+        }.ok()?;
+        let fd = self.next_fd;
+        self.next_fd += 1;
+        self.files.insert(fd, file);
+        Some(fd)
+    }
+
+    fn read(&mut self, fd: u64, size: u64) -> Option<Vec<u8>> {
+        let mut buffer = vec![0; size as usize];
+        let count = match fd {
+            STDIN => io::stdin().read(&mut buffer),
+            _ => self.files.get_mut(&fd)?.read(&mut buffer),
+        }.ok()?;
+        buffer.truncate(count);
+        Some(buffer)
+    }
+
+    fn write(&mut self, fd: u64, bytes: &[u8]) -> Option<usize> {
+        match fd {
+            STDOUT => io::stdout().write_all(bytes)
+                .and_then(|_| io::stdout().flush()),
+            STDERR => io::stderr().write_all(bytes),
+            _ => self.files.get_mut(&fd)?.write_all(bytes),
+        }.ok()?;
+        Some(bytes.len())
+    }
+
+    fn read_line(&mut self) -> String {
+        let mut line = String::new();
+        // EOF or a read error both just look like an empty line.
+        let _ = io::stdin().lock().read_line(&mut line);
+        line
+    }
+}
+
+impl SyscallHandler for Spim {
+    fn load(&mut self,
+            program: &Program,
+            _cpus: &mut [Cpu],
+            _memory: &mut Memory) -> Result<(), ProgramError> {
+        let (_, high) = program.bounds();
+        self.brk = (high + 7) & !7;
+        Ok(())
+    }
+
+    fn syscall(&mut self, cpu: &mut Cpu, memory: &mut Memory) -> SyscallResult {
+        let id = cpu.id();
+        let a0 = cpu.register(A0);
+        let a1 = cpu.register(A1);
+        let a2 = cpu.register(A2);
+
+        // Results are 32-bit values and get sign-extended like any other.
+        let result: i32 = match cpu.register(V0) {
+            PRINT_INT => {
+                self.write(STDOUT, (a0 as i32).to_string().as_bytes());
+                return SyscallResult::Resume;
+            },
+            // Floats come and go in $f12 and $f0. Whole numbers keep their
+            // ".0", as SPIM and MARS print them.
+            PRINT_FLOAT => {
+                let value = f32::from_bits(cpu.float_register(F12) as u32);
+                self.write(STDOUT, format!("{:?}", value).as_bytes());
+                return SyscallResult::Resume;
+            },
+            PRINT_DOUBLE => {
+                let value = f64::from_bits(cpu.float_register(F12));
+                self.write(STDOUT, format!("{:?}", value).as_bytes());
+                return SyscallResult::Resume;
+            },
+            PRINT_STRING => {
+                if let Some(string) = memory.read_string(id, a0) {
+                    self.write(STDOUT, &string);
+                }
+                return SyscallResult::Resume;
+            },
+            PRINT_CHAR => {
+                self.write(STDOUT, &[a0 as u8]);
+                return SyscallResult::Resume;
+            },
+            READ_INT => {
+                self.read_line().trim().parse().unwrap_or(0)
+            },
+            READ_FLOAT => {
+                let value: f32 = self.read_line().trim().parse().unwrap_or(0.0);
+                cpu.set_float_register(F0, value.to_bits() as u64);
+                return SyscallResult::Resume;
+            },
+            READ_DOUBLE => {
+                let value: f64 = self.read_line().trim().parse().unwrap_or(0.0);
+                cpu.set_float_register(F0, value.to_bits());
+                return SyscallResult::Resume;
+            },
+            READ_STRING => {
+                // Like fgets: at most a1 - 1 characters, newline included,
+                // always NUL-terminated.
+                if (a1 as i32) < 1 {
+                    return SyscallResult::Resume;
+                }
+                let mut line = self.read_line().into_bytes();
+                line.truncate(a1 as usize - 1);
+                line.push(0);
+                memory.write_virtual(id, a0, &line);
+                return SyscallResult::Resume;
+            },
+            READ_CHAR => {
+                match self.read(STDIN, 1) {
+                    Some(byte) if !byte.is_empty() => byte[0] as i32,
+                    _ => -1,
+                }
+            },
+            SBRK => {
+                let old = self.brk;
+                let new = old.wrapping_add(a0 as i32 as i64 as u64);
+                let mapped = new <= old ||
+                    memory.translate_address(id, new - 1).is_some();
+                if mapped {
+                    self.brk = new;
+                    cpu.set_register(V0, old);
+                } else {
+                    cpu.set_register(V0, -1i64 as u64);
+                }
+                return SyscallResult::Resume;
+            },
+            EXIT => return SyscallResult::Exit(0),
+            EXIT2 => return SyscallResult::Exit(a0 as i32),
+            OPEN => {
+                match memory.read_string(id, a0) {
+                    None => -1,
+                    Some(name) => match self.open(&name, a1) {
+                        None => -1,
+                        Some(fd) => fd as i32,
+                    },
+                }
+            },
+            READ if !memory.mapped(id, a1, a2 as u32 as u64) => -1,
+            READ => {
+                match self.read(a0, a2 as u32 as u64) {
+                    Some(bytes) if memory.write_virtual(id, a1, &bytes) =>
+                        bytes.len() as i32,
+                    _ => -1,
+                }
+            },
+            WRITE => {
+                let written = memory.read_virtual(id, a1, a2 as u32 as u64)
+                    .and_then(|bytes| self.write(a0, &bytes));
+                match written {
+                    None => -1,
+                    Some(count) => count as i32,
+                }
+            },
+            CLOSE => {
+                self.files.remove(&a0);
+                return SyscallResult::Resume;
+            },
+            _ => return SyscallResult::Unhandled,
+        };
+
+        cpu.set_register(V0, result as i64 as u64);
+        SyscallResult::Resume
+    }
+}
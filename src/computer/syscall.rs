@@ -0,0 +1,29 @@
+use crate::computer::cpu::Cpu;
+use crate::computer::memory::Memory;
+use crate::computer::program::{Program, ProgramError};
+
+pub enum SyscallResult {
+    // The syscall was serviced; carry on after the SYSCALL instruction.
+    Resume,
+    // The program asked to exit with the given status.
+    Exit(i32),
+    // Not something this handler knows about. The exception goes to the
+    // program's own handler, if it has one.
+    Unhandled,
+}
+
+// Services SYSCALL instructions on the host instead of in the guest. The
+// handler reads its arguments out of the CPU's registers and memory and
+// writes its results back the same way.
+pub trait SyscallHandler {
+    // Called once a program has been loaded, before it starts running, so
+    // the handler can set up whatever the program expects to find.
+    fn load(&mut self,
+            _program: &Program,
+            _cpus: &mut [Cpu],
+            _memory: &mut Memory) -> Result<(), ProgramError> {
+        Ok(())
+    }
+
+    fn syscall(&mut self, cpu: &mut Cpu, memory: &mut Memory) -> SyscallResult;
+}
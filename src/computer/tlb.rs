@@ -0,0 +1,141 @@
+// The joint TLB an R4000-style MMU translates mapped addresses with. Each
+// entry maps an even and odd pair of virtual pages, of any size from 4 KiB
+// to 256 MiB, onto physical pages. Entries are kept as the EntryHi,
+// PageMask and EntryLo images TLBWI writes and TLBR reads back.
+
+pub const ENTRIES: usize = 48;
+
+// EntryHi fields. Only VPN2 and R take part in a match, along with ASID
+// for entries that aren't global.
+pub const ENTRYHI_ASID: u64 = 0xff;
+pub const ENTRYHI_EHINV: u64 = 0x1 << 10;
+pub const ENTRYHI_VPN2: u64 = ((1 << SEGBITS) - 1) & !0x1fff;
+pub const ENTRYHI_R: u64 = 0x3 << 62;
+
+// EntryLo fields.
+const ENTRYLO_G: u64 = 0x1;
+const ENTRYLO_V: u64 = 0x1 << 1;
+const ENTRYLO_D: u64 = 0x1 << 2;
+const ENTRYLO_PFN_SHIFT: u64 = 6;
+pub const ENTRYLO_MASK: u64 = 0x3fffffff;
+
+// PageMask covers bits 28:13, two bits for every step up in page size.
+pub const PAGEMASK_MASK: u64 = 0x1fffe000;
+
+// The virtual address bits each 64-bit segment implements, and the
+// physical address bits EntryLo's PFN reaches.
+pub const SEGBITS: u32 = 40;
+pub const PABITS: u32 = 36;
+
+// Why a virtual address couldn't be turned into a physical one. Refill is
+// a miss, Invalid a match on a page that isn't valid, and Modified a store
+// to a page that isn't dirty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    AddressError,
+    Refill,
+    Invalid,
+    Modified,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Entry {
+    pub entry_hi: u64,
+    pub page_mask: u64,
+    pub entry_lo: [u64; 2],
+}
+
+impl Entry {
+    // G is only kept if both halves had it when the entry was written.
+    fn global(&self) -> bool {
+        self.entry_lo[0] & self.entry_lo[1] & ENTRYLO_G != 0
+    }
+
+    // Whether the entry maps `entry_hi`'s VPN2 and R for the given ASID.
+    // The bits PageMask covers don't count.
+    fn matches(&self, entry_hi: u64, asid: u64) -> bool {
+        let compared = ENTRYHI_R | (ENTRYHI_VPN2 & !self.page_mask);
+        self.entry_hi & ENTRYHI_EHINV == 0 &&
+            (self.entry_hi ^ entry_hi) & compared == 0 &&
+            (self.global() || self.entry_hi & ENTRYHI_ASID == asid)
+    }
+}
+
+pub struct Tlb {
+    entries: [Entry; ENTRIES],
+}
+
+// What's in a TLB at reset is undefined. Starting every entry out
+// invalidated means nothing matches until software writes it.
+pub fn new() -> Tlb {
+    let invalid = Entry {
+        entry_hi: ENTRYHI_EHINV,
+        ..Entry::default()
+    };
+    Tlb {
+        entries: [invalid; ENTRIES],
+    }
+}
+
+// Rounds PageMask down to a page size the TLB supports.
+fn page_mask(value: u64) -> u64 {
+    let ones = ((value & PAGEMASK_MASK) >> 13).trailing_ones() & !1;
+    ((1 << ones) - 1) << 13
+}
+
+impl Tlb {
+    pub fn entry(&self, index: usize) -> Entry {
+        self.entries[index]
+    }
+
+    pub fn set_entry(&mut self, index: usize, entry: Entry) {
+        let global = entry.entry_lo[0] & entry.entry_lo[1] & ENTRYLO_G;
+        let entry_lo = entry.entry_lo.map(|lo| (lo & !ENTRYLO_G) | global);
+        self.entries[index] = Entry {
+            entry_hi: entry.entry_hi,
+            page_mask: page_mask(entry.page_mask),
+            entry_lo,
+        };
+    }
+
+    // TLBP: the first entry that matches EntryHi.
+    pub fn probe(&self, entry_hi: u64) -> Option<usize> {
+        let asid = entry_hi & ENTRYHI_ASID;
+        self.entries.iter().position(|entry| entry.matches(entry_hi, asid))
+    }
+
+    // Where a mapped address lands for the given ASID.
+    pub fn lookup(&self, address: u64, asid: u64, store: bool)
+        -> Result<u64, Fault> {
+        let entry = match self.probe((address & !ENTRYHI_ASID) | asid) {
+            None => return Err(Fault::Refill),
+            Some(index) => &self.entries[index],
+        };
+        let offset = (entry.page_mask >> 1) | 0xfff;
+        let odd = address & (offset + 1) != 0;
+        let entry_lo = entry.entry_lo[odd as usize];
+        if entry_lo & ENTRYLO_V == 0 {
+            return Err(Fault::Invalid);
+        }
+        if store && entry_lo & ENTRYLO_D == 0 {
+            return Err(Fault::Modified);
+        }
+        let frame = (entry_lo >> ENTRYLO_PFN_SHIFT) << 12;
+        Ok((frame & !offset) | (address & offset))
+    }
+
+    // TLBINV takes out the entries for one ASID, leaving global ones be.
+    // TLBINVF takes out everything.
+    pub fn invalidate(&mut self, asid: Option<u64>) {
+        for entry in self.entries.iter_mut() {
+            let flushed = match asid {
+                None => true,
+                Some(asid) =>
+                    !entry.global() && entry.entry_hi & ENTRYHI_ASID == asid,
+            };
+            if flushed {
+                entry.entry_hi |= ENTRYHI_EHINV;
+            }
+        }
+    }
+}
@@ -1,6 +1,186 @@
-mod computer;
+use mips_emulator::computer;
+use mips_emulator::computer::cpu::{MmuKind, UnalignedPolicy};
+use mips_emulator::computer::decoder::IsaRevision;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+// Default amount of memory given to a program: 16 MiB.
+const DEFAULT_MEMORY: u64 = 16 * 1024 * 1024;
+
+fn usage(name: &str) -> ! {
+    eprintln!("usage: {} [--memory <bytes>] [--isa <r2 | r6>] \
+               [--unaligned <trap | emulate | allow>] \
+               [--mmu <base-limit | tlb>] [--spim | --linux] \
+               [--sandbox <dir>] [--disassemble | --disassemble-range \
+               <start> <end>] <program> [args...]", name);
+    process::exit(2);
+}
+
+// Addresses can be given in decimal or, with a 0x prefix, in hex.
+fn parse_address(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
 
 fn main() {
-    let mut com = computer::new(1, 1024);
-    com.step();
+    let args: Vec<String> = env::args().collect();
+
+    let mut spim = false;
+    let mut linux = false;
+    let mut memory = DEFAULT_MEMORY;
+    let mut revision = IsaRevision::Release6;
+    // Asking for a policy also asks for how many unaligned accesses there
+    // were.
+    let mut unaligned = None;
+    // A TLB means the program is a kernel, which does its own syscalls.
+    let mut mmu = MmuKind::BaseLimit;
+    // Files the program opens are kept inside this directory.
+    let mut sandbox = PathBuf::from(".");
+    let mut disassemble = false;
+    let mut range = None;
+    let mut i = 1;
+    while i < args.len() && args[i].starts_with("--") {
+        if args[i] == "--spim" {
+            spim = true;
+        } else if args[i] == "--linux" {
+            linux = true;
+        } else if args[i] == "--sandbox" {
+            match args.get(i + 1) {
+                None => usage(&args[0]),
+                Some(dir) => sandbox = PathBuf::from(dir),
+            }
+            i += 1;
+        } else if args[i] == "--disassemble" {
+            disassemble = true;
+        } else if args[i] == "--disassemble-range" {
+            let start = args.get(i + 1).and_then(|start| parse_address(start));
+            let end = args.get(i + 2).and_then(|end| parse_address(end));
+            match (start, end) {
+                (Some(start), Some(end)) => range = Some((start, end)),
+                _ => usage(&args[0]),
+            }
+            i += 2;
+        } else if args[i] == "--isa" {
+            match args.get(i + 1).map(|isa| isa.as_str()) {
+                Some("r2") => revision = IsaRevision::Release2,
+                Some("r6") => revision = IsaRevision::Release6,
+                _ => usage(&args[0]),
+            }
+            i += 1;
+        } else if args[i] == "--unaligned" {
+            match args.get(i + 1).map(|policy| policy.as_str()) {
+                Some("trap") => unaligned = Some(UnalignedPolicy::Trap),
+                Some("emulate") => unaligned = Some(UnalignedPolicy::Emulate),
+                Some("allow") => unaligned = Some(UnalignedPolicy::Allow),
+                _ => usage(&args[0]),
+            }
+            i += 1;
+        } else if args[i] == "--mmu" {
+            match args.get(i + 1).map(|kind| kind.as_str()) {
+                Some("base-limit") => mmu = MmuKind::BaseLimit,
+                Some("tlb") => mmu = MmuKind::Tlb,
+                _ => usage(&args[0]),
+            }
+            i += 1;
+        } else if args[i] == "--memory" {
+            match args.get(i + 1).map(|size| size.parse()) {
+                Some(Ok(size)) if size > 0 => memory = size,
+                _ => usage(&args[0]),
+            }
+            i += 1;
+        } else {
+            usage(&args[0]);
+        }
+        i += 1;
+    }
+    if i >= args.len() || (spim && linux) ||
+            (mmu == MmuKind::Tlb && (spim || linux)) {
+        usage(&args[0]);
+    }
+    let path = &args[i];
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            process::exit(1);
+        }
+    };
+
+    // Assembly source gets assembled; anything else had better be an ELF
+    // file.
+    let assembly = Path::new(path).extension()
+        .map(|extension| extension == "s" || extension == "asm")
+        .unwrap_or(false);
+    let program = if assembly {
+        let source = String::from_utf8_lossy(&bytes);
+        computer::assembler::assemble(&source).map_err(|error| error.to_string())
+    } else {
+        computer::program::parse(&bytes).map_err(|error| error.to_string())
+    };
+    let program = match program {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            process::exit(1);
+        }
+    };
+
+    let mut com = computer::new(1, memory, revision);
+    if let Some(policy) = unaligned {
+        com.set_unaligned_policy(policy);
+    }
+    com.set_mmu_kind(mmu);
+    if spim {
+        com.set_syscall_handler(Box::new(computer::spim::new(sandbox)));
+    } else if linux {
+        // The program sees everything from its own path onwards as its
+        // arguments, and gets our environment.
+        let linux = computer::linux::new(sandbox,
+                                         args[i..].to_vec(),
+                                         env::vars().map(|(key, value)| {
+                                             format!("{}={}", key, value)
+                                         }).collect());
+        com.set_syscall_handler(Box::new(linux));
+    }
+    if let Err(error) = com.load(&program) {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    }
+
+    // Disassembling shows the program as loaded rather than running it.
+    if disassemble {
+        for segment in program.segments.iter().filter(|segment| segment.executable) {
+            print!("{}", computer::disassembler::disassemble_memory(
+                com.memory(), 0, revision, segment.address,
+                segment.address + segment.size));
+        }
+        return;
+    }
+    if let Some((start, end)) = range {
+        print!("{}", computer::disassembler::disassemble_memory(
+            com.memory(), 0, revision, start, end));
+        return;
+    }
+
+    let mut faulted = false;
+    while com.running() {
+        for (cpu, exception) in com.step() {
+            eprintln!("cpu {}: {}", cpu, exception);
+            faulted = true;
+        }
+    }
+    if unaligned.is_some() {
+        eprintln!("cpu 0: unaligned accesses: {}", com.unaligned_accesses(0));
+    }
+
+    match com.exit_status() {
+        Some(status) => process::exit(status),
+        None if faulted => process::exit(1),
+        None => {},
+    }
 }
@@ -0,0 +1,87 @@
+use std::env;
+
+use mips_emulator::computer;
+use mips_emulator::computer::assembler::{self, ErrorKind};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::disassembler;
+use mips_emulator::computer::spim;
+
+mod common;
+use common::assemble;
+
+// A spread of formats: immediates, branch offsets in both directions, PC-
+// relative loads, and the FPU and MSA.
+const INSTRUCTIONS: &[&str] = &[
+    "lb $t0, -8($sp)",
+    "sd $a4, 8($a5)",
+    "ldpc $t2, 4096",
+    "lwupc $t1, -1024",
+    "auipc $a0, 0x1234",
+    "daddiu $sp, $sp, -32",
+    "dsll32 $t0, $t1, 4",
+    "ext $a0, $a1, 4, 8",
+    "bc -1024",
+    "balc 0x100",
+    "beqc $t0, $t1, 64",
+    "bnezc $t0, -64",
+    "bgezalc $t0, 64",
+    "beq $a0, $a1, 8",
+    "jic $t0, 16",
+    "jalr $ra, $t9",
+    "add.d $f0, $f2, $f4",
+    "maddf.s $f0, $f2, $f4",
+    "cmp.lt.d $f0, $f2, $f4",
+    "addv.w $w0, $w1, $w2",
+];
+
+#[test]
+fn disassembly_assembles_back_to_the_same_word() {
+    for source in INSTRUCTIONS {
+        let word = assemble(source);
+        let text = disassembler::disassemble(word, 0x120000000,
+                                             IsaRevision::Release6);
+        assert_eq!(assemble(&text), word, "{} -> {}", source, text);
+    }
+}
+
+#[test]
+fn an_assembled_program_runs() {
+    // Sums the words in a table and exits with the total.
+    let source = "
+            .data
+    table:  .word 1, 2, 3, 4, 5
+            .text
+    main:   la      $t0, table
+            li      $t1, 5
+            move    $a0, $zero
+    loop:   lw      $t2, 0($t0)
+            addu    $a0, $a0, $t2
+            daddiu  $t0, $t0, 4
+            addiu   $t1, $t1, -1
+            bnezc   $t1, loop
+            li      $v0, 17
+            syscall
+    ";
+    let program = assembler::assemble(source).unwrap();
+    let mut com = computer::new(1, 1 << 20, IsaRevision::Release6);
+    com.set_syscall_handler(Box::new(spim::new(env::temp_dir())));
+    com.load(&program).unwrap();
+    for _ in 0..100 {
+        if !com.running() {
+            break;
+        }
+        assert_eq!(com.step(), Vec::new());
+    }
+    assert_eq!(com.exit_status(), Some(15));
+}
+
+#[test]
+fn errors_say_which_line_they_are_on() {
+    let error = assembler::assemble("nop\nfrobnicate $a0\n").err().unwrap();
+    assert_eq!(error.line, 2);
+    assert!(matches!(error.kind, ErrorKind::UnknownInstruction(_)));
+
+    let error = assembler::assemble("nop\nnop\nbc nowhere\n").err().unwrap();
+    assert_eq!(error.line, 3);
+    assert!(matches!(error.kind, ErrorKind::UndefinedLabel(_)));
+}
@@ -0,0 +1,75 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::memory::{self, AddressMode, Memory};
+
+mod common;
+use common::{assemble, execute, A0, A1, A2, MEMORY};
+
+// Where the shared word is.
+const WORD: u64 = 0x100;
+
+// Two CPUs sharing a page of memory, both with $a1 pointing at WORD.
+fn setup() -> (Cpu, Cpu, Memory) {
+    let mut memory = memory::new(MEMORY, 2);
+    memory.set_mmu(0, 0, MEMORY - 1, AddressMode::Bits64);
+    memory.set_mmu(1, 0, MEMORY - 1, AddressMode::Bits64);
+    let mut first = cpu::new(0, IsaRevision::Release6);
+    let mut second = cpu::new(1, IsaRevision::Release6);
+    first.set_register(A1, WORD);
+    second.set_register(A1, WORD);
+    (first, second, memory)
+}
+
+// LL and then SC of $a0 + 1 on `cpu`, with `between` run in the middle.
+// Returns whether the SC succeeded.
+fn increment(cpu: &mut Cpu, memory: &mut Memory,
+             between: impl FnOnce(&mut Memory)) -> bool {
+    execute(cpu, memory, assemble("ll $a0, 0($a1)"));
+    execute(cpu, memory, assemble("addiu $a0, $a0, 1"));
+    between(memory);
+    execute(cpu, memory, assemble("sc $a0, 0($a1)"));
+    cpu.register(A0) == 1
+}
+
+#[test]
+fn sc_succeeds_when_nothing_else_stores() {
+    let (mut first, mut second, mut memory) = setup();
+    memory.write_word(WORD, 41);
+    assert!(increment(&mut first, &mut memory, |_| {}));
+    assert_eq!(memory.read_word(WORD), Some(42));
+
+    // Another CPU loading the word, or storing somewhere else, is fine.
+    second.set_register(A2, 7);
+    assert!(increment(&mut first, &mut memory, |memory| {
+        execute(&mut second, memory, assemble("lw $a0, 0($a1)"));
+        execute(&mut second, memory, assemble("sw $a2, 8($a1)"));
+    }));
+    assert_eq!(memory.read_word(WORD), Some(43));
+}
+
+#[test]
+fn sc_fails_after_another_cpu_stores() {
+    let (mut first, mut second, mut memory) = setup();
+    memory.write_word(WORD, 41);
+    second.set_register(A2, 100);
+    assert!(!increment(&mut first, &mut memory, |memory| {
+        execute(&mut second, memory, assemble("sw $a2, 0($a1)"));
+    }));
+    assert_eq!(memory.read_word(WORD), Some(100));
+
+    // Even to the other word in the same doubleword.
+    assert!(!increment(&mut first, &mut memory, |memory| {
+        execute(&mut second, memory, assemble("sw $a2, 4($a1)"));
+    }));
+    assert_eq!(memory.read_word(WORD), Some(100));
+
+    // The other CPU's SC breaks the link too, and it only succeeds if it
+    // has a link of its own.
+    execute(&mut first, &mut memory, assemble("ll $a0, 0($a1)"));
+    assert!(increment(&mut second, &mut memory, |_| {}));
+    execute(&mut first, &mut memory, assemble("sc $a0, 0($a1)"));
+    assert_eq!(first.register(A0), 0);
+    execute(&mut second, &mut memory, assemble("sc $a0, 0($a1)"));
+    assert_eq!(second.register(A0), 0);
+    assert_eq!(memory.read_word(WORD), Some(101));
+}
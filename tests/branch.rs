@@ -0,0 +1,117 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::Memory;
+
+mod common;
+use common::{assemble, A0, A1, RA, V0};
+
+// Lays out `code` from address 0 and points the CPU at it.
+fn setup(code: &[&str]) -> (Cpu, Memory) {
+    let mut memory = common::memory();
+    for (i, source) in code.iter().enumerate() {
+        memory.write_word(4 * i as u64, assemble(source));
+    }
+    (cpu::new(0, IsaRevision::Release6), memory)
+}
+
+#[test]
+fn compact_branches_and_link_always_link() {
+    for (value, target) in [(0, 0x14), (-1i64 as u64, 0x8)] {
+        let (mut cpu, mut memory) = setup(&["nop", "bgezalc $a0, 12"]);
+        cpu.set_register(A0, value);
+        assert_eq!(cpu.step(&mut memory), None);
+        assert_eq!(cpu.step(&mut memory), None);
+        assert_eq!(cpu.register(RA), 0x8, "{:#x}", value);
+        assert_eq!(cpu.pc(), target, "{:#x}", value);
+    }
+}
+
+#[test]
+fn delay_slots_run_before_the_branch_is_taken() {
+    let (mut cpu, mut memory) = setup(&[
+        "beq $a0, $a1, 8",
+        "addiu $v0, $v0, 1",
+        "addiu $v0, $v0, 10",
+        "addiu $v0, $v0, 100",
+    ]);
+    for _ in 0..3 {
+        assert_eq!(cpu.step(&mut memory), None);
+    }
+    assert_eq!(cpu.register(V0), 101);
+    assert_eq!(cpu.pc(), 0x10);
+}
+
+#[test]
+fn control_transfers_in_a_slot_are_reserved() {
+    // A delay slot, whether or not the branch is taken.
+    for value in [0, 1] {
+        let (mut cpu, mut memory) = setup(&["beq $a0, $zero, 8", "bc 16"]);
+        cpu.set_register(A0, value);
+        assert_eq!(cpu.step(&mut memory), None);
+        assert_eq!(cpu.step(&mut memory),
+                   Some(Exception::ReservedInstruction { pc: 4 }),
+                   "{}", value);
+    }
+
+    // The forbidden slot after a compact branch that isn't taken.
+    let (mut cpu, mut memory) = setup(&["beqzc $a0, 8", "jic $a1, 0"]);
+    cpu.set_register(A0, 1);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(cpu.step(&mut memory),
+               Some(Exception::ReservedInstruction { pc: 4 }));
+
+    // Anything else is fine there.
+    let (mut cpu, mut memory) = setup(&["beqzc $a0, 8", "addiu $v0, $v0, 1",
+                                        "bc 16"]);
+    cpu.set_register(A0, 1);
+    for _ in 0..3 {
+        assert_eq!(cpu.step(&mut memory), None);
+    }
+    assert_eq!((cpu.register(V0), cpu.pc()), (1, 0x1c));
+}
+
+#[test]
+fn branches_go_to_the_right_place() {
+    let minus_one = u64::MAX;
+    // Code from address 0, register values, steps to run including any
+    // delay slot, and where the PC ends up.
+    let cases: [(&[&str], u64, u64, usize, u64); 16] = [
+        (&["beq $a0, $a1, 16", "nop"], 1, 1, 2, 0x14),
+        (&["beq $a0, $a1, 16", "nop"], 1, 2, 2, 0x8),
+        (&["nop", "nop", "bne $a0, $a1, -12", "nop"], 1, 2, 4, 0x0),
+        (&["beqzc $a0, 0x40"], 0, 0, 1, 0x44),
+        (&["beqzc $a0, 0x40"], 1, 0, 1, 0x4),
+        (&["nop", "nop", "bnezc $a0, -8"], 1, 0, 3, 0x4),
+        (&["bc 0x100"], 0, 0, 1, 0x104),
+        (&["nop", "balc -4"], 0, 0, 2, 0x4),
+        (&["beqc $a0, $a1, 12"], 3, 3, 1, 0x10),
+        (&["bnec $a0, $a1, 12"], 3, 3, 1, 0x4),
+        // Unsigned and signed comparisons, rs against rt.
+        (&["bgeuc $a0, $a1, 8"], minus_one, 1, 1, 0xc),
+        (&["bgeuc $a0, $a1, 8"], 1, minus_one, 1, 0x4),
+        (&["bltuc $a0, $a1, 8"], 1, minus_one, 1, 0xc),
+        (&["bltuc $a0, $a1, 8"], minus_one, 1, 1, 0x4),
+        (&["bgec $a0, $a1, 8"], 1, minus_one, 1, 0xc),
+        (&["bltc $a0, $a1, 8"], 1, minus_one, 1, 0x4),
+    ];
+    for (code, a0, a1, steps, target) in cases {
+        let (mut cpu, mut memory) = setup(code);
+        cpu.set_register(A0, a0);
+        cpu.set_register(A1, a1);
+        for _ in 0..steps {
+            assert_eq!(cpu.step(&mut memory), None, "{:?}", code);
+        }
+        assert_eq!(cpu.pc(), target, "{:?} {:#x} {:#x}", code, a0, a1);
+    }
+}
+
+#[test]
+fn jalr_lands_on_its_target() {
+    let (mut cpu, mut memory) = setup(&["nop", "jalr $a0", "nop"]);
+    cpu.set_register(A0, 0x40);
+    for _ in 0..3 {
+        assert_eq!(cpu.step(&mut memory), None);
+    }
+    assert_eq!((cpu.pc(), cpu.register(RA)), (0x40, 0xc));
+}
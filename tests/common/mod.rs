@@ -0,0 +1,64 @@
+// Fixtures the integration tests share. Not every test uses all of them.
+#![allow(dead_code)]
+
+use mips_emulator::computer::assembler;
+use mips_emulator::computer::cpu::Cpu;
+use mips_emulator::computer::memory::{self, AddressMode, Endianness, Memory};
+
+pub const MEMORY: u64 = 4096;
+
+// Register numbers
+pub const V0: usize = 2;
+pub const V1: usize = 3;
+pub const A0: usize = 4;
+pub const A1: usize = 5;
+pub const A2: usize = 6;
+pub const S0: usize = 16;
+pub const S1: usize = 17;
+pub const S2: usize = 18;
+pub const S3: usize = 19;
+pub const T8: usize = 24;
+pub const SP: usize = 29;
+pub const RA: usize = 31;
+
+// A page of memory for CPU 0, with its addresses mapped straight onto it.
+pub fn memory() -> Memory {
+    let mut memory = memory::new(MEMORY, 1);
+    memory.set_mmu(0, 0, MEMORY - 1, AddressMode::Bits64);
+    memory
+}
+
+// Assembles a single instruction and returns its encoding.
+pub fn assemble(source: &str) -> u32 {
+    let program = assembler::assemble(source)
+        .unwrap_or_else(|error| panic!("{}: {}", source, error));
+    let text = &program.segments[0].data;
+    u32::from_be_bytes([text[0], text[1], text[2], text[3]])
+}
+
+// Runs one instruction, which mustn't raise an exception.
+pub fn execute(cpu: &mut Cpu, memory: &mut Memory, instruction: u32) {
+    cpu.execute_instruction(instruction, memory);
+    assert_eq!(cpu.exception(), None, "0x{:08x}", instruction);
+}
+
+// Lays out microMIPS or MIPS16e code from `address` a halfword at a time in
+// memory's byte order. Anything above 0xffff is a 32-bit instruction, and
+// its upper halfword goes first.
+pub fn write_halfwords(memory: &mut Memory, address: u64, code: &[u32]) {
+    let mut bytes = Vec::new();
+    for &instruction in code {
+        let halfwords = if instruction > 0xffff {
+            vec![(instruction >> 16) as u16, instruction as u16]
+        } else {
+            vec![instruction as u16]
+        };
+        for halfword in halfwords {
+            match memory.endianness() {
+                Endianness::Big => bytes.extend(halfword.to_be_bytes()),
+                Endianness::Little => bytes.extend(halfword.to_le_bytes()),
+            }
+        }
+    }
+    memory.write_bytes(address, &bytes);
+}
@@ -0,0 +1,85 @@
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::disassembler;
+
+mod common;
+
+const PC: u64 = 0x120001000;
+
+// Encodings from an independent assembler, with the text each should come
+// back as. Branches show their offset in bytes, as the assembler takes it.
+const BRANCHES: [(u32, &str); 16] = [
+    (0x10850004, "beq $a0, $a1, 0x10"),
+    (0x1485fffe, "bne $a0, $a1, -0x8"),
+    (0xd9200010, "beqzc $a5, 0x40"),
+    (0xf93fffff, "bnezc $a5, -0x4"),
+    (0xc8000040, "bc 0x100"),
+    (0xebffffc0, "balc -0x100"),
+    (0x18850002, "bgeuc $a0, $a1, 0x8"),
+    (0x1c850002, "bltuc $a0, $a1, 0x8"),
+    (0x18840002, "bgezalc $a0, 0x8"),
+    (0x1c840002, "bltzalc $a0, 0x8"),
+    (0x20850003, "beqc $a0, $a1, 0xc"),
+    (0x60850003, "bnec $a0, $a1, 0xc"),
+    (0x20a40001, "bovc $a1, $a0, 0x4"),
+    (0x45220002, "bc1eqz $f2, 0x8"),
+    (0xd8040008, "jic $a0, 8"),
+    (0x0080f809, "jalr $a0"),
+];
+
+const OTHERS: [(u32, &str); 8] = [
+    (0x0085102d, "daddu $v0, $a0, $a1"),
+    (0x0085102a, "slt $v0, $a0, $a1"),
+    (0x0085102b, "sltu $v0, $a0, $a1"),
+    (0x2882ffff, "slti $v0, $a0, -1"),
+    (0x2c82ffff, "sltiu $v0, $a0, -1"),
+    (0x00a41014, "dsllv $v0, $a0, $a1"),
+    (0x00a41016, "dsrlv $v0, $a0, $a1"),
+    (0x00a41017, "dsrav $v0, $a0, $a1"),
+];
+
+#[test]
+fn encodings_disassemble_the_same_at_any_pc() {
+    for (word, text) in BRANCHES.iter().chain(OTHERS.iter()) {
+        for pc in [0, PC] {
+            assert_eq!(disassembler::disassemble(*word, pc,
+                                                 IsaRevision::Release6),
+                       *text, "0x{:08x} at 0x{:x}", word, pc);
+        }
+    }
+}
+
+#[test]
+fn jumps_stay_in_the_region_of_the_pc() {
+    // J and JAL replace the low 28 bits of the address of their delay
+    // slot.
+    let cases = [
+        (0x08100000, PC, "j 0x120400000"),
+        (0x0c100004, PC, "jal 0x120400010"),
+        (0x08100000, 0, "j 0x400000"),
+        (0x0bffffff, 0x1ffffff8, "j 0x1ffffffc"),
+        (0x0bffffff, 0x1ffffffc, "j 0x2ffffffc"),
+        (0x08000000, 0x0ffffffc, "j 0x10000000"),
+    ];
+    for (word, pc, text) in cases {
+        assert_eq!(disassembler::disassemble(word, pc, IsaRevision::Release6),
+                   text, "0x{:08x} at 0x{:x}", word, pc);
+    }
+}
+
+#[test]
+fn reserved_words_come_out_as_data() {
+    // Removed in Release 6, or never an instruction at all.
+    for word in [0x70000000, 0x50850004, 0x88a40000, 0x0085001a, 0x7c0000ff] {
+        assert_eq!(disassembler::disassemble(word, PC, IsaRevision::Release6),
+                   format!(".word 0x{:08x}", word));
+    }
+
+    let mut memory = common::memory();
+    memory.write_word(0x100, 0x0085102d);
+    memory.write_word(0x104, 0x70000000);
+    let text = disassembler::disassemble_memory(&mut memory, 0,
+                                                IsaRevision::Release6,
+                                                0x100, 0x108);
+    assert_eq!(text, "0000000000000100: 0085102d  daddu $v0, $a0, $a1\n\
+                      0000000000000104: 70000000  .word 0x70000000\n");
+}
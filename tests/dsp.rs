@@ -0,0 +1,231 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::disassembler;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::Memory;
+
+mod common;
+use common::{execute, A0, V0, V1};
+
+// DSPControl fields.
+const ARITHMETIC_OVERFLOW: u32 = 1 << 20;
+const EXTRACT_OVERFLOW: u32 = 1 << 23;
+const EFI: u32 = 1 << 14;
+
+// Encodings checked against the LLVM assembler.
+const ADDU_S_QB: u32 = 0x7c641110;
+const ADDQ_S_PH: u32 = 0x7c641390;
+const CMPU_LT_QB: u32 = 0x7c640051;
+const PICK_QB: u32 = 0x7c6410d1;
+const DPAQ_S_W_PH_AC1: u32 = 0x7c640930;
+const EXTR_W_AC1_7: u32 = 0x7ce40838;
+const EXTR_R_W_AC1_7: u32 = 0x7ce40938;
+const EXTP_AC1_7: u32 = 0x7ce408b8;
+const EXTPDP_AC1_7: u32 = 0x7ce40ab8;
+const WRDSP_21: u32 = 0x7c60acf8;
+const RDDSP_21: u32 = 0x7c1514b8;
+const LWX: u32 = 0x7c64100a;
+const LBUX: u32 = 0x7c64118a;
+const BPOSGE32_8: u32 = 0x041c0002;
+const MULT: u32 = 0x00640018;
+const MULT_AC1: u32 = 0x00640818;
+const MFHI_AC1: u32 = 0x00201010;
+const MFLO_AC1: u32 = 0x00201012;
+
+const ENCODINGS: &[(&str, u32)] = &[
+    ("addu_s.qb $v0, $v1, $a0", ADDU_S_QB),
+    ("muleq_s.w.phl $v0, $v1, $a0", 0x7c641710),
+    ("mulq_rs.ph $v0, $v1, $a0", 0x7c6417d0),
+    ("raddu.w.qb $v0, $v1", 0x7c601510),
+    ("preceu.ph.qbla $v0, $a0", 0x7c041792),
+    ("repl.qb $v0, 171", 0x7cab1092),
+    ("repl.ph $v0, -5", 0x7ffb1292),
+    ("replv.ph $v0, $a0", 0x7c0412d2),
+    ("cmpu.lt.qb $v1, $a0", CMPU_LT_QB),
+    ("cmpgdu.le.qb $v0, $v1, $a0", 0x7c641691),
+    ("precrqu_s.qb.ph $v0, $v1, $a0", 0x7c6413d1),
+    ("precr_sra_r.ph.w $a0, $v1, 5", 0x7c642fd1),
+    ("shll_s.ph $v0, $a0, 13", 0x7da41313),
+    ("shllv_s.w $v0, $a0, $v1", 0x7c641593),
+    ("dpaq_sa.l.w $ac1, $v1, $a0", 0x7c640b30),
+    ("maq_sa.w.phr $ac1, $v1, $a0", 0x7c640cb0),
+    ("extr_rs.w $a0, $ac1, 7", 0x7ce409b8),
+    ("extrv_s.h $a0, $ac1, $v1", 0x7c640bf8),
+    ("extpdpv $a0, $ac1, $v1", 0x7c640af8),
+    ("shilo $ac1, -7", 0x7f900eb8),
+    ("mthlip $v1, $ac1", 0x7c600ff8),
+    ("wrdsp $v1, 21", WRDSP_21),
+    ("rddsp $v0, 21", RDDSP_21),
+    ("insv $a0, $v1", 0x7c64000c),
+    ("balign $a0, $v1, 3", 0x7c641c31),
+    ("lwx $v0, $a0($v1)", LWX),
+    ("bposge32 0x8", BPOSGE32_8),
+    ("mult $ac1, $v1, $a0", MULT_AC1),
+    ("mflo $v0, $ac1", MFLO_AC1),
+    ("mtlo $v1, $ac1", 0x00600813),
+];
+
+// A Release 2 CPU with the DSP ASE enabled whose addresses map straight
+// onto a page of memory.
+fn setup() -> (Cpu, Memory) {
+    let memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release2);
+    cpu.set_dsp_enabled(true);
+    (cpu, memory)
+}
+
+// Runs one instruction on $v1 and $a0 and returns $v0 and DSPControl.
+fn run(instruction: u32, v1: u64, a0: u64) -> (u64, u32) {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_register(V1, v1);
+    cpu.set_register(A0, a0);
+    execute(&mut cpu, &mut memory, instruction);
+    (cpu.register(V0), cpu.dsp_control())
+}
+
+#[test]
+fn encodings_round_trip() {
+    for &(source, word) in ENCODINGS {
+        assert_eq!(disassembler::disassemble(word, 0, IsaRevision::Release2),
+                   source);
+    }
+    // Release 6 took the DSP ASE's encodings back.
+    assert_eq!(disassembler::disassemble(ADDU_S_QB, 0, IsaRevision::Release6),
+               ".word 0x7c641110");
+}
+
+#[test]
+fn saturating_arithmetic_sets_the_overflow_flag() {
+    let (v0, control) = run(ADDU_S_QB, 0xf0108001, 0x20108001);
+    assert_eq!(v0, 0xffffffffff20ff02);
+    assert_eq!(control, ARITHMETIC_OVERFLOW);
+
+    let (v0, control) = run(ADDQ_S_PH, 0x7fff8000, 0x0001ffff);
+    assert_eq!(v0, 0x7fff8000);
+    assert_eq!(control, ARITHMETIC_OVERFLOW);
+
+    let (_, control) = run(ADDQ_S_PH, 0x00010002, 0x00030004);
+    assert_eq!(control, 0);
+}
+
+#[test]
+fn pick_uses_the_condition_bits_from_a_compare() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_register(V1, 0x01020304);
+    cpu.set_register(A0, 0x01030204);
+    execute(&mut cpu, &mut memory, CMPU_LT_QB);
+    assert_eq!(cpu.dsp_control(), 0x4 << 24);
+    execute(&mut cpu, &mut memory, PICK_QB);
+    assert_eq!(cpu.register(V0), 0x01020204);
+}
+
+#[test]
+fn accumulators_are_separate_from_hi_and_lo() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_register(V1, 0x80004000);
+    cpu.set_register(A0, 0x80004000);
+    // -1 times -1 saturates, and notes the overflow on accumulator 1.
+    execute(&mut cpu, &mut memory, DPAQ_S_W_PH_AC1);
+    assert_eq!(cpu.accumulator(1), 0x9fffffff);
+    assert_eq!(cpu.accumulator(0), 0);
+    assert_eq!(cpu.dsp_control(), 1 << 17);
+    execute(&mut cpu, &mut memory, MFHI_AC1);
+    assert_eq!(cpu.register(V0), 0);
+    execute(&mut cpu, &mut memory, MFLO_AC1);
+    assert_eq!(cpu.register(V0), 0xffffffff9fffffff);
+
+    cpu.set_register(V1, 3);
+    cpu.set_register(A0, 5);
+    execute(&mut cpu, &mut memory, MULT_AC1);
+    assert_eq!(cpu.accumulator(1), 15);
+    execute(&mut cpu, &mut memory, MULT);
+    assert_eq!(cpu.accumulator(0), 15);
+}
+
+#[test]
+fn extracts_shift_and_round() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_accumulator(1, 0x1c0);
+    execute(&mut cpu, &mut memory, EXTR_W_AC1_7);
+    assert_eq!(cpu.register(A0), 3);
+    execute(&mut cpu, &mut memory, EXTR_R_W_AC1_7);
+    assert_eq!(cpu.register(A0), 4);
+    assert_eq!(cpu.dsp_control(), 0);
+
+    cpu.set_accumulator(1, 1 << 40);
+    execute(&mut cpu, &mut memory, EXTR_W_AC1_7);
+    assert_eq!(cpu.dsp_control(), EXTRACT_OVERFLOW);
+}
+
+#[test]
+fn bit_field_extracts_follow_pos() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_accumulator(1, 0xab00);
+    cpu.set_dsp_control(15);
+    execute(&mut cpu, &mut memory, EXTP_AC1_7);
+    assert_eq!(cpu.register(A0), 0xab);
+    assert_eq!(cpu.dsp_control(), 15);
+    execute(&mut cpu, &mut memory, EXTPDP_AC1_7);
+    assert_eq!(cpu.register(A0), 0xab);
+    assert_eq!(cpu.dsp_control(), 7);
+
+    // Too few bits below POS leaves rt alone.
+    cpu.set_dsp_control(3);
+    execute(&mut cpu, &mut memory, EXTP_AC1_7);
+    assert_eq!(cpu.register(A0), 0xab);
+    assert_eq!(cpu.dsp_control(), 3 | EFI);
+}
+
+#[test]
+fn wrdsp_and_rddsp_only_touch_the_masked_fields() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_dsp_control(0x00f00000);
+    cpu.set_register(V1, u64::MAX);
+    // Mask 21 picks POS, the carry and the condition bits.
+    execute(&mut cpu, &mut memory, WRDSP_21);
+    assert_eq!(cpu.dsp_control(), 0xfff0203f);
+    execute(&mut cpu, &mut memory, RDDSP_21);
+    assert_eq!(cpu.register(V0), 0xffffffffff00203f);
+}
+
+#[test]
+fn dsp_instructions_need_the_dsp_enabled() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_dsp_enabled(false);
+    cpu.execute_instruction(ADDU_S_QB, &mut memory);
+    assert_eq!(cpu.exception(), Some(Exception::DspDisabled { pc: 0 }));
+
+    // So do the other accumulators, but not HI and LO.
+    let (mut cpu, mut memory) = setup();
+    cpu.set_dsp_enabled(false);
+    cpu.execute_instruction(MULT_AC1, &mut memory);
+    assert_eq!(cpu.exception(), Some(Exception::DspDisabled { pc: 0 }));
+    let (mut cpu, mut memory) = setup();
+    cpu.set_dsp_enabled(false);
+    execute(&mut cpu, &mut memory, MULT);
+}
+
+#[test]
+fn bposge32_tests_pos() {
+    for (pos, taken) in [(32, true), (31, false)] {
+        let (mut cpu, mut memory) = setup();
+        cpu.set_dsp_control(pos);
+        memory.write_word(0, BPOSGE32_8);
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+        assert_eq!(cpu.exception(), None);
+        assert_eq!(cpu.pc(), if taken { 12 } else { 8 }, "pos {}", pos);
+    }
+}
+
+#[test]
+fn indexed_loads_add_the_index_to_the_base() {
+    let (mut cpu, mut memory) = setup();
+    memory.write_word(0x104, 0x80000001);
+    cpu.set_register(V1, 0x100);
+    cpu.set_register(A0, 4);
+    execute(&mut cpu, &mut memory, LWX);
+    assert_eq!(cpu.register(V0), 0xffffffff80000001);
+    execute(&mut cpu, &mut memory, LBUX);
+    assert_eq!(cpu.register(V0), 0x80);
+}
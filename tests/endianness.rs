@@ -0,0 +1,107 @@
+use mips_emulator::computer;
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::memory::{Endianness, Memory};
+use mips_emulator::computer::program::{Class, Program, Segment};
+
+mod common;
+use common::{A0, A1, MEMORY};
+
+const ADDIU_A0_ZERO_5: u32 = 0x24040005;
+const LW_A0_0_A1: u32 = 0x8ca40000;
+const LWL_A0_0_A1: u32 = 0x88a40000;
+const LWL_A0_3_A1: u32 = 0x88a40003;
+const LWR_A0_0_A1: u32 = 0x98a40000;
+const LWR_A0_3_A1: u32 = 0x98a40003;
+const MTC0_A0_STATUS: u32 = 0x40846000;
+
+// Status with KSU set to user mode and RE set.
+const USER_REVERSE_ENDIAN: u64 = 0x02000010;
+
+// A Release 2 CPU, so LWL and LWR are still around, whose addresses map
+// straight onto a page of memory with the given byte order.
+fn setup(endianness: Endianness) -> (Cpu, Memory) {
+    let mut memory = common::memory();
+    memory.set_endianness(endianness);
+    (cpu::new(0, IsaRevision::Release2), memory)
+}
+
+#[test]
+fn data_follows_the_memory_byte_order() {
+    for (endianness, first) in [(Endianness::Big, 0x11),
+                                (Endianness::Little, 0x44)] {
+        let (mut cpu, mut memory) = setup(endianness);
+        memory.write_word(0x100, 0x11223344);
+        assert_eq!(memory.read_byte(0x100), Some(first), "{:?}", endianness);
+        cpu.set_register(A1, 0x100);
+        cpu.execute_instruction(LW_A0_0_A1, &mut memory);
+        assert_eq!(cpu.register(A0), 0x11223344, "{:?}", endianness);
+    }
+}
+
+#[test]
+fn instruction_fetch_follows_the_memory_byte_order() {
+    for endianness in [Endianness::Big, Endianness::Little] {
+        let (mut cpu, mut memory) = setup(endianness);
+        let bytes = match endianness {
+            Endianness::Big => ADDIU_A0_ZERO_5.to_be_bytes(),
+            Endianness::Little => ADDIU_A0_ZERO_5.to_le_bytes(),
+        };
+        memory.write_bytes(0, &bytes);
+        assert_eq!(cpu.step(&mut memory), None, "{:?}", endianness);
+        assert_eq!(cpu.register(A0), 5, "{:?}", endianness);
+    }
+}
+
+#[test]
+fn partial_loads_in_both_byte_orders() {
+    // Each byte order has its own LWL/LWR pairing for an unaligned word.
+    let cases = [
+        (Endianness::Big, [LWL_A0_0_A1, LWR_A0_3_A1], 0x11223344),
+        (Endianness::Little, [LWR_A0_0_A1, LWL_A0_3_A1], 0x44332211),
+    ];
+    for (endianness, instructions, expected) in cases {
+        let (mut cpu, mut memory) = setup(endianness);
+        memory.write_bytes(0x100, &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        cpu.set_register(A1, 0x101);
+        for instruction in instructions {
+            cpu.execute_instruction(instruction, &mut memory);
+        }
+        assert_eq!(cpu.exception(), None, "{:?}", endianness);
+        assert_eq!(cpu.register(A0), expected, "{:?}", endianness);
+    }
+}
+
+#[test]
+fn user_mode_can_run_reverse_endian() {
+    let (mut cpu, mut memory) = setup(Endianness::Big);
+    memory.write_bytes(0x100, &[0x11, 0x22, 0x33, 0x44]);
+    cpu.set_register(A1, 0x100);
+    cpu.set_register(A0, USER_REVERSE_ENDIAN);
+    cpu.execute_instruction(MTC0_A0_STATUS, &mut memory);
+    cpu.execute_instruction(LW_A0_0_A1, &mut memory);
+    assert_eq!(cpu.exception(), None);
+    assert_eq!(cpu.register(A0), 0x44332211);
+}
+
+#[test]
+fn loading_a_program_sets_the_byte_order() {
+    let program = Program {
+        class: Class::Elf64,
+        endianness: Endianness::Little,
+        entry: 0,
+        segments: vec![Segment {
+            address: 0,
+            size: 4,
+            data: ADDIU_A0_ZERO_5.to_le_bytes().to_vec(),
+            executable: true,
+        }],
+        headers: None,
+        header_size: 0,
+        header_count: 0,
+    };
+    let mut com = computer::new(1, MEMORY, IsaRevision::Release6);
+    com.load(&program).unwrap();
+    assert_eq!(com.memory().endianness(), Endianness::Little);
+    assert_eq!(com.memory().read_instruction(0), Some(ADDIU_A0_ZERO_5));
+}
@@ -0,0 +1,204 @@
+use mips_emulator::computer;
+use mips_emulator::computer::cpu::{self, Cpu, MmuKind, UnalignedPolicy};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::{self, Endianness, Memory};
+use mips_emulator::computer::program::{Class, Program, Segment};
+
+mod common;
+use common::{assemble, execute, A0, A1, A2, MEMORY, V0};
+
+// CP0 registers
+const BADVADDR: u32 = 8;
+const STATUS: u32 = 12;
+const CAUSE: u32 = 13;
+const EPC: u32 = 14;
+
+const EXL: u64 = 0x2;
+const CAUSE_BD: u64 = 1 << 31;
+
+const KSEG0: u64 = 0xffffffff80000000;
+// Where the code goes, and a word of data to load.
+const CODE: u64 = KSEG0 + 0x800;
+const DATA: u64 = KSEG0 + 0x900;
+// In kseg0 but past the end of memory.
+const MISSING: u64 = KSEG0 + 0x10000;
+
+// A word no revision decodes.
+const UNDECODABLE: u32 = 0x70000000;
+
+// A kernel for two CPUs. CPU 0 counts at 0x810 forever, and CPU 1 runs
+// into an undecodable word at 0x1024. The handler saves EPC at 0x800 and
+// Cause at 0x808 and then spins.
+const HANDLER: (u64, &[&str]) = (0x180, &[
+    "lui $s0, 0x8000",
+    "dmfc0 $t1, $14",
+    "sd $t1, 0x800($s0)",
+    "mfc0 $t2, $13",
+    "sw $t2, 0x808($s0)",
+    "beq $zero, $zero, -4",
+    "nop",
+]);
+const ENTRY: (u64, &[&str]) = (0x1000, &[
+    "lui $s0, 0x8000",
+    "mfc0 $t0, $15, 1",
+    "andi $t0, $t0, 0x3ff",
+    "bne $t0, $zero, 20",
+    "nop",
+    "addiu $t3, $t3, 1",
+    "sw $t3, 0x810($s0)",
+    "beq $zero, $zero, -12",
+    "nop",
+]);
+const FAULT: u64 = 0x1024;
+
+// dmtc0 $a0, $rd and dmfc0 $v0, $rd.
+fn dmtc0(rd: u32) -> u32 {
+    0x40a40000 | rd << 11
+}
+
+fn dmfc0(rd: u32) -> u32 {
+    0x40220000 | rd << 11
+}
+
+// A kernel-mode CPU running `code` from CODE, with kseg0 going straight to
+// memory and unaligned accesses trapping.
+fn setup(code: &[&str]) -> (Cpu, Memory) {
+    let mut memory = memory::new(MEMORY, 1);
+    for (i, source) in code.iter().enumerate() {
+        memory.write_word(CODE - KSEG0 + 4 * i as u64, assemble(source));
+    }
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_mmu_kind(MmuKind::Tlb);
+    cpu.set_unaligned_policy(UnalignedPolicy::Trap);
+    cpu.set_pc(CODE);
+    cpu.set_register(A1, DATA);
+    cpu.set_register(A2, MISSING);
+    (cpu, memory)
+}
+
+// Runs until an exception, hands it to the handler and returns it.
+fn fault(cpu: &mut Cpu, memory: &mut Memory) -> Exception {
+    let exception = loop {
+        if let Some(exception) = cpu.step(memory) {
+            break exception;
+        }
+    };
+    assert!(cpu.deliver_exception(memory));
+    exception
+}
+
+fn read_cp0(cpu: &mut Cpu, memory: &mut Memory, rd: u32) -> u64 {
+    execute(cpu, memory, dmfc0(rd));
+    cpu.register(V0)
+}
+
+#[test]
+fn bad_vaddr_is_only_set_by_address_faults() {
+    let (mut cpu, mut memory) = setup(&[
+        "lw $v0, 2($a1)",
+        "lw $v0, 0($a2)",
+        "syscall",
+    ]);
+    assert_eq!(fault(&mut cpu, &mut memory),
+               Exception::AddressErrorLoad { pc: CODE, address: DATA + 2 });
+    assert_eq!(read_cp0(&mut cpu, &mut memory, BADVADDR), DATA + 2);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, EPC), CODE);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, CAUSE) >> 2 & 0x1f, 4);
+
+    // A bus error or a syscall leaves BadVAddr as it was.
+    for (pc, code) in [(CODE + 4, 7), (CODE + 8, 8)] {
+        cpu.set_status(0);
+        cpu.set_pc(pc);
+        let exception = fault(&mut cpu, &mut memory);
+        assert_eq!(exception.bad_address(), None, "{}", exception);
+        assert_eq!(read_cp0(&mut cpu, &mut memory, BADVADDR), DATA + 2,
+                   "{}", exception);
+        assert_eq!(read_cp0(&mut cpu, &mut memory, EPC), pc, "{}", exception);
+        assert_eq!(read_cp0(&mut cpu, &mut memory, CAUSE) >> 2 & 0x1f, code,
+                   "{}", exception);
+    }
+}
+
+#[test]
+fn epc_points_at_the_branch_for_a_delay_slot() {
+    let (mut cpu, mut memory) = setup(&["beq $zero, $zero, 16",
+                                        "lw $v0, 2($a1)"]);
+    assert_eq!(fault(&mut cpu, &mut memory),
+               Exception::AddressErrorLoad { pc: CODE + 4, address: DATA + 2 });
+    assert_eq!(read_cp0(&mut cpu, &mut memory, EPC), CODE);
+    let cause = read_cp0(&mut cpu, &mut memory, CAUSE);
+    assert_eq!(cause & CAUSE_BD, CAUSE_BD);
+
+    // Outside a delay slot, BD is clear again.
+    cpu.set_status(0);
+    cpu.set_pc(CODE + 4);
+    fault(&mut cpu, &mut memory);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, EPC), CODE + 4);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, CAUSE) & CAUSE_BD, 0);
+}
+
+#[test]
+fn epc_is_kept_at_exception_level() {
+    let (mut cpu, mut memory) = setup(&["syscall"]);
+    cpu.set_register(A0, 0x1234);
+    execute(&mut cpu, &mut memory, dmtc0(EPC));
+    cpu.set_pc(CODE);
+    cpu.set_status(EXL);
+    assert_eq!(fault(&mut cpu, &mut memory), Exception::Syscall { pc: CODE });
+    assert_eq!(read_cp0(&mut cpu, &mut memory, EPC), 0x1234);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, STATUS) & EXL, EXL);
+}
+
+#[test]
+fn an_undecodable_word_only_stops_its_own_cpu() {
+    let mut data = vec![0; 0x2000];
+    for (address, code) in [HANDLER, ENTRY] {
+        for (i, source) in code.iter().enumerate() {
+            let offset = address as usize + 4 * i;
+            data[offset..offset + 4]
+                .copy_from_slice(&assemble(source).to_be_bytes());
+        }
+    }
+    let offset = FAULT as usize;
+    data[offset..offset + 4].copy_from_slice(&UNDECODABLE.to_be_bytes());
+    let program = Program {
+        class: Class::Elf64,
+        endianness: Endianness::Big,
+        entry: KSEG0 + ENTRY.0,
+        segments: vec![Segment {
+            address: KSEG0,
+            size: data.len() as u64,
+            data,
+            executable: true,
+        }],
+        headers: None,
+        header_size: 0,
+        header_count: 0,
+    };
+    let mut com = computer::new(2, 0x10000, IsaRevision::Release6);
+    com.set_mmu_kind(MmuKind::Tlb);
+    com.load(&program).unwrap();
+
+    // The exception goes to the kernel's handler rather than stopping
+    // anything.
+    for _ in 0..100 {
+        assert_eq!(com.step(), Vec::new());
+    }
+    let memory = com.memory();
+    let epc = (memory.read_word(0x800).unwrap() as u64) << 32 |
+        memory.read_word(0x804).unwrap() as u64;
+    let cause = memory.read_word(0x808).unwrap() as u64;
+    assert_eq!(epc, KSEG0 + FAULT);
+    assert_eq!(cause >> 2 & 0x1f, 10);
+    assert_eq!(cause & CAUSE_BD, 0);
+
+    // CPU 0 is still counting.
+    let count = memory.read_word(0x810).unwrap();
+    assert!(count > 0);
+    for _ in 0..30 {
+        assert_eq!(com.step(), Vec::new());
+    }
+    assert!(com.memory().read_word(0x810).unwrap() > count);
+    assert_eq!((com.exception(0), com.exception(1)), (None, None));
+}
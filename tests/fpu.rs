@@ -0,0 +1,248 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::Memory;
+
+mod common;
+use common::{assemble, execute, A0};
+
+// FCSR fields
+const RM_NEAREST: u64 = 0;
+const RM_ZERO: u64 = 1;
+const RM_UP: u64 = 2;
+const RM_DOWN: u64 = 3;
+const FLAG_INEXACT: u64 = 0x1 << 2;
+const FLAG_INVALID: u64 = 0x1 << 6;
+const ENABLE_INVALID: u64 = 0x1 << 11;
+const CAUSE_INVALID: u64 = 0x1 << 16;
+
+const QUIET_NAN: u64 = 0x7ff8000000000000;
+const SIGNALING_NAN: u64 = 0x7ff0000000000001;
+
+// A CPU with FCSR set to `fcsr` and $f2 and $f4 holding `fs` and `ft`.
+fn setup(fcsr: u64, fs: u64, ft: u64) -> (Cpu, Memory) {
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_register(A0, fcsr);
+    execute(&mut cpu, &mut memory, assemble("ctc1 $a0, $31"));
+    cpu.set_float_register(2, fs);
+    cpu.set_float_register(4, ft);
+    (cpu, memory)
+}
+
+// Runs `source`, which puts its result in $f0, and returns the result along
+// with FCSR.
+fn run(source: &str, fcsr: u64, fs: u64, ft: u64) -> (u64, u64) {
+    let (mut cpu, mut memory) = setup(fcsr, fs, ft);
+    execute(&mut cpu, &mut memory, assemble(source));
+    execute(&mut cpu, &mut memory, assemble("cfc1 $a0, $31"));
+    (cpu.float_register(0), cpu.register(A0) & 0xffffffff)
+}
+
+fn double(value: f64) -> u64 {
+    value.to_bits()
+}
+
+#[test]
+fn conversions_round_the_way_fcsr_says() {
+    let cases = [
+        (RM_NEAREST, 2.5, 2),
+        (RM_NEAREST, 3.5, 4),
+        (RM_NEAREST, -2.5, -2),
+        (RM_ZERO, 2.7, 2),
+        (RM_ZERO, -2.7, -2),
+        (RM_UP, 2.1, 3),
+        (RM_UP, -2.7, -2),
+        (RM_DOWN, 2.7, 2),
+        (RM_DOWN, -2.1, -3),
+    ];
+    for (mode, value, expected) in cases {
+        let (result, fcsr) = run("cvt.w.d $f0, $f2", mode, double(value), 0);
+        assert_eq!(result as u32 as i32, expected, "{} {}", mode, value);
+        assert_eq!(fcsr & FLAG_INEXACT, FLAG_INEXACT, "{} {}", mode, value);
+    }
+
+    // Arithmetic rounds the same way.
+    let third = |mode| run("div.s $f0, $f2, $f4", mode,
+                           1f32.to_bits() as u64, 3f32.to_bits() as u64).0;
+    assert_eq!(third(RM_NEAREST) as u32, 0x3eaaaaab);
+    assert_eq!(third(RM_ZERO) as u32, 0x3eaaaaaa);
+    assert_eq!(third(RM_UP) as u32, 0x3eaaaaab);
+    assert_eq!(third(RM_DOWN) as u32, 0x3eaaaaaa);
+
+    // Something exact isn't inexact.
+    let (result, fcsr) = run("cvt.w.d $f0, $f2", RM_NEAREST, double(-7.0), 0);
+    assert_eq!((result as u32 as i32, fcsr & FLAG_INEXACT), (-7, 0));
+}
+
+#[test]
+fn maddf_and_msubf_round_once() {
+    // (1 + 2^-30)^2 is 1 + 2^-29 + 2^-60, and the last bit only survives if
+    // the product isn't rounded before the addition.
+    let x = double(1.0 + 2f64.powi(-30));
+    let (mut cpu, mut memory) = setup(RM_NEAREST, x, x);
+    cpu.set_float_register(0, double(-(1.0 + 2f64.powi(-29))));
+    execute(&mut cpu, &mut memory, assemble("maddf.d $f0, $f2, $f4"));
+    assert_eq!(cpu.float_register(0), double(2f64.powi(-60)));
+
+    let (mut cpu, mut memory) = setup(RM_NEAREST, x, x);
+    cpu.set_float_register(0, double(1.0 + 2f64.powi(-29)));
+    execute(&mut cpu, &mut memory, assemble("msubf.d $f0, $f2, $f4"));
+    assert_eq!(cpu.float_register(0), double(-(2f64.powi(-60))));
+
+    let (mut cpu, mut memory) = setup(RM_NEAREST, 3f32.to_bits() as u64,
+                                      4f32.to_bits() as u64);
+    cpu.set_float_register(0, 2f32.to_bits() as u64);
+    execute(&mut cpu, &mut memory, assemble("msubf.s $f0, $f2, $f4"));
+    assert_eq!(cpu.float_register(0) as u32, (-10f32).to_bits());
+}
+
+#[test]
+fn inexact_fused_results_follow_the_rounding_mode() {
+    // Runs `source` with $f0 holding `fd` and returns $f0 and FCSR.
+    let fused = |source, mode, fd, fs, ft| {
+        let (mut cpu, mut memory) = setup(mode, fs, ft);
+        cpu.set_float_register(0, fd);
+        execute(&mut cpu, &mut memory, assemble(source));
+        execute(&mut cpu, &mut memory, assemble("cfc1 $a0, $31"));
+        (cpu.float_register(0), cpu.register(A0) & FLAG_INEXACT)
+    };
+
+    // (1 + 2^-30)^2 + 1 loses its 2^-60.
+    let x = double(1.0 + 2f64.powi(-30));
+    let sum = 2.0 + 2f64.powi(-29);
+    for (mode, expected) in [(RM_NEAREST, sum),
+                             (RM_ZERO, sum),
+                             (RM_UP, sum + 2f64.powi(-51)),
+                             (RM_DOWN, sum)] {
+        assert_eq!(fused("maddf.d $f0, $f2, $f4", mode, double(1.0), x, x),
+                   (double(expected), FLAG_INEXACT), "{}", mode);
+    }
+    assert_eq!(fused("msubf.d $f0, $f2, $f4", RM_UP, double(-1.0), x, x),
+               (double(-sum), FLAG_INEXACT));
+
+    // (1 + 2^-12)^2 + 2^-60 is just over halfway between two singles, but
+    // rounds to exactly halfway as a double, which mustn't then round down
+    // to even.
+    let single = |value: f32| value.to_bits() as u64;
+    let x = single(1.0 + 2f32.powi(-12));
+    let low = 1.0 + 2f32.powi(-11);
+    let high = low + 2f32.powi(-23);
+    for (mode, expected) in [(RM_NEAREST, high),
+                             (RM_ZERO, low),
+                             (RM_UP, high),
+                             (RM_DOWN, low)] {
+        let fd = single(2f32.powi(-60));
+        assert_eq!(fused("maddf.s $f0, $f2, $f4", mode, fd, x, x),
+                   (single(expected), FLAG_INEXACT), "{}", mode);
+    }
+}
+
+#[test]
+fn min_and_max_prefer_numbers_to_quiet_nans() {
+    let one = double(1.0);
+    let two = double(-2.0);
+    let cases = [
+        ("min.d $f0, $f2, $f4", one, two, two),
+        ("max.d $f0, $f2, $f4", one, two, one),
+        ("mina.d $f0, $f2, $f4", one, two, one),
+        ("maxa.d $f0, $f2, $f4", one, two, two),
+        ("min.d $f0, $f2, $f4", QUIET_NAN, one, one),
+        ("min.d $f0, $f2, $f4", one, QUIET_NAN, one),
+        ("max.d $f0, $f2, $f4", QUIET_NAN, one, one),
+        ("maxa.d $f0, $f2, $f4", one, QUIET_NAN, one),
+        // Two NaNs give the first, and zeros go by sign.
+        ("max.d $f0, $f2, $f4", QUIET_NAN | 1, QUIET_NAN | 2, QUIET_NAN | 1),
+        ("min.d $f0, $f2, $f4", double(0.0), double(-0.0), double(-0.0)),
+        ("max.d $f0, $f2, $f4", double(-0.0), double(0.0), double(0.0)),
+    ];
+    for (source, fs, ft, expected) in cases {
+        let (result, fcsr) = run(source, RM_NEAREST, fs, ft);
+        assert_eq!(result, expected, "{} {:#x} {:#x}", source, fs, ft);
+        assert_eq!(fcsr & FLAG_INVALID, 0, "{} {:#x} {:#x}", source, fs, ft);
+    }
+
+    // A signaling NaN is invalid, even next to a number.
+    let (result, fcsr) = run("min.d $f0, $f2, $f4", RM_NEAREST, one,
+                             SIGNALING_NAN);
+    assert_eq!(result, SIGNALING_NAN | QUIET_NAN);
+    assert_eq!(fcsr & FLAG_INVALID, FLAG_INVALID);
+}
+
+#[test]
+fn class_sorts_values_into_kinds() {
+    let cases = [
+        (SIGNALING_NAN, 0x001),
+        (QUIET_NAN, 0x002),
+        (double(f64::NEG_INFINITY), 0x004),
+        (double(-1.5), 0x008),
+        (double(-f64::MIN_POSITIVE / 2.0), 0x010),
+        (double(-0.0), 0x020),
+        (double(f64::INFINITY), 0x040),
+        (double(1.5), 0x080),
+        (double(f64::MIN_POSITIVE / 2.0), 0x100),
+        (double(0.0), 0x200),
+    ];
+    for (value, expected) in cases {
+        let (result, fcsr) = run("class.d $f0, $f2", RM_NEAREST, value, 0);
+        assert_eq!(result, expected, "{:#x}", value);
+        assert_eq!(fcsr & FLAG_INVALID, 0, "{:#x}", value);
+    }
+    let (result, _) = run("class.s $f0, $f2", RM_NEAREST,
+                          (-1f32).to_bits() as u64, 0);
+    assert_eq!(result, 0x008);
+}
+
+#[test]
+fn conversions_that_dont_fit_saturate() {
+    let cases = [
+        ("trunc.w.d $f0, $f2", double(1e10), 0x7fffffff),
+        ("trunc.w.d $f0, $f2", double(-1e10), 0x80000000),
+        ("trunc.w.d $f0, $f2", QUIET_NAN, 0),
+        ("cvt.w.d $f0, $f2", double(f64::INFINITY), 0x7fffffff),
+        ("trunc.l.d $f0, $f2", double(1e30), i64::MAX as u64),
+        ("trunc.l.d $f0, $f2", double(f64::NEG_INFINITY), i64::MIN as u64),
+        ("cvt.l.d $f0, $f2", SIGNALING_NAN, 0),
+    ];
+    for (source, value, expected) in cases {
+        let (result, fcsr) = run(source, RM_NEAREST, value, 0);
+        let result = if source.contains(".w.") {
+            result & 0xffffffff
+        } else {
+            result
+        };
+        assert_eq!(result, expected, "{} {:#x}", source, value);
+        assert_eq!(fcsr & FLAG_INVALID, FLAG_INVALID, "{} {:#x}", source,
+                   value);
+    }
+
+    // The biggest value that does fit is fine.
+    let (result, fcsr) = run("trunc.w.d $f0, $f2", RM_NEAREST,
+                             double(2147483647.9), 0);
+    assert_eq!((result & 0xffffffff, fcsr & FLAG_INVALID), (0x7fffffff, 0));
+}
+
+#[test]
+fn signaling_nans_are_quietened_or_trapped() {
+    // With the exception disabled the NaN comes out quiet, payload and
+    // all, and the Invalid flag is raised.
+    let (result, fcsr) = run("add.d $f0, $f2, $f4", RM_NEAREST, double(1.0),
+                             SIGNALING_NAN);
+    assert_eq!(result, SIGNALING_NAN | QUIET_NAN);
+    assert_eq!(fcsr & (FLAG_INVALID | CAUSE_INVALID),
+               FLAG_INVALID | CAUSE_INVALID);
+
+    // A quiet one goes straight through without any fuss.
+    let (result, fcsr) = run("mul.d $f0, $f2, $f4", RM_NEAREST,
+                             QUIET_NAN | 5, double(2.0));
+    assert_eq!((result, fcsr & FLAG_INVALID), (QUIET_NAN | 5, 0));
+
+    // With it enabled there's an exception, and $f0 is left alone.
+    let (mut cpu, mut memory) = setup(ENABLE_INVALID, SIGNALING_NAN,
+                                      double(1.0));
+    cpu.set_float_register(0, double(3.0));
+    cpu.set_pc(0);
+    cpu.execute_instruction(assemble("sub.d $f0, $f2, $f4"), &mut memory);
+    assert_eq!(cpu.exception(), Some(Exception::FloatingPoint { pc: 0 }));
+    assert_eq!(cpu.float_register(0), double(3.0));
+}
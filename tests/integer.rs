@@ -0,0 +1,71 @@
+use mips_emulator::computer::cpu;
+use mips_emulator::computer::decoder::IsaRevision;
+
+mod common;
+use common::{assemble, execute, A0, A1, A2};
+
+// Runs `source` with $a1 and $a2 set and returns $a0.
+fn run(source: &str, a1: u64, a2: u64) -> u64 {
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_register(A1, a1);
+    cpu.set_register(A2, a2);
+    execute(&mut cpu, &mut memory, assemble(source));
+    cpu.register(A0)
+}
+
+#[test]
+fn comparisons_put_rs_first() {
+    let minus_one = u64::MAX;
+    let cases = [
+        ("slt $a0, $a1, $a2", minus_one, 1, 1),
+        ("slt $a0, $a1, $a2", 1, minus_one, 0),
+        ("slt $a0, $a1, $a2", 1, 1, 0),
+        ("sltu $a0, $a1, $a2", minus_one, 1, 0),
+        ("sltu $a0, $a1, $a2", 1, minus_one, 1),
+        ("slti $a0, $a1, 5", minus_one, 0, 1),
+        ("slti $a0, $a1, 5", 7, 0, 0),
+        ("slti $a0, $a1, -1", minus_one, 0, 0),
+        // The immediate is sign-extended and then compared unsigned.
+        ("sltiu $a0, $a1, -1", 5, 0, 1),
+        ("sltiu $a0, $a1, -1", minus_one, 0, 0),
+        ("sltiu $a0, $a1, 5", 4, 0, 1),
+    ];
+    for (source, a1, a2, expected) in cases {
+        assert_eq!(run(source, a1, a2), expected, "{} {:#x} {:#x}",
+                   source, a1, a2);
+    }
+}
+
+#[test]
+fn variable_shifts_use_the_low_bits_of_rs() {
+    let top = 0x8000000000000000;
+    let cases = [
+        ("dsllv $a0, $a1, $a2", 1, 65, 2),
+        ("dsllv $a0, $a1, $a2", 1, 63, top),
+        ("dsrlv $a0, $a1, $a2", top, 0x7f, 1),
+        ("dsrav $a0, $a1, $a2", top, 0x7f, u64::MAX),
+        ("dsrav $a0, $a1, $a2", top, 4, 0xf800000000000000),
+        ("drotrv $a0, $a1, $a2", 1, 65, top),
+        // Word shifts take five bits and sign-extend their result.
+        ("sllv $a0, $a1, $a2", 1, 33, 2),
+        ("sllv $a0, $a1, $a2", 1, 31, 0xffffffff80000000),
+        ("srlv $a0, $a1, $a2", 0x80000000, 63, 1),
+    ];
+    for (source, a1, a2, expected) in cases {
+        assert_eq!(run(source, a1, a2), expected, "{} {:#x} {:#x}",
+                   source, a1, a2);
+    }
+}
+
+#[test]
+fn writes_to_zero_are_dropped() {
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    for source in ["addiu $zero, $zero, 5", "lui $zero, 1",
+                   "daddu $zero, $a1, $a1"] {
+        cpu.set_register(A1, 3);
+        execute(&mut cpu, &mut memory, assemble(source));
+        assert_eq!(cpu.register(0), 0, "{}", source);
+    }
+}
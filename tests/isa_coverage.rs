@@ -0,0 +1,282 @@
+use mips_emulator::computer::decoder::{self, Instruction, IsaRevision};
+use mips_emulator::computer::{cpu, disassembler};
+
+mod common;
+use common::{assemble, A0, A1, A2};
+
+// Every integer instruction in the Release 6 base architecture, each with
+// operands it takes and the mnemonic it disassembles back to. The CPU
+// executes whatever the decoder returns with a match that covers every
+// instruction, so one that decodes as something other than Reserved is one
+// the CPU runs.
+const INTEGER_INSTRUCTIONS: &[(&str, &str)] = &[
+    // Loads and stores
+    ("lb $a0, 8($a1)", "lb"),
+    ("lbu $a0, 8($a1)", "lbu"),
+    ("lh $a0, 8($a1)", "lh"),
+    ("lhu $a0, 8($a1)", "lhu"),
+    ("lw $a0, 8($a1)", "lw"),
+    ("lwu $a0, 8($a1)", "lwu"),
+    ("ld $a0, 8($a1)", "ld"),
+    ("sb $a0, 8($a1)", "sb"),
+    ("sh $a0, 8($a1)", "sh"),
+    ("sw $a0, 8($a1)", "sw"),
+    ("sd $a0, 8($a1)", "sd"),
+    ("ll $a0, 8($a1)", "ll"),
+    ("lld $a0, 8($a1)", "lld"),
+    ("sc $a0, 8($a1)", "sc"),
+    ("scd $a0, 8($a1)", "scd"),
+    ("llwp $a0, $a2, ($a1)", "llwp"),
+    ("scwp $a0, $a2, ($a1)", "scwp"),
+    ("sync", "sync"),
+    ("pref 5, 8($a1)", "pref"),
+    ("synci 8($a1)", "synci"),
+    ("lwpc $a0, 8", "lwpc"),
+    ("lwupc $a0, 8", "lwupc"),
+    ("ldpc $a0, 8", "ldpc"),
+    ("addiupc $a0, 8", "addiupc"),
+    ("auipc $a0, 0x1234", "auipc"),
+    ("aluipc $a0, 0x1234", "aluipc"),
+    // ALU instructions with an immediate
+    ("addiu $a0, $a1, -1", "addiu"),
+    ("andi $a0, $a1, 0xff", "andi"),
+    ("daddiu $a0, $a1, -1", "daddiu"),
+    ("lui $a0, 0x1234", "lui"),
+    ("aui $a0, $a1, 0x1234", "aui"),
+    ("daui $a0, $a1, 0x1234", "daui"),
+    ("dahi $a0, 0x1234", "dahi"),
+    ("dati $a0, 0x1234", "dati"),
+    ("ori $a0, $a1, 0xff", "ori"),
+    ("slti $a0, $a1, 5", "slti"),
+    ("sltiu $a0, $a1, 5", "sltiu"),
+    ("xori $a0, $a1, 0xff", "xori"),
+    // Three-operand ALU instructions
+    ("add $a0, $a1, $a2", "add"),
+    ("addu $a0, $a1, $a2", "addu"),
+    ("and $a0, $a1, $a2", "and"),
+    ("dadd $a0, $a1, $a2", "dadd"),
+    ("daddu $a0, $a1, $a2", "daddu"),
+    ("dsub $a0, $a1, $a2", "dsub"),
+    ("dsubu $a0, $a1, $a2", "dsubu"),
+    ("nor $a0, $a1, $a2", "nor"),
+    ("or $a0, $a1, $a2", "or"),
+    ("slt $a0, $a1, $a2", "slt"),
+    ("sltu $a0, $a1, $a2", "sltu"),
+    ("sub $a0, $a1, $a2", "sub"),
+    ("subu $a0, $a1, $a2", "subu"),
+    ("xor $a0, $a1, $a2", "xor"),
+    ("seleqz $a0, $a1, $a2", "seleqz"),
+    ("selnez $a0, $a1, $a2", "selnez"),
+    ("lsa $a0, $a1, $a2, 2", "lsa"),
+    ("dlsa $a0, $a1, $a2, 2", "dlsa"),
+    ("clo $a0, $a1", "clo"),
+    ("clz $a0, $a1", "clz"),
+    ("dclo $a0, $a1", "dclo"),
+    ("dclz $a0, $a1", "dclz"),
+    // Shifts
+    ("sll $a0, $a1, 3", "sll"),
+    ("srl $a0, $a1, 3", "srl"),
+    ("sra $a0, $a1, 3", "sra"),
+    ("rotr $a0, $a1, 3", "rotr"),
+    ("dsll $a0, $a1, 3", "dsll"),
+    ("dsrl $a0, $a1, 3", "dsrl"),
+    ("dsra $a0, $a1, 3", "dsra"),
+    ("drotr $a0, $a1, 3", "drotr"),
+    ("dsll32 $a0, $a1, 3", "dsll32"),
+    ("dsrl32 $a0, $a1, 3", "dsrl32"),
+    ("dsra32 $a0, $a1, 3", "dsra32"),
+    ("drotr32 $a0, $a1, 3", "drotr32"),
+    ("sllv $a0, $a1, $a2", "sllv"),
+    ("srlv $a0, $a1, $a2", "srlv"),
+    ("srav $a0, $a1, $a2", "srav"),
+    ("rotrv $a0, $a1, $a2", "rotrv"),
+    ("dsllv $a0, $a1, $a2", "dsllv"),
+    ("dsrlv $a0, $a1, $a2", "dsrlv"),
+    ("dsrav $a0, $a1, $a2", "dsrav"),
+    ("drotrv $a0, $a1, $a2", "drotrv"),
+    // Bit fields and byte shuffles
+    ("ext $a0, $a1, 3, 7", "ext"),
+    ("ins $a0, $a1, 3, 7", "ins"),
+    ("dext $a0, $a1, 3, 7", "dext"),
+    ("dextm $a0, $a1, 3, 40", "dextm"),
+    ("dextu $a0, $a1, 35, 7", "dextu"),
+    ("dins $a0, $a1, 3, 7", "dins"),
+    ("dinsm $a0, $a1, 3, 40", "dinsm"),
+    ("dinsu $a0, $a1, 35, 7", "dinsu"),
+    ("align $a0, $a1, $a2, 1", "align"),
+    ("dalign $a0, $a1, $a2, 1", "dalign"),
+    ("bitswap $a0, $a1", "bitswap"),
+    ("dbitswap $a0, $a1", "dbitswap"),
+    ("wsbh $a0, $a1", "wsbh"),
+    ("dsbh $a0, $a1", "dsbh"),
+    ("dshd $a0, $a1", "dshd"),
+    ("seb $a0, $a1", "seb"),
+    ("seh $a0, $a1", "seh"),
+    ("rdhwr $a0, $29", "rdhwr"),
+    // Multiplies and divides
+    ("mul $a0, $a1, $a2", "mul"),
+    ("muh $a0, $a1, $a2", "muh"),
+    ("mulu $a0, $a1, $a2", "mulu"),
+    ("muhu $a0, $a1, $a2", "muhu"),
+    ("dmul $a0, $a1, $a2", "dmul"),
+    ("dmuh $a0, $a1, $a2", "dmuh"),
+    ("dmulu $a0, $a1, $a2", "dmulu"),
+    ("dmuhu $a0, $a1, $a2", "dmuhu"),
+    ("div $a0, $a1, $a2", "div"),
+    ("mod $a0, $a1, $a2", "mod"),
+    ("divu $a0, $a1, $a2", "divu"),
+    ("modu $a0, $a1, $a2", "modu"),
+    ("ddiv $a0, $a1, $a2", "ddiv"),
+    ("dmod $a0, $a1, $a2", "dmod"),
+    ("ddivu $a0, $a1, $a2", "ddivu"),
+    ("dmodu $a0, $a1, $a2", "dmodu"),
+    // Compact branches and jumps
+    ("bc 8", "bc"),
+    ("balc 8", "balc"),
+    ("jic $a0, 8", "jic"),
+    ("jialc $a0, 8", "jialc"),
+    ("beqzc $a0, 8", "beqzc"),
+    ("bnezc $a0, 8", "bnezc"),
+    ("beqc $a0, $a1, 8", "beqc"),
+    ("bnec $a0, $a1, 8", "bnec"),
+    ("bovc $a0, $a1, 8", "bovc"),
+    ("bnvc $a0, $a1, 8", "bnvc"),
+    ("bgeuc $a0, $a1, 8", "bgeuc"),
+    ("bltuc $a0, $a1, 8", "bltuc"),
+    ("bgec $a0, $a1, 8", "bgec"),
+    ("bltc $a0, $a1, 8", "bltc"),
+    ("blezc $a0, 8", "blezc"),
+    ("bgezc $a0, 8", "bgezc"),
+    ("bgtzc $a0, 8", "bgtzc"),
+    ("bltzc $a0, 8", "bltzc"),
+    ("blezalc $a0, 8", "blezalc"),
+    ("bgezalc $a0, 8", "bgezalc"),
+    ("bgtzalc $a0, 8", "bgtzalc"),
+    ("bltzalc $a0, 8", "bltzalc"),
+    ("beqzalc $a0, 8", "beqzalc"),
+    ("bnezalc $a0, 8", "bnezalc"),
+    // Delayed branches and jumps
+    ("b 8", "b"),
+    ("beq $a0, $a1, 8", "beq"),
+    ("bne $a0, $a1, 8", "bne"),
+    ("blez $a0, 8", "blez"),
+    ("bgtz $a0, 8", "bgtz"),
+    ("bltz $a0, 8", "bltz"),
+    ("bgez $a0, 8", "bgez"),
+    ("bal 8", "bal"),
+    ("nal", "nal"),
+    ("j 0x120000100", "j"),
+    ("jal 0x120000100", "jal"),
+    ("jr $a0", "jr"),
+    ("jalr $a0", "jalr"),
+    // Traps and exceptions
+    ("teq $a0, $a1", "teq"),
+    ("tne $a0, $a1", "tne"),
+    ("tge $a0, $a1", "tge"),
+    ("tgeu $a0, $a1", "tgeu"),
+    ("tlt $a0, $a1", "tlt"),
+    ("tltu $a0, $a1", "tltu"),
+    ("sigrie 1", "sigrie"),
+    ("syscall", "syscall"),
+    ("break", "break"),
+    ("sdbbp", "sdbbp"),
+    // Privileged instructions
+    ("mfc0 $a0, $12", "mfc0"),
+    ("dmfc0 $a0, $14", "dmfc0"),
+    ("mtc0 $a0, $12", "mtc0"),
+    ("dmtc0 $a0, $14", "dmtc0"),
+    ("di", "di"),
+    ("ei", "ei"),
+    ("eret", "eret"),
+    ("deret", "deret"),
+    ("wait", "wait"),
+];
+
+// Runs a single instruction on a fresh CPU with some registers set, and
+// returns what's in $a0 afterwards.
+fn run(source: &str, registers: &[(usize, u64)]) -> u64 {
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    for &(index, value) in registers {
+        cpu.set_register(index, value);
+    }
+    cpu.execute_instruction(assemble(source), &mut memory);
+    assert_eq!(cpu.exception(), None, "{}", source);
+    cpu.register(A0)
+}
+
+#[test]
+fn every_integer_instruction_is_handled() {
+    let mut missing = Vec::new();
+    for &(source, mnemonic) in INTEGER_INSTRUCTIONS {
+        let word = assemble(source);
+        let text = disassembler::disassemble(word, 0x120000000,
+                                             IsaRevision::Release6);
+        if decoder::decode(word, IsaRevision::Release6) == Instruction::Reserved
+                || text.split(' ').next() != Some(mnemonic) {
+            missing.push(format!("{} ({:08x} disassembles as {})", source, word,
+                                 text));
+        }
+    }
+    assert!(missing.is_empty(), "not handled:\n{}", missing.join("\n"));
+}
+
+#[test]
+fn bit_field_instructions() {
+    let cases: &[(&str, u64, u64, u64)] = &[
+        // source, $a0, $a1, expected $a0
+        ("ext $a0, $a1, 4, 8", 0, 0x12345678, 0x67),
+        ("ext $a0, $a1, 0, 32", 0, 0x80000000, 0xffffffff80000000),
+        ("ins $a0, $a1, 8, 8", 0x11223344, 0xab, 0x1122ab44),
+        ("ins $a0, $a1, 16, 16", 0x1234, 0x8000, 0xffffffff80001234),
+        ("dext $a0, $a1, 28, 8", 0, 0xab0000000, 0xab),
+        ("dextm $a0, $a1, 4, 40", 0, 0x0123456789abcdef, 0x56789abcde),
+        ("dextu $a0, $a1, 36, 8", 0, 0xab000000000, 0xab),
+        ("dins $a0, $a1, 28, 8", 0, 0xff, 0xff0000000),
+        ("dinsm $a0, $a1, 24, 16", u64::MAX, 0, 0xffffff0000ffffff),
+        ("dinsu $a0, $a1, 48, 16", 0, 0xbeef, 0xbeef000000000000),
+    ];
+    for &(source, a0, a1, expected) in cases {
+        assert_eq!(run(source, &[(A0, a0), (A1, a1)]), expected, "{}", source);
+    }
+}
+
+#[test]
+fn byte_shuffle_instructions() {
+    let cases: &[(&str, u64, u64)] = &[
+        // source, $a1, expected $a0
+        ("wsbh $a0, $a1", 0x11223344, 0x22114433),
+        ("wsbh $a0, $a1", 0x00800000, 0xffffffff80000000),
+        ("dsbh $a0, $a1", 0x0011223344556677, 0x1100332255447766),
+        ("dshd $a0, $a1", 0x0011223344556677, 0x6677445522330011),
+        ("seb $a0, $a1", 0x80, 0xffffffffffffff80),
+        ("seh $a0, $a1", 0x12348000, 0xffffffffffff8000),
+    ];
+    for &(source, a1, expected) in cases {
+        assert_eq!(run(source, &[(A1, a1)]), expected, "{}", source);
+    }
+}
+
+#[test]
+fn add_and_select_instructions() {
+    let cases: &[(&str, u64, u64, u64, u64)] = &[
+        // source, $a0, $a1, $a2, expected $a0
+        ("lsa $a0, $a1, $a2, 2", 0, 3, 1, 13),
+        ("lsa $a0, $a1, $a2, 1", 0, 0x40000000, 0, 0xffffffff80000000),
+        ("dlsa $a0, $a1, $a2, 4", 0, 0x1000000000, 1, 0x10000000001),
+        ("seleqz $a0, $a1, $a2", 0, 7, 0, 7),
+        ("seleqz $a0, $a1, $a2", 0, 7, 1, 0),
+        ("selnez $a0, $a1, $a2", 0, 7, 1, 7),
+        ("selnez $a0, $a1, $a2", 0, 7, 0, 0),
+        ("aui $a0, $a1, 0x1234", 0, 0x5678, 0, 0x12345678),
+        ("aui $a0, $a1, 0x8000", 0, 1, 0, 0xffffffff80000001),
+        ("daui $a0, $a1, 0x8000", 0, 0x100000000, 0, 0x80000000),
+        ("dahi $a0, 1", 5, 0, 0, 0x100000005),
+        ("dahi $a0, 0xffff", 0x100000000, 0, 0, 0),
+        ("dati $a0, 0x1234", 0, 0, 0, 0x1234000000000000),
+    ];
+    for &(source, a0, a1, a2, expected) in cases {
+        assert_eq!(run(source, &[(A0, a0), (A1, a1), (A2, a2)]), expected,
+                   "{}", source);
+    }
+}
@@ -0,0 +1,418 @@
+use std::env;
+use std::fs;
+use std::os::unix;
+use std::path::PathBuf;
+use std::process;
+
+use mips_emulator::computer;
+use mips_emulator::computer::assembler;
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::linux::{self, Linux};
+use mips_emulator::computer::memory::{self, AddressMode, Endianness, Memory};
+use mips_emulator::computer::program::{Class, Program, ProgramError, Segment};
+use mips_emulator::computer::syscall::{SyscallHandler, SyscallResult};
+
+mod common;
+use common::{A0, MEMORY, SP, V0};
+
+const A3: usize = 7;
+
+// Syscall numbers and error numbers
+const READ: u64 = 5000;
+const CLOSE: u64 = 5003;
+const MMAP: u64 = 5009;
+const MUNMAP: u64 = 5011;
+const BRK: u64 = 5012;
+const RT_SIGPROCMASK: u64 = 5014;
+const EXIT: u64 = 5058;
+const UNAME: u64 = 5061;
+const EXIT_GROUP: u64 = 5205;
+const OPENAT: u64 = 5247;
+const GETRANDOM: u64 = 5313;
+const EBADF: u64 = 9;
+const ENOMEM: u64 = 12;
+const EACCES: u64 = 13;
+const EFAULT: u64 = 14;
+const ENOTDIR: u64 = 20;
+const EINVAL: u64 = 22;
+
+const AT_FDCWD: u64 = -100i64 as u64;
+const O_WRONLY: u64 = 0x1;
+const O_CREAT: u64 = 0x100;
+const MAP_PRIVATE: u64 = 0x2;
+const MAP_ANONYMOUS: u64 = 0x800;
+
+// Auxiliary vector entry types
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+const AT_PLATFORM: u64 = 15;
+const AT_RANDOM: u64 = 25;
+const AT_EXECFN: u64 = 31;
+
+// Enough memory for a program, a heap and the stack, all mapped straight
+// through.
+const LARGE: u64 = 4 << 20;
+const ENTRY: u64 = 0x120;
+const PROGRAM_END: u64 = 0x800;
+// Scratch space for paths and buffers.
+const BUFFER: u64 = 0x10000;
+
+// An empty directory of its own for each test to use as the root.
+fn sandbox(name: &str) -> PathBuf {
+    let root = env::temp_dir()
+        .join(format!("mips-emulator-linux-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+fn program() -> Program {
+    Program {
+        class: Class::Elf64,
+        endianness: Endianness::Big,
+        entry: ENTRY,
+        segments: vec![Segment {
+            address: 0,
+            size: PROGRAM_END,
+            data: Vec::new(),
+            executable: true,
+        }],
+        headers: Some(0x40),
+        header_size: 56,
+        header_count: 1,
+    }
+}
+
+// A handler that has loaded `program()`, with the stack at the top of
+// memory.
+fn setup(root: PathBuf, args: &[&str], env: &[&str])
+    -> (Linux, Cpu, Memory) {
+    let strings = |strings: &[&str]| {
+        strings.iter().map(|string| string.to_string()).collect()
+    };
+    let mut linux = linux::new(root, strings(args), strings(env));
+    let mut memory = memory::new(LARGE, 1);
+    memory.set_mmu(0, 0, LARGE - 1, AddressMode::Bits64);
+    let mut cpus = [cpu::new(0, IsaRevision::Release6)];
+    cpus[0].set_register(SP, LARGE);
+    linux.load(&program(), &mut cpus, &mut memory).unwrap();
+    let [cpu] = cpus;
+    (linux, cpu, memory)
+}
+
+// Makes a syscall with arguments from $a0 on, and returns $v0, or the error
+// number if $a3 says it failed.
+fn syscall(linux: &mut Linux,
+           cpu: &mut Cpu,
+           memory: &mut Memory,
+           number: u64,
+           arguments: &[u64]) -> Result<u64, u64> {
+    cpu.set_register(V0, number);
+    for (i, &argument) in arguments.iter().enumerate() {
+        cpu.set_register(A0 + i, argument);
+    }
+    assert!(matches!(linux.syscall(cpu, memory), SyscallResult::Resume),
+            "{} {:x?}", number, arguments);
+    match cpu.register(A3) {
+        0 => Ok(cpu.register(V0)),
+        _ => Err(cpu.register(V0)),
+    }
+}
+
+// Puts a NUL-terminated path at BUFFER and returns its address.
+fn path(memory: &mut Memory, path: &str) -> u64 {
+    memory.write_bytes(BUFFER, path.as_bytes());
+    memory.write_byte(BUFFER + path.len() as u64, 0);
+    BUFFER
+}
+
+// Opens a file, writes to it, then makes a write that can't be done and
+// exits with the error it got.
+const PROGRAM: &str = "
+        .data
+msg:    .asciiz \"hello\\n\"
+name:   .asciiz \"out\"
+        .text
+main:
+        li      $v0, 5002
+        la      $a0, name
+        li      $a1, 0x101
+        li      $a2, 420
+        syscall
+        move    $s0, $v0
+        li      $v0, 5001
+        move    $a0, $s0
+        la      $a1, msg
+        li      $a2, 6
+        syscall
+        li      $v0, 5001
+        move    $a0, $s0
+        la      $a1, msg
+        li      $a2, -1
+        syscall
+        dsll    $a0, $a3, 7
+        or      $a0, $a0, $v0
+        li      $v0, 5058
+        syscall
+";
+
+#[test]
+fn a_program_writes_a_file_and_exits() {
+    let root = env::temp_dir()
+        .join(format!("mips-emulator-linux-{}", process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let program = assembler::assemble(PROGRAM).unwrap();
+    let mut com = computer::new(1, 1 << 20, IsaRevision::Release6);
+    com.set_syscall_handler(Box::new(linux::new(root.clone(),
+                                                vec!["test".to_string()],
+                                                Vec::new())));
+    com.load(&program).unwrap();
+    for _ in 0..1000 {
+        if !com.running() {
+            break;
+        }
+        assert_eq!(com.step(), Vec::new());
+    }
+    let written = fs::read(root.join("out"));
+    fs::remove_dir_all(&root).unwrap();
+
+    // The second write failed with EFAULT, and $a3 set to say so, rather
+    // than the size taking down the host.
+    assert_eq!(com.exit_status(), Some(0x80 | EFAULT as i32));
+    assert_eq!(written.unwrap(), b"hello\n");
+}
+
+#[test]
+fn sizes_are_checked_before_anything_is_made_that_big() {
+    let mut linux = linux::new(env::temp_dir(), Vec::new(), Vec::new());
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    // The arguments go in $a0 to $a3.
+    let cases = [
+        (GETRANDOM, [0, u64::MAX, 0, 0], EFAULT),
+        (GETRANDOM, [MEMORY - 4, 8, 0, 0], EFAULT),
+        (RT_SIGPROCMASK, [0, 0, 0, u64::MAX], EINVAL),
+        (RT_SIGPROCMASK, [0, 0, MEMORY, 16], EFAULT),
+    ];
+    for (number, arguments, error) in cases {
+        cpu.set_register(V0, number);
+        for (i, &argument) in arguments.iter().enumerate() {
+            cpu.set_register(A0 + i, argument);
+        }
+        linux.syscall(&mut cpu, &mut memory);
+        assert_eq!((cpu.register(V0), cpu.register(A3)), (error, 1),
+                   "{} {:x?}", number, arguments);
+    }
+}
+
+#[test]
+fn exit_keeps_the_low_byte_of_the_status() {
+    let (mut linux, mut cpu, mut memory) =
+        setup(env::temp_dir(), &[], &[]);
+    for (number, status, expected) in [(EXIT, 7, 7),
+                                       (EXIT, 0x1ff, 0xff),
+                                       (EXIT_GROUP, 0x100, 0),
+                                       (EXIT_GROUP, u64::MAX, 0xff)] {
+        cpu.set_register(V0, number);
+        cpu.set_register(A0, status);
+        let result = linux.syscall(&mut cpu, &mut memory);
+        assert!(matches!(result, SyscallResult::Exit(code) if code == expected),
+                "{} 0x{:x}", number, status);
+    }
+}
+
+#[test]
+fn the_initial_stack_has_arguments_environment_and_auxv() {
+    let (_, cpu, mut memory) =
+        setup(env::temp_dir(), &["prog", "x"], &["A=1"]);
+    let sp = cpu.register(SP);
+    assert_eq!(sp % 16, 0);
+    let word = |memory: &mut Memory, index: u64| {
+        memory.read_virtual_value(0, sp + index * 8, 8).unwrap()
+    };
+    let string = |memory: &mut Memory, address| {
+        String::from_utf8(memory.read_string(0, address).unwrap()).unwrap()
+    };
+    let words: Vec<u64> = (0..6).map(|index| word(&mut memory, index))
+        .collect();
+
+    assert_eq!(words[0], 2);
+    assert_eq!(string(&mut memory, words[1]), "prog");
+    assert_eq!(string(&mut memory, words[2]), "x");
+    assert_eq!(words[3], 0);
+    assert_eq!(string(&mut memory, words[4]), "A=1");
+    assert_eq!(words[5], 0);
+
+    let mut auxv = Vec::new();
+    for index in (6..).step_by(2) {
+        let kind = word(&mut memory, index);
+        auxv.push((kind, word(&mut memory, index + 1)));
+        if kind == AT_NULL {
+            break;
+        }
+    }
+    assert_eq!(auxv.last(), Some(&(AT_NULL, 0)));
+    let entry = |kind| {
+        auxv.iter().find(|(entry, _)| *entry == kind).unwrap().1
+    };
+    assert_eq!(entry(AT_PHDR), 0x40);
+    assert_eq!(entry(AT_PAGESZ), 4096);
+    assert_eq!(entry(AT_ENTRY), ENTRY);
+    assert_eq!(string(&mut memory, entry(AT_PLATFORM)), "mips64");
+    assert_eq!(string(&mut memory, entry(AT_EXECFN)), "prog");
+    // The random bytes are on the stack above everything else.
+    let random = entry(AT_RANDOM);
+    assert!(random > sp && random + 16 <= LARGE, "0x{:x}", random);
+}
+
+#[test]
+fn a_stack_that_doesnt_fit_fails_the_load() {
+    let mut com = computer::new(1, MEMORY, IsaRevision::Release6);
+    let huge = "x".repeat(2 * MEMORY as usize);
+    com.set_syscall_handler(Box::new(linux::new(env::temp_dir(),
+                                                vec![huge],
+                                                Vec::new())));
+    let mut program = program();
+    program.segments[0].size = 4;
+    assert!(matches!(com.load(&program), Err(ProgramError::Stack(_))));
+}
+
+#[test]
+fn brk_grows_and_shrinks_the_heap() {
+    let (mut linux, mut cpu, mut memory) =
+        setup(env::temp_dir(), &[], &[]);
+    let mut brk = |memory: &mut Memory, address| {
+        syscall(&mut linux, &mut cpu, memory, BRK, &[address]).unwrap()
+    };
+    // It starts on the page after the program.
+    let start = brk(&mut memory, 0);
+    assert_eq!(start, 0x1000);
+    assert_eq!(brk(&mut memory, start + 0x2000), start + 0x2000);
+    memory.write_word(start + 0x1800, 0xdeadbeef);
+    assert_eq!(brk(&mut memory, start + 0x1000), start + 0x1000);
+    // Memory given back and taken again is zeroed.
+    assert_eq!(brk(&mut memory, start + 0x2000), start + 0x2000);
+    assert_eq!(memory.read_word(start + 0x1800), Some(0));
+    // Below the start or past the stack, it stays where it was.
+    assert_eq!(brk(&mut memory, start - 0x1000), start + 0x2000);
+    assert_eq!(brk(&mut memory, LARGE), start + 0x2000);
+}
+
+#[test]
+fn mmap_hands_out_pages_below_the_stack() {
+    let root = sandbox("mmap");
+    fs::write(root.join("data"), b"mapped").unwrap();
+    let (mut linux, mut cpu, mut memory) = setup(root.clone(), &[], &[]);
+    let anonymous = MAP_PRIVATE | MAP_ANONYMOUS;
+
+    let first = syscall(&mut linux, &mut cpu, &mut memory, MMAP,
+                        &[0, 0x1800, 3, anonymous, u64::MAX, 0]).unwrap();
+    assert_eq!(first % 4096, 0);
+    assert!(first + 0x2000 <= LARGE - (1 << 20), "0x{:x}", first);
+    let second = syscall(&mut linux, &mut cpu, &mut memory, MMAP,
+                         &[0, 0x1000, 3, anonymous, u64::MAX, 0]).unwrap();
+    assert_eq!(second, first - 0x1000);
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, MUNMAP,
+                       &[second, 0x1000]), Ok(0));
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, MMAP,
+                       &[0, 0, 3, anonymous, u64::MAX, 0]), Err(EINVAL));
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, MMAP,
+                       &[0, 1 << 40, 3, anonymous, u64::MAX, 0]),
+               Err(ENOMEM));
+
+    // A file's contents are copied in, and its position left alone.
+    let name = path(&mut memory, "data");
+    let fd = syscall(&mut linux, &mut cpu, &mut memory, OPENAT,
+                     &[AT_FDCWD, name, 0]).unwrap();
+    let mapped = syscall(&mut linux, &mut cpu, &mut memory, MMAP,
+                         &[0, 0x1000, 1, MAP_PRIVATE, fd, 0]).unwrap();
+    assert_eq!(memory.read_virtual(0, mapped, 7).unwrap(), b"mapped\0");
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, READ,
+                       &[fd, BUFFER, 3]), Ok(3));
+    assert_eq!(memory.read_virtual(0, BUFFER, 3).unwrap(), b"map");
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn files_open_read_and_close_inside_the_root() {
+    let root = sandbox("files");
+    fs::create_dir(root.join("dir")).unwrap();
+    fs::write(root.join("dir/data"), b"abc").unwrap();
+    let (mut linux, mut cpu, mut memory) = setup(root.clone(), &[], &[]);
+    let mut openat = |memory: &mut Memory, dirfd, name: &str, flags| {
+        let name = path(memory, name);
+        syscall(&mut linux, &mut cpu, memory, OPENAT, &[dirfd, name, flags])
+    };
+
+    let dir = openat(&mut memory, AT_FDCWD, "/dir", 0).unwrap();
+    let file = openat(&mut memory, dir, "data", 0).unwrap();
+    // A path relative to a file, or to an fd that isn't open, goes nowhere.
+    assert_eq!(openat(&mut memory, file, "data", 0), Err(ENOTDIR));
+    assert_eq!(openat(&mut memory, 99, "data", 0), Err(EBADF));
+    // An absolute path ignores the directory.
+    assert!(openat(&mut memory, file, "/dir/data", 0).is_ok());
+
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, READ,
+                       &[file, BUFFER, 16]), Ok(3));
+    assert_eq!(memory.read_virtual(0, BUFFER, 3).unwrap(), b"abc");
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, CLOSE, &[file]),
+               Ok(0));
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, READ,
+                       &[file, BUFFER, 16]), Err(EBADF));
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, CLOSE, &[file]),
+               Err(EBADF));
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn paths_cant_get_out_of_the_root() {
+    let root = sandbox("escape");
+    let outside = sandbox("escape-outside");
+    fs::write(outside.join("secret"), b"secret").unwrap();
+    unix::fs::symlink(&outside, root.join("link")).unwrap();
+    unix::fs::symlink(outside.join("new"), root.join("dangling")).unwrap();
+    fs::write(root.join("inside"), b"").unwrap();
+    unix::fs::symlink("inside", root.join("fine")).unwrap();
+    let (mut linux, mut cpu, mut memory) = setup(root.clone(), &[], &[]);
+    let mut openat = |memory: &mut Memory, name: &str, flags| {
+        let name = path(memory, name);
+        syscall(&mut linux, &mut cpu, memory, OPENAT,
+                &[AT_FDCWD, name, flags])
+    };
+
+    assert_eq!(openat(&mut memory, "../escape-outside/secret", 0),
+               Err(EACCES));
+    assert_eq!(openat(&mut memory, "link/secret", 0), Err(EACCES));
+    assert_eq!(openat(&mut memory, "/link/secret", 0), Err(EACCES));
+    assert_eq!(openat(&mut memory, "link/other", O_WRONLY | O_CREAT),
+               Err(EACCES));
+    assert_eq!(openat(&mut memory, "dangling", O_WRONLY | O_CREAT),
+               Err(EACCES));
+    // A symlink that stays inside is fine.
+    assert!(openat(&mut memory, "fine", 0).is_ok());
+
+    let created = [outside.join("other").exists(),
+                   outside.join("new").exists()];
+    fs::remove_dir_all(&root).unwrap();
+    fs::remove_dir_all(&outside).unwrap();
+    assert_eq!(created, [false, false]);
+}
+
+#[test]
+fn uname_fills_in_each_field() {
+    let (mut linux, mut cpu, mut memory) =
+        setup(env::temp_dir(), &[], &[]);
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, UNAME, &[BUFFER]),
+               Ok(0));
+    let field = |memory: &mut Memory, index: u64| {
+        memory.read_string(0, BUFFER + index * 65).unwrap()
+    };
+    assert_eq!(field(&mut memory, 0), b"Linux");
+    assert_eq!(field(&mut memory, 1), b"mips");
+    assert_eq!(field(&mut memory, 4), b"mips64");
+    assert_eq!(syscall(&mut linux, &mut cpu, &mut memory, UNAME, &[LARGE]),
+               Err(EFAULT));
+}
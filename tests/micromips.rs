@@ -0,0 +1,152 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::{IsaMode, IsaRevision};
+use mips_emulator::computer::disassembler;
+use mips_emulator::computer::memory::{AddressMode, Endianness, Memory};
+
+// Where the exception vectors are after reset, in kseg0.
+const EBASE: u64 = 0xffffffff80000000;
+mod common;
+use common::{A0, A1, RA, S0, S1, SP, V0, V1};
+
+// microMIPS encodings, 32-bit ones with the first halfword on top.
+const LI16_V0_5: u32 = 0xed05;
+const LI16_V0_MINUS_1: u32 = 0xed7f;
+const ADDIU_V0_V1_MINUS_5: u32 = 0x3043fffb;
+const ADDU16_V0_V1_A0: u32 = 0x05c4;
+const BEQZC16_V0_16: u32 = 0x8d08;
+const BREAK16_3: u32 = 0x44db;
+const JALRC_V0: u32 = 0x03e20f3c;
+const LWM32_S0_S1_8_V1: u32 = 0x20435008;
+const SWM32_S0_S1_8_V1: u32 = 0x2043d008;
+const LWM16_S0_S1_RA_8_SP: u32 = 0x4522;
+const MOVEP_A0_A1_V0_V1: u32 = 0x46b6;
+
+// Standard MIPS encodings.
+const JRC_RA: u32 = 0xd81f0000;
+const MFC0_A0_EPC: u32 = 0x40047000;
+const ERET: u32 = 0x42000018;
+
+// A Release 6 CPU whose addresses map straight onto a page of memory, with
+// the given halfwords laid out from 0x100 and the PC there in microMIPS mode.
+fn setup(endianness: Endianness, code: &[u32]) -> (Cpu, Memory) {
+    let mut memory = common::memory();
+    memory.set_endianness(endianness);
+    common::write_halfwords(&mut memory, 0x100, code);
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_pc(0x101);
+    (cpu, memory)
+}
+
+#[test]
+fn disassembles_both_sizes() {
+    let cases = [
+        (ADDU16_V0_V1_A0, 2, "addu $v0, $v1, $a0"),
+        (ADDIU_V0_V1_MINUS_5, 4, "addiu $v0, $v1, -5"),
+        (MOVEP_A0_A1_V0_V1, 2, "movep $a0, $a1, $v0, $v1"),
+        (SWM32_S0_S1_8_V1, 4, "swm32 $s0, $s1, 8($v1)"),
+        (JALRC_V0, 4, "jalrc $v0"),
+        (BEQZC16_V0_16, 2, "beqzc $v0, 0x10"),
+        (0x0000, 2, ".hword 0x0000"),
+    ];
+    for (instruction, size, text) in cases {
+        assert_eq!(disassembler::disassemble_micromips(instruction, size, 0x100),
+                   text);
+    }
+}
+
+#[test]
+fn runs_mixed_sizes_in_both_byte_orders() {
+    let code = [LI16_V0_5, ADDIU_V0_V1_MINUS_5, ADDU16_V0_V1_A0,
+                BEQZC16_V0_16, LI16_V0_MINUS_1];
+    for endianness in [Endianness::Big, Endianness::Little] {
+        let (mut cpu, mut memory) = setup(endianness, &code);
+        cpu.set_register(V1, 20);
+        cpu.set_register(A0, 7);
+        assert_eq!(cpu.step(&mut memory), None);
+        assert_eq!(cpu.register(V0), 5, "{:?}", endianness);
+        assert_eq!(cpu.step(&mut memory), None);
+        assert_eq!(cpu.register(V0), 15, "{:?}", endianness);
+        assert_eq!(cpu.step(&mut memory), None);
+        assert_eq!(cpu.register(V0), 27, "{:?}", endianness);
+        // Not taken, and there's no forbidden slot to skip.
+        assert_eq!(cpu.step(&mut memory), None);
+        assert_eq!(cpu.step(&mut memory), None);
+        assert_eq!(cpu.register(V0), u64::MAX, "{:?}", endianness);
+        assert_eq!(cpu.pc(), 0x10c, "{:?}", endianness);
+    }
+}
+
+#[test]
+fn compact_branches_count_from_the_next_halfword() {
+    let (mut cpu, mut memory) = setup(Endianness::Big, &[BEQZC16_V0_16]);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(cpu.pc(), 0x112);
+    assert_eq!(cpu.isa_mode(), IsaMode::MicroMips);
+}
+
+#[test]
+fn register_jumps_switch_modes() {
+    let (mut cpu, mut memory) = setup(Endianness::Big, &[JALRC_V0]);
+    memory.write_word(0x200, JRC_RA);
+    cpu.set_register(V0, 0x200);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(cpu.pc(), 0x200);
+    assert_eq!(cpu.isa_mode(), IsaMode::Standard);
+    assert_eq!(cpu.register(RA), 0x105);
+
+    // The link brings us back to microMIPS code after the JALRC.
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(cpu.pc(), 0x104);
+    assert_eq!(cpu.isa_mode(), IsaMode::MicroMips);
+}
+
+#[test]
+fn multiple_loads_and_stores() {
+    let code = [SWM32_S0_S1_8_V1, LWM32_S0_S1_8_V1, LWM16_S0_S1_RA_8_SP];
+    let (mut cpu, mut memory) = setup(Endianness::Big, &code);
+    cpu.set_register(V1, 0x300);
+    cpu.set_register(S0, 0x11);
+    cpu.set_register(S1, 0x22);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(memory.read_word(0x308), Some(0x11));
+    assert_eq!(memory.read_word(0x30c), Some(0x22));
+
+    memory.write_word(0x308, 0xffffff00);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(cpu.register(S0), 0xffffffffffffff00);
+    assert_eq!(cpu.register(S1), 0x22);
+
+    cpu.set_register(SP, 0x300);
+    memory.write_word(0x310, 0x33);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(cpu.register(S0), 0xffffffffffffff00);
+    assert_eq!(cpu.register(S1), 0x22);
+    assert_eq!(cpu.register(RA), 0x33);
+}
+
+#[test]
+fn movep_reads_both_sources_first() {
+    let (mut cpu, mut memory) = setup(Endianness::Big, &[MOVEP_A0_A1_V0_V1]);
+    cpu.set_register(V0, 1);
+    cpu.set_register(V1, 2);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(cpu.register(A0), 1);
+    assert_eq!(cpu.register(A1), 2);
+}
+
+#[test]
+fn exceptions_keep_the_mode_in_epc() {
+    // Map the page at the vectors so the handler can be reached.
+    let (mut cpu, mut memory) = setup(Endianness::Big, &[BREAK16_3]);
+    memory.set_mmu(0, EBASE.wrapping_neg(), u64::MAX, AddressMode::Bits64);
+    cpu.set_pc(EBASE + 0x101);
+    assert!(cpu.step(&mut memory).is_some());
+    assert!(cpu.deliver_exception(&mut memory));
+    assert_eq!(cpu.isa_mode(), IsaMode::Standard);
+    cpu.execute_instruction(MFC0_A0_EPC, &mut memory);
+    assert_eq!(cpu.register(A0), EBASE + 0x101);
+
+    cpu.execute_instruction(ERET, &mut memory);
+    assert_eq!(cpu.pc(), EBASE + 0x100);
+    assert_eq!(cpu.isa_mode(), IsaMode::MicroMips);
+}
@@ -0,0 +1,205 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::{IsaMode, IsaRevision};
+use mips_emulator::computer::disassembler;
+use mips_emulator::computer::memory::{AddressMode, Endianness, Memory};
+
+// Where the exception vectors are after reset, in kseg0.
+const EBASE: u64 = 0xffffffff80000000;
+mod common;
+use common::{A0, RA, S0, S1, S2, S3, SP, T8, V0, V1};
+
+// MIPS16e encodings, extended ones and JAL with the first halfword on top.
+const LI_V0_5: u32 = 0x6a05;
+const LI_V0_0X1234: u32 = 0xf2226a14;
+const ADDIU_V0_MINUS_1: u32 = 0x4aff;
+const ADDU_V1_V0_A0: u32 = 0xe28d;
+const CMPI_V0_5: u32 = 0x7205;
+const BTEQZ_2: u32 = 0x6001;
+const BEQZ_V0_4: u32 = 0x2202;
+const MOVE_T8_V0: u32 = 0x651a;
+const MOVE_V1_T8: u32 = 0x6778;
+const LWPC_A0_4: u32 = 0xb401;
+const ADDIUPC_V0_8: u32 = 0x0a02;
+const JR_RA: u32 = 0xe820;
+const JRC_RA: u32 = 0xe8a0;
+const JAL_0X400: u32 = 0x18000100;
+const BREAK: u32 = 0xe805;
+const SAVE_32_RA_S0_S1: u32 = 0x64f4;
+const RESTORE_32_RA_S0_S1: u32 = 0x6474;
+const SAVE_A0_64_RA_S0_S2_S3: u32 = 0xf20464e8;
+const RESTORE_64_RA_S0_S2_S3: u32 = 0xf2046468;
+
+// Standard MIPS encodings.
+const JALX_0X100: u32 = 0x74000040;
+const MFC0_A0_EPC: u32 = 0x40047000;
+const MFC0_A0_CAUSE: u32 = 0x40046800;
+
+const CAUSE_BD: u64 = 1 << 31;
+
+// A Release 2 CPU whose addresses map straight onto a page of memory, with
+// the given instructions laid out from 0x100 and the PC there in MIPS16e
+// mode.
+fn setup(endianness: Endianness, code: &[u32]) -> (Cpu, Memory) {
+    let mut memory = common::memory();
+    memory.set_endianness(endianness);
+    common::write_halfwords(&mut memory, 0x100, code);
+    let mut cpu = cpu::new(0, IsaRevision::Release2);
+    cpu.set_pc(0x101);
+    (cpu, memory)
+}
+
+fn run(cpu: &mut Cpu, memory: &mut Memory, steps: usize) {
+    for _ in 0..steps {
+        assert_eq!(cpu.step(memory), None, "at 0x{:x}", cpu.pc());
+    }
+}
+
+#[test]
+fn disassembles_both_sizes() {
+    let cases = [
+        (ADDU_V1_V0_A0, 2, "addu $v1, $v0, $a0"),
+        (ADDIU_V0_MINUS_1, 2, "addiu $v0, $v0, -1"),
+        (LI_V0_0X1234, 4, "ori $v0, $zero, 0x1234"),
+        (JRC_RA, 2, "jic $ra, 0"),
+        (SAVE_A0_64_RA_S0_S2_S3, 4, "save $a0, 64, $s0, $s2, $s3, $ra"),
+        (RESTORE_32_RA_S0_S1, 2, "restore 32, $s0, $s1, $ra"),
+        (0xe809, 2, ".hword 0xe809"),
+    ];
+    for (instruction, size, text) in cases {
+        assert_eq!(disassembler::disassemble_mips16(instruction, size, 0x100),
+                   text);
+    }
+}
+
+#[test]
+fn runs_mixed_sizes_in_both_byte_orders() {
+    let code = [LI_V0_5, ADDIU_V0_MINUS_1, LI_V0_0X1234, ADDU_V1_V0_A0];
+    for endianness in [Endianness::Big, Endianness::Little] {
+        let (mut cpu, mut memory) = setup(endianness, &code);
+        assert_eq!(cpu.isa_mode(), IsaMode::Mips16);
+        run(&mut cpu, &mut memory, 2);
+        assert_eq!(cpu.register(V0), 4, "{:?}", endianness);
+        cpu.set_register(A0, 10);
+        run(&mut cpu, &mut memory, 2);
+        assert_eq!(cpu.register(V0), 0x1234, "{:?}", endianness);
+        assert_eq!(cpu.register(V1), 0x1234 + 10, "{:?}", endianness);
+        assert_eq!(cpu.pc(), 0x10a, "{:?}", endianness);
+    }
+}
+
+#[test]
+fn branches_have_no_delay_slot() {
+    let code = [CMPI_V0_5, BTEQZ_2, LI_V0_5, BEQZ_V0_4, MOVE_T8_V0,
+                MOVE_V1_T8];
+    let (mut cpu, mut memory) = setup(Endianness::Big, &code);
+    cpu.set_register(V0, 5);
+    run(&mut cpu, &mut memory, 2);
+    assert_eq!(cpu.register(T8), 0);
+    assert_eq!(cpu.pc(), 0x106);
+
+    // Not taken, and there's no forbidden slot either.
+    run(&mut cpu, &mut memory, 3);
+    assert_eq!(cpu.register(V1), 5);
+    assert_eq!(cpu.pc(), 0x10c);
+}
+
+#[test]
+fn jalx_and_register_jumps_switch_modes() {
+    let (mut cpu, mut memory) = setup(Endianness::Big, &[LI_V0_5, JRC_RA]);
+    memory.write_word(0, JALX_0X100);
+    cpu.set_pc(0);
+    assert_eq!(cpu.isa_mode(), IsaMode::Standard);
+    run(&mut cpu, &mut memory, 2);
+    assert_eq!(cpu.pc(), 0x100);
+    assert_eq!(cpu.isa_mode(), IsaMode::Mips16);
+    assert_eq!(cpu.register(RA), 8);
+
+    run(&mut cpu, &mut memory, 2);
+    assert_eq!(cpu.register(V0), 5);
+    assert_eq!(cpu.pc(), 8);
+    assert_eq!(cpu.isa_mode(), IsaMode::Standard);
+}
+
+#[test]
+fn jal_links_past_a_halfword_delay_slot() {
+    let (mut cpu, mut memory) = setup(Endianness::Big, &[JAL_0X400, LI_V0_5]);
+    run(&mut cpu, &mut memory, 2);
+    assert_eq!(cpu.register(V0), 5);
+    assert_eq!(cpu.register(RA), 0x107);
+    assert_eq!(cpu.pc(), 0x400);
+    assert_eq!(cpu.isa_mode(), IsaMode::Mips16);
+}
+
+#[test]
+fn pc_relative_instructions_round_down() {
+    let (mut cpu, mut memory) = setup(Endianness::Big, &[LI_V0_5, LWPC_A0_4]);
+    memory.write_word(0x104, 0xdeadbeef);
+    run(&mut cpu, &mut memory, 2);
+    assert_eq!(cpu.register(A0), 0xffffffffdeadbeef);
+
+    // In a delay slot they count from the jump.
+    let code = [LI_V0_5, JR_RA, ADDIUPC_V0_8];
+    let (mut cpu, mut memory) = setup(Endianness::Big, &code);
+    cpu.set_register(RA, 0x201);
+    run(&mut cpu, &mut memory, 3);
+    assert_eq!(cpu.register(V0), 0x108);
+    assert_eq!(cpu.pc(), 0x200);
+    assert_eq!(cpu.isa_mode(), IsaMode::Mips16);
+}
+
+#[test]
+fn save_and_restore() {
+    let code = [SAVE_32_RA_S0_S1, RESTORE_32_RA_S0_S1];
+    let (mut cpu, mut memory) = setup(Endianness::Big, &code);
+    cpu.set_register(SP, 0x800);
+    cpu.set_register(RA, 0x11);
+    cpu.set_register(S0, 0x22);
+    cpu.set_register(S1, 0x33);
+    run(&mut cpu, &mut memory, 1);
+    assert_eq!(cpu.register(SP), 0x7e0);
+    assert_eq!(memory.read_word(0x7fc), Some(0x11));
+    assert_eq!(memory.read_word(0x7f8), Some(0x33));
+    assert_eq!(memory.read_word(0x7f4), Some(0x22));
+
+    memory.write_word(0x7fc, 0x44);
+    run(&mut cpu, &mut memory, 1);
+    assert_eq!(cpu.register(SP), 0x800);
+    assert_eq!(cpu.register(RA), 0x44);
+    assert_eq!(cpu.register(S0), 0x22);
+}
+
+#[test]
+fn extended_save_stores_arguments_in_the_callers_frame() {
+    let code = [SAVE_A0_64_RA_S0_S2_S3, RESTORE_64_RA_S0_S2_S3];
+    let (mut cpu, mut memory) = setup(Endianness::Big, &code);
+    cpu.set_register(SP, 0x800);
+    for (register, value) in [(A0, 1), (RA, 2), (S3, 3), (S2, 4), (S0, 5)] {
+        cpu.set_register(register, value);
+    }
+    run(&mut cpu, &mut memory, 1);
+    assert_eq!(cpu.register(SP), 0x7c0);
+    for (address, value) in [(0x800, 1), (0x7fc, 2), (0x7f8, 3), (0x7f4, 4),
+                             (0x7f0, 5)] {
+        assert_eq!(memory.read_word(address), Some(value), "0x{:x}", address);
+    }
+
+    memory.write_word(0x7f4, 6);
+    run(&mut cpu, &mut memory, 1);
+    assert_eq!(cpu.register(SP), 0x800);
+    assert_eq!(cpu.register(S2), 6);
+}
+
+#[test]
+fn exceptions_in_delay_slots_restart_at_the_jump() {
+    let (mut cpu, mut memory) = setup(Endianness::Big, &[JR_RA, BREAK]);
+    memory.set_mmu(0, EBASE.wrapping_neg(), u64::MAX, AddressMode::Bits64);
+    cpu.set_pc(EBASE + 0x101);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert!(cpu.step(&mut memory).is_some());
+    assert!(cpu.deliver_exception(&mut memory));
+    assert_eq!(cpu.isa_mode(), IsaMode::Standard);
+    cpu.execute_instruction(MFC0_A0_EPC, &mut memory);
+    assert_eq!(cpu.register(A0), EBASE + 0x101);
+    cpu.execute_instruction(MFC0_A0_CAUSE, &mut memory);
+    assert_ne!(cpu.register(A0) & CAUSE_BD, 0);
+}
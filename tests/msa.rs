@@ -0,0 +1,175 @@
+use mips_emulator::computer::assembler;
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::disassembler;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::Memory;
+
+mod common;
+use common::{assemble, A0};
+
+// Encodings checked against the LLVM assembler.
+const ENCODINGS: &[(&str, u32)] = &[
+    ("addv.w $w1, $w2, $w3", 0x7843104e),
+    ("ld.d $w4, 16($a0)", 0x78022123),
+    ("st.b $w1, -512($a0)", 0x7a002064),
+    ("ldi.h $w1, -5", 0x7b3fd847),
+    ("sat_s.w $w1, $w2, 7", 0x7847104a),
+    ("srlri.h $w1, $w2, 15", 0x79ef104a),
+    ("fill.w $w1, $a0", 0x7b02205e),
+    ("fadd.d $w1, $w2, $w3", 0x7823105b),
+    ("fcor.w $w1, $w2, $w3", 0x7843105c),
+    ("fsaf.w $w1, $w2, $w3", 0x7a03105a),
+    ("fexdo.h $w1, $w2, $w3", 0x7a03105b),
+    ("ffint_u.d $w1, $w2", 0x7b3f105e),
+    ("maddr_q.w $w1, $w2, $w3", 0x7b63105c),
+    ("copy_s.w $a0, $w2[1]", 0x78b11119),
+    ("splati.d $w1, $w2[1]", 0x78791059),
+    ("insve.h $w1[3], $w2[0]", 0x79631059),
+    ("sld.h $w1, $w2[$a0]", 0x78241054),
+    ("bseli.b $w1, $w2, 3", 0x7a031041),
+    ("shf.w $w1, $w2, 27", 0x7a1b1042),
+    ("bsel.v $w1, $w2, $w3", 0x78c3105e),
+    ("move.v $w1, $w2", 0x78be1059),
+    ("ctcmsa $1, $a0", 0x783e2059),
+    ("bnz.d $w1, 0x8", 0x47e10002),
+    ("bz.v $w1, 0x8", 0x45610002),
+];
+
+// A CPU with MSA enabled whose addresses map straight onto a page of
+// memory.
+fn setup() -> (Cpu, Memory) {
+    let memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_msa_enabled(true);
+    (cpu, memory)
+}
+
+// Runs one instruction on $w2 and $w3 and returns $w1.
+fn run(source: &str, w2: u128, w3: u128) -> u128 {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_vector_register(2, w2);
+    cpu.set_vector_register(3, w3);
+    cpu.execute_instruction(assemble(source), &mut memory);
+    assert_eq!(cpu.exception(), None, "{}", source);
+    cpu.vector_register(1)
+}
+
+#[test]
+fn encodings_round_trip() {
+    for &(source, word) in ENCODINGS {
+        assert_eq!(assemble(source), word, "{}", source);
+        assert_eq!(disassembler::disassemble(word, 0, IsaRevision::Release6),
+                   source);
+    }
+}
+
+#[test]
+fn formats_an_operation_does_not_take_are_rejected() {
+    for source in ["dotp_s.b $w1, $w2, $w3", "copy_u.d $a0, $w2[0]",
+                   "fadd.h $w1, $w2, $w3", "andi.h $w1, $w2, 1",
+                   "splati.w $w1, $w2[4]", "ld.d $w1, 4($a0)"] {
+        assert!(assembler::assemble(source).is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn msa_instructions_need_msa_enabled() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_msa_enabled(false);
+    cpu.execute_instruction(assemble("addv.w $w1, $w2, $w3"), &mut memory);
+    assert_eq!(cpu.exception(), Some(Exception::MsaDisabled { pc: 0 }));
+}
+
+#[test]
+fn integer_operations_work_lane_by_lane() {
+    let cases: &[(&str, u128, u128, u128)] = &[
+        // source, $w2, $w3, expected $w1
+        ("addv.w $w1, $w2, $w3", 0x00000004_00000003_00000002_ffffffff,
+         0x00000040_00000030_00000020_00000002,
+         0x00000044_00000033_00000022_00000001),
+        ("adds_u.b $w1, $w2, $w3", 0xf0, 0x20, 0xff),
+        ("subs_s.h $w1, $w2, $w3", 0x8000, 1, 0x8000),
+        ("div_s.w $w1, $w2, $w3", 0xfffffff9,
+         0x00000002_00000002_00000002_00000002, 0xfffffffd),
+        ("ceq.d $w1, $w2, $w3", 5 << 64 | 6, 5 << 64 | 7,
+         u128::from(u64::MAX) << 64),
+        ("slli.h $w1, $w2, 4", 0x1234_0001, 0, 0x2340_0010),
+        ("pcnt.b $w1, $w2", 0xff_0f, 0, 0x08_04),
+        ("and.v $w1, $w2, $w3", 0xf0f0, 0xff00, 0xf000),
+        ("hadd_s.d $w1, $w2, $w3", 3 << 96 | 5 << 32, 1 << 64 | 10,
+         4 << 64 | 15),
+    ];
+    for &(source, w2, w3, expected) in cases {
+        assert_eq!(run(source, w2, w3), expected, "{}", source);
+    }
+}
+
+#[test]
+fn floating_point_operations_work_lane_by_lane() {
+    let lanes = |high: f64, low: f64| {
+        u128::from(high.to_bits()) << 64 | u128::from(low.to_bits())
+    };
+    let (w2, w3) = (lanes(2.5, 1.0), lanes(0.5, f64::NAN));
+    let sum = run("fadd.d $w1, $w2, $w3", w2, w3);
+    assert_eq!(f64::from_bits((sum >> 64) as u64), 3.0);
+    assert!(f64::from_bits(sum as u64).is_nan());
+    // FCOR is true for the ordered lane and false for the one with a NaN.
+    assert_eq!(run("fcor.d $w1, $w2, $w3", w2, w3),
+               u128::from(u64::MAX) << 64);
+}
+
+#[test]
+fn elements_move_between_vectors_and_registers() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_vector_register(2, 0x44444444_83333333_22222222_11111111);
+    cpu.set_register(A0, 0xabcd);
+    for source in ["copy_s.w $a0, $w2[2]", "insert.w $w1[1], $a0",
+                   "splati.w $w3, $w2[0]"] {
+        cpu.execute_instruction(assemble(source), &mut memory);
+        assert_eq!(cpu.exception(), None, "{}", source);
+    }
+    assert_eq!(cpu.register(A0), 0xffffffff83333333);
+    assert_eq!(cpu.vector_register(1), 0x83333333_00000000);
+    assert_eq!(cpu.vector_register(3), 0x11111111_11111111_11111111_11111111);
+}
+
+#[test]
+fn vectors_load_and_store_by_element() {
+    let (mut cpu, mut memory) = setup();
+    for i in 0..16 {
+        memory.write_byte(0x100 + i, i as u8);
+    }
+    cpu.set_register(A0, 0x100);
+    cpu.execute_instruction(assemble("ld.h $w1, 0($a0)"), &mut memory);
+    assert_eq!(cpu.exception(), None);
+    // Each element is in the memory's byte order.
+    assert_eq!(cpu.vector_register(1),
+               0x0e0f_0c0d_0a0b_0809_0607_0405_0203_0001);
+
+    cpu.execute_instruction(assemble("st.h $w1, 32($a0)"), &mut memory);
+    assert_eq!(cpu.exception(), None);
+    for i in 0..16 {
+        assert_eq!(memory.read_byte(0x120 + i), Some(i as u8));
+    }
+}
+
+#[test]
+fn branches_test_the_whole_vector_or_each_element() {
+    let cases = [
+        ("bnz.v $w1, 0x8", 0x100, true),
+        ("bz.v $w1, 0x8", 0x100, false),
+        ("bnz.b $w1, 0x8", 0x100, false),
+        ("bz.b $w1, 0x8", 0x100, true),
+        ("bnz.w $w1, 0x8", 0x1_00000001_00000001_00000001, true),
+    ];
+    for (source, w1, taken) in cases {
+        let (mut cpu, mut memory) = setup();
+        cpu.set_vector_register(1, w1);
+        memory.write_word(0, assemble(source));
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+        assert_eq!(cpu.exception(), None, "{}", source);
+        assert_eq!(cpu.pc(), if taken { 12 } else { 8 }, "{}", source);
+    }
+}
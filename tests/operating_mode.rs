@@ -0,0 +1,173 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::{self, AddressMode, Memory};
+
+mod common;
+use common::{A0, A1, A2, MEMORY};
+
+const DADDU_A0_A1_A2: u32 = 0x00a6202d;
+const ADDU_A0_A1_A2: u32 = 0x00a62021;
+const LW_A0_0_A1: u32 = 0x8ca40000;
+const MFC0_A0_STATUS: u32 = 0x40046000;
+const DI_A0: u32 = 0x41646000;
+const TLBP: u32 = 0x42000008;
+const ERET: u32 = 0x42000018;
+const WAIT: u32 = 0x42000020;
+const MTC0_A1_COUNT: u32 = 0x40854800;
+const MFC0_A0_COUNT: u32 = 0x40044800;
+const RDHWR_A0_CC: u32 = 0x7c04103b;
+
+// Status fields
+const KSU_SUPERVISOR: u64 = 0x08;
+const KSU_USER: u64 = 0x10;
+const UX: u64 = 0x20;
+const SX: u64 = 0x40;
+const KX: u64 = 0x80;
+const PX: u64 = 0x800000;
+const EXL: u64 = 0x2;
+const CU0: u64 = 0x10000000;
+
+// Just past the 32-bit address space, mapped onto the start of memory.
+const HIGH: u64 = 0x100000000;
+
+fn setup(status: u64) -> (Cpu, Memory) {
+    let mut memory = memory::new(MEMORY, 1);
+    memory.set_mmu(0, HIGH.wrapping_neg(), HIGH + MEMORY - 1,
+                   AddressMode::Bits64);
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_status(status);
+    (cpu, memory)
+}
+
+#[test]
+fn doubleword_operations_depend_on_the_mode() {
+    let cases = [
+        (0, true),
+        (KSU_SUPERVISOR, false),
+        (KSU_SUPERVISOR | SX, true),
+        (KSU_USER, false),
+        (KSU_USER | UX, true),
+        (KSU_USER | PX, true),
+        (KSU_USER | KX | SX, false),
+        // The exception level is kernel mode whatever KSU says.
+        (KSU_USER | EXL, true),
+    ];
+    for (status, allowed) in cases {
+        let (mut cpu, mut memory) = setup(status);
+        cpu.set_register(A1, 1 << 32);
+        cpu.set_register(A2, 1);
+        cpu.execute_instruction(DADDU_A0_A1_A2, &mut memory);
+        if allowed {
+            assert_eq!(cpu.exception(), None, "{:#x}", status);
+            assert_eq!(cpu.register(A0), (1 << 32) + 1, "{:#x}", status);
+        } else {
+            assert_eq!(cpu.exception(),
+                       Some(Exception::ReservedInstruction { pc: 0 }),
+                       "{:#x}", status);
+        }
+
+        // Word operations work in every mode.
+        let (mut cpu, mut memory) = setup(status);
+        cpu.execute_instruction(ADDU_A0_A1_A2, &mut memory);
+        assert_eq!(cpu.exception(), None, "{:#x}", status);
+    }
+}
+
+#[test]
+fn addresses_are_limited_to_32_bits_without_64_bit_addressing() {
+    let cases = [
+        (0, false),
+        (KX, true),
+        (KSU_SUPERVISOR | KX, false),
+        (KSU_SUPERVISOR | SX, true),
+        // PX allows doubleword operations but not the addresses.
+        (KSU_USER | PX, false),
+        (KSU_USER | UX, true),
+    ];
+    for (status, allowed) in cases {
+        let (mut cpu, mut memory) = setup(status);
+        memory.write_word(0, 0x12345678);
+        cpu.set_register(A1, HIGH);
+        cpu.execute_instruction(LW_A0_0_A1, &mut memory);
+        if allowed {
+            assert_eq!(cpu.exception(), None, "{:#x}", status);
+            assert_eq!(cpu.register(A0), 0x12345678, "{:#x}", status);
+        } else {
+            assert_eq!(cpu.exception(),
+                       Some(Exception::AddressErrorLoad {
+                           pc: 0,
+                           address: HIGH,
+                       }),
+                       "{:#x}", status);
+        }
+    }
+}
+
+#[test]
+fn fetching_from_a_64_bit_address_needs_64_bit_addressing() {
+    let (mut cpu, mut memory) = setup(KSU_USER | PX);
+    memory.write_word(0, ADDU_A0_A1_A2);
+    cpu.set_pc(HIGH);
+    assert_eq!(cpu.step(&mut memory),
+               Some(Exception::AddressErrorLoad { pc: HIGH, address: HIGH }));
+
+    let (mut cpu, mut memory) = setup(KSU_USER | UX);
+    memory.write_word(0, ADDU_A0_A1_A2);
+    cpu.set_pc(HIGH);
+    assert_eq!(cpu.step(&mut memory), None);
+    assert_eq!(cpu.pc(), HIGH + 4);
+}
+
+#[test]
+fn cp0_instructions_need_kernel_mode_or_cu0() {
+    let cases = [
+        (0, true),
+        (KSU_SUPERVISOR, false),
+        (KSU_USER, false),
+        (KSU_USER | EXL, true),
+        (KSU_SUPERVISOR | CU0, true),
+        (KSU_USER | CU0, true),
+    ];
+    for (status, allowed) in cases {
+        let (mut cpu, mut memory) = setup(status);
+        cpu.execute_instruction(MFC0_A0_STATUS, &mut memory);
+        if allowed {
+            assert_eq!(cpu.exception(), None, "{:#x}", status);
+            assert_eq!(cpu.register(A0), status, "{:#x}", status);
+        } else {
+            assert_eq!(cpu.exception(),
+                       Some(Exception::CoprocessorUnusable { pc: 0, unit: 0 }),
+                       "{:#x}", status);
+            assert_eq!(cpu.register(A0), 0, "{:#x}", status);
+        }
+    }
+
+    // The rest of the privileged instructions are stopped the same way,
+    // before they change anything.
+    for instruction in [DI_A0, TLBP, ERET, WAIT] {
+        let (mut cpu, mut memory) = setup(KSU_USER | UX);
+        cpu.execute_instruction(instruction, &mut memory);
+        assert_eq!(cpu.exception(),
+                   Some(Exception::CoprocessorUnusable { pc: 0, unit: 0 }),
+                   "0x{:08x}", instruction);
+        assert_eq!(cpu.status(), KSU_USER | UX, "0x{:08x}", instruction);
+    }
+}
+
+#[test]
+fn count_goes_up_with_every_instruction() {
+    let (mut cpu, mut memory) = setup(0);
+    cpu.set_register(A1, 0xfffffffe);
+    cpu.execute_instruction(MTC0_A1_COUNT, &mut memory);
+    cpu.execute_instruction(MFC0_A0_COUNT, &mut memory);
+    assert_eq!(cpu.register(A0), 0xffffffffffffffff);
+
+    // It wraps round at 32 bits, and RDHWR sees it too.
+    cpu.execute_instruction(RDHWR_A0_CC, &mut memory);
+    assert_eq!(cpu.exception(), None);
+    assert_eq!(cpu.register(A0), 0);
+    cpu.execute_instruction(ADDU_A0_A1_A2, &mut memory);
+    cpu.execute_instruction(RDHWR_A0_CC, &mut memory);
+    assert_eq!(cpu.register(A0), 2);
+}
@@ -0,0 +1,82 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::memory::Memory;
+
+mod common;
+use common::{execute, A0};
+
+// All with $a0 as the destination. The immediates are at either end of
+// their range, or show which way they're scaled.
+const ADDIUPC_MINUS_4: u32 = 0xec87ffff;
+const ADDIUPC_MIN: u32 = 0xec840000;
+const ADDIUPC_MAX: u32 = 0xec83ffff;
+const AUIPC_MINUS_1: u32 = 0xec9effff;
+const AUIPC_0X1234: u32 = 0xec9e1234;
+const ALUIPC_MIN: u32 = 0xec9f8000;
+const ALUIPC_1: u32 = 0xec9f0001;
+const LWPC_MINUS_4: u32 = 0xec8fffff;
+const LWUPC_8: u32 = 0xec900002;
+const LDPC_8: u32 = 0xec980001;
+const LDPC_MINUS_8: u32 = 0xec9bffff;
+const LDPC_MIN: u32 = 0xec9a0000;
+
+// Runs `instruction` at `pc` and returns $a0.
+fn run(cpu: &mut Cpu, memory: &mut Memory, pc: u64, instruction: u32) -> u64 {
+    cpu.set_pc(pc);
+    execute(cpu, memory, instruction);
+    cpu.register(A0)
+}
+
+#[test]
+fn addresses_are_worked_out_from_the_pc() {
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    let cases = [
+        // ADDIUPC's 19-bit immediate counts words, and is signed.
+        (ADDIUPC_MINUS_4, 0x7fc),
+        (ADDIUPC_MIN, 0x800u64.wrapping_sub(1 << 20)),
+        (ADDIUPC_MAX, 0x800 + (1 << 20) - 4),
+        // AUIPC's goes in the upper halfword, and is signed too.
+        (AUIPC_MINUS_1, 0x800u64.wrapping_sub(1 << 16)),
+        (AUIPC_0X1234, 0x12340800),
+        // ALUIPC clears the lower halfword afterwards.
+        (ALUIPC_MIN, 0xffffffff80000000),
+        (ALUIPC_1, 0x10000),
+    ];
+    for (instruction, expected) in cases {
+        assert_eq!(run(&mut cpu, &mut memory, 0x800, instruction), expected,
+                   "0x{:08x}", instruction);
+    }
+}
+
+#[test]
+fn loads_are_relative_to_the_pc() {
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    memory.write_word(0x7fc, 0x80000001);
+    memory.write_word(0x808, 0x80000002);
+    assert_eq!(run(&mut cpu, &mut memory, 0x800, LWPC_MINUS_4),
+               0xffffffff80000001);
+    assert_eq!(run(&mut cpu, &mut memory, 0x800, LWUPC_8), 0x80000002);
+}
+
+#[test]
+fn ldpc_counts_from_the_doubleword_holding_the_pc() {
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    memory.write(0x7f8, 0x1122334455667788, 8);
+    memory.write(0x808, 0x99aabbccddeeff00, 8);
+    for pc in [0x800, 0x804] {
+        assert_eq!(run(&mut cpu, &mut memory, pc, LDPC_8),
+                   0x99aabbccddeeff00, "{:#x}", pc);
+        assert_eq!(run(&mut cpu, &mut memory, pc, LDPC_MINUS_8),
+                   0x1122334455667788, "{:#x}", pc);
+    }
+
+    // The 18-bit immediate counts doublewords and is signed, so the lowest
+    // one reaches 1MB back, which isn't mapped.
+    cpu.set_pc(0x804);
+    cpu.execute_instruction(LDPC_MIN, &mut memory);
+    assert_eq!(cpu.exception().and_then(|e| e.bad_address()),
+               Some(0x800u64.wrapping_sub(1 << 20)));
+}
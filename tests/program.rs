@@ -0,0 +1,216 @@
+use mips_emulator::computer;
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::memory::Endianness;
+use mips_emulator::computer::program::{self, Class, ProgramError};
+
+const PT_LOAD: u32 = 1;
+const PT_PHDR: u32 = 6;
+const PF_X: u32 = 1;
+const PF_R: u32 = 4;
+
+// Where the segment data starts in the files built here.
+const DATA: u64 = 0x100;
+const CODE: &[u8] = &[0x24, 0x02, 0x00, 0x07, 0x00, 0x00, 0x00, 0x0c];
+
+struct Header {
+    kind: u32,
+    flags: u32,
+    offset: u64,
+    address: u64,
+    file_size: u64,
+    memory_size: u64,
+}
+
+fn load_header(address: u64, memory_size: u64) -> Header {
+    Header {
+        kind: PT_LOAD,
+        flags: PF_R | PF_X,
+        offset: DATA,
+        address,
+        file_size: CODE.len() as u64,
+        memory_size,
+    }
+}
+
+// Writes `value` as a `size`-byte field at `offset` in the file's byte
+// order.
+fn put(file: &mut [u8], endianness: Endianness, offset: u64, size: usize,
+       value: u64) {
+    let bytes = match endianness {
+        Endianness::Big => value.to_be_bytes()[8 - size..].to_vec(),
+        Endianness::Little => value.to_le_bytes()[..size].to_vec(),
+    };
+    let offset = offset as usize;
+    file[offset..offset + size].copy_from_slice(&bytes);
+}
+
+// An executable MIPS ELF file with the given program headers straight
+// after the ELF header, and CODE at DATA.
+fn elf(class: Class, endianness: Endianness, entry: u64,
+       headers: &[Header]) -> Vec<u8> {
+    let mut file = vec![0; DATA as usize];
+    file.extend(CODE);
+    file[0..4].copy_from_slice(b"\x7fELF");
+    file[4] = match class {
+        Class::Elf32 => 1,
+        Class::Elf64 => 2,
+    };
+    file[5] = match endianness {
+        Endianness::Little => 1,
+        Endianness::Big => 2,
+    };
+    file[6] = 1;
+    let put = |file: &mut Vec<u8>, offset, size, value| {
+        put(file, endianness, offset, size, value)
+    };
+    put(&mut file, 16, 2, 2);
+    put(&mut file, 18, 2, 8);
+    put(&mut file, 20, 4, 1);
+    match class {
+        Class::Elf32 => {
+            put(&mut file, 24, 4, entry);
+            put(&mut file, 28, 4, 52);
+            put(&mut file, 40, 2, 52);
+            put(&mut file, 42, 2, 32);
+            put(&mut file, 44, 2, headers.len() as u64);
+            for (i, header) in headers.iter().enumerate() {
+                let at = 52 + 32 * i as u64;
+                put(&mut file, at, 4, header.kind as u64);
+                put(&mut file, at + 4, 4, header.offset);
+                put(&mut file, at + 8, 4, header.address);
+                put(&mut file, at + 16, 4, header.file_size);
+                put(&mut file, at + 20, 4, header.memory_size);
+                put(&mut file, at + 24, 4, header.flags as u64);
+            }
+        },
+        Class::Elf64 => {
+            put(&mut file, 24, 8, entry);
+            put(&mut file, 32, 8, 64);
+            put(&mut file, 52, 2, 64);
+            put(&mut file, 54, 2, 56);
+            put(&mut file, 56, 2, headers.len() as u64);
+            for (i, header) in headers.iter().enumerate() {
+                let at = 64 + 56 * i as u64;
+                put(&mut file, at, 4, header.kind as u64);
+                put(&mut file, at + 4, 4, header.flags as u64);
+                put(&mut file, at + 8, 8, header.offset);
+                put(&mut file, at + 16, 8, header.address);
+                put(&mut file, at + 32, 8, header.file_size);
+                put(&mut file, at + 40, 8, header.memory_size);
+            }
+        },
+    }
+    file
+}
+
+#[test]
+fn elf64_segments_are_loaded_with_their_bss() {
+    let file = elf(Class::Elf64, Endianness::Big, 0x120000000,
+                   &[load_header(0x120000000, 0x20)]);
+    let program = program::parse(&file).unwrap();
+    assert!(matches!(program.class, Class::Elf64));
+    assert_eq!(program.endianness, Endianness::Big);
+    assert_eq!(program.entry, 0x120000000);
+    assert_eq!(program.segments.len(), 1);
+    let segment = &program.segments[0];
+    assert_eq!((segment.address, segment.size), (0x120000000, 0x20));
+    assert_eq!(segment.data, CODE);
+    assert!(segment.executable);
+    assert_eq!(program.headers, None);
+
+    // The image goes at the bottom of memory, with the BSS zeroed.
+    let mut com = computer::new(1, 4096, IsaRevision::Release6);
+    com.memory().write_word(8, 0xffffffff);
+    com.load(&program).unwrap();
+    assert_eq!(com.memory().read_word(0), Some(0x24020007));
+    assert_eq!(com.memory().read_word(8), Some(0));
+}
+
+#[test]
+fn elf32_addresses_are_sign_extended() {
+    let file = elf(Class::Elf32, Endianness::Little, 0x80001000,
+                   &[load_header(0x80001000, 8)]);
+    let program = program::parse(&file).unwrap();
+    assert!(matches!(program.class, Class::Elf32));
+    assert_eq!(program.endianness, Endianness::Little);
+    assert_eq!(program.entry, 0xffffffff80001000);
+    assert_eq!(program.segments[0].address, 0xffffffff80001000);
+    assert_eq!(program.header_size, 32);
+}
+
+#[test]
+fn pt_phdr_says_where_the_headers_are() {
+    let phdr = Header {
+        kind: PT_PHDR,
+        flags: PF_R,
+        offset: 64,
+        address: 0x120000040,
+        file_size: 112,
+        memory_size: 112,
+    };
+    let file = elf(Class::Elf64, Endianness::Big, 0x120000000,
+                   &[phdr, load_header(0x120000100, 8)]);
+    let program = program::parse(&file).unwrap();
+    assert_eq!(program.headers, Some(0x120000040));
+    assert_eq!((program.header_size, program.header_count), (56, 2));
+    assert_eq!(program.segments.len(), 1);
+}
+
+#[test]
+fn headers_inside_a_loaded_segment_are_found() {
+    let mut load = load_header(0x120000000, 0x108);
+    load.offset = 0;
+    load.file_size = 0x108;
+    let file = elf(Class::Elf64, Endianness::Big, 0x120000000, &[load]);
+    let program = program::parse(&file).unwrap();
+    assert_eq!(program.headers, Some(0x120000040));
+
+    // Even if working out their address wraps round.
+    let mut load = load_header(u64::MAX - 0x10, 0x108);
+    load.offset = 0;
+    load.file_size = 0x108;
+    let file = elf(Class::Elf64, Endianness::Big, 0x120000000, &[load]);
+    let program = program::parse(&file).unwrap();
+    assert_eq!(program.headers, Some(0x2f));
+}
+
+#[test]
+fn malformed_files_are_rejected() {
+    let file = elf(Class::Elf64, Endianness::Big, 0x120000000,
+                   &[load_header(0x120000000, 8)]);
+    assert!(matches!(program::parse(&file[..40]),
+                     Err(ProgramError::Truncated)));
+    assert!(matches!(program::parse(&file[..DATA as usize + 4]),
+                     Err(ProgramError::Truncated)));
+    assert!(matches!(program::parse(b"#!/bin/sh\n"), Err(ProgramError::NotElf)));
+
+    let mut other = file.clone();
+    other[19] = 62;
+    assert!(matches!(program::parse(&other), Err(ProgramError::NotMips(62))));
+    let mut other = file.clone();
+    other[4] = 3;
+    assert!(matches!(program::parse(&other),
+                     Err(ProgramError::UnsupportedClass(3))));
+
+    let mut load = load_header(0x120000000, 8);
+    load.memory_size = 0;
+    let file = elf(Class::Elf64, Endianness::Big, 0x120000000, &[load]);
+    assert!(matches!(program::parse(&file),
+                     Err(ProgramError::NoLoadableSegments)));
+}
+
+#[test]
+fn nothing_loads_into_no_memory() {
+    let file = elf(Class::Elf64, Endianness::Big, 0x120000000,
+                   &[load_header(0x120000000, 8)]);
+    let mut program = program::parse(&file).unwrap();
+    let mut com = computer::new(1, 0, IsaRevision::Release6);
+    assert!(matches!(com.load(&program),
+                     Err(ProgramError::TooLarge { needed: 8, available: 0 })));
+
+    // Not even an empty one.
+    program.segments[0].data.clear();
+    program.segments[0].size = 0;
+    assert!(matches!(com.load(&program),
+                     Err(ProgramError::TooLarge { needed: 1, available: 0 })));
+}
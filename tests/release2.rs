@@ -0,0 +1,176 @@
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::disassembler;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::Memory;
+
+mod common;
+use common::{execute, A0, A1, RA, V0, V1};
+
+// Release 2 encodings the assembler, which only speaks Release 6, can't
+// produce.
+const MULT_A0_A1: u32 = 0x00850018;
+const MULTU_A0_A1: u32 = 0x00850019;
+const DIV_A0_A1: u32 = 0x0085001a;
+const DIVU_A0_A1: u32 = 0x0085001b;
+const MFHI_V0: u32 = 0x00001010;
+const MFLO_V1: u32 = 0x00001812;
+const MTHI_A0: u32 = 0x00800011;
+const MTLO_A1: u32 = 0x00a00013;
+const BEQL_A0_A1_16: u32 = 0x50850004;
+const BNEL_A0_A1_16: u32 = 0x54850004;
+const BLEZL_A0_16: u32 = 0x58800004;
+const BLEZ_A0_16: u32 = 0x18800004;
+const BGTZ_A0_16: u32 = 0x1c800004;
+const LWL_A0_1_A1: u32 = 0x88a40001;
+const LWR_A0_1_A1: u32 = 0x98a40001;
+const SWL_A0_1_A1: u32 = 0xa8a40001;
+const SWR_A0_1_A1: u32 = 0xb8a40001;
+const ADDIU_V0_V0_1: u32 = 0x24420001;
+
+// The same words are compact branches on Release 6: POP06 with rs = 0 and
+// POP07 with rs = 0.
+const POP06_A0_16: u32 = 0x18040004;
+const POP07_A0_16: u32 = 0x1c040004;
+
+fn setup() -> (Cpu, Memory) {
+    (cpu::new(0, IsaRevision::Release2), common::memory())
+}
+
+// Lays out `code` from address 0.
+fn write_code(memory: &mut Memory, code: &[u32]) {
+    for (i, &instruction) in code.iter().enumerate() {
+        memory.write_word(4 * i as u64, instruction);
+    }
+}
+
+fn hi_lo(cpu: &mut Cpu, memory: &mut Memory, instruction: u32, rs: u64,
+         rt: u64) -> (u64, u64) {
+    cpu.set_register(A0, rs);
+    cpu.set_register(A1, rt);
+    execute(cpu, memory, instruction);
+    execute(cpu, memory, MFHI_V0);
+    execute(cpu, memory, MFLO_V1);
+    (cpu.register(V0), cpu.register(V1))
+}
+
+#[test]
+fn multiply_and_divide_go_through_hi_and_lo() {
+    let (mut cpu, mut memory) = setup();
+    let minus = |value: i64| value as u64;
+    // Each half comes back as a sign-extended word.
+    assert_eq!(hi_lo(&mut cpu, &mut memory, MULT_A0_A1, minus(-6), 7),
+               (minus(-1), minus(-42)));
+    assert_eq!(hi_lo(&mut cpu, &mut memory, MULTU_A0_A1, 0xffffffff, 2),
+               (1, minus(-2)));
+    assert_eq!(hi_lo(&mut cpu, &mut memory, MULT_A0_A1, 0x40000000, 4),
+               (1, 0));
+    // Quotients round towards zero and the remainder takes the dividend's
+    // sign.
+    assert_eq!(hi_lo(&mut cpu, &mut memory, DIV_A0_A1, minus(-43), 5),
+               (minus(-3), minus(-8)));
+    assert_eq!(hi_lo(&mut cpu, &mut memory, DIVU_A0_A1, 0xffffffff, 0x10),
+               (0xf, 0x0fffffff));
+
+    cpu.set_register(A0, 0x1234);
+    cpu.set_register(A1, 0x5678);
+    execute(&mut cpu, &mut memory, MTHI_A0);
+    execute(&mut cpu, &mut memory, MTLO_A1);
+    execute(&mut cpu, &mut memory, MFHI_V0);
+    execute(&mut cpu, &mut memory, MFLO_V1);
+    assert_eq!((cpu.register(V0), cpu.register(V1)), (0x1234, 0x5678));
+    assert_eq!(cpu.accumulator(0), 0x0000123400005678);
+}
+
+#[test]
+fn branch_likely_only_runs_its_delay_slot_when_taken() {
+    for (branch, a0, a1, taken) in [(BEQL_A0_A1_16, 1, 1, true),
+                                    (BEQL_A0_A1_16, 1, 2, false),
+                                    (BNEL_A0_A1_16, 1, 2, true),
+                                    (BNEL_A0_A1_16, 1, 1, false),
+                                    (BLEZL_A0_16, 0, 0, true),
+                                    (BLEZL_A0_16, 1, 0, false)] {
+        let (mut cpu, mut memory) = setup();
+        write_code(&mut memory, &[branch, ADDIU_V0_V0_1]);
+        cpu.set_register(A0, a0);
+        cpu.set_register(A1, a1);
+        assert_eq!(cpu.step(&mut memory), None, "0x{:08x}", branch);
+        if taken {
+            assert_eq!(cpu.step(&mut memory), None, "0x{:08x}", branch);
+            assert_eq!(cpu.register(V0), 1, "0x{:08x}", branch);
+            assert_eq!(cpu.pc(), 0x14, "0x{:08x}", branch);
+        } else {
+            assert_eq!(cpu.register(V0), 0, "0x{:08x}", branch);
+            assert_eq!(cpu.pc(), 8, "0x{:08x}", branch);
+        }
+    }
+}
+
+#[test]
+fn blez_and_bgtz_decode_by_revision() {
+    // With rt = 0 they're the same branch on both revisions.
+    for revision in [IsaRevision::Release2, IsaRevision::Release6] {
+        assert_eq!(disassembler::disassemble(BLEZ_A0_16, 0, revision),
+                   "blez $a0, 0x10");
+        assert_eq!(disassembler::disassemble(BGTZ_A0_16, 0, revision),
+                   "bgtz $a0, 0x10");
+    }
+    // A nonzero rt is reserved on Release 2 and makes them POP06 and POP07.
+    let cases = [(POP06_A0_16, "blezalc $a0, 0x10", 0),
+                 (POP07_A0_16, "bgtzalc $a0, 0x10", 1)];
+    for (word, text, a0) in cases {
+        assert_eq!(disassembler::disassemble(word, 0, IsaRevision::Release2),
+                   format!(".word 0x{:08x}", word));
+        assert_eq!(disassembler::disassemble(word, 0, IsaRevision::Release6),
+                   text);
+
+        let (mut cpu, mut memory) = setup();
+        write_code(&mut memory, &[word]);
+        assert_eq!(cpu.step(&mut memory),
+                   Some(Exception::ReservedInstruction { pc: 0 }), "{}", text);
+
+        let mut cpu = cpu::new(0, IsaRevision::Release6);
+        cpu.set_register(A0, a0);
+        assert_eq!(cpu.step(&mut memory), None, "{}", text);
+        assert_eq!(cpu.register(RA), 4, "{}", text);
+        assert_eq!(cpu.pc(), 0x14, "{}", text);
+    }
+}
+
+#[test]
+fn partial_word_accesses_merge_bytes() {
+    let (mut cpu, mut memory) = setup();
+    let bytes = [0x11, 0x22, 0x33, 0x44];
+    cpu.set_register(A1, 0x100);
+
+    // Big-endian, so LWL fills from the top and LWR from the bottom.
+    memory.write_bytes(0x100, &bytes);
+    cpu.set_register(A0, 0xaabbccdd);
+    execute(&mut cpu, &mut memory, LWL_A0_1_A1);
+    assert_eq!(cpu.register(A0), 0x223344dd);
+    cpu.set_register(A0, 0xaabbccdd);
+    execute(&mut cpu, &mut memory, LWR_A0_1_A1);
+    assert_eq!(cpu.register(A0) as u32, 0xaabb1122);
+
+    cpu.set_register(A0, 0xaabbccdd);
+    execute(&mut cpu, &mut memory, SWL_A0_1_A1);
+    assert_eq!(memory.read_word(0x100), Some(0x11aabbcc));
+    memory.write_bytes(0x100, &bytes);
+    execute(&mut cpu, &mut memory, SWR_A0_1_A1);
+    assert_eq!(memory.read_word(0x100), Some(0xccdd3344));
+}
+
+#[test]
+fn release2_encodings_are_reserved_on_release6() {
+    let mut memory = common::memory();
+    for word in [MULT_A0_A1, MULTU_A0_A1, DIV_A0_A1, DIVU_A0_A1, MFHI_V0,
+                 MFLO_V1, MTHI_A0, MTLO_A1, BEQL_A0_A1_16, BNEL_A0_A1_16,
+                 BLEZL_A0_16, LWL_A0_1_A1, LWR_A0_1_A1, SWL_A0_1_A1,
+                 SWR_A0_1_A1] {
+        let mut cpu = cpu::new(0, IsaRevision::Release6);
+        cpu.execute_instruction(word, &mut memory);
+        assert_eq!(cpu.exception(),
+                   Some(Exception::ReservedInstruction { pc: 0 }),
+                   "0x{:08x}", word);
+    }
+}
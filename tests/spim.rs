@@ -0,0 +1,236 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+use mips_emulator::computer::cpu::{self, Cpu};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::memory::Memory;
+use mips_emulator::computer::spim::{self, Spim};
+use mips_emulator::computer::syscall::{SyscallHandler, SyscallResult};
+
+mod common;
+use common::{A0, A1, A2, MEMORY, V0};
+
+// Services
+const SBRK: u64 = 9;
+const EXIT: u64 = 10;
+const OPEN: u64 = 13;
+const READ: u64 = 14;
+const WRITE: u64 = 15;
+const CLOSE: u64 = 16;
+const EXIT2: u64 = 17;
+
+// Flags for OPEN
+const OPEN_READ: u64 = 0;
+const OPEN_WRITE: u64 = 1;
+const OPEN_APPEND: u64 = 9;
+
+// Scratch space for names and buffers.
+const BUFFER: u64 = 0x800;
+
+// Prints an int, a char, a string, a float and a double, then exits with
+// status 3.
+const PRINTS: &str = "
+        .data
+msg:    .asciiz \"hi\"
+        .text
+main:
+        li      $v0, 1
+        li      $a0, -42
+        syscall
+        li      $v0, 11
+        li      $a0, 32
+        syscall
+        li      $v0, 4
+        la      $a0, msg
+        syscall
+        li      $v0, 11
+        li      $a0, 32
+        syscall
+        lui     $t0, 0x3f80
+        mtc1    $t0, $f12
+        li      $v0, 2
+        syscall
+        li      $v0, 11
+        li      $a0, 32
+        syscall
+        lui     $t0, 0x4004
+        dsll32  $t0, $t0, 0
+        dmtc1   $t0, $f12
+        li      $v0, 3
+        syscall
+        li      $v0, 17
+        li      $a0, 3
+        syscall
+";
+
+// Exits with EXIT, which always means success, before it can print.
+const EXITS: &str = "
+main:
+        li      $v0, 10
+        li      $a0, 5
+        syscall
+        li      $v0, 1
+        syscall
+";
+
+// An empty directory of its own for each test to use as the sandbox.
+fn sandbox(name: &str) -> PathBuf {
+    let dir = env::temp_dir()
+        .join(format!("mips-emulator-spim-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Runs `source` with the emulator and returns what it printed and its exit
+// status.
+fn run(name: &str, source: &str) -> (String, Option<i32>) {
+    let dir = sandbox(name);
+    let path = dir.join("program.s");
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_mips_emulator"))
+        .arg("--spim")
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    (String::from_utf8(output.stdout).unwrap(), output.status.code())
+}
+
+// Makes a syscall with arguments from $a0 on and returns $v0.
+fn syscall(spim: &mut Spim,
+           cpu: &mut Cpu,
+           memory: &mut Memory,
+           number: u64,
+           arguments: &[u64]) -> u64 {
+    cpu.set_register(V0, number);
+    for (i, &argument) in arguments.iter().enumerate() {
+        cpu.set_register(A0 + i, argument);
+    }
+    assert!(matches!(spim.syscall(cpu, memory), SyscallResult::Resume),
+            "{} {:x?}", number, arguments);
+    cpu.register(V0)
+}
+
+// Opens `name`, which goes in memory at BUFFER.
+fn open(spim: &mut Spim, cpu: &mut Cpu, memory: &mut Memory, name: &str,
+        flags: u64) -> u64 {
+    memory.write_bytes(BUFFER, name.as_bytes());
+    memory.write_byte(BUFFER + name.len() as u64, 0);
+    syscall(spim, cpu, memory, OPEN, &[BUFFER, flags])
+}
+
+#[test]
+fn buffers_bigger_than_memory_are_refused() {
+    let mut spim = spim::new(env::temp_dir());
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    for size in [u32::MAX, MEMORY as u32 + 1] {
+        cpu.set_register(V0, READ);
+        cpu.set_register(A0, 0);
+        cpu.set_register(A1, 0);
+        cpu.set_register(A2, size as u64);
+        assert!(matches!(spim.syscall(&mut cpu, &mut memory),
+                         SyscallResult::Resume));
+        assert_eq!(cpu.register(V0), u64::MAX, "{:#x}", size);
+    }
+
+    // Or that run off the end of it.
+    cpu.set_register(V0, READ);
+    cpu.set_register(A1, MEMORY - 1);
+    cpu.set_register(A2, 2);
+    spim.syscall(&mut cpu, &mut memory);
+    assert_eq!(cpu.register(V0), u64::MAX);
+}
+
+#[test]
+fn console_output_and_exit_status() {
+    assert_eq!(run("prints", PRINTS), ("-42 hi 1.0 2.5".to_string(), Some(3)));
+    assert_eq!(run("exits", EXITS), (String::new(), Some(0)));
+}
+
+#[test]
+fn exit_services_stop_the_program() {
+    let mut spim = spim::new(env::temp_dir());
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_register(A0, 5);
+    cpu.set_register(V0, EXIT);
+    assert!(matches!(spim.syscall(&mut cpu, &mut memory),
+                     SyscallResult::Exit(0)));
+    cpu.set_register(V0, EXIT2);
+    assert!(matches!(spim.syscall(&mut cpu, &mut memory),
+                     SyscallResult::Exit(5)));
+}
+
+#[test]
+fn sbrk_hands_out_memory_until_it_runs_out() {
+    let mut spim = spim::new(env::temp_dir());
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    let mut sbrk = |memory: &mut Memory, size: i64| {
+        syscall(&mut spim, &mut cpu, memory, SBRK, &[size as u64])
+    };
+    assert_eq!(sbrk(&mut memory, 0x100), 0);
+    assert_eq!(sbrk(&mut memory, 0x100), 0x100);
+    assert_eq!(sbrk(&mut memory, -0x80), 0x200);
+    assert_eq!(sbrk(&mut memory, 0), 0x180);
+    // Past the end of memory it fails and the break stays put.
+    assert_eq!(sbrk(&mut memory, MEMORY as i64), u64::MAX);
+    assert_eq!(sbrk(&mut memory, 0), 0x180);
+}
+
+#[test]
+fn files_are_opened_inside_the_sandbox() {
+    let dir = sandbox("files");
+    let mut spim = spim::new(dir.clone());
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    memory.write_bytes(0x100, b"hello");
+
+    let fd = open(&mut spim, &mut cpu, &mut memory, "out", OPEN_WRITE);
+    assert_eq!(syscall(&mut spim, &mut cpu, &mut memory, WRITE,
+                       &[fd, 0x100, 5]), 5);
+    syscall(&mut spim, &mut cpu, &mut memory, CLOSE, &[fd]);
+    // Writing to it once it's closed fails.
+    assert_eq!(syscall(&mut spim, &mut cpu, &mut memory, WRITE,
+                       &[fd, 0x100, 5]), u64::MAX);
+
+    let fd = open(&mut spim, &mut cpu, &mut memory, "out", OPEN_APPEND);
+    syscall(&mut spim, &mut cpu, &mut memory, WRITE, &[fd, 0x100, 2]);
+    syscall(&mut spim, &mut cpu, &mut memory, CLOSE, &[fd]);
+
+    let fd = open(&mut spim, &mut cpu, &mut memory, "./out", OPEN_READ);
+    assert_eq!(syscall(&mut spim, &mut cpu, &mut memory, READ,
+                       &[fd, 0x200, 16]), 7);
+    assert_eq!(memory.read_virtual(0, 0x200, 7).unwrap(), b"hellohe");
+    syscall(&mut spim, &mut cpu, &mut memory, CLOSE, &[fd]);
+
+    let written = fs::read(dir.join("out"));
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(written.unwrap(), b"hellohe");
+}
+
+#[test]
+fn paths_out_of_the_sandbox_are_rejected() {
+    let dir = sandbox("escape");
+    let inner = dir.join("inner");
+    fs::create_dir(&inner).unwrap();
+    fs::write(dir.join("secret"), b"secret").unwrap();
+    let mut spim = spim::new(inner);
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+
+    let absolute = dir.join("secret").to_str().unwrap().to_string();
+    for name in ["../secret", "a/../../secret", absolute.as_str()] {
+        assert_eq!(open(&mut spim, &mut cpu, &mut memory, name, OPEN_READ),
+                   u64::MAX, "{}", name);
+    }
+    let escaped = open(&mut spim, &mut cpu, &mut memory, "../new",
+                       OPEN_WRITE);
+    let created = dir.join("new").exists();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!((escaped, created), (u64::MAX, false));
+}
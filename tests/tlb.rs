@@ -0,0 +1,396 @@
+use mips_emulator::computer;
+use mips_emulator::computer::cpu::{self, Cpu, MmuKind};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::disassembler;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::{self, Endianness, Memory};
+use mips_emulator::computer::program::{Class, Program, ProgramError, Segment};
+
+mod common;
+use common::{assemble, execute, A0, A1, V0};
+
+// Enough memory for pages in a few different frames.
+const MEMORY: u64 = 64 * 1024;
+
+// CP0 registers.
+const INDEX: u32 = 0;
+const RANDOM: u32 = 1;
+const ENTRYLO0: u32 = 2;
+const ENTRYLO1: u32 = 3;
+const CONTEXT: u32 = 4;
+const PAGEMASK: u32 = 5;
+const WIRED: u32 = 6;
+const BADVADDR: u32 = 8;
+const ENTRYHI: u32 = 10;
+const XCONTEXT: u32 = 20;
+
+// EntryLo and Index fields.
+const G: u64 = 0x1;
+const V: u64 = 0x2;
+const D: u64 = 0x4;
+const INDEX_P: u64 = 1 << 31;
+
+// Status fields.
+const KSU_USER: u64 = 0x10;
+const UX: u64 = 0x20;
+const EXL: u64 = 0x2;
+
+const KSEG0: u64 = 0xffffffff80000000;
+
+// Encodings checked against the LLVM assembler.
+const TLBP: u32 = 0x42000008;
+const TLBR: u32 = 0x42000001;
+const TLBWI: u32 = 0x42000002;
+const TLBWR: u32 = 0x42000006;
+const TLBINV: u32 = 0x42000003;
+const TLBINVF: u32 = 0x42000004;
+const LW_V0_0_A1: u32 = 0x8ca20000;
+const SW_V0_0_A1: u32 = 0xaca20000;
+
+const ENCODINGS: &[(&str, u32)] = &[
+    ("tlbp", TLBP),
+    ("tlbr", TLBR),
+    ("tlbwi", TLBWI),
+    ("tlbwr", TLBWR),
+    ("tlbinv", TLBINV),
+    ("tlbinvf", TLBINVF),
+];
+
+// dmtc0 $a0, $rd and dmfc0 $v0, $rd.
+fn dmtc0(rd: u32) -> u32 {
+    0x40a40000 | rd << 11
+}
+
+fn dmfc0(rd: u32) -> u32 {
+    0x40220000 | rd << 11
+}
+
+// A Release 6 CPU in kernel mode with a TLB that's empty to start with.
+fn setup() -> (Cpu, Memory) {
+    let memory = memory::new(MEMORY, 1);
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_mmu_kind(MmuKind::Tlb);
+    (cpu, memory)
+}
+
+fn write_cp0(cpu: &mut Cpu, memory: &mut Memory, rd: u32, value: u64) {
+    cpu.set_register(A0, value);
+    execute(cpu, memory, dmtc0(rd));
+}
+
+fn read_cp0(cpu: &mut Cpu, memory: &mut Memory, rd: u32) -> u64 {
+    execute(cpu, memory, dmfc0(rd));
+    cpu.register(V0)
+}
+
+// Writes TLB entry `index` for `entry_hi`, mapping its even and odd pages
+// onto the physical addresses in `lo0` and `lo1`, each with its flags.
+fn write_entry(cpu: &mut Cpu, memory: &mut Memory, index: u64,
+               entry_hi: u64, lo0: (u64, u64), lo1: (u64, u64)) {
+    write_cp0(cpu, memory, INDEX, index);
+    write_cp0(cpu, memory, ENTRYHI, entry_hi);
+    write_cp0(cpu, memory, ENTRYLO0, (lo0.0 >> 12) << 6 | lo0.1);
+    write_cp0(cpu, memory, ENTRYLO1, (lo1.0 >> 12) << 6 | lo1.1);
+    execute(cpu, memory, TLBWI);
+}
+
+// Loads a word from `address` with an LW at PC 0.
+fn load(cpu: &mut Cpu, memory: &mut Memory, address: u64)
+    -> Result<u64, Exception> {
+    cpu.set_pc(0);
+    cpu.set_register(A1, address);
+    cpu.execute_instruction(LW_V0_0_A1, memory);
+    match cpu.exception() {
+        None => Ok(cpu.register(V0)),
+        Some(exception) => {
+            cpu.clear_exception();
+            Err(exception)
+        },
+    }
+}
+
+#[test]
+fn encodings_round_trip() {
+    for &(source, word) in ENCODINGS {
+        assert_eq!(assemble(source), word, "{}", source);
+        assert_eq!(disassembler::disassemble(word, 0, IsaRevision::Release6),
+                   source);
+    }
+}
+
+#[test]
+fn mapped_loads_go_through_the_tlb() {
+    let (mut cpu, mut memory) = setup();
+    memory.write_word(0x3004, 0x11111111);
+    memory.write_word(0x5008, 0x22222222);
+    write_entry(&mut cpu, &mut memory, 0, 0x400000,
+                (0x3000, V), (0x5000, V));
+    assert_eq!(load(&mut cpu, &mut memory, 0x400004), Ok(0x11111111));
+    assert_eq!(load(&mut cpu, &mut memory, 0x401008), Ok(0x22222222));
+    // kseg0 goes straight to physical memory.
+    assert_eq!(load(&mut cpu, &mut memory, KSEG0 + 0x3004), Ok(0x11111111));
+}
+
+#[test]
+fn a_miss_takes_the_refill_vector() {
+    let (mut cpu, mut memory) = setup();
+    write_cp0(&mut cpu, &mut memory, CONTEXT, 0xffffffffc0000000);
+    write_cp0(&mut cpu, &mut memory, ENTRYHI, 0x5);
+    memory.write_word(0x1000, LW_V0_0_A1);
+    cpu.set_pc(KSEG0 + 0x1000);
+    cpu.set_register(A1, 0x7f6e5d4c);
+    assert_eq!(cpu.step(&mut memory),
+               Some(Exception::TlbLoad {
+                   pc: KSEG0 + 0x1000,
+                   address: 0x7f6e5d4c,
+               }));
+    assert!(cpu.deliver_exception(&mut memory));
+    assert_eq!(cpu.pc(), KSEG0);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, BADVADDR), 0x7f6e5d4c);
+    // The ASID is kept, and VPN2 is the even/odd pair that missed.
+    assert_eq!(read_cp0(&mut cpu, &mut memory, ENTRYHI), 0x7f6e4005);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, CONTEXT),
+               0xffffffffc0000000 | (0x7f6e5d4c >> 13) << 4);
+
+    // Missing again inside the handler goes to the general vector.
+    cpu.set_register(A1, 0x1000);
+    cpu.execute_instruction(LW_V0_0_A1, &mut memory);
+    assert!(cpu.deliver_exception(&mut memory));
+    assert_eq!(cpu.pc(), KSEG0 + 0x180);
+}
+
+#[test]
+fn a_miss_on_a_64_bit_address_takes_the_xtlb_refill_vector() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_status(KSU_USER | UX);
+    cpu.set_pc(0x100);
+    cpu.set_register(A1, 0x2345678000);
+    cpu.execute_instruction(LW_V0_0_A1, &mut memory);
+    assert!(cpu.deliver_exception(&mut memory));
+    assert_eq!(cpu.pc(), KSEG0 + 0x80);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, XCONTEXT),
+               (0x2345678000 >> 13) << 4);
+
+    // Without UX a user address is 32 bits, and a miss on one gets the
+    // ordinary refill vector.
+    let (mut cpu, mut memory) = setup();
+    cpu.set_status(KSU_USER);
+    cpu.set_register(A1, 0x1000);
+    cpu.execute_instruction(LW_V0_0_A1, &mut memory);
+    assert!(cpu.deliver_exception(&mut memory));
+    assert_eq!(cpu.pc(), KSEG0);
+}
+
+#[test]
+fn invalid_and_clean_pages_fault() {
+    let (mut cpu, mut memory) = setup();
+    write_entry(&mut cpu, &mut memory, 0, 0x2000, (0x4000, V), (0x6000, 0));
+    assert_eq!(load(&mut cpu, &mut memory, 0x3000),
+               Err(Exception::TlbInvalidLoad { pc: 0, address: 0x3000 }));
+
+    cpu.set_pc(0);
+    cpu.set_register(A1, 0x2000);
+    cpu.execute_instruction(SW_V0_0_A1, &mut memory);
+    assert_eq!(cpu.exception(),
+               Some(Exception::TlbModified { pc: 0, address: 0x2000 }));
+    assert!(cpu.deliver_exception(&mut memory));
+    assert_eq!(cpu.pc(), KSEG0 + 0x180);
+
+    // Once the page is dirty the store goes through.
+    let (mut cpu, mut memory) = setup();
+    write_entry(&mut cpu, &mut memory, 0, 0x2000,
+                (0x4000, V | D), (0x6000, 0));
+    cpu.set_register(V0, 0xabcd);
+    cpu.set_register(A1, 0x2010);
+    execute(&mut cpu, &mut memory, SW_V0_0_A1);
+    assert_eq!(memory.read_word(0x4010), Some(0xabcd));
+    cpu.set_pc(0);
+    cpu.set_register(A1, 0x3000);
+    cpu.execute_instruction(SW_V0_0_A1, &mut memory);
+    assert_eq!(cpu.exception(),
+               Some(Exception::TlbInvalidStore { pc: 0, address: 0x3000 }));
+}
+
+#[test]
+fn unaligned_accesses_can_span_two_pages() {
+    let (mut cpu, mut memory) = setup();
+    write_entry(&mut cpu, &mut memory, 0, 0x400000,
+                (0x3000, V | D), (0x8000, V | D));
+    memory.write_bytes(0x3ffe, &[0xaa, 0xbb]);
+    memory.write_bytes(0x8000, &[0xcc, 0xdd]);
+    assert_eq!(load(&mut cpu, &mut memory, 0x400ffe), Ok(0xffffffffaabbccdd));
+
+    cpu.set_register(V0, 0x11223344);
+    cpu.set_register(A1, 0x400fff);
+    execute(&mut cpu, &mut memory, SW_V0_0_A1);
+    assert_eq!(memory.read_byte(0x3fff), Some(0x11));
+    assert_eq!(memory.read_word(0x8000), Some(0x22334400));
+
+    // The second page has to be mapped and valid as well, or nothing is
+    // done.
+    assert_eq!(load(&mut cpu, &mut memory, 0x401ffe),
+               Err(Exception::TlbLoad { pc: 0, address: 0x402001 }));
+    let (mut cpu, mut memory) = setup();
+    write_entry(&mut cpu, &mut memory, 0, 0x400000,
+                (0x3000, V | D), (0x8000, 0));
+    cpu.set_pc(0);
+    cpu.set_register(V0, 0x11223344);
+    cpu.set_register(A1, 0x400ffe);
+    cpu.execute_instruction(SW_V0_0_A1, &mut memory);
+    assert_eq!(cpu.exception(),
+               Some(Exception::TlbInvalidStore { pc: 0, address: 0x401001 }));
+    assert_eq!(memory.read_word(0x3ffc), Some(0));
+}
+
+#[test]
+fn entries_match_on_asid_unless_global() {
+    let (mut cpu, mut memory) = setup();
+    memory.write_word(0x4000, 1);
+    memory.write_word(0x8000, 2);
+    write_entry(&mut cpu, &mut memory, 0, 0x10000 | 1,
+                (0x4000, V), (0x4000, V));
+    write_entry(&mut cpu, &mut memory, 1, 0x20000 | 1,
+                (0x8000, V | G), (0x8000, V | G));
+
+    write_cp0(&mut cpu, &mut memory, ENTRYHI, 2);
+    assert_eq!(load(&mut cpu, &mut memory, 0x10000),
+               Err(Exception::TlbLoad { pc: 0, address: 0x10000 }));
+    assert_eq!(load(&mut cpu, &mut memory, 0x20000), Ok(2));
+    write_cp0(&mut cpu, &mut memory, ENTRYHI, 1);
+    assert_eq!(load(&mut cpu, &mut memory, 0x10000), Ok(1));
+}
+
+#[test]
+fn probe_and_read_find_the_entry_written() {
+    let (mut cpu, mut memory) = setup();
+    write_cp0(&mut cpu, &mut memory, PAGEMASK, 0x6000);
+    write_entry(&mut cpu, &mut memory, 7, 0x7fff8000 | 3,
+                (0x4000, V | D), (0x8000, V));
+
+    write_cp0(&mut cpu, &mut memory, ENTRYHI, 0x7fffa000 | 3);
+    execute(&mut cpu, &mut memory, TLBP);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, INDEX), 7);
+    write_cp0(&mut cpu, &mut memory, ENTRYHI, 0x7fffa000 | 4);
+    execute(&mut cpu, &mut memory, TLBP);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, INDEX), INDEX_P);
+
+    write_cp0(&mut cpu, &mut memory, PAGEMASK, 0);
+    write_cp0(&mut cpu, &mut memory, INDEX, 7);
+    execute(&mut cpu, &mut memory, TLBR);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, ENTRYHI), 0x7fff8000 | 3);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, PAGEMASK), 0x6000);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, ENTRYLO0), 0x4 << 6 | V | D);
+    assert_eq!(read_cp0(&mut cpu, &mut memory, ENTRYLO1), 0x8 << 6 | V);
+}
+
+#[test]
+fn bigger_pages_cover_more_addresses() {
+    let (mut cpu, mut memory) = setup();
+    memory.write_word(0x4000 + 0x3ffc, 3);
+    memory.write_word(0x8000 + 0x0010, 4);
+    // 16 KiB pages.
+    write_cp0(&mut cpu, &mut memory, PAGEMASK, 0x6000);
+    write_entry(&mut cpu, &mut memory, 0, 0x0,
+                (0x4000, V), (0x8000, V));
+    assert_eq!(load(&mut cpu, &mut memory, 0x3ffc), Ok(3));
+    assert_eq!(load(&mut cpu, &mut memory, 0x4010), Ok(4));
+    assert_eq!(load(&mut cpu, &mut memory, 0x8000),
+               Err(Exception::TlbLoad { pc: 0, address: 0x8000 }));
+}
+
+#[test]
+fn random_skips_the_wired_entries() {
+    let (mut cpu, mut memory) = setup();
+    assert_eq!(read_cp0(&mut cpu, &mut memory, RANDOM), 47);
+    write_cp0(&mut cpu, &mut memory, WIRED, 45);
+    write_cp0(&mut cpu, &mut memory, ENTRYLO0, V);
+    write_cp0(&mut cpu, &mut memory, ENTRYLO1, V);
+    let mut written = Vec::new();
+    for page in 0..4 {
+        write_cp0(&mut cpu, &mut memory, ENTRYHI, page << 13);
+        let random = read_cp0(&mut cpu, &mut memory, RANDOM);
+        execute(&mut cpu, &mut memory, TLBWR);
+        execute(&mut cpu, &mut memory, TLBP);
+        assert_eq!(read_cp0(&mut cpu, &mut memory, INDEX), random);
+        written.push(random);
+    }
+    assert_eq!(written, [47, 46, 45, 47]);
+}
+
+#[test]
+fn tlbinv_only_takes_out_the_current_asid() {
+    let (mut cpu, mut memory) = setup();
+    write_entry(&mut cpu, &mut memory, 0, 0x2000 | 1, (0, V), (0, V));
+    write_entry(&mut cpu, &mut memory, 1, 0x4000 | 2, (0, V), (0, V));
+    write_entry(&mut cpu, &mut memory, 2, 0x6000 | 1, (0, V | G), (0, V | G));
+
+    write_cp0(&mut cpu, &mut memory, ENTRYHI, 1);
+    execute(&mut cpu, &mut memory, TLBINV);
+    assert!(load(&mut cpu, &mut memory, 0x2000).is_err());
+    assert!(load(&mut cpu, &mut memory, 0x6000).is_ok());
+    write_cp0(&mut cpu, &mut memory, ENTRYHI, 2);
+    assert!(load(&mut cpu, &mut memory, 0x4000).is_ok());
+
+    execute(&mut cpu, &mut memory, TLBINVF);
+    assert!(load(&mut cpu, &mut memory, 0x4000).is_err());
+    assert!(load(&mut cpu, &mut memory, 0x6000).is_err());
+}
+
+#[test]
+fn user_mode_cannot_reach_the_kernel_segments() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_status(KSU_USER);
+    assert_eq!(load(&mut cpu, &mut memory, KSEG0),
+               Err(Exception::AddressErrorLoad { pc: 0, address: KSEG0 }));
+    // At the exception level the CPU's back in kernel mode.
+    cpu.set_status(KSU_USER | EXL);
+    assert_eq!(load(&mut cpu, &mut memory, KSEG0), Ok(0));
+}
+
+#[test]
+fn tlb_instructions_are_reserved_without_a_tlb() {
+    let (mut cpu, mut memory) = setup();
+    cpu.set_mmu_kind(MmuKind::BaseLimit);
+    assert_eq!(cpu.mmu_kind(), MmuKind::BaseLimit);
+    for &(_, word) in ENCODINGS {
+        cpu.execute_instruction(word, &mut memory);
+        assert_eq!(cpu.exception(),
+                   Some(Exception::ReservedInstruction { pc: 0 }));
+        cpu.clear_exception();
+    }
+}
+
+// A kernel with one segment of code at `address`.
+fn kernel(address: u64) -> Program {
+    Program {
+        segments: vec![Segment {
+            address,
+            size: 0x100,
+            data: vec![0x24, 0x02, 0x00, 0x07],
+            executable: true,
+        }],
+        entry: address,
+        endianness: Endianness::Big,
+        class: Class::Elf32,
+        headers: None,
+        header_size: 0,
+        header_count: 0,
+    }
+}
+
+#[test]
+fn kernels_load_at_their_physical_addresses() {
+    let mut com = computer::new(1, MEMORY, IsaRevision::Release6);
+    com.set_mmu_kind(MmuKind::Tlb);
+    com.load(&kernel(0xffffffffa0001000)).unwrap();
+    assert_eq!(com.memory().read_word(0x1000), Some(0x24020007));
+    com.step();
+    assert_eq!(com.exception(0), None);
+
+    let mut com = computer::new(1, MEMORY, IsaRevision::Release6);
+    com.set_mmu_kind(MmuKind::Tlb);
+    assert!(matches!(com.load(&kernel(0x400000)),
+                     Err(ProgramError::Mapped(0x400000))));
+    assert!(matches!(com.load(&kernel(KSEG0 + MEMORY)),
+                     Err(ProgramError::TooLarge { .. })));
+}
@@ -0,0 +1,70 @@
+use mips_emulator::computer::cpu;
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::exception::Exception;
+
+mod common;
+use common::{assemble, A0, A1};
+
+const PC: u64 = 0x100;
+
+// Runs `source` at PC with $a0 and $a1 set and returns what it raised.
+fn run(source: &str, a0: u64, a1: u64) -> Option<Exception> {
+    let mut memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_pc(PC);
+    cpu.set_register(A0, a0);
+    cpu.set_register(A1, a1);
+    cpu.execute_instruction(assemble(source), &mut memory);
+    cpu.exception()
+}
+
+#[test]
+fn traps_compare_signed_and_unsigned() {
+    let minus_one = u64::MAX;
+    let cases = [
+        ("teq", 5, 5, true),
+        ("teq", 5, 6, false),
+        ("tne", 5, 6, true),
+        ("tne", 5, 5, false),
+        // -1 is the smaller signed value and the larger unsigned one.
+        ("tge", 1, minus_one, true),
+        ("tge", minus_one, 1, false),
+        ("tgeu", minus_one, 1, true),
+        ("tgeu", 1, minus_one, false),
+        ("tlt", minus_one, 1, true),
+        ("tlt", 1, minus_one, false),
+        ("tltu", 1, minus_one, true),
+        ("tltu", minus_one, 1, false),
+    ];
+    for (mnemonic, a0, a1, taken) in cases {
+        let source = format!("{} $a0, $a1, 42", mnemonic);
+        let expected = taken.then_some(Exception::Trap { pc: PC, code: 42 });
+        assert_eq!(run(&source, a0, a1), expected, "{} {} {}",
+                   mnemonic, a0 as i64, a1 as i64);
+    }
+}
+
+#[test]
+fn a_trap_without_a_code_reports_zero() {
+    assert_eq!(run("teq $a0, $a1", 0, 0),
+               Some(Exception::Trap { pc: PC, code: 0 }));
+}
+
+#[test]
+fn break_reports_its_whole_code_field() {
+    // A single code goes in the upper ten bits of the field.
+    assert_eq!(run("break", 0, 0),
+               Some(Exception::Breakpoint { pc: PC, code: 0 }));
+    assert_eq!(run("break 7", 0, 0),
+               Some(Exception::Breakpoint { pc: PC, code: 7 << 10 }));
+    assert_eq!(run("break 7, 3", 0, 0),
+               Some(Exception::Breakpoint { pc: PC, code: 7 << 10 | 3 }));
+}
+
+#[test]
+fn sdbbp_raises_a_debug_breakpoint() {
+    assert_eq!(run("sdbbp", 0, 0),
+               Some(Exception::DebugBreakpoint { pc: PC, code: 0 }));
+    assert_eq!(run("sdbbp 0x12345", 0, 0),
+               Some(Exception::DebugBreakpoint { pc: PC, code: 0x12345 }));
+}
@@ -0,0 +1,68 @@
+use mips_emulator::computer::cpu::{self, UnalignedPolicy};
+use mips_emulator::computer::decoder::IsaRevision;
+use mips_emulator::computer::exception::Exception;
+use mips_emulator::computer::memory::Memory;
+
+mod common;
+use common::{A0, A1, MEMORY};
+
+// LW $a0, 1($a1) and SW $a0, 2($a1)
+const LW_A0_1_A1: u32 = 0x8ca40001;
+const SW_A0_2_A1: u32 = 0xaca40002;
+
+// A CPU whose addresses map straight onto a page of memory, with $a1
+// pointing at `base` and $a0 holding `value`.
+fn setup(policy: UnalignedPolicy, base: u64, value: u64) -> (cpu::Cpu, Memory) {
+    let memory = common::memory();
+    let mut cpu = cpu::new(0, IsaRevision::Release6);
+    cpu.set_unaligned_policy(policy);
+    cpu.set_register(A0, value);
+    cpu.set_register(A1, base);
+    (cpu, memory)
+}
+
+#[test]
+fn aligned_accesses_are_not_counted() {
+    let (mut cpu, mut memory) = setup(UnalignedPolicy::Trap, 0xff, 0);
+    cpu.execute_instruction(LW_A0_1_A1, &mut memory);
+    assert_eq!(cpu.exception(), None);
+    assert_eq!(cpu.unaligned_accesses(), 0);
+}
+
+#[test]
+fn unaligned_loads_follow_the_policy() {
+    for policy in [UnalignedPolicy::Allow, UnalignedPolicy::Emulate] {
+        let (mut cpu, mut memory) = setup(policy, 0x100, 0);
+        memory.write(0x100, 0x8877665544332211, 8);
+        cpu.execute_instruction(LW_A0_1_A1, &mut memory);
+        assert_eq!(cpu.exception(), None, "{:?}", policy);
+        assert_eq!(cpu.register(4), 0x77665544, "{:?}", policy);
+        assert_eq!(cpu.unaligned_accesses(), 1, "{:?}", policy);
+    }
+
+    let (mut cpu, mut memory) = setup(UnalignedPolicy::Trap, 0x100, 0);
+    cpu.execute_instruction(LW_A0_1_A1, &mut memory);
+    assert_eq!(cpu.exception(),
+               Some(Exception::AddressErrorLoad { pc: 0, address: 0x101 }));
+    assert_eq!(cpu.unaligned_accesses(), 1);
+}
+
+#[test]
+fn unaligned_stores_off_the_end_of_memory() {
+    // Emulated a byte at a time, the store gets as far as the last byte of
+    // memory before it faults.
+    let (mut cpu, mut memory) = setup(UnalignedPolicy::Emulate, MEMORY - 4,
+                                      0x44332211);
+    cpu.execute_instruction(SW_A0_2_A1, &mut memory);
+    assert_eq!(cpu.exception(),
+               Some(Exception::AddressErrorStore { pc: 0, address: MEMORY }));
+    assert_eq!(memory.read(MEMORY - 2, 2), Some(0x4433));
+
+    // In one go, it doesn't write anything.
+    let (mut cpu, mut memory) = setup(UnalignedPolicy::Allow, MEMORY - 4,
+                                      0x44332211);
+    cpu.execute_instruction(SW_A0_2_A1, &mut memory);
+    assert_eq!(cpu.exception(),
+               Some(Exception::BusErrorData { pc: 0, address: MEMORY - 2 }));
+    assert_eq!(memory.read(MEMORY - 2, 2), Some(0));
+}